@@ -0,0 +1,73 @@
+/*!
+ * Time checkerboard sweeps with neighbour sums over whole rows against sweeps through the neighbour lists.
+ *
+ * Run with `cargo bench --bench checkerboard`, add `--features avx2` for the AVX2 neighbour sums.
+ * A local field of zero on one site makes the sweeps take the general path without changing the physics.
+ * Prints the time per site of one sweep with a single thread.
+ */
+
+use std::time::Instant;
+
+use ising::checkerboard::{evolve_checkerboard, neighbour_products, set_max_threads};
+use ising::{Configuration, Rng, hamiltonian, neighbour_sum};
+
+/// Extents of the square lattices.
+const SIZES: [usize; 3] = [256, 512, 1024];
+
+/// Number of timed sweeps per lattice.
+const NSWEEP: usize = 100;
+
+/// Temperature of the sweeps, close to Tc.
+const TEMPERATURE: f64 = 2.3;
+
+/// Return the time per site and sweep in nanoseconds of NSWEEP sweeps on an n*n lattice.
+fn time_sweeps(n: usize, general: bool) -> f64 {
+    let mut rng = Rng::from_seed([1; 32]);
+    let mut cfg = Configuration::random(n, n, &mut rng);
+    if general {
+        cfg.add_local_field(0, 0.);
+    }
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    // thermalise a bit so the acceptance rate is that of the temperature
+    evolve_checkerboard(&mut cfg, &mut energy, 1./TEMPERATURE, 1., 0., &mut rng, 10, None);
+
+    let start = Instant::now();
+    evolve_checkerboard(&mut cfg, &mut energy, 1./TEMPERATURE, 1., 0., &mut rng, NSWEEP, None);
+    start.elapsed().as_secs_f64() * 1e9 / (NSWEEP*n*n) as f64
+}
+
+/// Return the time per site of the neighbour sums of NSWEEP sweeps in nanoseconds, by rows or per site.
+fn time_neighbour_sums(n: usize, by_rows: bool) -> f64 {
+    let mut rng = Rng::from_seed([2; 32]);
+    let cfg = Configuration::random(n, n, &mut rng);
+    let mut products = vec![0; n];
+    let mut checksum = 0;
+
+    let start = Instant::now();
+    for _ in 0..NSWEEP {
+        for y in 0..n {
+            if by_rows {
+                neighbour_products(&cfg, y, &mut products);
+            } else {
+                products.iter_mut().enumerate().for_each(|(x, k)| *k = cfg[y*n + x] * neighbour_sum(&cfg, y*n + x));
+            }
+            checksum += products[y % n];
+        }
+    }
+    let time = start.elapsed().as_secs_f64() * 1e9 / (NSWEEP*n*n) as f64;
+    // keep the sums from being optimised away
+    assert!(checksum.abs() <= (4*NSWEEP*n) as i32);
+    time
+}
+
+fn main() {
+    set_max_threads(1);
+    println!("{:>6} {:>12} {:>12} {:>8} {:>12} {:>12} {:>8}", "size", "sweep lists", "sweep rows", "speedup",
+             "sums lists", "sums rows", "speedup");
+    for n in SIZES {
+        let (general, square) = (time_sweeps(n, true), time_sweeps(n, false));
+        let (per_site, by_rows) = (time_neighbour_sums(n, false), time_neighbour_sums(n, true));
+        println!("{:>6} {:>9.2} ns {:>9.2} ns {:>7.1}x {:>9.2} ns {:>9.2} ns {:>7.1}x", format!("{}²", n),
+                 general, square, general / square, per_site, by_rows, per_site / by_rows);
+    }
+}
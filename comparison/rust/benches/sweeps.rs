@@ -0,0 +1,85 @@
+/*!
+ * Benchmarks of single sweeps, energy differences, the Hamiltonian, cluster updates, and random numbers.
+ *
+ * Run with `cargo bench --bench sweeps`, names after `--` select benchmarks, e.g.
+ * `cargo bench --bench sweeps -- checkerboard`. Every benchmark starts from a configuration thermalised at
+ * TEMPERATURE in zero field and uses a single thread.
+ * Checkerboard sweeps are timed with the neighbour sums of whole rows and, as checkerboard-lists,
+ * through the neighbour lists. Build with `--features avx2` to time the AVX2 neighbour sums instead.
+ * Sweeps use the default Rng, the random numbers are timed for all kinds.
+ */
+
+mod timing;
+
+use ising::benchmark::Algorithm;
+use ising::checkerboard::set_max_threads;
+use ising::swendsen_wang::swendsen_wang_update;
+use ising::wolff::wolff_update;
+use ising::{Configuration, Rng, RngKind, delta_e, hamiltonian};
+use timing::Bencher;
+
+/// Extents of the square lattices.
+const SIZES: [usize; 3] = [16, 64, 256];
+
+/// Temperature of all updates, close to Tc.
+const TEMPERATURE: f64 = 2.3;
+
+/// Number of sweeps before timing.
+const NTHERM: usize = 50;
+
+/// Algorithms whose sweeps are timed; Wolff and Swendsen-Wang are timed as cluster updates.
+const SWEEPS: [Algorithm; 4] = [Algorithm::Metropolis, Algorithm::HeatBath, Algorithm::Checkerboard,
+                                Algorithm::Kawasaki];
+
+/// Return a configuration on an n*n lattice thermalised with the algorithm, its energy, and the Rng.
+fn thermalised(n: usize, algorithm: Algorithm) -> (Configuration, f64, Rng) {
+    let mut rng = Rng::from_seed([n as u8; 32]);
+    let mut cfg = Configuration::random(n, n, &mut rng);
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    algorithm.evolve(&mut cfg, &mut energy, 1./TEMPERATURE, 1., 0., &mut rng, NTHERM, None);
+    (cfg, energy, rng)
+}
+
+fn main() {
+    set_max_threads(1);
+    let beta = 1./TEMPERATURE;
+    let mut bencher = Bencher::new("sweeps");
+
+    for kind in [RngKind::Xoshiro, RngKind::ChaCha] {
+        let mut rng = Rng::new(kind, [3; 32]);
+        bencher.bench(&format!("rng/{}/gen_real", kind.name()), || rng.gen_real());
+        bencher.bench(&format!("rng/{}/gen_index", kind.name()), || rng.gen_index(256*256));
+    }
+
+    for n in SIZES {
+        for algorithm in SWEEPS {
+            let (mut cfg, mut energy, mut rng) = thermalised(n, algorithm);
+            bencher.bench(&format!("sweep/{}/{}", algorithm.name(), n), || {
+                algorithm.evolve(&mut cfg, &mut energy, beta, 1., 0., &mut rng, 1, None)
+            });
+        }
+        // a local field of zero takes the general path without changing the physics
+        let (mut cfg, mut energy, mut rng) = thermalised(n, Algorithm::Checkerboard);
+        cfg.add_local_field(0, 0.);
+        bencher.bench(&format!("sweep/checkerboard-lists/{}", n), || {
+            Algorithm::Checkerboard.evolve(&mut cfg, &mut energy, beta, 1., 0., &mut rng, 1, None)
+        });
+
+        let (mut cfg, mut energy, mut rng) = thermalised(n, Algorithm::Wolff);
+        bencher.bench(&format!("cluster/wolff/{}", n), || wolff_update(&mut cfg, &mut energy, beta, 1., 0., &mut rng));
+        let (mut cfg, mut energy, mut rng) = thermalised(n, Algorithm::SwendsenWang);
+        bencher.bench(&format!("cluster/swendsen-wang/{}", n), || {
+            swendsen_wang_update(&mut cfg, &mut energy, beta, 1., 0., &mut rng)
+        });
+
+        let (cfg, _, _) = thermalised(n, Algorithm::Metropolis);
+        let mut idx = 0;
+        bencher.bench(&format!("delta_e/{}", n), || {
+            idx = (idx + 1) % cfg.size();
+            delta_e(&cfg, idx, 1., 0.)
+        });
+        bencher.bench(&format!("hamiltonian/{}", n), || hamiltonian(&cfg, 1., 0.));
+    }
+
+    bencher.finish();
+}
@@ -0,0 +1,110 @@
+/*!
+ * Small benchmark harness shared by the benchmarks, modelled on criterion.
+ *
+ * Benchmarks are plain binaries with `harness = false` that time closures with a Bencher.
+ * Every benchmark runs in batches of iterations that take at least BATCH_TIME and reports the median,
+ * fastest, and slowest time per iteration of SAMPLES batches.
+ * The medians are saved to `target/benches/<name>.dat` and compared with those of the previous run,
+ * so regressions between two builds, e.g. with and without a feature, show up as changes in percent.
+ * Command line arguments that do not start with '-' select the benchmarks whose names contain one of them.
+ */
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::hint::black_box;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Shortest time of one batch of iterations.
+const BATCH_TIME: Duration = Duration::from_millis(10);
+
+/// Number of timed batches per benchmark.
+const SAMPLES: usize = 20;
+
+/// Runs, prints, and saves benchmarks.
+pub struct Bencher {
+    /// File with the medians of this and the previous run.
+    fname: PathBuf,
+    /// Only run benchmarks whose names contain one of these, all if empty.
+    filters: Vec<String>,
+    /// Median times per iteration in nanoseconds of the previous run.
+    previous: HashMap<String, f64>,
+    /// Names and median times per iteration in nanoseconds of this run.
+    results: Vec<(String, f64)>,
+}
+
+impl Bencher {
+    /// Create a bencher for the benchmark binary of the given name, reads the filters from the command line.
+    pub fn new(name: &str) -> Bencher {
+        let fname = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target").join("benches")
+            .join(format!("{}.dat", name));
+        // cargo passes --bench to the binary
+        let filters = std::env::args().skip(1).filter(|arg| !arg.starts_with('-')).collect();
+        let previous = std::fs::read_to_string(&fname).unwrap_or_default().lines()
+            .filter_map(|line| {
+                let (name, time) = line.rsplit_once(' ')?;
+                Some((name.to_string(), time.parse().ok()?))
+            })
+            .collect();
+        println!("{:<36} {:>11} {:>11} {:>11} {:>8}", "benchmark", "median", "fastest", "slowest", "change");
+        Bencher{fname, filters, previous, results: Vec::new()}
+    }
+
+    /// Time f unless the benchmark is not selected.
+    pub fn bench<T>(&mut self, name: &str, mut f: impl FnMut() -> T) {
+        if !self.filters.is_empty() && !self.filters.iter().any(|filter| name.contains(filter.as_str())) {
+            return;
+        }
+
+        // double the batch size until a batch takes long enough, this doubles as warm up
+        let mut niter = 1;
+        while time_batch(&mut f, niter) < BATCH_TIME {
+            niter *= 2;
+        }
+        let mut times: Vec<f64> = (0..SAMPLES)
+            .map(|_| time_batch(&mut f, niter).as_secs_f64() * 1e9 / niter as f64)
+            .collect();
+        times.sort_by(f64::total_cmp);
+        let median = times[SAMPLES / 2];
+
+        let change = self.previous.get(name)
+            .map_or(String::new(), |previous| format!("{:+.1}%", (median / previous - 1.) * 100.));
+        println!("{:<36} {:>11} {:>11} {:>11} {:>8}", name, format_time(median), format_time(times[0]),
+                 format_time(times[SAMPLES - 1]), change);
+        self.results.push((name.to_string(), median));
+    }
+
+    /// Save the results, keeping the previous results of benchmarks that were not run.
+    pub fn finish(mut self) {
+        for (name, time) in &self.results {
+            self.previous.insert(name.clone(), *time);
+        }
+        let mut names: Vec<&String> = self.previous.keys().collect();
+        names.sort();
+        let mut content = String::new();
+        for name in names {
+            writeln!(content, "{} {}", name, self.previous[name]).unwrap();
+        }
+        std::fs::create_dir_all(self.fname.parent().unwrap()).unwrap();
+        std::fs::write(&self.fname, content).unwrap();
+    }
+}
+
+/// Return the time of niter calls of f.
+fn time_batch<T>(f: &mut impl FnMut() -> T, niter: usize) -> Duration {
+    let start = Instant::now();
+    for _ in 0..niter {
+        black_box(f());
+    }
+    start.elapsed()
+}
+
+/// Format a time in nanoseconds with a unit that keeps the number short.
+fn format_time(ns: f64) -> String {
+    match ns {
+        ns if ns < 1e3 => format!("{:.2} ns", ns),
+        ns if ns < 1e6 => format!("{:.2} µs", ns / 1e3),
+        ns if ns < 1e9 => format!("{:.2} ms", ns / 1e6),
+        ns => format!("{:.2} s", ns / 1e9),
+    }
+}
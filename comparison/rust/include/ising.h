@@ -0,0 +1,52 @@
+/*
+ * C interface to the Rust implementation of the Ising model, see src/ffi.rs.
+ *
+ * Link against the shared library that `cargo build --release` writes to target/release,
+ * e.g. libising.so on Linux.
+ */
+
+#ifndef ISING_H
+#define ISING_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Opaque state of a simulation. */
+typedef struct Simulation IsingSimulation;
+
+/* Create a simulation on an nx*ny periodic lattice with a hot start, NULL if the arguments are invalid. */
+IsingSimulation *ising_create(size_t nx, size_t ny, double temperature, double coupling, double field,
+                              uint64_t seed);
+/* Release a simulation, sim may be NULL. */
+void ising_destroy(IsingSimulation *sim);
+
+/* Select the update algorithm by name, e.g. "metropolis" or "wolff", returns 0 on success and -1 otherwise. */
+int32_t ising_set_algorithm(IsingSimulation *sim, const char *name);
+/* Change the temperature, returns 0 on success and -1 if it is not positive. */
+int32_t ising_set_temperature(IsingSimulation *sim, double temperature);
+
+/* Run nsweep sweeps, measuring after each if measure is nonzero, returns the number of accepted updates. */
+size_t ising_sweep(IsingSimulation *sim, size_t nsweep, int32_t measure);
+
+/* Current energy, not per site. */
+double ising_energy(const IsingSimulation *sim);
+/* Number of measurements since creation or the last ising_clear_measurements. */
+size_t ising_nmeasurement(const IsingSimulation *sim);
+/* Copy up to len measurements of the energy per site, returns the number of copied values. */
+size_t ising_read_energy(const IsingSimulation *sim, double *out, size_t len);
+/* Copy up to len measurements of the magnetisation per site, returns the number of copied values. */
+size_t ising_read_magnetisation(const IsingSimulation *sim, double *out, size_t len);
+/* Remove all measurements. */
+void ising_clear_measurements(IsingSimulation *sim);
+/* Copy up to len spins in row major order, returns the number of copied spins. */
+size_t ising_read_spins(const IsingSimulation *sim, int32_t *out, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
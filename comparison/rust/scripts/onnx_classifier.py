@@ -0,0 +1,38 @@
+#! /usr/bin/env python3
+
+"""
+Evaluate an ONNX model on spin configurations sent by the Rust simulation.
+
+Usage (from the simulation):
+    cargo run --release -- --classifier "python3 scripts/onnx_classifier.py model.onnx"
+
+Reads one configuration per line from stdin and writes the flattened model output
+as one line of numbers to stdout. The model gets a float32 tensor of shape
+(1, NY, NX) with spins +1/-1.
+
+Requires numpy and onnxruntime.
+"""
+
+import os
+import sys
+
+import numpy as np
+import onnxruntime
+
+def main():
+    if len(sys.argv) != 2:
+        print("You need to provide the ONNX model file as a command line argument!",
+              file=sys.stderr)
+        sys.exit(1)
+
+    session = onnxruntime.InferenceSession(sys.argv[1])
+    input_name = session.get_inputs()[0].name
+    shape = (1, int(os.environ["ISING_NY"]), int(os.environ["ISING_NX"]))
+
+    for line in sys.stdin:
+        cfg = np.array(line.split(), dtype=np.float32).reshape(shape)
+        output = session.run(None, {input_name: cfg})[0]
+        print(" ".join(map(str, np.ravel(output))), flush=True)
+
+if __name__ == "__main__":
+    main()
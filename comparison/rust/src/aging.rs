@@ -0,0 +1,160 @@
+/*!
+ * Two-time correlation and response functions after a quench.
+ */
+
+use std::path::Path;
+
+use crate::error::{Error, OutputFile};
+use crate::{Configuration, Rng, delta_e, hamiltonian, info, write_row};
+
+/// Compute the overlap (1/N) Σ_i s_i s'_i of two spin configurations.
+pub fn overlap(a: &[i32], b: &[i32]) -> f64 {
+    a.iter().zip(b.iter()).map(|(x, y)| x*y).sum::<i32>() as f64 / a.len() as f64
+}
+
+/// Two-time functions measured after a single quench.
+pub struct Aging {
+    /// C(t_w+τ, t_w) for each waiting time and τ = 0, ..., nsweep-t_w.
+    pub correlation: Vec<Vec<f64>>,
+    /// Integrated response χ(t_w+τ, t_w) in the same layout as correlation.
+    /**
+     * Empty if no response was measured.
+     */
+    pub response: Vec<Vec<f64>>,
+}
+
+/// A copy of the system that is perturbed by a random field from t_w on.
+struct Replica {
+    cfg: Configuration,
+    energy: f64,
+    /// Random signs ε_i of the perturbing field.
+    signs: Vec<f64>,
+}
+
+/// Perform one Metropolis sweep with coupling j on several systems using the same random numbers.
+/**
+ * In every step, all systems propose to flip the same site and accept or reject
+ * with the same uniform random number. Identical systems thus stay identical and
+ * slightly perturbed ones follow nearly the same trajectory.
+ */
+fn coupled_sweep(systems: &mut [(&mut Configuration, &mut f64)], beta: f64, j: f64, rng: &mut Rng) {
+    let size = systems[0].0.size();
+    for _step in 0..size {
+        let idx = rng.gen_index(size);
+        let r = rng.gen_real();
+        for (cfg, energy) in systems.iter_mut() {
+            let delta = delta_e(cfg, idx, j, 0.);
+            if delta <= 0. || (-beta*delta).exp() > r {
+                cfg[idx] *= -1;
+                **energy += delta;
+            }
+        }
+    }
+}
+
+/// Quench a random configuration to inverse temperature beta with coupling j and measure C(t, t_w).
+/**
+ * Starts from a random configuration (infinite temperature) at t = 0 and evolves
+ * for nsweep sweeps while measuring C(t_w+τ, t_w) = (1/N) Σ_i s_i(t_w+τ) s_i(t_w)
+ * for every waiting time t_w in waiting_times.
+ *
+ * If field is given, also measures the integrated response to a random field ε_i field
+ * with ε_i = ±1, switched on at t_w (zero-field-cooled protocol):
+ * χ(t, t_w) = (1/N) Σ_i ε_i (s^ε_i(t) - s_i(t)) / field.
+ * All systems are evolved with coupled_sweep, so the perturbed replicas stay
+ * correlated with the unperturbed system and the difference has a small variance.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn quench(nx: usize, ny: usize, beta: f64, j: f64, waiting_times: &[usize],
+              nsweep: usize, field: Option<f64>, rng: &mut Rng) -> Aging {
+    let mut cfg = Configuration::random(nx, ny, rng);
+    let mut energy = hamiltonian(&cfg, j, 0.);
+
+    let mut references: Vec<Option<Vec<i32>>> = vec![None; waiting_times.len()];
+    let mut replicas: Vec<Option<Replica>> = waiting_times.iter().map(|_| None).collect();
+    let mut correlation: Vec<Vec<f64>> = vec![Vec::new(); waiting_times.len()];
+    let mut response: Vec<Vec<f64>> = vec![Vec::new(); waiting_times.len()];
+
+    for t in 0..=nsweep {
+        if t > 0 {
+            let mut systems = vec![(&mut cfg, &mut energy)];
+            systems.extend(replicas.iter_mut().flatten()
+                           .map(|replica| (&mut replica.cfg, &mut replica.energy)));
+            coupled_sweep(&mut systems, beta, j, rng);
+        }
+
+        for (iw, &tw) in waiting_times.iter().enumerate() {
+            if t == tw {
+                references[iw] = Some(cfg.cfg.clone());
+                if let Some(h) = field {
+                    replicas[iw] = Some(perturbed_replica(&cfg, j, h, rng));
+                }
+            }
+            if let Some(reference) = &references[iw] {
+                correlation[iw].push(overlap(&cfg.cfg, reference));
+            }
+            if let (Some(replica), Some(h)) = (&replicas[iw], field) {
+                let diff: f64 = replica.signs.iter().zip(replica.cfg.cfg.iter().zip(cfg.cfg.iter()))
+                    .map(|(eps, (sp, s))| eps * (sp - s) as f64)
+                    .sum();
+                response[iw].push(diff / cfg.size() as f64 / h);
+            }
+        }
+    }
+
+    Aging{correlation, response}
+}
+
+/// Make a copy of cfg with coupling j in a random field of strength h with signs drawn from rng.
+fn perturbed_replica(cfg: &Configuration, j: f64, h: f64, rng: &mut Rng) -> Replica {
+    let signs: Vec<f64> = (0..cfg.size()).map(|_| rng.gen_spin() as f64).collect();
+    let mut perturbed = cfg.clone();
+    perturbed.local_field = Some(signs.iter().map(|eps| eps*h).collect());
+    let energy = hamiltonian(&perturbed, j, 0.);
+    Replica{cfg: perturbed, energy, signs}
+}
+
+/// Average two-time functions over nrepeat quenches with coupling j and write results to datadir.
+/**
+ * Writes `waiting_times.dat` with lines `<iw>: <t_w>` and for each waiting time
+ * a file `aging_<iw>.dat` with a row C(t_w+τ, t_w) for τ = 0, ..., nsweep-t_w.
+ * If field is given, a second row contains T χ(t_w+τ, t_w), so plotting the second
+ * row against the first gives the fluctuation-dissipation plot. In equilibrium
+ * T χ = 1 - C, the slope -T dχ/dC is the fluctuation-dissipation ratio.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn run(datadir: &Path, nx: usize, ny: usize, temp: f64, j: f64, waiting_times: &[usize],
+           nsweep: usize, nrepeat: usize, field: Option<f64>, rng: &mut Rng) -> Result<(), Error> {
+    assert!(waiting_times.iter().all(|&tw| tw <= nsweep),
+            "Waiting times must not exceed the number of sweeps");
+
+    info!("Running {} quenches to temperature {}", nrepeat, temp);
+    let zeros = || -> Vec<Vec<f64>> {
+        waiting_times.iter().map(|&tw| vec![0.; nsweep-tw+1]).collect()
+    };
+    let mut correlation = zeros();
+    let mut response = zeros();
+
+    let accumulate = |avg: &mut Vec<Vec<f64>>, sample: &[Vec<f64>]| {
+        for (a, s) in avg.iter_mut().zip(sample.iter()) {
+            a.iter_mut().zip(s.iter()).for_each(|(a, s)| *a += s / nrepeat as f64);
+        }
+    };
+    for _ in 0..nrepeat {
+        let aging = quench(nx, ny, 1./temp, j, waiting_times, nsweep, field, rng);
+        accumulate(&mut correlation, &aging.correlation);
+        accumulate(&mut response, &aging.response);
+    }
+
+    let mut twfile = OutputFile::create(&datadir.join("waiting_times.dat"))?;
+    for (iw, tw) in waiting_times.iter().enumerate() {
+        writeln!(twfile, "{}: {}", iw, tw)?;
+        let mut agingfile = OutputFile::create(&datadir.join(format!("aging_{}.dat", iw)))?;
+        write_row(&mut agingfile, &correlation[iw])?;
+        if field.is_some() {
+            let tchi: Vec<f64> = response[iw].iter().map(|chi| temp*chi).collect();
+            write_row(&mut agingfile, &tchi)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,275 @@
+/*!
+ * Estimators of thermodynamic quantities from Monte-Carlo histories.
+ */
+
+use crate::{Observables, Rng};
+
+pub mod fits;
+
+/// Compute the mean of a series.
+pub fn mean(data: &[f64]) -> f64 {
+    data.iter().sum::<f64>() / data.len() as f64
+}
+
+/// Compute the variance ⟨x²⟩ - ⟨x⟩² of a series.
+pub fn variance(data: &[f64]) -> f64 {
+    let m = mean(data);
+    data.iter().map(|x| (x-m).powi(2)).sum::<f64>() / data.len() as f64
+}
+
+/// Average a quantity over disorder realisations, returns the mean and its standard error.
+/**
+ * The realisations are independent, so no binning is needed. The error is 0 for a single realisation.
+ */
+pub fn disorder_average(values: &[f64]) -> (f64, f64) {
+    let n = values.len();
+    if n < 2 {
+        return (mean(values), 0.);
+    }
+    (mean(values), (variance(values) / (n - 1) as f64).sqrt())
+}
+
+/// Compute the mean of a series whose samples carry the weights exp(log_weights), e.g. for reweighting.
+/**
+ * The weights are shifted by their maximum before exponentiating, so they may be large.
+ */
+pub fn reweighted_mean(data: &[f64], log_weights: &[f64]) -> f64 {
+    let max = log_weights.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let weights: Vec<f64> = log_weights.iter().map(|w| (w - max).exp()).collect();
+    data.iter().zip(&weights).map(|(x, w)| x*w).sum::<f64>() / weights.iter().sum::<f64>()
+}
+
+/// Compute the magnetic susceptibility χ = β N (⟨m²⟩ - ⟨m⟩²) from the magnetisation per site.
+/**
+ * In zero field, ⟨m⟩ vanishes on finite lattices if the chain tunnels between both
+ * ordered phases, so below Tc, χ is dominated by ⟨m²⟩.
+ */
+pub fn susceptibility(magnetisation: &[f64], beta: f64, size: usize) -> f64 {
+    beta * size as f64 * variance(magnetisation)
+}
+
+/// Compute the specific heat per site C = β² (⟨E²⟩ - ⟨E⟩²) / N from the total energy.
+pub fn specific_heat(energy: &[f64], beta: f64, size: usize) -> f64 {
+    beta*beta * variance(energy) / size as f64
+}
+
+/// Compute the Binder cumulant U₄ = 1 - ⟨m⁴⟩ / (3⟨m²⟩²) from the magnetisation per site.
+/**
+ * U₄ goes to 2/3 in the ordered phase and to 0 in the disordered phase. Curves for different
+ * lattice sizes intersect at Tc up to corrections to scaling.
+ */
+pub fn binder_cumulant(magnetisation: &[f64]) -> f64 {
+    let m2 = mean(&magnetisation.iter().map(|m| m*m).collect::<Vec<f64>>());
+    let m4 = mean(&magnetisation.iter().map(|m| m.powi(4)).collect::<Vec<f64>>());
+    1. - m4 / (3.*m2*m2)
+}
+
+/// Compute the second-moment correlation length ξ = sqrt(⟨S(0)⟩ / ⟨S(k)⟩ - 1) / (2 sin(k/2)).
+/**
+ * s0 and sk are the structure factors at k = 0 and k = 2π/nx of each measurement.
+ * Returns 0 if noise makes ⟨S(0)⟩ <= ⟨S(k)⟩, which can happen far above Tc where ξ is small.
+ */
+pub fn second_moment_length(s0: &[f64], sk: &[f64], nx: usize) -> f64 {
+    let ratio = mean(s0) / mean(sk);
+    (ratio - 1.).max(0.).sqrt() / (2. * (std::f64::consts::PI / nx as f64).sin())
+}
+
+/// Bin size of jackknife estimates in units of τ_int if it is derived automatically, see binsize.
+const BINSIZE_TAU: f64 = 5.;
+/// Minimum number of bins if the bin size is derived automatically, see binsize.
+const MIN_NBIN: usize = 10;
+/// Maximum number of bins if the bin size is derived automatically, see binsize.
+const MAX_NBIN: usize = 100;
+
+/// Compute an estimator and its binned jackknife error.
+/**
+ * data is divided into bins of binsize measurements, a trailing incomplete bin is dropped.
+ * The jackknife samples apply estimator to data with one bin left out each.
+ * Returns the estimator on all complete bins and its error.
+ */
+pub fn jackknife<F>(data: &[f64], binsize: usize, estimator: F) -> (f64, f64)
+where F: Fn(&[f64]) -> f64 {
+    jackknife_series(&[data], binsize, |reduced| estimator(&reduced[0]))
+}
+
+/// Compute an estimator of several series and its binned jackknife error.
+/**
+ * All series must have the same length. Like jackknife but leaves out the same bin
+ * of all series to keep their correlations.
+ */
+pub fn jackknife_series<F>(series: &[&[f64]], binsize: usize, estimator: F) -> (f64, f64)
+where F: Fn(&[Vec<f64>]) -> f64 {
+    let (full, samples) = jackknife_samples(series, binsize, estimator);
+    (full, jackknife_error(&samples))
+}
+
+/// Compute an estimator of several series on all complete bins and its jackknife samples.
+/**
+ * See jackknife_series, sample i leaves out bin i of all series. The samples allow propagating
+ * the error through further analysis, e.g. fits::jackknife.
+ */
+pub fn jackknife_samples<F>(series: &[&[f64]], binsize: usize, estimator: F) -> (f64, Vec<f64>)
+where F: Fn(&[Vec<f64>]) -> f64 {
+    let nbin = series[0].len() / binsize;
+    let series: Vec<&[f64]> = series.iter().map(|data| &data[..nbin*binsize]).collect();
+    let full = estimator(&series.iter().map(|data| data.to_vec()).collect::<Vec<Vec<f64>>>());
+
+    // jackknife samples leave out one bin each
+    let mut reduced: Vec<Vec<f64>> = series.iter().map(|data| Vec::with_capacity(data.len())).collect();
+    let samples: Vec<f64> = (0..nbin).map(|i| {
+        for (data, reduced) in series.iter().zip(reduced.iter_mut()) {
+            reduced.clear();
+            reduced.extend_from_slice(&data[..i*binsize]);
+            reduced.extend_from_slice(&data[(i+1)*binsize..]);
+        }
+        estimator(&reduced)
+    }).collect();
+    (full, samples)
+}
+
+/// Compute the jackknife error sqrt((n-1)/n Σ (x_i - x̄)²) from n jackknife samples x_i.
+pub fn jackknife_error(samples: &[f64]) -> f64 {
+    let nbin = samples.len();
+    let mean = mean(samples);
+    let var = samples.iter().map(|x| (x-mean).powi(2)).sum::<f64>() * (nbin-1) as f64 / nbin as f64;
+    var.sqrt()
+}
+
+/// Compute an estimator of several series and its binned bootstrap error.
+/**
+ * All series must have the same length and are divided into bins of binsize measurements,
+ * a trailing incomplete bin is dropped. Each of the nresample bootstrap samples draws bins
+ * with replacement, using the same bins for all series to keep their correlations,
+ * and applies estimator to the resampled series.
+ * Returns the estimator on all complete bins and the standard deviation of the bootstrap samples.
+ */
+pub fn bootstrap<F>(series: &[&[f64]], binsize: usize, nresample: usize, rng: &mut Rng,
+                    estimator: F) -> (f64, f64)
+where F: Fn(&[Vec<f64>]) -> f64 {
+    let nbin = series[0].len() / binsize;
+    let full: Vec<Vec<f64>> = series.iter().map(|data| data[..nbin*binsize].to_vec()).collect();
+    let estimate = estimator(&full);
+
+    let mut resampled = vec![Vec::with_capacity(nbin*binsize); series.len()];
+    let samples: Vec<f64> = (0..nresample).map(|_| {
+        resampled.iter_mut().for_each(|data| data.clear());
+        for _ in 0..nbin {
+            let bin = rng.gen_index(nbin);
+            for (data, original) in resampled.iter_mut().zip(full.iter()) {
+                data.extend_from_slice(&original[bin*binsize..(bin+1)*binsize]);
+            }
+        }
+        estimator(&resampled)
+    }).collect();
+    (estimate, variance(&samples).sqrt())
+}
+
+/// Derive a jackknife bin size from the integrated autocorrelation time tau of a history of nsample measurements.
+/**
+ * Bins span at least BINSIZE_TAU τ_int so they are approximately independent but there are
+ * at least MIN_NBIN of them to keep the error estimate stable. There are at most MAX_NBIN bins
+ * because the cost of the jackknife grows with the number of bins times nsample.
+ */
+pub fn binsize(tau: f64, nsample: usize) -> usize {
+    ((BINSIZE_TAU*tau).ceil() as usize).max(nsample.div_ceil(MAX_NBIN)).min(nsample / MIN_NBIN).max(1)
+}
+
+/// Parameter S of Wolff's automatic windowing, see Autocorrelation::new.
+const WINDOW_S: f64 = 1.5;
+
+/// Integrated autocorrelation time of a Monte-Carlo history.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Autocorrelation {
+    /// Integrated autocorrelation time τ_int in units of measurements.
+    pub tau: f64,
+    /// Statistical error of tau.
+    pub error: f64,
+    /// Summation window.
+    pub window: usize,
+    /// Number of measurements.
+    pub nsample: usize,
+}
+
+impl Autocorrelation {
+    /// Estimate τ_int = 1/2 + Σ_{t=1}^W Γ(t)/Γ(0) with Wolff's automatic windowing.
+    /**
+     * See U. Wolff, Comput. Phys. Commun. 156 (2004) 143. The window W is the first t where
+     * g(t) = exp(-t/τ) - τ/sqrt(t N) becomes negative with τ = S / ln((2τ_int(t)+1) / (2τ_int(t)-1)),
+     * which balances the systematic error from truncating the sum against its statistical error.
+     * The error of τ_int is estimated as 2 τ_int sqrt((W + 1/2 - τ_int) / N).
+     * Constant data gives τ_int = 1/2.
+     */
+    pub fn new(data: &[f64]) -> Autocorrelation {
+        let n = data.len();
+        let mean = mean(data);
+        let gamma = |t: usize| -> f64 {
+            (0..n-t).map(|i| (data[i]-mean)*(data[i+t]-mean)).sum::<f64>() / (n-t) as f64
+        };
+
+        let gamma0 = gamma(0);
+        let mut tau = 0.5;
+        let mut window = 0;
+        if gamma0 > 0. {
+            for t in 1..n/2 {
+                tau += gamma(t) / gamma0;
+                window = t;
+                let tau_exp = if tau > 0.5 { WINDOW_S / ((2.*tau+1.) / (2.*tau-1.)).ln() } else { f64::MIN_POSITIVE };
+                if (-(t as f64)/tau_exp).exp() - tau_exp/((t*n) as f64).sqrt() < 0. {
+                    break;
+                }
+            }
+        }
+        // truncation at small t can produce nonsensical values for very short histories
+        let tau = tau.max(0.5);
+        let error = 2. * tau * ((window as f64 + 0.5 - tau).max(0.) / n as f64).sqrt();
+        Autocorrelation{tau, error, window, nsample: n}
+    }
+
+    /// Return the number of effectively independent measurements N / (2 τ_int).
+    pub fn effective_samples(&self) -> f64 {
+        self.nsample as f64 / (2.*self.tau)
+    }
+}
+
+/// Thermodynamic quantities at one temperature with errors as (value, error).
+pub struct Summary {
+    /// Mean energy per site.
+    pub energy: (f64, f64),
+    /// Mean absolute magnetisation per site.
+    pub abs_magnetisation: (f64, f64),
+    /// Magnetic susceptibility.
+    pub susceptibility: (f64, f64),
+    /// Specific heat per site.
+    pub specific_heat: (f64, f64),
+    /// Binder cumulant.
+    pub binder: (f64, f64),
+    /// Second-moment correlation length along x.
+    pub correlation_length: (f64, f64),
+}
+
+impl Summary {
+    /// Compute the summary of observables measured at inverse temperature beta on a lattice with nx columns.
+    /**
+     * Quantities per site are normalised to nsite, the number of occupied sites, see Configuration::nsite.
+     * Errors are estimated with a jackknife with bins of binsize measurements, except for the
+     * Binder cumulant which uses a bootstrap with nresample samples.
+     */
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(obs: &Observables, beta: f64, nx: usize, nsite: usize, binsize: usize, nresample: usize,
+               rng: &mut Rng) -> Summary {
+        let size = nsite;
+        let energy: Vec<f64> = obs.energy.iter().map(|e| e / size as f64).collect();
+        let abs_magnetisation: Vec<f64> = obs.magnetisation.iter().map(|m| m.abs()).collect();
+        // S(0) = N m²
+        let structure0: Vec<f64> = obs.magnetisation.iter().map(|m| m*m * size as f64).collect();
+        Summary{energy: jackknife(&energy, binsize, mean),
+                abs_magnetisation: jackknife(&abs_magnetisation, binsize, mean),
+                susceptibility: jackknife(&obs.magnetisation, binsize,
+                                          |m| susceptibility(m, beta, size)),
+                specific_heat: jackknife(&obs.energy, binsize, |e| specific_heat(e, beta, size)),
+                binder: bootstrap(&[&obs.magnetisation], binsize, nresample, rng,
+                                  |m| binder_cumulant(&m[0])),
+                correlation_length: jackknife_series(&[&structure0, &obs.structure], binsize,
+                                                     |s| second_moment_length(&s[0], &s[1], nx))}
+    }
+}
@@ -0,0 +1,150 @@
+/*!
+ * Least-squares fits of finite-size scaling laws.
+ *
+ * At criticality, the maximum of the susceptibility grows as χ_max ~ L^(γ/ν) and pseudo-critical
+ * temperatures like the position of that maximum shift as T(L) = Tc + a L^(-1/ν).
+ * Fits weight every point by its error σ. Errors of the fit parameters are propagated from the
+ * data either with jackknife samples of every point or with the errors σ themselves, assuming
+ * the points are independent, e.g. from separate simulations of each lattice size.
+ */
+
+/// Power law y = amplitude * x^exponent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PowerLaw {
+    /// Prefactor of the power law.
+    pub amplitude: f64,
+    /// Exponent, e.g. γ/ν for χ_max.
+    pub exponent: f64,
+    /// χ² of the fit.
+    pub chi2: f64,
+}
+
+impl PowerLaw {
+    /// Evaluate the power law at x.
+    pub fn eval(&self, x: f64) -> f64 {
+        self.amplitude * x.powf(self.exponent)
+    }
+}
+
+/// Shift of a pseudo-critical point y = limit + amplitude * x^(-exponent).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shift {
+    /// Limit x -> ∞, e.g. Tc.
+    pub limit: f64,
+    /// Prefactor of the correction.
+    pub amplitude: f64,
+    /// Exponent of the correction, e.g. 1/ν.
+    pub exponent: f64,
+    /// χ² of the fit.
+    pub chi2: f64,
+}
+
+impl Shift {
+    /// Evaluate the shift at x.
+    pub fn eval(&self, x: f64) -> f64 {
+        self.limit + self.amplitude * x.powf(-self.exponent)
+    }
+}
+
+/// Smallest exponent of the correction in fits of a shift.
+const SHIFT_EXPONENT_MIN: f64 = 0.05;
+/// Largest exponent of the correction in fits of a shift.
+const SHIFT_EXPONENT_MAX: f64 = 10.;
+/// Number of exponents on the grid that brackets the minimum of χ² in fits of a shift.
+const SHIFT_NGRID: usize = 200;
+
+/// Fit a straight line y = intercept + slope * x by weighted least squares.
+/**
+ * Returns (intercept, slope, χ²). Needs at least two distinct x.
+ */
+pub fn linear(x: &[f64], y: &[f64], sigma: &[f64]) -> (f64, f64, f64) {
+    let w: Vec<f64> = sigma.iter().map(|s| 1. / (s*s)).collect();
+    let sum = |f: &dyn Fn(usize) -> f64| (0..x.len()).map(|i| w[i] * f(i)).sum::<f64>();
+    let (s, sx, sy) = (sum(&|_| 1.), sum(&|i| x[i]), sum(&|i| y[i]));
+    let (sxx, sxy) = (sum(&|i| x[i]*x[i]), sum(&|i| x[i]*y[i]));
+    let det = s*sxx - sx*sx;
+    let intercept = (sxx*sy - sx*sxy) / det;
+    let slope = (s*sxy - sx*sy) / det;
+    (intercept, slope, sum(&|i| (y[i] - intercept - slope*x[i]).powi(2)))
+}
+
+/// Fit a power law to positive data y with errors sigma.
+/**
+ * Fits a straight line to ln y over ln x with the errors σ / y of ln y.
+ * χ² is that of the logarithmic fit.
+ */
+pub fn power_law(x: &[f64], y: &[f64], sigma: &[f64]) -> PowerLaw {
+    let ln_x: Vec<f64> = x.iter().map(|x| x.ln()).collect();
+    let ln_y: Vec<f64> = y.iter().map(|y| y.ln()).collect();
+    let ln_sigma: Vec<f64> = sigma.iter().zip(y).map(|(s, y)| s / y).collect();
+    let (intercept, slope, chi2) = linear(&ln_x, &ln_y, &ln_sigma);
+    PowerLaw{amplitude: intercept.exp(), exponent: slope, chi2}
+}
+
+/// Fit a shift to data y with errors sigma, needs at least three distinct x.
+/**
+ * For a fixed exponent, the fit is linear in limit and amplitude. The exponent minimises χ² between
+ * SHIFT_EXPONENT_MIN and SHIFT_EXPONENT_MAX, bracketed on a logarithmic grid and refined by a
+ * golden section search.
+ */
+pub fn shift(x: &[f64], y: &[f64], sigma: &[f64]) -> Shift {
+    let fit = |exponent: f64| {
+        let corrections: Vec<f64> = x.iter().map(|x| x.powf(-exponent)).collect();
+        let (limit, amplitude, chi2) = linear(&corrections, y, sigma);
+        Shift{limit, amplitude, exponent, chi2}
+    };
+    let ratio = (SHIFT_EXPONENT_MAX / SHIFT_EXPONENT_MIN).powf(1. / (SHIFT_NGRID-1) as f64);
+    let grid: Vec<f64> = (0..SHIFT_NGRID).map(|k| SHIFT_EXPONENT_MIN * ratio.powi(k as i32)).collect();
+    let best = (0..SHIFT_NGRID).min_by(|&a, &b| fit(grid[a]).chi2.total_cmp(&fit(grid[b]).chi2)).unwrap();
+
+    let (mut a, mut b) = (grid[best.saturating_sub(1)], grid[(best+1).min(SHIFT_NGRID-1)]);
+    let golden = (5_f64.sqrt() - 1.) / 2.;
+    while b - a > 1e-10 * b {
+        let (c, d) = (b - golden*(b-a), a + golden*(b-a));
+        if fit(c).chi2 < fit(d).chi2 {
+            b = d;
+        }
+        else {
+            a = c;
+        }
+    }
+    fit((a+b) / 2.)
+}
+
+/// Fit data y and propagate errors from the jackknife samples of every point.
+/**
+ * fit maps data to fit parameters. samples[i] holds the jackknife samples of y[i], see
+ * analysis::jackknife_samples, and points are assumed to be independent. Each point in turn is replaced
+ * by its samples, and the squared jackknife errors of the parameters from all points are summed.
+ * Returns (value, error) of every parameter.
+ */
+pub fn jackknife<F>(y: &[f64], samples: &[Vec<f64>], fit: F) -> Vec<(f64, f64)>
+where F: Fn(&[f64]) -> Vec<f64> {
+    let full = fit(y);
+    let mut var = vec![0.; full.len()];
+    let mut data = y.to_vec();
+    for (i, samples) in samples.iter().enumerate() {
+        let params: Vec<Vec<f64>> = samples.iter().map(|&sample| {
+            data[i] = sample;
+            fit(&data)
+        }).collect();
+        data[i] = y[i];
+        for (k, var) in var.iter_mut().enumerate() {
+            let column: Vec<f64> = params.iter().map(|p| p[k]).collect();
+            *var += super::jackknife_error(&column).powi(2);
+        }
+    }
+    full.into_iter().zip(var).map(|(value, var)| (value, var.sqrt())).collect()
+}
+
+/// Fit data y and propagate its errors sigma linearly.
+/**
+ * Like jackknife but shifts each point by its error in turn instead of using samples, and sums
+ * the squared changes of the parameters. Returns (value, error) of every parameter.
+ */
+pub fn propagate<F>(y: &[f64], sigma: &[f64], fit: F) -> Vec<(f64, f64)>
+where F: Fn(&[f64]) -> Vec<f64> {
+    let samples: Vec<Vec<f64>> = y.iter().zip(sigma).map(|(y, s)| vec![y + s, y - s]).collect();
+    // the jackknife error of two samples y ± σ is σ
+    jackknife(y, &samples, fit)
+}
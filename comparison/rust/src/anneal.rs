@@ -0,0 +1,52 @@
+/*!
+ * Simulated annealing, a single chain that follows a schedule of inverse temperatures.
+ *
+ * The chain keeps the lowest energy configuration it visits, so slow schedules that end
+ * at a large β find (approximate) ground states, e.g. of random bond instances.
+ */
+
+use crate::benchmark::Algorithm;
+use crate::{Configuration, Rng};
+
+/// Energies of one step of an annealing schedule.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Step {
+    /// Inverse temperature of the step.
+    pub beta: f64,
+    /// Mean energy over the sweeps of the step.
+    pub mean_energy: f64,
+    /// Lowest energy after any sweep of the step.
+    pub min_energy: f64,
+    /// Accepted updates per sweep and active site, see Algorithm::evolve.
+    pub acceptance: f64,
+}
+
+/// Evolve cfg with nsweep sweeps of algorithm at each inverse temperature of betas in turn.
+/**
+ * Returns the energies of every step and the configuration with the lowest energy
+ * after any sweep, which is the initial one if no sweep lowers the energy.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn anneal(cfg: &mut Configuration, energy: &mut f64, j: f64, h: f64, algorithm: Algorithm,
+              betas: &[f64], nsweep: usize, rng: &mut Rng) -> (Vec<Step>, Configuration) {
+    let mut best = cfg.clone();
+    let mut best_energy = *energy;
+    let nactive = cfg.active_sites().len();
+
+    let steps = betas.iter().map(|&beta| {
+        let mut step = Step{beta, mean_energy: 0., min_energy: f64::INFINITY, acceptance: 0.};
+        let mut naccept: usize = 0;
+        for _sweep in 0..nsweep {
+            naccept += algorithm.evolve(cfg, energy, beta, j, h, rng, 1, None);
+            step.mean_energy += *energy / nsweep as f64;
+            step.min_energy = step.min_energy.min(*energy);
+            if *energy < best_energy {
+                best_energy = *energy;
+                best.cfg.copy_from_slice(&cfg.cfg);
+            }
+        }
+        step.acceptance = naccept as f64 / (nsweep*nactive) as f64;
+        step
+    }).collect();
+    (steps, best)
+}
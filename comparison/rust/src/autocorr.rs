@@ -0,0 +1,35 @@
+/*!
+ * Integrated autocorrelation times of Monte-Carlo histories.
+ */
+
+/// Window factor of the automatic windowing procedure, see integrated_time.
+const WINDOW_FACTOR: f64 = 6.;
+
+/// Estimate the integrated autocorrelation time τ_int = 1/2 + Σ_{t=1}^W Γ(t)/Γ(0) in units of measurements.
+/**
+ * Uses Sokal's automatic windowing: the window W is the smallest t with t >= 6 τ_int(t).
+ * With this convention, τ_int = 1/2 for uncorrelated data and the error of the mean
+ * is enlarged by a factor sqrt(2 τ_int).
+ * Returns 1/2 for constant data.
+ */
+pub fn integrated_time(data: &[f64]) -> f64 {
+    let n = data.len();
+    let mean = data.iter().sum::<f64>() / n as f64;
+    let gamma = |t: usize| -> f64 {
+        (0..n-t).map(|i| (data[i]-mean)*(data[i+t]-mean)).sum::<f64>() / (n-t) as f64
+    };
+
+    let gamma0 = gamma(0);
+    if gamma0 <= 0. {
+        return 0.5;
+    }
+
+    let mut tau = 0.5;
+    for t in 1..n/2 {
+        tau += gamma(t) / gamma0;
+        if t as f64 >= WINDOW_FACTOR*tau {
+            break;
+        }
+    }
+    tau
+}
@@ -0,0 +1,228 @@
+/*!
+ * Compare the efficiency of update algorithms on the same system.
+ */
+
+use std::path::Path;
+use std::time::Instant;
+
+use crate::autocorr::integrated_time;
+use crate::checkerboard::evolve_checkerboard;
+use crate::error::{Error, OutputFile};
+use crate::kawasaki::evolve_kawasaki;
+use crate::model::{Acceptance, SweepOrder, parse_steps};
+use crate::swendsen_wang::evolve_swendsen_wang;
+use crate::wolff::evolve_wolff;
+use crate::{Configuration, Observables, Rng, RngKind, evolve_in_order, hamiltonian, info};
+
+/// Update algorithms that can be compared.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Algorithm {
+    /// Single spin flip Metropolis at random sites, see evolve.
+    Metropolis,
+    /// Wolff single cluster updates, see wolff::evolve_wolff.
+    Wolff,
+    /// Swendsen-Wang multi cluster updates, see swendsen_wang::evolve_swendsen_wang.
+    SwendsenWang,
+    /// Metropolis with parallel checkerboard sweeps, see checkerboard::evolve_checkerboard.
+    Checkerboard,
+    /// Single spin flip heat bath (Glauber dynamics) at random sites, see evolve_glauber.
+    HeatBath,
+    /// Exchanges of neighbouring spins with conserved magnetisation, see kawasaki::evolve_kawasaki.
+    Kawasaki,
+}
+
+impl Algorithm {
+    /// Parse the name of an algorithm, 'metropolis', 'wolff', 'swendsen-wang', 'checkerboard', 'heatbath',
+    /// or 'kawasaki'.
+    pub fn parse(name: &str) -> Result<Algorithm, String> {
+        match name {
+            "metropolis" => Ok(Algorithm::Metropolis),
+            "wolff" => Ok(Algorithm::Wolff),
+            "swendsen-wang" => Ok(Algorithm::SwendsenWang),
+            "checkerboard" => Ok(Algorithm::Checkerboard),
+            "heatbath" => Ok(Algorithm::HeatBath),
+            "kawasaki" => Ok(Algorithm::Kawasaki),
+            _ => Err(format!("Unknown algorithm '{}'", name)),
+        }
+    }
+
+    /// Parse a comma separated list of algorithm names, e.g. 'metropolis,wolff'.
+    pub fn parse_list(spec: &str) -> Result<Vec<Algorithm>, String> {
+        spec.split(',').map(Algorithm::parse).collect()
+    }
+
+    /// Return the name of the algorithm as accepted by parse_list.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::Metropolis => "metropolis",
+            Algorithm::Wolff => "wolff",
+            Algorithm::SwendsenWang => "swendsen-wang",
+            Algorithm::Checkerboard => "checkerboard",
+            Algorithm::HeatBath => "heatbath",
+            Algorithm::Kawasaki => "kawasaki",
+        }
+    }
+
+    /// Return true for the single spin flip algorithms at random sites, Metropolis and heat bath.
+    /**
+     * Only these support non-uniform couplings, e.g. from boundaries or random bonds.
+     */
+    pub fn is_single_flip(&self) -> bool {
+        matches!(self, Algorithm::Metropolis | Algorithm::HeatBath)
+    }
+
+    /// Perform nsweep sweeps with coupling j, measure after every sweep if obs is given.
+    /**
+     * Returns the number of flipped spins.
+     */
+    #[allow(clippy::too_many_arguments)]
+    pub fn evolve(&self, cfg: &mut Configuration, energy: &mut f64, beta: f64, j: f64, h: f64,
+                  rng: &mut Rng, nsweep: usize, obs: Option<&mut Observables>) -> usize {
+        self.evolve_in_order(SweepOrder::Random, cfg, energy, beta, j, h, rng, nsweep, obs)
+    }
+
+    /// Like evolve but the single flip algorithms visit the sites in the given order.
+    /**
+     * The other algorithms have their own order and ignore it.
+     */
+    #[allow(clippy::too_many_arguments)]
+    pub fn evolve_in_order(&self, order: SweepOrder, cfg: &mut Configuration, energy: &mut f64, beta: f64,
+                           j: f64, h: f64, rng: &mut Rng, nsweep: usize, obs: Option<&mut Observables>) -> usize {
+        match self {
+            Algorithm::Metropolis => evolve_in_order(cfg, energy, beta, j, h, Acceptance::Metropolis, order, rng,
+                                                     nsweep, obs),
+            Algorithm::Wolff => evolve_wolff(cfg, energy, beta, j, h, rng, nsweep, obs),
+            Algorithm::SwendsenWang => evolve_swendsen_wang(cfg, energy, beta, j, h, rng, nsweep, obs),
+            Algorithm::Checkerboard => evolve_checkerboard(cfg, energy, beta, j, h, rng, nsweep, obs),
+            Algorithm::HeatBath => evolve_in_order(cfg, energy, beta, j, h, Acceptance::Glauber, order, rng,
+                                                   nsweep, obs),
+            Algorithm::Kawasaki => evolve_kawasaki(cfg, energy, beta, j, h, rng, nsweep, obs),
+        }
+    }
+}
+
+/// Sweeps of several algorithms that make up one sweep of a run, e.g. 1 Metropolis sweep and 2 Wolff clusters.
+/**
+ * Mixing local and cluster updates lets clusters decorrelate large scales while single flips
+ * relax the short scales, e.g. in local fields where cluster flips are often rejected.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct UpdateSchedule {
+    /// Number of sweeps and algorithm, run in this order.
+    pub steps: Vec<(usize, Algorithm)>,
+}
+
+impl UpdateSchedule {
+    /// Create a schedule of a single sweep of algorithm.
+    pub fn single(algorithm: Algorithm) -> UpdateSchedule {
+        UpdateSchedule{steps: vec![(1, algorithm)]}
+    }
+
+    /// Parse a schedule like `metropolis: 1, wolff: 2` or `1 metropolis + 2 wolff`, see model::parse_steps.
+    /**
+     * Algorithms are named as in Algorithm::parse. kawasaki conserves the magnetisation
+     * and cannot be mixed with the other algorithms.
+     */
+    pub fn parse(spec: &str) -> Result<UpdateSchedule, String> {
+        let steps = parse_steps(spec, Algorithm::parse)?;
+        if steps.len() > 1 && steps.iter().any(|(_, algorithm)| *algorithm == Algorithm::Kawasaki) {
+            return Err(format!("Update schedule '{}' cannot mix kawasaki with other algorithms", spec));
+        }
+        Ok(UpdateSchedule{steps})
+    }
+
+    /// Return the algorithms of all steps.
+    pub fn algorithms(&self) -> impl Iterator<Item = Algorithm> + '_ {
+        self.steps.iter().map(|&(_, algorithm)| algorithm)
+    }
+
+    /// Evolve cfg by nsweep sweeps of the schedule, measure after every one of them if obs is given.
+    /**
+     * The single flip algorithms visit the sites in the given order, see Algorithm::evolve_in_order.
+     * Returns the number of flipped spins of all steps.
+     */
+    #[allow(clippy::too_many_arguments)]
+    pub fn evolve_in_order(&self, order: SweepOrder, cfg: &mut Configuration, energy: &mut f64, beta: f64,
+                           j: f64, h: f64, rng: &mut Rng, nsweep: usize, mut obs: Option<&mut Observables>) -> usize {
+        // a single algorithm runs all sweeps at once to reuse its buffers
+        if let [(1, algorithm)] = self.steps[..] {
+            return algorithm.evolve_in_order(order, cfg, energy, beta, j, h, rng, nsweep, obs);
+        }
+        let mut naccept = 0;
+        for _sweep in 0..nsweep {
+            for &(n, algorithm) in &self.steps {
+                naccept += algorithm.evolve_in_order(order, cfg, energy, beta, j, h, rng, n, None);
+            }
+            if let Some(o) = &mut obs {
+                o.measure(cfg, *energy);
+            }
+        }
+        naccept
+    }
+}
+
+/// Efficiency measures of one algorithm.
+pub struct Timing {
+    /// Sweeps per second of wall clock time.
+    pub sweeps_per_second: f64,
+    /// Integrated autocorrelation time of the energy in sweeps.
+    pub tau_energy: f64,
+    /// Integrated autocorrelation time of the absolute (staggered for J < 0) magnetisation in sweeps.
+    /**
+     * The sign of the magnetisation is meaningless in zero field and flipped by cluster updates.
+     */
+    pub tau_magnetisation: f64,
+}
+
+impl Timing {
+    /// Number of effectively independent samples per second, based on the larger τ_int.
+    pub fn effective_samples_per_second(&self) -> f64 {
+        self.sweeps_per_second / (2.*self.tau_energy.max(self.tau_magnetisation))
+    }
+}
+
+/// Thermalise and run nsweep measured sweeps with one algorithm, drawing the hot start from rng.
+#[allow(clippy::too_many_arguments)]
+pub fn measure(algorithm: Algorithm, nx: usize, ny: usize, beta: f64, j: f64, h: f64,
+               ntherm: usize, nsweep: usize, mut rng: Rng) -> Timing {
+    let mut cfg = Configuration::random(nx, ny, &mut rng);
+    let mut energy = hamiltonian(&cfg, j, h);
+    algorithm.evolve(&mut cfg, &mut energy, beta, j, h, &mut rng, ntherm, None);
+
+    let mut obs = Observables::new();
+    let start_time = Instant::now();
+    algorithm.evolve(&mut cfg, &mut energy, beta, j, h, &mut rng, nsweep, Some(&mut obs));
+    let duration = start_time.elapsed().as_secs_f64();
+    let order = if j < 0. { &obs.staggered } else { &obs.magnetisation };
+    let abs_magnetisation: Vec<f64> = order.iter().map(|m| m.abs()).collect();
+
+    Timing{sweeps_per_second: nsweep as f64 / duration,
+           tau_energy: integrated_time(&obs.energy),
+           tau_magnetisation: integrated_time(&abs_magnetisation)}
+}
+
+/// Run all algorithms with identical parameters, seed, and kind of Rng and write a comparison to `benchmark.dat`.
+/**
+ * Each line of `benchmark.dat` is
+ * `<i>: <name> <sweeps/s> <τ_int(E)> <τ_int(|M|)> <effective samples/s>`
+ * with τ_int in sweeps. The same table is printed.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn run(datadir: &Path, nx: usize, ny: usize, temp: f64, j: f64, h: f64, algorithms: &[Algorithm],
+           ntherm: usize, nsweep: usize, rng: RngKind, seed: [u8; 32]) -> Result<(), Error> {
+    info!("Benchmarking at temperature {}, coupling {}, and field {} on a {}x{} lattice",
+          temp, j, h, nx, ny);
+    info!("{:>14} {:>12} {:>10} {:>10} {:>12}", "algorithm", "sweeps/s", "tau_E", "tau_|M|", "eff/s");
+
+    let mut benchfile = OutputFile::create(&datadir.join("benchmark.dat"))?;
+    for (i, algorithm) in algorithms.iter().enumerate() {
+        let timing = measure(*algorithm, nx, ny, 1./temp, j, h, ntherm, nsweep, Rng::new(rng, seed));
+        info!("{:>14} {:>12.1} {:>10.2} {:>10.2} {:>12.1}", algorithm.name(),
+              timing.sweeps_per_second, timing.tau_energy, timing.tau_magnetisation,
+              timing.effective_samples_per_second());
+        writeln!(benchfile, "{}: {} {} {} {} {}", i, algorithm.name(),
+                 timing.sweeps_per_second, timing.tau_energy, timing.tau_magnetisation,
+                 timing.effective_samples_per_second())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,111 @@
+/*!
+ * Exact sampling with coupling from the past (Propp and Wilson).
+ *
+ * Heat bath updates are monotone for ferromagnetic couplings J > 0: if one configuration
+ * is larger than another at every site, this remains true after updating both with the
+ * same site and random number. So once the chains started from all spins up and
+ * all spins down meet, every chain has met and the common state is an exact sample.
+ */
+
+use std::path::Path;
+
+use crate::error::{Error, OutputFile};
+use crate::{Configuration, Rng, RngState, hamiltonian, info, magnetisation, neighbour_sum, write_row};
+
+/// Set the spin at site idx by heat bath at inverse temperature beta, coupling j, and field h using uniform u.
+fn heat_bath(cfg: &mut Configuration, idx: usize, beta: f64, j: f64, h: f64, u: f64) {
+    let local = cfg.local_field.as_ref().map_or(0., |field| field[idx]);
+    let field = j*neighbour_sum(cfg, idx) as f64 + h + local;
+    let prob_up = 1. / (1. + (-2.*beta*field).exp());
+    cfg[idx] = if u < prob_up { 1 } else { -1 };
+}
+
+/// An exact sample and the number of sweeps into the past that were needed.
+pub struct Sample {
+    /// The sampled configuration.
+    pub cfg: Configuration,
+    /// Number of sweeps from the start of the chains to time 0.
+    pub sweeps: usize,
+}
+
+/// Draw an exact equilibrium sample of an nx*ny lattice at inverse temperature beta, coupling j, and field h.
+/**
+ * j must be positive.
+ * Starts the extremal chains 1, 2, 4, ... sweeps in the past, reusing the random
+ * numbers of later times, until they coalesce at time 0. Only keeps the state of a
+ * generator split off rng per block of sweeps and draws its moves again for each start,
+ * so the memory does not grow with the number of sweeps.
+ * Returns None if they have not coalesced after maxsweep sweeps.
+ * This happens at low temperatures where the time to coalesce grows exponentially
+ * with the size of the lattice.
+ */
+pub fn sample(nx: usize, ny: usize, beta: f64, j: f64, h: f64, rng: &mut Rng,
+              maxsweep: usize) -> Option<Sample> {
+    assert!(j > 0., "Coupling from the past requires a ferromagnetic coupling");
+    let size = nx*ny;
+    // blocks[k] holds the state of the generator of the moves from -2^k to -2^(k-1) sweeps
+    // (-1 to 0 for k = 0), the moves are drawn again from it for every restart
+    let mut blocks: Vec<RngState> = Vec::new();
+    let nsweep = |k: usize| if k == 0 { 1 } else { 1 << (k-1) };
+
+    loop {
+        blocks.push(rng.split().state());
+        let total = 1 << (blocks.len()-1);
+
+        let mut top = Configuration::cold(nx, ny, 1);
+        let mut bottom = Configuration::cold(nx, ny, -1);
+        for (k, state) in blocks.iter().enumerate().rev() {
+            let mut moves = Rng::from_state(*state);
+            for _ in 0..nsweep(k)*size {
+                let (idx, u) = (moves.gen_index(size), moves.gen_real());
+                heat_bath(&mut top, idx, beta, j, h, u);
+                heat_bath(&mut bottom, idx, beta, j, h, u);
+            }
+        }
+
+        if top.cfg == bottom.cfg {
+            return Some(Sample{cfg: top, sweeps: total});
+        }
+        if 2*total > maxsweep {
+            return None;
+        }
+    }
+}
+
+/// Draw nsample exact samples at each temperature and write their observables to datadir.
+/**
+ * Writes `<idx>_cftp.dat` for each temperature with rows energy, magnetisation, and
+ * number of sweeps needed for coalescence of each sample. Stops at a temperature as soon
+ * as one sample does not coalesce within maxsweep sweeps, the file then only contains
+ * the samples drawn so far.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn run(datadir: &Path, nx: usize, ny: usize, temperatures: &[f64], j: f64, h: f64,
+           nsample: usize, maxsweep: usize, rng: &mut Rng) -> Result<(), Error> {
+    for (i, temp) in temperatures.iter().enumerate() {
+        info!("Drawing {} exact samples at temperature {}", nsample, temp);
+        let beta = 1./temp;
+        let mut energy = Vec::new();
+        let mut mag = Vec::new();
+        let mut sweeps = Vec::new();
+        for _ in 0..nsample {
+            match sample(nx, ny, beta, j, h, rng, maxsweep) {
+                Some(s) => {
+                    energy.push(hamiltonian(&s.cfg, j, h));
+                    mag.push(magnetisation(&s.cfg));
+                    sweeps.push(s.sweeps as f64);
+                }
+                None => {
+                    info!("  No coalescence within {} sweeps", maxsweep);
+                    break;
+                }
+            }
+        }
+
+        let mut cftpfile = OutputFile::create(&datadir.join(format!("{}_cftp.dat", i)))?;
+        write_row(&mut cftpfile, &energy)?;
+        write_row(&mut cftpfile, &mag)?;
+        write_row(&mut cftpfile, &sweeps)?;
+    }
+    Ok(())
+}
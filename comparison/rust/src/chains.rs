@@ -0,0 +1,93 @@
+/*!
+ * Independent Markov chains that run in parallel threads.
+ *
+ * All chains share the lattice and local fields but have their own spins and random stream,
+ * so their histories can be concatenated into one larger sample.
+ */
+
+use crate::{Configuration, Observables, Rng, change_field, hamiltonian};
+use crate::benchmark::UpdateSchedule;
+use crate::model::SweepOrder;
+use crate::schedule::Schedule;
+
+/// State of one Markov chain.
+pub struct Chain {
+    /// Current configuration.
+    pub cfg: Configuration,
+    /// Energy of cfg, recomputed at the start of each run.
+    pub energy: f64,
+    /// Random stream of this chain.
+    pub rng: Rng,
+}
+
+impl Chain {
+    /// Create a chain with a hot start on the lattice and local fields of cfg.
+    pub fn new(cfg: &Configuration, rng: Rng) -> Chain {
+        let mut chain = Chain::from_configuration(cfg.clone(), rng);
+        for &idx in chain.cfg.active_sites().to_vec().iter() {
+            chain.cfg[idx] = chain.rng.gen_spin();
+        }
+        chain
+    }
+
+    /// Create a chain that starts from the spins of cfg.
+    pub fn from_configuration(cfg: Configuration, rng: Rng) -> Chain {
+        Chain{cfg, energy: 0., rng}
+    }
+
+    /// Thermalise for ntherm sweeps at the initial field and measure during nprod production sweeps.
+    /**
+     * The field changes during production according to field like in the main temperature scan.
+     * Every sweep runs all steps of updates, see UpdateSchedule::evolve_in_order.
+     * Returns the measured observables and the number of accepted production flips.
+     */
+    #[allow(clippy::too_many_arguments)]
+    pub fn run(&mut self, updates: &UpdateSchedule, order: SweepOrder, beta: f64, j: f64, field: &dyn Schedule,
+               ntherm: usize, nprod: usize) -> (Observables, usize) {
+        let h0 = field.start();
+        self.energy = hamiltonian(&self.cfg, j, h0);
+        updates.evolve_in_order(order, &mut self.cfg, &mut self.energy, beta, j, h0, &mut self.rng, ntherm, None);
+
+        let mut obs = Observables::new();
+        let mut naccept = 0;
+        for sweep in 0..nprod {
+            let h = change_field(&self.cfg, &mut self.energy, field, sweep, nprod);
+            naccept += updates.evolve_in_order(order, &mut self.cfg, &mut self.energy, beta, j, h, &mut self.rng, 1,
+                                               Some(&mut obs));
+        }
+        (obs, naccept)
+    }
+}
+
+/// Run f on every chain in a separate thread and return the results in the order of chains.
+pub fn run_parallel<F, R>(chains: &mut [Chain], f: F) -> Vec<R>
+where F: Fn(&mut Chain) -> R + Sync, R: Send {
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = chains.iter_mut().map(|chain| scope.spawn(|| f(chain))).collect();
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    })
+}
+
+/// Concatenate histories of several chains.
+/**
+ * Returns the merged history and the index of the chain of each measurement.
+ * Histories of additional observables are concatenated by the names of the observables.
+ */
+pub fn merge(histories: Vec<Observables>) -> (Observables, Vec<usize>) {
+    let mut merged = Observables::new();
+    let mut ids = Vec::new();
+    for (id, obs) in histories.into_iter().enumerate() {
+        ids.extend(std::iter::repeat_n(id, obs.energy.len()));
+        merged.energy.extend(obs.energy);
+        merged.magnetisation.extend(obs.magnetisation);
+        merged.staggered.extend(obs.staggered);
+        merged.structure.extend(obs.structure);
+        for series in obs.custom {
+            match merged.custom.iter_mut().find(|merged| merged.name() == series.name()) {
+                Some(merged) => merged.values.extend(series.values),
+                None => merged.custom.push(series),
+            }
+        }
+    }
+    (merged, ids)
+}
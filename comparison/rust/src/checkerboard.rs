@@ -0,0 +1,279 @@
+/*!
+ * Checkerboard (red-black) Metropolis sweeps.
+ *
+ * Sites with even x+y (black) only have odd (white) neighbours and vice versa, so all
+ * sites of one colour can be updated at the same time. A sweep visits all black sites
+ * and then all white sites in parallel threads. Each block of ROWS_PER_STREAM rows draws from
+ * its own substream of the sweep, see Rng::split, and threads update whole blocks, which makes
+ * the result independent of the number of threads.
+ *
+ * On periodic square lattices in zero field, rows are updated with vectorised neighbour sums of the whole
+ * row and a batch of random numbers per row, see neighbour_products. The avx2 feature adds an AVX2
+ * version of the neighbour sums that is used if the CPU supports it.
+ */
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::model::Acceptance;
+use crate::{BondEnergy, Configuration, Observables, Rng, delta_e, has_bond_energy};
+
+/// Smallest number of sites of one colour per thread, below this, threads cost more than they save.
+const MIN_SITES_PER_THREAD: usize = 4096;
+
+/// Number of rows that draw from one random stream of a sweep, the largest number of threads is ny over this.
+pub(crate) const ROWS_PER_STREAM: usize = 16;
+
+/// Largest number of threads of a sweep set with set_max_threads, 0 for the available parallelism.
+static MAX_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// Use at most nthreads threads in all following sweeps, 0 to use the available parallelism of the machine.
+pub fn set_max_threads(nthreads: usize) {
+    MAX_THREADS.store(nthreads, Ordering::Relaxed);
+}
+
+/// Return the largest number of threads of a sweep.
+fn max_threads() -> usize {
+    match MAX_THREADS.load(Ordering::Relaxed) {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+}
+
+/// Return true if checkerboard sweeps are valid on an nx*ny lattice, i.e. a 2D lattice with even nx and ny.
+/**
+ * Odd extents have no checkerboard decomposition with periodic boundaries. On a chain, flips
+ * with ΔE = 0 are always accepted, so domain walls move in lockstep and never annihilate,
+ * which makes the sweeps non-ergodic.
+ */
+pub fn is_supported(nx: usize, ny: usize) -> bool {
+    ny > 1 && nx.is_multiple_of(2) && ny.is_multiple_of(2)
+}
+
+/// Decide which sites of the given colour in row y to flip with Metropolis.
+/**
+ * Only reads cfg, the decisions are independent because sites of one colour are not neighbours.
+ * Returns the indices of the sites to flip and their changes in energy.
+ */
+#[allow(clippy::too_many_arguments)]
+fn row_flips(cfg: &Configuration, active: &[bool], y: usize, colour: usize, beta: f64, j: f64, h: f64,
+             rng: &mut Rng) -> Vec<(usize, f64)> {
+    let mut flips = Vec::new();
+    for x in ((y+colour) % 2..cfg.nx).step_by(2) {
+        let idx = y*cfg.nx + x;
+        if !active[idx] {
+            continue;
+        }
+        let delta = delta_e(cfg, idx, j, h);
+        if delta <= 0. || (-beta*delta).exp() > rng.gen_real() {
+            flips.push((idx, delta));
+        }
+    }
+    flips
+}
+
+/// Like row_flips on a periodic square lattice in zero field, see Configuration::is_periodic_square.
+/**
+ * Computes k = s_i Σ_j s_j for the whole row with neighbour_products and draws the random numbers of all
+ * sites of the colour up front, then looks up the acceptance probabilities of k = -4, ..., 4 in probabilities.
+ */
+pub(crate) fn row_flips_square(cfg: &Configuration, y: usize, colour: usize, j: f64, probabilities: &[f64],
+                    rng: &mut Rng) -> Vec<(usize, f64)> {
+    let mut randoms = vec![0.; cfg.nx/2];
+    rng.fill_reals(&mut randoms);
+    let mut products = vec![0; cfg.nx];
+    neighbour_products(cfg, y, &mut products);
+
+    let first = (y+colour) % 2;
+    let mut flips = vec![(0, 0.); cfg.nx/2];
+    // write every candidate and only keep the accepted ones, unpredictable branches cost more than the stores
+    let mut naccept = 0;
+    for (i, &random) in randoms.iter().enumerate() {
+        let x = first + 2*i;
+        let k = products[x];
+        flips[naccept] = (y*cfg.nx + x, 2.*(j*k as f64));
+        naccept += (probabilities[(k+4) as usize] > random) as usize;
+    }
+    flips.truncate(naccept);
+    flips
+}
+
+/// Write k = s_i Σ_j s_j of all sites of row y to products, cfg must be on a periodic square lattice.
+/**
+ * Adds up the rows above and below and the row itself shifted left and right instead of looking up the
+ * neighbours of every site, so the loop vectorises. products must have length nx.
+ * See Configuration::is_periodic_square for the required lattice.
+ */
+pub fn neighbour_products(cfg: &Configuration, y: usize, products: &mut [i32]) {
+    let (nx, ny) = (cfg.nx, cfg.ny);
+    assert_eq!(products.len(), nx, "Need one product per site of the row");
+    let row = &cfg.cfg[y*nx..(y+1)*nx];
+    let above = &cfg.cfg[(y+1) % ny * nx..][..nx];
+    let below = &cfg.cfg[(y+ny-1) % ny * nx..][..nx];
+
+    // the neighbours of the first and last sites wrap around
+    for x in [0, nx-1] {
+        products[x] = row[x] * (row[(x+1) % nx] + row[(x+nx-1) % nx] + above[x] + below[x]);
+    }
+    if nx > 2 {
+        interior_products(row, above, below, &mut products[1..nx-1]);
+    }
+}
+
+/// Write the products of the sites x = 1, ..., nx-2 of row to out, see neighbour_products.
+fn interior_products(row: &[i32], above: &[i32], below: &[i32], out: &mut [i32]) {
+    #[cfg(all(feature = "avx2", target_arch = "x86_64"))]
+    if is_x86_feature_detected!("avx2") {
+        // safe because the CPU supports AVX2
+        unsafe { avx2::interior_products(row, above, below, out) };
+        return;
+    }
+
+    let sites = row[1..].iter().zip(&row[2..]).zip(&row[..out.len()]).zip(&above[1..]).zip(&below[1..]);
+    for (k, ((((site, right), left), up), down)) in out.iter_mut().zip(sites) {
+        *k = site * (right + left + up + down);
+    }
+}
+
+/// Neighbour sums with AVX2 intrinsics.
+#[cfg(all(feature = "avx2", target_arch = "x86_64"))]
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    /// Number of i32 in an AVX2 register.
+    const LANES: usize = 8;
+
+    /// Like super::interior_products but with LANES sites at a time.
+    /**
+     * # Safety
+     * The CPU must support AVX2.
+     */
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn interior_products(row: &[i32], above: &[i32], below: &[i32], out: &mut [i32]) {
+        let n = out.len();
+        let full = n - n % LANES;
+        for i in (0..full).step_by(LANES) {
+            // the slices check the bounds of the unaligned loads and stores
+            let load = |values: &[i32]| _mm256_loadu_si256(values[..LANES].as_ptr() as *const __m256i);
+            let sum = _mm256_add_epi32(_mm256_add_epi32(load(&row[i+2..]), load(&row[i..])),
+                                       _mm256_add_epi32(load(&above[i+1..]), load(&below[i+1..])));
+            let products = _mm256_mullo_epi32(load(&row[i+1..]), sum);
+            _mm256_storeu_si256(out[i..i+LANES].as_mut_ptr() as *mut __m256i, products);
+        }
+        for i in full..n {
+            out[i] = row[i+1] * (row[i+2] + row[i] + above[i+1] + below[i+1]);
+        }
+    }
+}
+
+/// Sites of a lattice as seen by checkerboard sweeps, the same for all sweeps of a configuration.
+enum Sites {
+    /// A periodic square lattice in zero field, rows are updated with row_flips_square.
+    Square,
+    /// Any other lattice in any field with true for the sites that are updated, see row_flips.
+    Active(Vec<bool>),
+}
+
+impl Sites {
+    /// Classify the lattice of cfg in field h.
+    fn of(cfg: &Configuration, h: f64) -> Sites {
+        if h == 0. && cfg.local_field.is_none() && cfg.is_periodic_square() {
+            return Sites::Square;
+        }
+        let mut active = vec![false; cfg.size()];
+        cfg.active_sites().iter().for_each(|&idx| active[idx] = true);
+        Sites::Active(active)
+    }
+}
+
+/// Return the acceptance probabilities of k = s_i Σ_j s_j = -4, ..., 4 for row_flips_square.
+pub(crate) fn square_probabilities(beta: f64, j: f64) -> Vec<f64> {
+    // capped so that ΔE = 0 is accepted at β = ∞
+    (-4..=4).map(|k| Acceptance::Metropolis.probability(beta, 2.*(j*k as f64)).min(1.)).collect()
+}
+
+/// Perform one checkerboard sweep with coupling j, first over black and then over white sites.
+/**
+ * The lattice of cfg must be supported, see is_supported. Pinned sites are not updated.
+ * Returns the number of accepted flips.
+ */
+pub fn checkerboard_sweep(cfg: &mut Configuration, energy: &mut f64, beta: f64, j: f64, h: f64,
+                          rng: &mut Rng) -> usize {
+    assert!(is_supported(cfg.nx, cfg.ny), "Checkerboard sweeps need a 2D lattice with even nx and ny");
+    let sites = Sites::of(cfg, h);
+    sweep(cfg, &sites, energy, beta, j, h, rng)
+}
+
+/// Like checkerboard_sweep with the sites of cfg classified already.
+#[allow(clippy::too_many_arguments)]
+fn sweep(cfg: &mut Configuration, sites: &Sites, energy: &mut f64, beta: f64, j: f64, h: f64,
+         rng: &mut Rng) -> usize {
+    let nthreads = max_threads().min(cfg.size() / 2 / MIN_SITES_PER_THREAD).max(1);
+    let probabilities = square_probabilities(beta, j);
+
+    // one stream per block of rows for both colours
+    let mut streams: Vec<Rng> = (0..cfg.ny.div_ceil(ROWS_PER_STREAM)).map(|_| rng.split()).collect();
+    let nthreads = nthreads.min(streams.len());
+
+    let mut naccept = 0;
+    for colour in 0..2 {
+        let flips_of = |cfg: &Configuration, y: usize, rng: &mut Rng| match sites {
+            Sites::Square => row_flips_square(cfg, y, colour, j, &probabilities, rng),
+            Sites::Active(active) => row_flips(cfg, active, y, colour, beta, j, h, rng),
+        };
+        let block_flips = |cfg: &Configuration, block: usize, rng: &mut Rng| {
+            (block*ROWS_PER_STREAM..cfg.ny.min((block+1)*ROWS_PER_STREAM))
+                .flat_map(|y| flips_of(cfg, y, rng)).collect::<Vec<_>>()
+        };
+        let flips: Vec<(usize, f64)> = if nthreads == 1 {
+            streams.iter_mut().enumerate().flat_map(|(block, rng)| block_flips(cfg, block, rng)).collect()
+        } else {
+            let (cfg, block_flips) = (&*cfg, &block_flips);
+            let chunk = streams.len().div_ceil(nthreads);
+            thread::scope(|scope| {
+                let handles: Vec<_> = streams.chunks_mut(chunk).enumerate().map(|(c, streams)| scope.spawn(move || {
+                    streams.iter_mut().enumerate()
+                        .flat_map(|(i, rng)| block_flips(cfg, c*chunk + i, rng)).collect::<Vec<_>>()
+                })).collect();
+                handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+            })
+        };
+
+        // flips of one colour do not interact, so the changes in energy add up
+        for &(idx, delta) in flips.iter() {
+            cfg[idx] *= -1;
+            *energy += delta;
+        }
+        naccept += flips.len();
+    }
+    naccept
+}
+
+/// Evolve a configuration in Monte-Carlo time with checkerboard sweeps with coupling j.
+/**
+ * Measures observables once per sweep if obs is given.
+ * In zero field with uniform couplings, the energy is tracked exactly as a BondEnergy.
+ * Returns the number of accepted flips.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn evolve_checkerboard(cfg: &mut Configuration, energy: &mut f64, beta: f64, j: f64, h: f64,
+                           rng: &mut Rng, nsweep: usize, mut obs: Option<&mut Observables>) -> usize {
+    let mut naccept: usize = 0;
+    let mut bond_energy = has_bond_energy(cfg, h).then(|| BondEnergy::from_energy(*energy, j));
+    assert!(is_supported(cfg.nx, cfg.ny), "Checkerboard sweeps need a 2D lattice with even nx and ny");
+    let sites = Sites::of(cfg, h);
+
+    for _sweep in 0..nsweep {
+        let before = *energy;
+        naccept += sweep(cfg, &sites, energy, beta, j, h, rng);
+        if let Some(bond_energy) = bond_energy.as_mut() {
+            bond_energy.track(before, energy);
+        }
+
+        if let Some(o) = &mut obs {
+            o.measure(cfg, *energy);
+        }
+    }
+
+    naccept
+}
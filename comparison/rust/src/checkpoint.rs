@@ -0,0 +1,195 @@
+/*!
+ * Checkpoints of temperature scans to resume them after a crash.
+ *
+ * A checkpoint holds everything that changes during a scan: the spins, the energy,
+ * the positions of the random streams, and the measurements of the current temperature.
+ * Files are little endian binary, starting with MAGIC, and are replaced atomically.
+ */
+
+use std::fs;
+use std::path::Path;
+
+use crate::{Observables, RngState};
+
+/// First bytes of a checkpoint file, including the format version.
+const MAGIC: &[u8; 8] = b"ISINGCK4";
+
+/// State of a temperature scan.
+#[derive(Debug, PartialEq)]
+pub struct Checkpoint {
+    /// Run parameters as TOML, see config::RunParams::to_toml, to detect resuming with different parameters.
+    pub params: String,
+    /// Index of the current temperature.
+    pub temperature: usize,
+    /// Number of completed production sweeps at the current temperature.
+    /**
+     * 0 means that the temperature has not been thermalised yet.
+     */
+    pub sweep: usize,
+    /// Spins of the configuration.
+    pub spins: Vec<i32>,
+    /// Local field of the configuration.
+    pub local_field: Option<Vec<f64>>,
+    /// Energy of the configuration as tracked by the updates.
+    pub energy: f64,
+    /// State of the random stream of the Markov chain.
+    pub rng: RngState,
+    /// State of the random stream of the analysis.
+    pub analysis_rng: RngState,
+    /// Measurements of the current temperature.
+    pub obs: Observables,
+}
+
+impl Checkpoint {
+    /// Write the checkpoint to fname, replacing any existing file only once writing succeeded.
+    pub fn write(&self, fname: &Path) -> Result<(), String> {
+        let mut bytes = MAGIC.to_vec();
+        put_bytes(&mut bytes, self.params.as_bytes());
+        put_u64(&mut bytes, self.temperature as u64);
+        put_u64(&mut bytes, self.sweep as u64);
+        put_u64(&mut bytes, self.spins.len() as u64);
+        self.spins.iter().for_each(|&s| bytes.push(s as i8 as u8));
+        match &self.local_field {
+            Some(field) => {
+                bytes.push(1);
+                put_f64s(&mut bytes, field);
+            }
+            None => bytes.push(0),
+        }
+        bytes.extend_from_slice(&self.energy.to_le_bytes());
+        for state in [&self.rng, &self.analysis_rng] {
+            put_rng_state(&mut bytes, state);
+        }
+        put_f64s(&mut bytes, &self.obs.energy);
+        put_f64s(&mut bytes, &self.obs.magnetisation);
+        put_f64s(&mut bytes, &self.obs.staggered);
+        put_f64s(&mut bytes, &self.obs.structure);
+
+        let tmpname = fname.with_extension("tmp");
+        fs::write(&tmpname, &bytes)
+            .and_then(|_| fs::rename(&tmpname, fname))
+            .map_err(|err| format!("Cannot write checkpoint '{}': {}", fname.display(), err))
+    }
+
+    /// Read a checkpoint written by Checkpoint::write.
+    pub fn read(fname: &Path) -> Result<Checkpoint, String> {
+        let bytes = fs::read(fname)
+            .map_err(|err| format!("Cannot read checkpoint '{}': {}", fname.display(), err))?;
+        Checkpoint::decode(&bytes)
+            .ok_or_else(|| format!("'{}' is not a valid checkpoint", fname.display()))
+    }
+
+    /// Decode the contents of a checkpoint file, returns None if they are malformed.
+    fn decode(bytes: &[u8]) -> Option<Checkpoint> {
+        let mut reader = Reader{bytes};
+        if reader.take(MAGIC.len())? != MAGIC {
+            return None;
+        }
+        let params = String::from_utf8(reader.bytes_field()?.to_vec()).ok()?;
+        let temperature = reader.u64()? as usize;
+        let sweep = reader.u64()? as usize;
+        let nspin = reader.u64()? as usize;
+        let spins = reader.take(nspin)?.iter().map(|&b| b as i8 as i32).collect();
+        let local_field = match reader.take(1)?[0] {
+            0 => None,
+            _ => Some(reader.f64s()?),
+        };
+        let energy = reader.f64()?;
+        let (rng, analysis_rng) = (reader.rng_state()?, reader.rng_state()?);
+        let obs = Observables{energy: reader.f64s()?, magnetisation: reader.f64s()?, staggered: reader.f64s()?,
+                              structure: reader.f64s()?, custom: Vec::new()};
+        if !reader.bytes.is_empty() {
+            return None;
+        }
+        Some(Checkpoint{params, temperature, sweep, spins, local_field, energy,
+                        rng, analysis_rng, obs})
+    }
+}
+
+/// Append a u64.
+fn put_u64(bytes: &mut Vec<u8>, x: u64) {
+    bytes.extend_from_slice(&x.to_le_bytes());
+}
+
+/// Append a length prefixed byte string.
+fn put_bytes(bytes: &mut Vec<u8>, data: &[u8]) {
+    put_u64(bytes, data.len() as u64);
+    bytes.extend_from_slice(data);
+}
+
+/// Append a length prefixed array of f64.
+fn put_f64s(bytes: &mut Vec<u8>, data: &[f64]) {
+    put_u64(bytes, data.len() as u64);
+    data.iter().for_each(|x| bytes.extend_from_slice(&x.to_le_bytes()));
+}
+
+/// Append the state of an Rng as a tag byte, 0 for xoshiro256++ and 1 for ChaCha12, and the state.
+fn put_rng_state(bytes: &mut Vec<u8>, state: &RngState) {
+    match state {
+        RngState::Xoshiro(s) => {
+            bytes.push(0);
+            s.iter().for_each(|&word| put_u64(bytes, word));
+        }
+        RngState::ChaCha{seed, stream, word_pos} => {
+            bytes.push(1);
+            bytes.extend_from_slice(seed);
+            put_u64(bytes, *stream);
+            bytes.extend_from_slice(&word_pos.to_le_bytes());
+        }
+    }
+}
+
+/// Read fields from the front of a byte string.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// Remove n bytes from the front.
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if n > self.bytes.len() {
+            return None;
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Some(head)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn f64(&mut self) -> Option<f64> {
+        Some(f64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn bytes_field(&mut self) -> Option<&'a [u8]> {
+        let n = self.u64()? as usize;
+        self.take(n)
+    }
+
+    /// Read a state written by put_rng_state.
+    fn rng_state(&mut self) -> Option<RngState> {
+        match self.take(1)?[0] {
+            0 => {
+                let s = [self.u64()?, self.u64()?, self.u64()?, self.u64()?];
+                // xoshiro256++ cannot leave the all zero state
+                (s != [0; 4]).then_some(RngState::Xoshiro(s))
+            }
+            1 => {
+                let seed = self.take(32)?.try_into().ok()?;
+                let stream = self.u64()?;
+                let word_pos = u128::from_le_bytes(self.take(16)?.try_into().ok()?);
+                Some(RngState::ChaCha{seed, stream, word_pos})
+            }
+            _ => None,
+        }
+    }
+
+    fn f64s(&mut self) -> Option<Vec<f64>> {
+        let n = self.u64()? as usize;
+        Some(self.take(n.checked_mul(8)?)?.chunks_exact(8)
+             .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+             .collect())
+    }
+}
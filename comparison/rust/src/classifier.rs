@@ -0,0 +1,70 @@
+/*!
+ * Feed configuration snapshots through an external classifier.
+ *
+ * The classifier runs as a separate process, e.g. scripts/onnx_classifier.py
+ * which evaluates an ONNX model with onnxruntime. This keeps machine learning
+ * runtimes out of the build of the simulation.
+ */
+
+use std::io;
+use std::io::prelude::*;
+use std::io::{BufReader, BufWriter};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use crate::Configuration;
+
+/// Handle to a running classifier process.
+/**
+ * For every snapshot, the spins are written to the standard input of the process
+ * as a single line of space separated values (+1/-1) in row major order (x runs fastest).
+ * The process must answer with a single line of space separated numbers on its standard output,
+ * e.g. the predicted probabilities of each phase.
+ * The lattice shape is passed in environment variables ISING_NX and ISING_NY.
+ */
+pub struct Classifier {
+    child: Child,
+    stdin: Option<BufWriter<ChildStdin>>,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Classifier {
+    /// Start the classifier by running command in a shell.
+    pub fn spawn(command: &str, nx: usize, ny: usize) -> io::Result<Classifier> {
+        let mut child = Command::new("sh")
+            .arg("-c").arg(command)
+            .env("ISING_NX", nx.to_string())
+            .env("ISING_NY", ny.to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().map(BufWriter::new);
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Ok(Classifier{child, stdin, stdout})
+    }
+
+    /// Send a configuration to the classifier and return its output.
+    pub fn classify(&mut self, cfg: &Configuration) -> io::Result<Vec<f64>> {
+        let stdin = self.stdin.as_mut().unwrap();
+        let line: Vec<String> = cfg.cfg.iter().map(|s| s.to_string()).collect();
+        writeln!(stdin, "{}", line.join(" "))?;
+        stdin.flush()?;
+
+        let mut answer = String::new();
+        if self.stdout.read_line(&mut answer)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                      "Classifier terminated unexpectedly"));
+        }
+        answer.split_whitespace()
+            .map(|x| x.parse::<f64>().map_err(|_| io::Error::new(
+                io::ErrorKind::InvalidData, format!("Invalid classifier output '{}'", x))))
+            .collect()
+    }
+}
+
+impl Drop for Classifier {
+    /// Close the input of the process and wait for it to finish.
+    fn drop(&mut self) {
+        self.stdin = None;
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,94 @@
+/*!
+ * Geometric clusters of aligned spins.
+ *
+ * Unlike the Fortuin-Kasteleyn clusters of wolff and swendsen_wang, geometric clusters contain all aligned
+ * neighbours, see ClusterDistribution for their statistics during a run.
+ */
+
+use crate::Configuration;
+
+/// Return the sizes of all geometric clusters of sites with the given spin.
+/**
+ * A cluster is a maximal set of nearest neighbours that all have the same spin.
+ * Clusters are found via flood-fill and returned in no particular order.
+ */
+pub fn cluster_sizes(cfg: &Configuration, spin: i32) -> Vec<usize> {
+    let mut visited = vec![false; cfg.size()];
+    let mut stack = Vec::new();
+    let mut sizes = Vec::new();
+
+    for start in 0..cfg.size() {
+        if visited[start] || cfg[start] != spin {
+            continue;
+        }
+
+        let mut size = 0;
+        visited[start] = true;
+        stack.push(start);
+        while let Some(site) = stack.pop() {
+            size += 1;
+            for &neigh in cfg.neighbours_of(site) {
+                if !visited[neigh] && cfg[neigh] == spin {
+                    visited[neigh] = true;
+                    stack.push(neigh);
+                }
+            }
+        }
+        sizes.push(size);
+    }
+
+    sizes
+}
+
+/// Return the size of the largest cluster of sites with the given spin, 0 if there is none.
+pub fn largest_cluster(cfg: &Configuration, spin: i32) -> usize {
+    cluster_sizes(cfg, spin).into_iter().max().unwrap_or(0)
+}
+
+/// Accumulate the distribution of the sizes of geometric clusters of both spins.
+/**
+ * Near Tc, the number n_s of clusters of size s per site follows a power law and the largest cluster
+ * starts to span the lattice, like in percolation. Vacancies belong to no cluster.
+ */
+pub struct ClusterDistribution {
+    /// Σ over measurements of the number of clusters of size s at index s-1.
+    counts: Vec<usize>,
+    /// Size of the largest cluster of either spin per occupied site after each measurement.
+    largest: Vec<f64>,
+    /// Number of occupied sites.
+    nsite: usize,
+}
+
+impl ClusterDistribution {
+    /// Create an accumulator for a lattice with nsite occupied sites.
+    pub fn new(nsite: usize) -> ClusterDistribution {
+        ClusterDistribution{counts: Vec::new(), largest: Vec::new(), nsite}
+    }
+
+    /// Add the clusters of cfg.
+    pub fn measure(&mut self, cfg: &Configuration) {
+        let mut largest = 0;
+        for size in cluster_sizes(cfg, 1).into_iter().chain(cluster_sizes(cfg, -1)) {
+            if size > self.counts.len() {
+                self.counts.resize(size, 0);
+            }
+            self.counts[size-1] += 1;
+            largest = largest.max(size);
+        }
+        self.largest.push(largest as f64 / self.nsite as f64);
+    }
+
+    /// Return n_s, the number of clusters of size s per site averaged over all measurements, for s = 1, 2, ...
+    /**
+     * Ends with the largest size that was seen, Σ_s s n_s = 1.
+     */
+    pub fn distribution(&self) -> Vec<f64> {
+        let norm = (self.largest.len()*self.nsite) as f64;
+        self.counts.iter().map(|&count| count as f64 / norm).collect()
+    }
+
+    /// Return the size of the largest cluster per site of every measurement.
+    pub fn largest(&self) -> &[f64] {
+        &self.largest
+    }
+}
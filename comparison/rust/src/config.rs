@@ -0,0 +1,716 @@
+/*!
+ * Run parameters and reading them from configuration files.
+ *
+ * Configuration files use a subset of TOML: `key = value` pairs with integers, floats,
+ * strings, booleans, and (possibly multi-line) arrays of those, plus `#` comments.
+ * Tables are not supported.
+ */
+
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rand::prelude::*;
+
+use crate::RngKind;
+use crate::error::{Error, write_file};
+
+/// Parameters of the lattice, the Markov chain, and the output of a run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunParams {
+    /// Number of lattice sites in x direction.
+    pub nx: usize,
+    /// Number of lattice sites in y direction, 1 for a chain.
+    pub ny: usize,
+    /// Number of thermalisation sweeps in the beginning.
+    pub ntherm_init: usize,
+    /// Number of thermalisation sweeps per temperature.
+    pub ntherm: usize,
+    /// Number of production sweeps per temperature.
+    pub nprod: usize,
+    /// Temperatures to run at.
+    pub temperatures: Vec<f64>,
+    /// Seed of the random number generator.
+    pub seed: [u8; 32],
+    /// Algorithm of the random number generator.
+    pub rng: RngKind,
+    /// Updates of one sweep, see benchmark::UpdateSchedule::parse and heisenberg::UpdateSchedule::parse.
+    /**
+     * None runs the single algorithm given with --algorithm or update given with --heisenberg.
+     */
+    pub updates: Option<String>,
+    /// Names of additional observables to measure in temperature scans, see observable::Registry.
+    pub observables: Vec<String>,
+    /// Directory to write output files to.
+    pub datadir: PathBuf,
+    /// Constants of the individual modes.
+    pub constants: Constants,
+}
+
+/// Define Constants with the given fields and their documentation.
+/**
+ * Every field is a key of configuration files and an argument `--<key>` with `-` in place of `_`.
+ */
+macro_rules! constants {
+    ($($name:ident: $type:ty = $doc:literal,)*) => {
+        /// Constants of the individual modes, see the run parameters in main.rs for their defaults.
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct Constants {
+            $(#[doc = $doc] pub $name: $type,)*
+        }
+
+        impl Constants {
+            /// Names of all constants in the order of the fields.
+            pub const NAMES: &[&str] = &[$(stringify!($name)),*];
+
+            /// Return the documentation of the constant called name.
+            pub fn describe(name: &str) -> Option<&'static str> {
+                match name {
+                    $(stringify!($name) => Some($doc.trim()),)*
+                    _ => None,
+                }
+            }
+
+            /// Set the constant called key to value, returns false if there is no such constant.
+            fn set(&mut self, key: &str, value: &Value) -> Result<bool, String> {
+                match key {
+                    $(stringify!($name) => self.$name = ConstantValue::from_value(key, value)?,)*
+                    _ => return Ok(false),
+                }
+                Ok(true)
+            }
+
+            /// Set the constant called key to a command line argument, returns false if there is no such constant.
+            fn set_arg(&mut self, key: &str, arg: &str) -> Result<bool, String> {
+                match key {
+                    $(stringify!($name) => self.$name = ConstantValue::from_arg(key, arg)?,)*
+                    _ => return Ok(false),
+                }
+                Ok(true)
+            }
+
+            /// Return the names and values of all constants in TOML syntax, which is also valid JSON.
+            pub fn values(&self) -> Vec<(&'static str, String)> {
+                vec![$((stringify!($name), self.$name.to_toml())),*]
+            }
+        }
+    };
+}
+
+constants! {
+    ntau_skt: usize = " Number of time separations (in sweeps) of the dynamic structure factor.",
+    t_quench: f64 = " Temperature to quench to in aging mode.",
+    nsweep_aging: usize = " Number of sweeps after each quench in aging mode.",
+    nrepeat_aging: usize = " Number of quenches to average over in aging mode.",
+    h_response: f64 = " Strength of the random field used to measure the response in aging mode.",
+    waiting_times: Vec<usize> = " Waiting times (in sweeps after the quench) of aging mode.",
+    nbin_histogram: usize = " Number of bins of energy and magnetisation histograms.",
+    nreweight: usize = " Number of temperatures of reweighted curves.",
+    t_latent: f64 = " Temperature close to the transition for the latent heat measurement.",
+    latent_lattices: Vec<(usize, usize)> = " Lattice sizes (nx, ny) to locate the transition on with --latent-heat.",
+    npilot_thin: usize = " Number of production sweeps used to estimate tau_int for --auto-thin.",
+    energy_tolerance: f64 = " Largest accepted drift of the running energy per site with --check-energy.",
+    nblock_auto_therm: usize = " Number of sweeps between two checks of equilibration with --auto-therm.",
+    nmax_auto_therm: usize = " Maximum number of thermalisation sweeps per temperature with --auto-therm.",
+    t_benchmark: f64 = " Temperature of the algorithm benchmark.",
+    nsweep_benchmark: usize = " Number of timed sweeps per algorithm in the benchmark.",
+    nskip_classifier: usize = " Number of sweeps between snapshots sent to the classifier.",
+    frame_delay_movie: u16 = " Time between frames of GIF movies of the quench in 1/100 s.",
+    max_angle_heisenberg: f64 = " Initial maximum rotation angle of Metropolis proposals for --heisenberg.",
+    acceptance_heisenberg: f64 = " Acceptance rate the Heisenberg proposal width is tuned to during thermalisation.",
+    nsample_export: usize = " Number of exported configurations per temperature.",
+    nskip_export: usize = " Minimum number of sweeps between exported configurations, more if 2 tau_int is larger.",
+    npilot_export: usize = " Number of sweeps per temperature used to estimate tau_int for --export and --covariance.",
+    shard_size_export: usize = " Maximum number of configurations per exported file.",
+    nrepeat_nucleation: usize = " Number of decays per temperature and field in nucleation mode.",
+    nmax_nucleation: usize = " Maximum number of sweeps for a single decay in nucleation mode.",
+    nucleation_temperatures: Vec<f64> = " Temperatures of nucleation mode, should be below Tc.",
+    nucleation_fields: Vec<f64> = " (Weak) fields of nucleation mode.",
+    nsample_cftp: usize = " Number of exact samples per temperature with coupling from the past.",
+    nmax_cftp: usize = " Maximum number of sweeps into the past for a single exact sample.",
+    t_multicanonical: f64 = " Temperature of multicanonical sampling.",
+    niter_multicanonical: usize = " Number of iterations of the multicanonical weights.",
+    nsweep_multicanonical: usize = " Number of sweeps per iteration of the multicanonical weights.",
+    ln_f_wang_landau: f64 = " Final logarithm of the Wang-Landau modification factor.",
+    flatness_wang_landau: f64 = " Minimum fraction of the mean count for a flat Wang-Landau energy histogram.",
+    ncheck_wang_landau: usize = " Number of sweeps between two checks of the Wang-Landau flatness.",
+    nbin_validate: usize = " Number of bins for error estimates in validation mode.",
+    max_deviation: f64 = " Largest accepted deviation from exact results in validation mode, in units of the error.",
+    width_refine: f64 = " Half width of the window around Tc that --refine-tc adds temperatures in.",
+}
+
+impl Constants {
+    /// Return the name of the constant that the command line argument `--<name>` sets, if there is one.
+    pub fn key(arg: &str) -> Option<String> {
+        let name = arg.strip_prefix("--").filter(|name| !name.contains('_'))?;
+        let key = name.replace('-', "_");
+        Constants::NAMES.contains(&key.as_str()).then_some(key)
+    }
+
+    /// Set the constant called key to the value of a command line argument.
+    /**
+     * Lists are comma separated, lattice sizes are given as `<nx>x<ny>`, e.g. `--latent-lattices 4x4,8x8`.
+     * Call check once all constants are set.
+     */
+    pub fn parse_arg(&mut self, key: &str, value: &str) -> Result<(), String> {
+        if !self.set_arg(key, value)? {
+            return Err(format!("Unknown parameter '{}'", key));
+        }
+        Ok(())
+    }
+
+    /// Check the ranges of all constants beyond the types of their values.
+    pub fn check(&self) -> Result<(), String> {
+        let temperatures = [self.t_quench, self.t_latent, self.t_benchmark, self.t_multicanonical];
+        if temperatures.iter().chain(&self.nucleation_temperatures).any(|&t| t <= 0.) {
+            return Err("Temperatures must be positive".to_string());
+        }
+        let fractions = [("acceptance_heisenberg", self.acceptance_heisenberg),
+                         ("flatness_wang_landau", self.flatness_wang_landau)];
+        for (name, value) in fractions {
+            if value <= 0. || value >= 1. {
+                return Err(format!("{} must be in (0, 1)", name));
+            }
+        }
+        let positive = [("energy_tolerance", self.energy_tolerance), ("ln_f_wang_landau", self.ln_f_wang_landau),
+                        ("max_deviation", self.max_deviation), ("width_refine", self.width_refine)];
+        for (name, value) in positive {
+            if value <= 0. {
+                return Err(format!("{} must be positive", name));
+            }
+        }
+        if self.max_angle_heisenberg <= 0. || self.max_angle_heisenberg > std::f64::consts::PI {
+            return Err("max_angle_heisenberg must be in (0, π]".to_string());
+        }
+        if self.waiting_times.iter().any(|&tw| tw > self.nsweep_aging) {
+            return Err("waiting_times must not exceed nsweep_aging".to_string());
+        }
+        if self.nbin_validate < 2 {
+            return Err("nbin_validate must be at least 2".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Values of constants, read from configuration files and command line arguments.
+trait ConstantValue: Sized {
+    /// Convert a value of a configuration file.
+    fn from_value(key: &str, value: &Value) -> Result<Self, String>;
+
+    /// Convert a command line argument, which is parsed like a value of a configuration file by default.
+    fn from_arg(key: &str, arg: &str) -> Result<Self, String> {
+        match parse_value(arg.trim()) {
+            Ok((value, rest)) if rest.trim().is_empty() => Self::from_value(key, &value),
+            _ => Err(format!("Invalid value '{}' for {}", arg, key)),
+        }
+    }
+
+    /// Format the value as TOML.
+    fn to_toml(&self) -> String;
+}
+
+impl ConstantValue for usize {
+    fn from_value(key: &str, value: &Value) -> Result<usize, String> {
+        positive(key, value)
+    }
+
+    fn to_toml(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl ConstantValue for u16 {
+    fn from_value(key: &str, value: &Value) -> Result<u16, String> {
+        u16::try_from(count(key, value)?).map_err(|_| format!("{} must be at most {}", key, u16::MAX))
+    }
+
+    fn to_toml(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl ConstantValue for f64 {
+    fn from_value(key: &str, value: &Value) -> Result<f64, String> {
+        match float(key, value) {
+            Ok(x) if x.is_finite() => Ok(x),
+            _ => Err(format!("{} must be a number", key)),
+        }
+    }
+
+    fn to_toml(&self) -> String {
+        // {:?} always prints a decimal point or exponent, so the value stays a float
+        format!("{:?}", self)
+    }
+}
+
+impl<T: ConstantValue> ConstantValue for Vec<T> {
+    fn from_value(key: &str, value: &Value) -> Result<Vec<T>, String> {
+        match value {
+            Value::Array(items) if !items.is_empty() => items.iter().map(|item| T::from_value(key, item)).collect(),
+            _ => Err(format!("{} must be a non-empty array", key)),
+        }
+    }
+
+    fn from_arg(key: &str, arg: &str) -> Result<Vec<T>, String> {
+        arg.split(',').map(|item| T::from_arg(key, item)).collect()
+    }
+
+    fn to_toml(&self) -> String {
+        format!("[{}]", self.iter().map(|item| item.to_toml()).collect::<Vec<String>>().join(", "))
+    }
+}
+
+impl ConstantValue for (usize, usize) {
+    fn from_value(key: &str, value: &Value) -> Result<(usize, usize), String> {
+        match value {
+            Value::Array(items) if items.len() == 2 => Ok((positive(key, &items[0])?, positive(key, &items[1])?)),
+            _ => Err(format!("{} must contain pairs [nx, ny] of positive integers", key)),
+        }
+    }
+
+    fn from_arg(key: &str, arg: &str) -> Result<(usize, usize), String> {
+        let extent = |n: &str| n.trim().parse().ok().filter(|&n| n > 0);
+        match arg.split_once('x').map(|(nx, ny)| (extent(nx), extent(ny))) {
+            Some((Some(nx), Some(ny))) => Ok((nx, ny)),
+            _ => Err(format!("Invalid lattice size '{}' for {}, expected <nx>x<ny>", arg, key)),
+        }
+    }
+
+    fn to_toml(&self) -> String {
+        format!("[{}, {}]", self.0, self.1)
+    }
+}
+
+/// A value in a configuration file.
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+impl RunParams {
+    /// Read a configuration file and overwrite all parameters that are set in it.
+    pub fn read(&mut self, fname: &Path) -> Result<(), String> {
+        let text = fs::read_to_string(fname)
+            .map_err(|err| format!("Cannot read config file '{}': {}", fname.display(), err))?;
+        self.apply_toml(&text)
+            .map_err(|err| format!("In config file '{}': {}", fname.display(), err))
+    }
+
+    /// Overwrite all parameters that are set in the TOML document text.
+    /**
+     * Keys are the names of the fields of RunParams and Constants. seed is either an integer, which is
+     * expanded with expand_seed, or an array of 32 bytes. rng is the name of an RngKind.
+     * updates and the names in observables are checked when they are used.
+     */
+    pub fn apply_toml(&mut self, text: &str) -> Result<(), String> {
+        for (key, value) in parse_toml(text)? {
+            match key.as_str() {
+                "nx" => self.nx = positive(&key, &value)?,
+                "ny" => self.ny = positive(&key, &value)?,
+                "ntherm_init" => self.ntherm_init = count(&key, &value)?,
+                "ntherm" => self.ntherm = count(&key, &value)?,
+                "nprod" => self.nprod = positive(&key, &value)?,
+                "temperatures" => {
+                    let temperatures = match &value {
+                        Value::Array(items) => items.iter().map(|item| float(&key, item))
+                            .collect::<Result<Vec<f64>, String>>()?,
+                        _ => return Err(format!("{} must be an array of numbers", key)),
+                    };
+                    if temperatures.is_empty() || temperatures.iter().any(|&t| t <= 0.) {
+                        return Err("temperatures must be a non-empty list of positive numbers".to_string());
+                    }
+                    self.temperatures = temperatures;
+                }
+                "seed" => self.seed = match &value {
+                    Value::Integer(seed) if *seed >= 0 => expand_seed(*seed as u64),
+                    Value::Array(items) if items.len() == 32 => {
+                        let mut seed = [0; 32];
+                        for (byte, item) in seed.iter_mut().zip(items.iter()) {
+                            *byte = match item {
+                                Value::Integer(b @ 0..=255) => *b as u8,
+                                _ => return Err("seed bytes must be integers in [0, 255]".to_string()),
+                            };
+                        }
+                        seed
+                    }
+                    _ => return Err("seed must be a non-negative integer or an array of 32 bytes".to_string()),
+                },
+                "rng" => self.rng = match &value {
+                    Value::String(name) => RngKind::parse(name)?,
+                    _ => return Err("rng must be a string".to_string()),
+                },
+                "updates" => self.updates = match value {
+                    Value::String(updates) => Some(updates),
+                    _ => return Err("updates must be a string".to_string()),
+                },
+                "observables" => self.observables = match &value {
+                    Value::Array(items) => items.iter().map(|item| match item {
+                        Value::String(name) => Ok(name.clone()),
+                        _ => Err("observables must be an array of strings".to_string()),
+                    }).collect::<Result<Vec<String>, String>>()?,
+                    _ => return Err("observables must be an array of strings".to_string()),
+                },
+                "datadir" => self.datadir = match value {
+                    Value::String(dir) => PathBuf::from(dir),
+                    _ => return Err("datadir must be a string".to_string()),
+                },
+                _ => if !self.constants.set(&key, &value)? {
+                    return Err(format!("Unknown parameter '{}'", key));
+                },
+            }
+        }
+        if self.ny == 1 && self.nx < 2 {
+            return Err("A chain needs at least 2 sites".to_string());
+        }
+        self.constants.check()
+    }
+
+    /// Format all parameters as a TOML document that can be read by apply_toml.
+    pub fn to_toml(&self) -> String {
+        let list = |values: Vec<String>| values.join(", ");
+        let mut text = String::new();
+        writeln!(text, "nx = {}", self.nx).unwrap();
+        writeln!(text, "ny = {}", self.ny).unwrap();
+        writeln!(text, "ntherm_init = {}", self.ntherm_init).unwrap();
+        writeln!(text, "ntherm = {}", self.ntherm).unwrap();
+        writeln!(text, "nprod = {}", self.nprod).unwrap();
+        // {:?} always prints a decimal point or exponent, so the values stay floats
+        writeln!(text, "temperatures = [{}]",
+                 list(self.temperatures.iter().map(|t| format!("{:?}", t)).collect())).unwrap();
+        writeln!(text, "seed = [{}]", list(self.seed.iter().map(|b| b.to_string()).collect())).unwrap();
+        writeln!(text, "rng = {:?}", self.rng.name()).unwrap();
+        if let Some(updates) = &self.updates {
+            writeln!(text, "updates = {:?}", updates).unwrap();
+        }
+        if !self.observables.is_empty() {
+            writeln!(text, "observables = [{}]",
+                     list(self.observables.iter().map(|name| format!("{:?}", name)).collect())).unwrap();
+        }
+        writeln!(text, "datadir = {:?}", self.datadir.display().to_string()).unwrap();
+        for (name, value) in self.constants.values() {
+            writeln!(text, "{} = {}", name, value).unwrap();
+        }
+        text
+    }
+
+    /// Write all parameters to a TOML file.
+    pub fn write(&self, fname: &Path) -> Result<(), Error> {
+        write_file(fname, self.to_toml())
+    }
+}
+
+/// Format the parameters of a run as a JSON object to reproduce it.
+/**
+ * Contains the package version, the command line, seed if it was given as a single integer,
+ * and all parameters of params, including the full 32 byte seed.
+ */
+pub fn manifest(params: &RunParams, seed: Option<u64>, command: &[String]) -> String {
+    let list = |values: Vec<String>| format!("[{}]", values.join(", "));
+    let fields = [
+        ("version", json_string(env!("CARGO_PKG_VERSION"))),
+        ("command", list(command.iter().map(|arg| json_string(arg)).collect())),
+        ("seed", seed.map_or("null".to_string(), |seed| seed.to_string())),
+        ("seed_bytes", list(params.seed.iter().map(|b| b.to_string()).collect())),
+        ("rng", json_string(params.rng.name())),
+        ("updates", params.updates.as_deref().map_or("null".to_string(), json_string)),
+        ("observables", list(params.observables.iter().map(|name| json_string(name)).collect())),
+        ("nx", params.nx.to_string()),
+        ("ny", params.ny.to_string()),
+        ("ntherm_init", params.ntherm_init.to_string()),
+        ("ntherm", params.ntherm.to_string()),
+        ("nprod", params.nprod.to_string()),
+        ("temperatures", list(params.temperatures.iter().map(|t| format!("{:?}", t)).collect())),
+        ("datadir", json_string(&params.datadir.display().to_string())),
+    ];
+    let body: Vec<String> = fields.into_iter().chain(params.constants.values())
+        .map(|(key, value)| format!("  \"{}\": {}", key, value)).collect();
+    format!("{{\n{}\n}}\n", body.join(",\n"))
+}
+
+/// Parse temperatures given as `<start>:<stop>:<n>` or a comma separated list, all must be positive.
+/**
+ * `<start>:<stop>:<n>` gives n evenly spaced temperatures from start to stop, both included,
+ * and can run from high to low temperatures.
+ */
+pub fn parse_temperatures(spec: &str) -> Result<Vec<f64>, String> {
+    let parts: Vec<&str> = spec.split(':').collect();
+    if parts.len() == 3 {
+        let bound = |part: &str| match part.trim().parse::<f64>() {
+            Ok(t) if t > 0. => Ok(t),
+            _ => Err(format!("Invalid temperature '{}' in '{}', must be a positive number", part, spec)),
+        };
+        let (start, stop) = (bound(parts[0])?, bound(parts[1])?);
+        let n = match parts[2].trim().parse::<usize>() {
+            Ok(n) if n >= 2 => n,
+            _ => return Err(format!("Invalid number of temperatures '{}' in '{}', must be at least 2", parts[2], spec)),
+        };
+        return Ok(linspace(start, stop, n));
+    }
+    spec.split(',').map(|temp| match temp.trim().parse::<f64>() {
+        Ok(t) if t > 0. => Ok(t),
+        _ => Err(format!("Invalid temperature '{}', must be a positive number", temp)),
+    }).collect()
+}
+
+/// Read temperatures from a file with numbers separated by whitespace, commas, or newlines and `#` comments.
+pub fn read_temperatures(fname: &Path) -> Result<Vec<f64>, String> {
+    let text = fs::read_to_string(fname)
+        .map_err(|err| format!("Cannot read temperature file '{}': {}", fname.display(), err))?;
+    let temperatures = text.lines()
+        .flat_map(|line| line.split('#').next().unwrap().split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|temp| !temp.is_empty())
+        .map(|temp| match temp.parse::<f64>() {
+            Ok(t) if t > 0. => Ok(t),
+            _ => Err(format!("Invalid temperature '{}' in '{}', must be a positive number", temp, fname.display())),
+        })
+        .collect::<Result<Vec<f64>, String>>()?;
+    if temperatures.is_empty() {
+        return Err(format!("Temperature file '{}' contains no temperatures", fname.display()));
+    }
+    Ok(temperatures)
+}
+
+/// Add n evenly spaced temperatures in [tc - width, tc + width] to temperatures.
+/**
+ * The result is sorted in the direction of temperatures, decreasing if the first temperature is
+ * larger than the last and increasing otherwise, and temperatures that coincide up to 1e-9 are only kept once.
+ * Points of the window that are not positive are dropped.
+ */
+pub fn refine(temperatures: &[f64], tc: f64, width: f64, n: usize) -> Vec<f64> {
+    let decreasing = temperatures.first() > temperatures.last();
+    let window = if n == 1 { vec![tc] } else { linspace(tc - width, tc + width, n) };
+    let mut refined: Vec<f64> = temperatures.iter().copied().chain(window.into_iter().filter(|&t| t > 0.)).collect();
+    refined.sort_by(|a, b| if decreasing { b.total_cmp(a) } else { a.total_cmp(b) });
+    refined.dedup_by(|a, b| (*a - *b).abs() < 1e-9);
+    refined
+}
+
+/// Return n >= 2 evenly spaced values from start to stop, both included.
+fn linspace(start: f64, stop: f64, n: usize) -> Vec<f64> {
+    (0..n).map(|i| start + (stop - start) * i as f64 / (n - 1) as f64).collect()
+}
+
+/// Draw a 64 bit seed from the OS entropy source.
+pub fn random_seed() -> u64 {
+    rand::rng().random()
+}
+
+/// Increment of the SplitMix64 state, 2^64 divided by the golden ratio.
+const GOLDEN_GAMMA: u64 = 0x9e3779b97f4a7c15;
+
+/// Return the SplitMix64 output of state, a bijection on 64 bit words with good avalanche.
+fn mix64(state: u64) -> u64 {
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Expand a 64 bit seed into a 32 byte seed for Rng using SplitMix64.
+pub fn expand_seed(seed: u64) -> [u8; 32] {
+    let mut state = seed;
+    let mut bytes = [0; 32];
+    for chunk in bytes.chunks_exact_mut(8) {
+        state = state.wrapping_add(GOLDEN_GAMMA);
+        chunk.copy_from_slice(&mix64(state).to_le_bytes());
+    }
+    bytes
+}
+
+/// Derive the seed of an independent random stream from seed.
+/**
+ * Streams with different numbers are independent of each other and of seed itself.
+ * seed and stream are hashed together with SplitMix64, so every bit of the derived seed depends on
+ * every bit of both. Unlike combining them linearly, e.g. by XOR, this does not map related seeds
+ * and streams to equal or related derived seeds.
+ */
+pub fn derive_seed(seed: [u8; 32], stream: u64) -> [u8; 32] {
+    let words = seed.chunks_exact(8).map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()));
+    // hash of stream and all words of seed
+    let key = words.clone().fold(stream, |key, word| mix64((key ^ word).wrapping_add(GOLDEN_GAMMA)));
+    let mut derived = [0; 32];
+    for (i, (chunk, word)) in derived.chunks_exact_mut(8).zip(words).enumerate() {
+        let state = (key ^ word).wrapping_add(GOLDEN_GAMMA.wrapping_mul(i as u64 + 1));
+        chunk.copy_from_slice(&mix64(state).to_le_bytes());
+    }
+    derived
+}
+
+/// Quote and escape text as a JSON string.
+pub fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Return value as a non-negative integer.
+fn count(key: &str, value: &Value) -> Result<usize, String> {
+    match value {
+        Value::Integer(n) if *n >= 0 => Ok(*n as usize),
+        _ => Err(format!("{} must be a non-negative integer", key)),
+    }
+}
+
+/// Return value as a positive integer.
+fn positive(key: &str, value: &Value) -> Result<usize, String> {
+    match count(key, value) {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("{} must be a positive integer", key)),
+    }
+}
+
+/// Return value as a float, integers are converted.
+fn float(key: &str, value: &Value) -> Result<f64, String> {
+    match value {
+        Value::Integer(n) => Ok(*n as f64),
+        Value::Float(x) => Ok(*x),
+        _ => Err(format!("{} must contain numbers", key)),
+    }
+}
+
+/// Parse a TOML document into a list of key value pairs in order of appearance.
+fn parse_toml(text: &str) -> Result<Vec<(String, Value)>, String> {
+    let mut pairs: Vec<(String, Value)> = Vec::new();
+    let mut lines = text.lines().enumerate();
+
+    while let Some((lineno, line)) = lines.next() {
+        let mut line = strip_comment(line).trim().to_string();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            return Err(format!("line {}: tables are not supported", lineno+1));
+        }
+
+        // arrays may continue over several lines
+        while bracket_depth(&line) > 0 {
+            match lines.next() {
+                Some((_, next)) => {
+                    line.push(' ');
+                    line.push_str(strip_comment(next).trim());
+                }
+                None => return Err(format!("line {}: unterminated array", lineno+1)),
+            }
+        }
+
+        let (key, value) = line.split_once('=')
+            .ok_or_else(|| format!("line {}: expected <key> = <value>", lineno+1))?;
+        let key = key.trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(format!("line {}: invalid key '{}'", lineno+1, key));
+        }
+        if pairs.iter().any(|(k, _)| k == key) {
+            return Err(format!("line {}: duplicate key '{}'", lineno+1, key));
+        }
+        let (value, rest) = parse_value(value.trim())
+            .map_err(|err| format!("line {}: {}", lineno+1, err))?;
+        if !rest.trim().is_empty() {
+            return Err(format!("line {}: unexpected '{}' after value", lineno+1, rest.trim()));
+        }
+        pairs.push((key.to_string(), value));
+    }
+
+    Ok(pairs)
+}
+
+/// Remove a `#` comment that is not part of a string.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\\' if in_string => { escaped = !escaped; continue; }
+            '"' if !escaped => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+/// Return the number of unclosed square brackets outside of strings.
+fn bracket_depth(line: &str) -> i32 {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in line.chars() {
+        match c {
+            '\\' if in_string => { escaped = !escaped; continue; }
+            '"' if !escaped => in_string = !in_string,
+            '[' if !in_string => depth += 1,
+            ']' if !in_string => depth -= 1,
+            _ => {}
+        }
+        escaped = false;
+    }
+    depth
+}
+
+/// Parse a value at the beginning of text and return it along with the remaining text.
+fn parse_value(text: &str) -> Result<(Value, &str), String> {
+    if let Some(rest) = text.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return Ok((Value::String(value), &rest[i+1..])),
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, '"')) => value.push('"'),
+                    Some((_, '\\')) => value.push('\\'),
+                    _ => return Err("invalid escape sequence in string".to_string()),
+                },
+                _ => value.push(c),
+            }
+        }
+        return Err("unterminated string".to_string());
+    }
+
+    if let Some(mut rest) = text.strip_prefix('[') {
+        let mut items = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::Array(items), after));
+            }
+            let (item, after) = parse_value(rest)?;
+            items.push(item);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after;
+            }
+            else if !rest.starts_with(']') {
+                return Err("expected ',' or ']' in array".to_string());
+            }
+        }
+    }
+
+    // bare values end at a separator
+    let end = text.find([',', ']']).unwrap_or(text.len());
+    let (token, rest) = (text[..end].trim(), &text[end..]);
+    let number = token.replace('_', "");
+    let value = match token {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ if number.parse::<i64>().is_ok() => Value::Integer(number.parse().unwrap()),
+        _ if number.parse::<f64>().is_ok() && !token.is_empty() => Value::Float(number.parse().unwrap()),
+        _ => return Err(format!("invalid value '{}'", token)),
+    };
+    Ok((value, rest))
+}
@@ -0,0 +1,58 @@
+/*!
+ * Two-point spin-spin correlation functions along the lattice axes.
+ */
+
+use crate::Configuration;
+
+/// Accumulate G(r) = ⟨s(r₀) s(r₀+r)⟩ for separations r along x and y.
+/**
+ * Each measurement averages over all r₀ and separations run from 0 to n/2 along an axis
+ * with n sites, larger separations repeat by periodicity.
+ * G is not connected, so it approaches ⟨m⟩² for large r in the ordered phase.
+ */
+pub struct Correlation {
+    /// Σ over measurements of the translation average of s(r₀) s(r₀ + r x̂).
+    sum_x: Vec<f64>,
+    /// Σ over measurements of the translation average of s(r₀) s(r₀ + r ŷ), empty for a chain.
+    sum_y: Vec<f64>,
+    /// Number of measurements.
+    nmeasure: usize,
+}
+
+impl Correlation {
+    /// Create an accumulator for an nx*ny lattice.
+    pub fn new(nx: usize, ny: usize) -> Correlation {
+        Correlation{sum_x: vec![0.; nx/2 + 1],
+                    sum_y: if ny == 1 { Vec::new() } else { vec![0.; ny/2 + 1] },
+                    nmeasure: 0}
+    }
+
+    /// Add the correlations of cfg.
+    pub fn measure(&mut self, cfg: &Configuration) {
+        let (nx, ny) = (cfg.nx, cfg.ny);
+        let size = cfg.nsite() as f64;
+        for (r, sum) in self.sum_x.iter_mut().enumerate() {
+            let total: i32 = (0..ny).map(|y| {
+                (0..nx).map(|x| cfg[y*nx + x] * cfg[y*nx + (x+r) % nx]).sum::<i32>()
+            }).sum();
+            *sum += total as f64 / size;
+        }
+        for (r, sum) in self.sum_y.iter_mut().enumerate() {
+            let total: i32 = (0..ny).map(|y| {
+                (0..nx).map(|x| cfg[y*nx + x] * cfg[((y+r) % ny)*nx + x]).sum::<i32>()
+            }).sum();
+            *sum += total as f64 / size;
+        }
+        self.nmeasure += 1;
+    }
+
+    /// Return G(r) along x averaged over all measurements.
+    pub fn x(&self) -> Vec<f64> {
+        self.sum_x.iter().map(|sum| sum / self.nmeasure as f64).collect()
+    }
+
+    /// Return G(r) along y averaged over all measurements, empty for a chain.
+    pub fn y(&self) -> Vec<f64> {
+        self.sum_y.iter().map(|sum| sum / self.nmeasure as f64).collect()
+    }
+}
@@ -0,0 +1,116 @@
+/*!
+ * Site-site covariance matrix of spin configurations for principal component analysis.
+ */
+
+use std::path::Path;
+
+use crate::error::{Error, OutputFile};
+use crate::export::{ExportParams, decorrelation_skip};
+use crate::npy::NpzWriter;
+use crate::{Configuration, Rng, evolve, hamiltonian, info};
+
+/// Largest number of matrix elements of all covariance matrices of a run, 1 GiB of f64.
+pub const MAX_ENTRIES: usize = 1 << 27;
+
+/// Check that the covariance matrices of ntemperature temperatures of a lattice with size sites fit in memory.
+pub fn check_size(size: usize, ntemperature: usize) -> Result<(), String> {
+    match size.checked_mul(size).and_then(|n| n.checked_mul(ntemperature)) {
+        Some(n) if n <= MAX_ENTRIES => Ok(()),
+        _ => Err(format!("The covariance matrices of {} sites at {} temperatures exceed {} elements, \
+                          use a smaller lattice or fewer temperatures", size, ntemperature, MAX_ENTRIES)),
+    }
+}
+
+/// Accumulate the covariance of sites over snapshots.
+pub struct Covariance {
+    nsample: usize,
+    /// Σ s_i over all snapshots.
+    sum: Vec<f64>,
+    /// Σ s_i s_j over all snapshots, row major.
+    products: Vec<f64>,
+}
+
+impl Covariance {
+    /// Create an empty accumulator for configurations with size sites.
+    /**
+     * The matrix must pass check_size for a single temperature.
+     */
+    pub fn new(size: usize) -> Covariance {
+        assert!(check_size(size, 1).is_ok(), "A covariance matrix of {} sites exceeds {} elements", size, MAX_ENTRIES);
+        Covariance{nsample: 0, sum: vec![0.; size], products: vec![0.; size*size]}
+    }
+
+    /// Add a snapshot.
+    pub fn push(&mut self, cfg: &Configuration) {
+        let size = self.sum.len();
+        for i in 0..size {
+            self.sum[i] += cfg[i] as f64;
+            let row = &mut self.products[i*size..(i+1)*size];
+            for (j, prod) in row.iter_mut().enumerate() {
+                *prod += (cfg[i]*cfg[j]) as f64;
+            }
+        }
+        self.nsample += 1;
+    }
+
+    /// Return the mean ⟨s_i⟩ of each site.
+    pub fn mean(&self) -> Vec<f64> {
+        self.sum.iter().map(|s| s / self.nsample as f64).collect()
+    }
+
+    /// Return the covariance matrix ⟨s_i s_j⟩ - ⟨s_i⟩⟨s_j⟩, row major.
+    pub fn matrix(&self) -> Vec<f64> {
+        let size = self.sum.len();
+        let mean = self.mean();
+        self.products.iter().enumerate()
+            .map(|(k, prod)| prod / self.nsample as f64 - mean[k / size]*mean[k % size])
+            .collect()
+    }
+}
+
+/// Measure the covariance matrix at all temperatures and write it to `covariance.npz`.
+/**
+ * The archive contains arrays
+ *  - `temperature`: float64 with shape (nt,),
+ *  - `mean`: float64 ⟨s_i⟩ with shape (nt, ny*nx),
+ *  - `covariance`: float64 ⟨s_i s_j⟩ - ⟨s_i⟩⟨s_j⟩ with shape (nt, ny*nx, ny*nx).
+ *
+ * Sites are in row major order (x runs fastest). The eigenvectors of the covariance
+ * matrix are the principal components of the configurations.
+ * Snapshots are taken as by export::run every export::decorrelation_skip sweeps, which are listed in `skip.dat`,
+ * params.shard_size is not used.
+ * Fails without allocating the matrices if they do not pass check_size.
+ */
+pub fn run(datadir: &Path, nx: usize, ny: usize, temperatures: &[f64],
+           params: &ExportParams, rng: &mut Rng) -> Result<(), Error> {
+    let size = nx*ny;
+    check_size(size, temperatures.len())?;
+    let mut means = Vec::with_capacity(temperatures.len()*size);
+    let mut matrices = Vec::with_capacity(temperatures.len()*size*size);
+
+    let mut skipfile = OutputFile::create(&datadir.join("skip.dat"))?;
+    let mut cfg = Configuration::random(nx, ny, rng);
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    for temp in temperatures.iter() {
+        info!("Accumulating covariance of {} configurations at temperature {}", params.nsample, temp);
+        let beta = 1./temp;
+        evolve(&mut cfg, &mut energy, beta, 1., 0., rng, params.ntherm, None);
+        let (nskip, tau) = decorrelation_skip(&mut cfg, &mut energy, beta, params, rng);
+        writeln!(skipfile, "{}: {} {}", temp, nskip, tau)?;
+        let mut covariance = Covariance::new(size);
+        for _ in 0..params.nsample {
+            evolve(&mut cfg, &mut energy, beta, 1., 0., rng, nskip, None);
+            covariance.push(&cfg);
+        }
+        means.extend(covariance.mean());
+        matrices.extend(covariance.matrix());
+    }
+
+    let nt = temperatures.len();
+    let mut npz = NpzWriter::new();
+    npz.add("temperature", &[nt], temperatures);
+    npz.add("mean", &[nt, size], &means);
+    npz.add("covariance", &[nt, size, size], &matrices);
+    let fname = datadir.join("covariance.npz");
+    npz.write(&fname).map_err(Error::io(&fname))
+}
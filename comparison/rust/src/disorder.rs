@@ -0,0 +1,103 @@
+/*!
+ * Generate quenched disorder, spatially correlated random fields and random bonds.
+ */
+
+use std::f64::consts::PI;
+
+use crate::fourier::{Complex, dft_2d};
+use crate::{Configuration, Rng};
+
+/// Parameters of a correlated random field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CorrelatedDisorder {
+    /// Standard deviation of the field at each site.
+    pub strength: f64,
+    /// Correlation exponent a, correlations decay like r^-a.
+    pub exponent: f64,
+}
+
+impl CorrelatedDisorder {
+    /// Parse parameters from a string `<strength>:<exponent>`.
+    pub fn parse(spec: &str) -> Result<CorrelatedDisorder, String> {
+        let number = |s: &str| s.trim().parse::<f64>()
+            .map_err(|_| format!("Invalid number '{}' in disorder '{}'", s, spec));
+        match spec.split(':').collect::<Vec<_>>().as_slice() {
+            [strength, exponent] => Ok(CorrelatedDisorder{strength: number(strength)?,
+                                                          exponent: number(exponent)?}),
+            _ => Err(format!("Invalid disorder '{}', expected <strength>:<exponent>", spec)),
+        }
+    }
+
+    /// Draw a field on an nx*ny lattice.
+    /**
+     * Filters Gaussian white noise in Fourier space such that the power spectrum is
+     * S(k) ∝ |k|^(a-d) with d = 2 (d = 1 for a chain), which gives correlations
+     * C(r) ∝ r^-a for 0 < a < d. Uses the lattice momentum |k|² = Σ_μ 4 sin²(k_μ/2)
+     * to respect periodic boundaries. The k = 0 mode is removed, so the field has zero mean,
+     * and the result is rescaled to have standard deviation strength.
+     * a >= d gives short range correlations, in particular a = d gives white noise.
+     */
+    pub fn generate(&self, nx: usize, ny: usize, rng: &mut Rng) -> Vec<f64> {
+        let dim = if ny == 1 { 1. } else { 2. };
+        let mut data: Vec<Complex> = (0..nx*ny).map(|_| (gaussian(rng), 0.)).collect();
+        dft_2d(&mut data, nx, ny, false);
+
+        for y in 0..ny {
+            for x in 0..nx {
+                let k2 = 4.*(PI*x as f64 / nx as f64).sin().powi(2)
+                    + 4.*(PI*y as f64 / ny as f64).sin().powi(2);
+                let amplitude = if k2 > 0. { k2.powf((self.exponent-dim) / 4.) } else { 0. };
+                let (re, im) = data[y*nx + x];
+                data[y*nx + x] = (amplitude*re, amplitude*im);
+            }
+        }
+
+        dft_2d(&mut data, nx, ny, true);
+        let field: Vec<f64> = data.iter().map(|(re, _)| *re).collect();
+        let std = (field.iter().map(|f| f*f).sum::<f64>() / field.len() as f64).sqrt();
+        if std == 0. {
+            return field;  // all zero, e.g. on a single site
+        }
+        field.iter().map(|f| self.strength * f / std).collect()
+    }
+}
+
+/// Distribution of random bonds J_ij = J ε_ij of the Edwards-Anderson spin glass.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RandomBonds {
+    /// ε_ij = ±1 with equal probability.
+    Binary,
+    /// ε_ij from the standard normal distribution.
+    Gaussian,
+}
+
+impl RandomBonds {
+    /// Parse a distribution from its name, `binary` or `gaussian`.
+    pub fn parse(name: &str) -> Result<RandomBonds, String> {
+        match name {
+            "binary" => Ok(RandomBonds::Binary),
+            "gaussian" => Ok(RandomBonds::Gaussian),
+            _ => Err(format!("Unknown bond distribution '{}'", name)),
+        }
+    }
+
+    /// Draw a single factor ε_ij.
+    pub fn draw(&self, rng: &mut Rng) -> f64 {
+        match self {
+            RandomBonds::Binary => rng.gen_spin() as f64,
+            RandomBonds::Gaussian => gaussian(rng),
+        }
+    }
+
+    /// Multiply the coupling factor of each bond of cfg by an independent ε_ij, see Configuration::scale_bonds.
+    pub fn apply<S: Clone>(&self, cfg: &mut Configuration<S>, rng: &mut Rng) {
+        cfg.scale_bonds(|| self.draw(rng));
+    }
+}
+
+/// Draw a normally distributed number with the Box-Muller transform.
+fn gaussian(rng: &mut Rng) -> f64 {
+    let u = 1. - rng.gen_real();  // in (0, 1] to avoid log(0)
+    let v = rng.gen_real();
+    (-2.*u.ln()).sqrt() * (2.*PI*v).cos()
+}
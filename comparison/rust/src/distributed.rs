@@ -0,0 +1,361 @@
+/*!
+ * Checkerboard sweeps of one lattice distributed over processes that exchange halo rows over TCP.
+ *
+ * The periodic square lattice is cut into strips of whole rows, one per process (rank), see Strip.
+ * Every rank holds its own rows and a halo row below and above them, which are copies of the
+ * boundary rows of its neighbours in the ring of ranks, see Ring. A half sweep updates the sites of
+ * one colour in the own rows and then exchanges the boundary rows with both neighbours, so every
+ * rank sees the current spins of the other colour. Only zero field with uniform couplings is supported.
+ *
+ * All ranks start from the same seed and split the random streams of all blocks of
+ * checkerboard::ROWS_PER_STREAM rows like checkerboard sweeps on one lattice, but only use those of
+ * their own blocks. So a distributed run produces the same configurations as evolve_checkerboard on the
+ * whole lattice for any number of ranks. Ranks are single threaded, run one per core.
+ */
+
+use std::io::prelude::*;
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::checkerboard::{ROWS_PER_STREAM, is_supported, row_flips_square, square_probabilities};
+use crate::error::Error;
+use crate::{Configuration, Format, Observables, Rng, has_bond_energy, info, neighbour_sum, write_observables};
+
+/// How long a rank waits for its neighbours to start listening and to connect.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Pause between attempts to connect.
+const CONNECT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Connections of a rank to its neighbours in the ring of ranks.
+/**
+ * Rank r connects to rank r+1 (next) and accepts the connection of rank r-1 (prev), modulo the
+ * number of ranks. A single rank is its own neighbour and has no connections.
+ */
+pub struct Ring {
+    /// Index of this process.
+    rank: usize,
+    /// Number of processes.
+    nranks: usize,
+    /// Connections to (next, prev), None for a single rank.
+    links: Option<(TcpStream, TcpStream)>,
+}
+
+impl Ring {
+    /// Ring of a single rank.
+    pub fn single() -> Ring {
+        Ring{rank: 0, nranks: 1, links: None}
+    }
+
+    /// Connect rank to its neighbours, addresses has one `host:port` per rank that the rank listens on.
+    /**
+     * All ranks must be started with the same addresses within CONNECT_TIMEOUT of each other.
+     */
+    pub fn connect(rank: usize, addresses: &[String]) -> Result<Ring, Error> {
+        let nranks = addresses.len();
+        if rank >= nranks {
+            return Err(Error::Message(format!("Rank {} needs at least {} addresses", rank, rank+1)));
+        }
+        if nranks == 1 {
+            return Ok(Ring::single());
+        }
+
+        let failed = |what: &str, address: &str, err: std::io::Error| {
+            Error::Message(format!("Cannot {} {}: {}", what, address, err))
+        };
+        let listener = TcpListener::bind(&addresses[rank]).map_err(|err| failed("listen on", &addresses[rank], err))?;
+        let start = Instant::now();
+
+        // the neighbour may not listen yet
+        let next_address = &addresses[(rank+1) % nranks];
+        let mut next = loop {
+            match TcpStream::connect(next_address) {
+                Ok(stream) => break stream,
+                Err(err) if start.elapsed() > CONNECT_TIMEOUT => return Err(failed("connect to", next_address, err)),
+                Err(_) => thread::sleep(CONNECT_INTERVAL),
+            }
+        };
+        next.write_all(&[rank as u64, nranks as u64].map(u64::to_le_bytes).concat())
+            .map_err(|err| failed("connect to", next_address, err))?;
+
+        listener.set_nonblocking(true).map_err(|err| failed("listen on", &addresses[rank], err))?;
+        let mut prev = loop {
+            match listener.accept() {
+                Ok((stream, _)) => break stream,
+                Err(err) if start.elapsed() > CONNECT_TIMEOUT || err.kind() != std::io::ErrorKind::WouldBlock =>
+                    return Err(failed("accept on", &addresses[rank], err)),
+                Err(_) => thread::sleep(CONNECT_INTERVAL),
+            }
+        };
+        let mut header = [0; 16];
+        prev.set_nonblocking(false).and_then(|_| prev.read_exact(&mut header))
+            .map_err(|err| failed("accept on", &addresses[rank], err))?;
+        let (prev_rank, prev_nranks) = (u64::from_le_bytes(header[..8].try_into().unwrap()),
+                                        u64::from_le_bytes(header[8..].try_into().unwrap()));
+        if prev_rank as usize != (rank+nranks-1) % nranks || prev_nranks as usize != nranks {
+            return Err(Error::Message(format!(
+                "Rank {} of {} connected to rank {} of {}, all ranks need the same addresses",
+                prev_rank, prev_nranks, rank, nranks)));
+        }
+
+        for stream in [&next, &prev] {
+            stream.set_nodelay(true).map_err(|err| failed("connect to", next_address, err))?;
+        }
+        Ok(Ring{rank, nranks, links: Some((next, prev))})
+    }
+
+    /// Return the index of this process.
+    pub fn rank(&self) -> usize {
+        self.rank
+    }
+
+    /// Return the number of processes.
+    pub fn nranks(&self) -> usize {
+        self.nranks
+    }
+
+    /// Send to_prev and to_next to the neighbours and receive what they sent into from_prev and from_next.
+    fn exchange(&mut self, to_prev: &[u8], to_next: &[u8], from_prev: &mut [u8], from_next: &mut [u8])
+                -> Result<(), Error> {
+        let Some((next, prev)) = &self.links else {
+            from_prev.copy_from_slice(to_next);
+            from_next.copy_from_slice(to_prev);
+            return Ok(());
+        };
+        // send in a thread, both neighbours may send large messages at the same time
+        let (mut next, mut prev) = (next, prev);
+        thread::scope(|scope| {
+            let sender = scope.spawn(move || -> std::io::Result<()> {
+                let (mut next, mut prev) = (next, prev);
+                prev.write_all(to_prev)?;
+                next.write_all(to_next)
+            });
+            let received = prev.read_exact(from_prev).and_then(|_| next.read_exact(from_next));
+            sender.join().unwrap().and(received)
+        }).map_err(|err| Error::Message(format!("Halo exchange of rank {} failed: {}", self.rank, err)))
+    }
+
+    /// Return the sums of values over all ranks, added up in the order of the ranks.
+    pub fn sum(&mut self, values: &[f64]) -> Result<Vec<f64>, Error> {
+        let Some((next, prev)) = &mut self.links else {
+            return Ok(values.to_vec());
+        };
+        let (rank, nranks) = (self.rank, self.nranks);
+        let receive = |stream: &mut TcpStream| -> std::io::Result<Vec<f64>> {
+            let mut bytes = vec![0; 8*values.len()];
+            stream.read_exact(&mut bytes)?;
+            Ok(bytes.chunks_exact(8).map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap())).collect())
+        };
+        let send = |stream: &mut TcpStream, values: &[f64]| {
+            stream.write_all(&values.iter().flat_map(|x| x.to_le_bytes()).collect::<Vec<u8>>())
+        };
+
+        // pass the partial sums from rank 0 to the last rank and the totals on from rank 0
+        let result = (|| {
+            if rank == 0 {
+                send(next, values)?;
+                let total = receive(prev)?;
+                send(next, &total)?;
+                Ok(total)
+            } else {
+                let partial: Vec<f64> = receive(prev)?.iter().zip(values).map(|(a, b)| a + b).collect();
+                send(next, &partial)?;
+                let total = receive(prev)?;
+                if rank + 1 < nranks {
+                    send(next, &total)?;
+                }
+                Ok(total)
+            }
+        })();
+        result.map_err(|err: std::io::Error| Error::Message(format!("Reduction on rank {} failed: {}", rank, err)))
+    }
+}
+
+/// Return the first block of ROWS_PER_STREAM rows of a rank, the blocks are divided as evenly as possible.
+fn first_block(nblock: usize, rank: usize, nranks: usize) -> usize {
+    rank * nblock / nranks
+}
+
+/// The rows of a periodic square lattice that belong to one rank.
+pub struct Strip {
+    /// Number of sites in x direction.
+    pub nx: usize,
+    /// Number of rows of the whole lattice.
+    pub ny: usize,
+    /// First row of the strip in the whole lattice.
+    pub y0: usize,
+    /// Number of rows of the strip.
+    pub rows: usize,
+    /// Blocks of ROWS_PER_STREAM rows of the strip.
+    blocks: std::ops::Range<usize>,
+    /// Own rows with a halo row below and above, a periodic lattice whose wrap around in y is never used.
+    cfg: Configuration,
+    /// This rank's share of the bond sum Σ s_i s_k of the whole lattice.
+    /**
+     * Starts as the bonds of the own sites to their neighbours at x+1 and y+1 and changes by -2k with
+     * every flip of an own site, so the shares of all ranks add up to the bond sum.
+     */
+    bonds: i64,
+}
+
+impl Strip {
+    /// Return the strip of rank out of nranks of configuration cfg on a periodic square lattice.
+    pub fn from_configuration(cfg: &Configuration, rank: usize, nranks: usize) -> Result<Strip, Error> {
+        if !cfg.is_periodic_square() || !has_bond_energy(cfg, 0.) {
+            return Err(Error::Message("Distributed sweeps need a periodic square lattice without local fields"
+                                      .to_string()));
+        }
+        let nx = cfg.nx;
+        let mut strip = Strip::cold(nx, cfg.ny, rank, nranks)?;
+        for yl in 0..strip.rows+2 {
+            let y = (strip.y0 + strip.ny + yl - 1) % strip.ny;
+            strip.cfg.cfg[yl*nx..(yl+1)*nx].copy_from_slice(&cfg.cfg[y*nx..(y+1)*nx]);
+        }
+        strip.count_bonds();
+        Ok(strip)
+    }
+
+    /// Return the strip of rank out of nranks of a hot start on an nx*ny lattice.
+    /**
+     * The spins of every block of rows are drawn from a stream split off rng, so all ranks get
+     * parts of the same configuration if they pass the same rng, which ends in the same state.
+     * Every rank draws all spins but only stores its own rows and the halos.
+     */
+    pub fn random(nx: usize, ny: usize, rank: usize, nranks: usize, rng: &mut Rng) -> Result<Strip, Error> {
+        let mut strip = Strip::cold(nx, ny, rank, nranks)?;
+        let (below, above) = ((strip.y0 + ny - 1) % ny, (strip.y0 + strip.rows) % ny);
+        let mut row = vec![0; nx];
+        for block in 0..ny.div_ceil(ROWS_PER_STREAM) {
+            let mut stream = rng.split();
+            for y in block*ROWS_PER_STREAM..ny.min((block+1)*ROWS_PER_STREAM) {
+                row.iter_mut().for_each(|s| *s = stream.gen_spin());
+                // a single rank is its own neighbour, so a row can be own and halo
+                let own = (strip.y0..strip.y0+strip.rows).contains(&y).then(|| y - strip.y0 + 1);
+                let halos = [(y == below).then_some(0), (y == above).then_some(strip.rows + 1)];
+                for yl in [own, halos[0], halos[1]].into_iter().flatten() {
+                    strip.cfg.cfg[yl*nx..(yl+1)*nx].copy_from_slice(&row);
+                }
+            }
+        }
+        strip.count_bonds();
+        Ok(strip)
+    }
+
+    /// Return the strip of rank out of nranks of an nx*ny lattice with all spins up.
+    fn cold(nx: usize, ny: usize, rank: usize, nranks: usize) -> Result<Strip, Error> {
+        if !is_supported(nx, ny) {
+            return Err(Error::Message("Distributed sweeps need a 2D lattice with even NX and NY".to_string()));
+        }
+        let nblock = ny.div_ceil(ROWS_PER_STREAM);
+        if nblock < nranks {
+            return Err(Error::Message(format!("Cannot split {} rows over {} ranks, every rank needs {} rows",
+                                              ny, nranks, ROWS_PER_STREAM)));
+        }
+        let blocks = first_block(nblock, rank, nranks)..first_block(nblock, rank+1, nranks);
+        let y0 = blocks.start * ROWS_PER_STREAM;
+        let rows = ny.min(blocks.end * ROWS_PER_STREAM) - y0;
+        let cfg = Configuration::cold(nx, rows + 2, 1);
+        Ok(Strip{nx, ny, y0, rows, blocks, cfg, bonds: 0})
+    }
+
+    /// Set bonds to the bonds of the own sites to their neighbours at x+1 and y+1.
+    fn count_bonds(&mut self) {
+        let (nx, cfg) = (self.nx, &self.cfg);
+        self.bonds = (nx..(self.rows+1)*nx).map(|idx| {
+            let right = idx - idx % nx + (idx + 1) % nx;
+            (cfg[idx] * (cfg[right] + cfg[idx + nx])) as i64
+        }).sum();
+    }
+
+    /// Return the spins of the own rows.
+    pub fn spins(&self) -> &[i32] {
+        &self.cfg.cfg[self.nx..(self.rows+1)*self.nx]
+    }
+
+    /// Perform one checkerboard sweep in zero field with coupling j, returns the number of accepted flips.
+    /**
+     * All ranks must call this with rngs in the same state.
+     */
+    pub fn sweep(&mut self, ring: &mut Ring, beta: f64, j: f64, rng: &mut Rng) -> Result<usize, Error> {
+        let nx = self.nx;
+        let probabilities = square_probabilities(beta, j);
+        let mut streams: Vec<Rng> = (0..self.ny.div_ceil(ROWS_PER_STREAM)).map(|_| rng.split()).collect();
+
+        let mut naccept = 0;
+        for colour in 0..2 {
+            let mut flips = Vec::new();
+            for (block, stream) in self.blocks.clone().zip(&mut streams[self.blocks.clone()]) {
+                for y in block*ROWS_PER_STREAM..self.ny.min((block+1)*ROWS_PER_STREAM) {
+                    // y0 is even, so local rows have the other parity
+                    flips.extend(row_flips_square(&self.cfg, y - self.y0 + 1, 1 - colour, j, &probabilities,
+                                                  stream));
+                }
+            }
+            for &(idx, _) in flips.iter() {
+                self.bonds -= 2 * (self.cfg[idx] * neighbour_sum(&self.cfg, idx)) as i64;
+                self.cfg[idx] *= -1;
+            }
+            naccept += flips.len();
+
+            let (below, rest) = self.cfg.cfg.split_at_mut(nx);
+            let (own, above) = rest.split_at_mut(self.rows*nx);
+            let to_prev: Vec<u8> = own[..nx].iter().map(|&s| s as u8).collect();
+            let to_next: Vec<u8> = own[(self.rows-1)*nx..].iter().map(|&s| s as u8).collect();
+            let (mut from_prev, mut from_next) = (vec![0; nx], vec![0; nx]);
+            ring.exchange(&to_prev, &to_next, &mut from_prev, &mut from_next)?;
+            below.iter_mut().zip(from_prev).for_each(|(s, byte)| *s = byte as i8 as i32);
+            above.iter_mut().zip(from_next).for_each(|(s, byte)| *s = byte as i8 as i32);
+        }
+        Ok(naccept)
+    }
+
+    /// Return the energy with coupling j and the magnetisation per site of the whole lattice.
+    /**
+     * All ranks must call this at the same time.
+     */
+    pub fn measure(&self, ring: &mut Ring, j: f64) -> Result<(f64, f64), Error> {
+        let magnetisation = self.spins().iter().sum::<i32>();
+        let sums = ring.sum(&[self.bonds as f64, magnetisation as f64])?;
+        Ok((-j*sums[0], sums[1] / (self.nx*self.ny) as f64))
+    }
+}
+
+/// Run a temperature scan with checkerboard sweeps distributed over the ranks of ring.
+/**
+ * Starts from a hot start and writes the observables of every temperature to `<idx>.<ext>` in
+ * datadir like a temperature scan, only rank 0 writes output.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn run(datadir: &Path, nx: usize, ny: usize, temperatures: &[f64], j: f64, ntherm_init: usize,
+           ntherm: usize, nprod: usize, format: Format, ring: &mut Ring, rng: &mut Rng) -> Result<(), Error> {
+    let mut strip = Strip::random(nx, ny, ring.rank(), ring.nranks(), rng)?;
+    info!("Rank {} of {} holds rows {} to {}", ring.rank(), ring.nranks(), strip.y0, strip.y0 + strip.rows - 1);
+    for _sweep in 0..ntherm_init {
+        strip.sweep(ring, 1./temperatures[0], j, rng)?;
+    }
+
+    for (i, temp) in temperatures.iter().enumerate() {
+        info!("Running distributed checkerboard sweeps for temperature {}", temp);
+        let beta = 1./temp;
+        for _sweep in 0..ntherm {
+            strip.sweep(ring, beta, j, rng)?;
+        }
+
+        let mut obs = Observables::new();
+        let mut naccept = 0;
+        for _sweep in 0..nprod {
+            naccept += strip.sweep(ring, beta, j, rng)?;
+            let (energy, magnetisation) = strip.measure(ring, j)?;
+            obs.energy.push(energy);
+            obs.magnetisation.push(magnetisation);
+        }
+        let total = ring.sum(&[naccept as f64])?[0];
+        info!("  Acceptance rate: {}", total / (nprod*nx*ny) as f64);
+        if ring.rank() == 0 {
+            write_observables(&datadir.join(format!("{}.{}", i, format.extension())), &obs, format)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,103 @@
+/*!
+ * Errors of runs and output files that report them.
+ *
+ * I/O errors carry the path they occurred at, so the message names the file that could not be written.
+ * Write output with OutputFile, whose write_fmt returns an Error, so `writeln!(file, ...)?` propagates it.
+ */
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// Error of a run.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing path failed.
+    Io{path: PathBuf, source: io::Error},
+    /// The data directory exists and is not empty and may not be deleted.
+    NonEmptyDirectory(PathBuf),
+    /// Any other error, described by its message.
+    Message(String),
+}
+
+impl Error {
+    /// Return a function that turns an io::Error into an Error at path, for use with map_err.
+    pub fn io(path: &Path) -> impl FnOnce(io::Error) -> Error {
+        let path = path.to_path_buf();
+        move |source| Error::Io{path, source}
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io{path, source} => {
+                write!(f, "Cannot access '{}': {}", path.display(), source)?;
+                match source.kind() {
+                    io::ErrorKind::PermissionDenied => write!(f, ", check its permissions or choose another datadir"),
+                    io::ErrorKind::NotFound => write!(f, ", check that its parent directory exists"),
+                    io::ErrorKind::StorageFull => write!(f, ", free some disk space or choose another datadir"),
+                    _ => Ok(()),
+                }
+            }
+            Error::NonEmptyDirectory(path) =>
+                write!(f, "Data directory '{}' is not empty, pass --force to delete it or choose another datadir",
+                       path.display()),
+            Error::Message(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io{source, ..} => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Error {
+        Error::Message(message)
+    }
+}
+
+/// A file for writing output that reports errors with its path.
+pub struct OutputFile {
+    /// Path the file was opened at.
+    path: PathBuf,
+    /// The open file.
+    file: fs::File,
+}
+
+impl OutputFile {
+    /// Create a file at path, truncating it if it exists.
+    pub fn create(path: &Path) -> Result<OutputFile, Error> {
+        let file = fs::File::create(path).map_err(Error::io(path))?;
+        Ok(OutputFile{path: path.to_path_buf(), file})
+    }
+
+    /// Open an existing file at path for appending.
+    pub fn append(path: &Path) -> Result<OutputFile, Error> {
+        let file = fs::OpenOptions::new().append(true).open(path).map_err(Error::io(path))?;
+        Ok(OutputFile{path: path.to_path_buf(), file})
+    }
+
+    /// Return the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Write formatted text, used by write! and writeln!.
+    pub fn write_fmt(&mut self, args: fmt::Arguments) -> Result<(), Error> {
+        self.file.write_fmt(args).map_err(Error::io(&self.path))
+    }
+}
+
+/// Write contents to the file at path, see fs::write.
+pub fn write_file(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), Error> {
+    fs::write(path, contents).map_err(Error::io(path))
+}
@@ -0,0 +1,293 @@
+/*!
+ * Closed-form results used to validate the Monte-Carlo simulation.
+ *
+ * Besides the periodic chain and the critical temperature, this covers Kaufman's partition function
+ * of finite periodic square lattices and exact enumerations of all states of tiny lattices,
+ * which also give magnetisations.
+ */
+
+use std::path::Path;
+
+use crate::error::{Error, OutputFile};
+use crate::{Configuration, delta_e, hamiltonian, info};
+
+/// Exact energy per site of a periodic chain of `n` sites with coupling `j` at temperature `temp`.
+/**
+ * Uses the transfer matrix result Z = λ₊ⁿ + λ₋ⁿ with λ₊ = 2cosh(βJ), λ₋ = 2sinh(βJ)
+ * and h = 0.
+ * Derivatives of Z are normalised to λ₊ⁿ to avoid overflows at low temperatures.
+ */
+pub fn chain_energy(n: usize, j: f64, temp: f64) -> f64 {
+    // Z depends only on K = βJ
+    let (z, dz, _) = chain_partition_derivatives(n, j / temp);
+    -j * dz / z / n as f64
+}
+
+/// Exact specific heat per site of a periodic chain of `n` sites at temperature `temp`.
+/**
+ * C = β² (⟨E²⟩ - ⟨E⟩²) / n, see `chain_energy` for conventions.
+ */
+pub fn chain_specific_heat(n: usize, j: f64, temp: f64) -> f64 {
+    let k = j / temp;
+    let (z, dz, ddz) = chain_partition_derivatives(n, k);
+    k * k * (ddz / z - (dz / z).powi(2)) / n as f64
+}
+
+/// Return Z, dZ/dK, and d²Z/dK² of a periodic chain with K = βJ, all divided by λ₊ⁿ.
+fn chain_partition_derivatives(n: usize, k: f64) -> (f64, f64, f64) {
+    assert!(n >= 2, "A periodic chain needs at least 2 sites");
+
+    let nf = n as f64;
+    let ni = n as i32;
+    let r = k.tanh(); // λ₋ / λ₊
+
+    let z = 1. + r.powi(ni);
+    let dz = nf * (r + r.powi(ni - 1));
+    let ddz = nf * ((nf - 1.) * (r * r + r.powi(ni - 2)) + 1. + r.powi(ni));
+    (z, dz, ddz)
+}
+
+/// Exact critical temperature of the anisotropic square lattice Ising model with couplings `jx` and `jy`.
+/**
+ * Solves Onsager's condition sinh(2|Jx|/T) sinh(2|Jy|/T) = 1 by bisection, which holds for
+ * ferro- and antiferromagnets alike. Reduces to 2J / ln(1 + √2) for Jx = Jy = J
+ * and vanishes if either coupling is 0.
+ */
+pub fn critical_temperature(jx: f64, jy: f64) -> f64 {
+    let (jx, jy) = (jx.abs(), jy.abs());
+    if jx == 0. || jy == 0. {
+        return 0.;
+    }
+    // the left hand side decreases monotonically with T
+    let excess = |temp: f64| (2. * jx / temp).sinh() * (2. * jy / temp).sinh() - 1.;
+    let (mut lo, mut hi) = (0., jx + jy);
+    while excess(hi) > 0. {
+        hi *= 2.;
+    }
+    for _ in 0..200 {
+        let mid = (lo + hi) / 2.;
+        if excess(mid) > 0. {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    (lo + hi) / 2.
+}
+
+/// Return ln(2 cosh x) without overflow.
+fn ln_2cosh(x: f64) -> f64 {
+    x.abs() + (-2.*x.abs()).exp().ln_1p()
+}
+
+/// Return ln|2 sinh x| without overflow, -∞ for x = 0.
+fn ln_2sinh(x: f64) -> f64 {
+    x.abs() + (-(-2.*x.abs()).exp()).ln_1p()
+}
+
+/// Logarithm of the exact partition function of the periodic nx*ny square lattice with coupling `j` at `temp`.
+/**
+ * Uses Kaufman's result for h = 0, see B. Kaufman, Phys. Rev. 76 (1949) 1232,
+ * Z = ½ (2 sinh 2K)^(N/2) Σ_{i=1}^4 Z_i with K = βJ and products over the ny rows
+ * Z_1,2 = Π_r 2 cosh / sinh(nx γ_{2r+1} / 2) and Z_3,4 = Π_r 2 cosh / sinh(nx γ_{2r} / 2),
+ * where cosh γ_l = cosh 2K coth 2K - cos(π l / ny) and γ_0 = 2K + ln tanh K, which is negative below Tc.
+ * An antiferromagnet needs even nx and ny, where Z does not depend on the sign of J.
+ */
+pub fn square_ln_partition_function(nx: usize, ny: usize, j: f64, temp: f64) -> f64 {
+    assert!(nx >= 2 && ny >= 2, "A periodic square lattice needs at least 2 sites in each direction");
+    assert!(j >= 0. || (nx.is_multiple_of(2) && ny.is_multiple_of(2)), "An antiferromagnet needs even nx and ny");
+    let n = (nx*ny) as f64;
+    let k = j.abs() / temp;
+    if k == 0. {
+        return n * 2_f64.ln();
+    }
+
+    let gamma = |l: usize| {
+        if l == 0 {
+            2.*k + k.tanh().ln()
+        }
+        else {
+            let c = (2.*k).cosh() / (2.*k).tanh() - (std::f64::consts::PI * l as f64 / ny as f64).cos();
+            c.acosh()
+        }
+    };
+    let m = nx as f64;
+    let (mut z1, mut z2, mut z3, mut z4) = (0., 0., 0., 0.);
+    let mut sign4 = 1.;
+    for r in 0..ny {
+        let (odd, even) = (m * gamma(2*r+1) / 2., m * gamma(2*r) / 2.);
+        z1 += ln_2cosh(odd);
+        z2 += ln_2sinh(odd);
+        z3 += ln_2cosh(even);
+        z4 += ln_2sinh(even);
+        sign4 *= even.signum();
+    }
+    // Z_1 is the largest term
+    let sum = 1. + (z2 - z1).exp() + (z3 - z1).exp() + sign4 * (z4 - z1).exp();
+    -2_f64.ln() + n / 2. * (2. * (2.*k).sinh()).ln() + z1 + sum.ln()
+}
+
+/// Relative step in K of the numerical derivatives of square_ln_partition_function.
+const SQUARE_STEP: f64 = 1e-4;
+
+/// Return d ln Z / dK and d² ln Z / dK² of the periodic square lattice by central differences.
+fn square_derivatives(nx: usize, ny: usize, k: f64) -> (f64, f64) {
+    let step = SQUARE_STEP * k;
+    let ln_z = |k: f64| square_ln_partition_function(nx, ny, 1., 1./k);
+    let (lo, mid, hi) = (ln_z(k - step), ln_z(k), ln_z(k + step));
+    ((hi - lo) / (2.*step), (hi - 2.*mid + lo) / (step*step))
+}
+
+/// Exact energy per site of the periodic nx*ny square lattice with coupling `j` at temperature `temp`.
+/**
+ * Differentiates square_ln_partition_function numerically, which is accurate to about 1e-8.
+ */
+pub fn square_energy(nx: usize, ny: usize, j: f64, temp: f64) -> f64 {
+    if j == 0. {
+        return 0.;
+    }
+    let (d, _) = square_derivatives(nx, ny, j.abs() / temp);
+    -j.abs() * d / (nx*ny) as f64
+}
+
+/// Exact specific heat per site of the periodic nx*ny square lattice, see `square_energy`.
+pub fn square_specific_heat(nx: usize, ny: usize, j: f64, temp: f64) -> f64 {
+    if j == 0. {
+        return 0.;
+    }
+    let k = j.abs() / temp;
+    let (_, dd) = square_derivatives(nx, ny, k);
+    k * k * dd / (nx*ny) as f64
+}
+
+/// Largest number of occupied sites of an exact enumeration.
+pub const MAX_ENUMERATION_SITES: usize = 25;
+
+/// Exact enumeration of all states of a configuration.
+#[derive(Clone)]
+pub struct Enumeration {
+    /// Configuration with all occupied spins up, the first state.
+    cfg: Configuration,
+    /// Coupling constant.
+    j: f64,
+    /// Uniform external field.
+    h: f64,
+    /// Lowest total energy of all states.
+    ground: f64,
+}
+
+/// Exact thermal averages at one temperature, see analysis::Summary for their definitions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Averages {
+    /// Energy per site.
+    pub energy: f64,
+    /// Specific heat per site.
+    pub specific_heat: f64,
+    /// Absolute magnetisation per site.
+    pub abs_magnetisation: f64,
+    /// Magnetic susceptibility.
+    pub susceptibility: f64,
+    /// Binder cumulant.
+    pub binder: f64,
+    /// Free energy -T ln Z per site.
+    pub free_energy: f64,
+}
+
+impl Enumeration {
+    /// Prepare the enumeration of all states of the occupied sites of cfg with coupling j and field h.
+    /**
+     * Supports any lattice, coupling factors, and local field of cfg and needs at most
+     * MAX_ENUMERATION_SITES occupied sites. Visits all states once to find the ground state energy.
+     */
+    pub fn new(cfg: &Configuration, j: f64, h: f64) -> Enumeration {
+        assert!(cfg.nsite() <= MAX_ENUMERATION_SITES, "Exact enumeration supports at most {} sites",
+                MAX_ENUMERATION_SITES);
+        let mut cfg = cfg.clone();
+        for idx in cfg.active_sites().to_vec() {
+            cfg[idx] = 1;
+        }
+        let mut enumeration = Enumeration{cfg, j, h, ground: f64::INFINITY};
+        let mut ground = f64::INFINITY;
+        enumeration.for_each(|energy, _| ground = ground.min(energy));
+        enumeration.ground = ground;
+        enumeration
+    }
+
+    /// Return the number of occupied sites N.
+    pub fn nsite(&self) -> usize {
+        self.cfg.nsite()
+    }
+
+    /// Return the lowest total energy of all states.
+    pub fn ground_state_energy(&self) -> f64 {
+        self.ground
+    }
+
+    /// Call f with the total energy and total magnetisation of all 2^N states.
+    /**
+     * Visits the states in Gray code order, which flips one spin per step, and tracks the energy with delta_e.
+     */
+    pub fn for_each<F>(&self, mut f: F)
+    where F: FnMut(f64, i32) {
+        let mut cfg = self.cfg.clone();
+        let active = cfg.active_sites().to_vec();
+        let mut energy = hamiltonian(&cfg, self.j, self.h);
+        let mut m = active.len() as i32;
+        f(energy, m);
+        for state in 1_usize..1 << active.len() {
+            // the next Gray code differs in the lowest set bit of state
+            let idx = active[state.trailing_zeros() as usize];
+            energy += delta_e(&cfg, idx, self.j, self.h);
+            m -= 2*cfg[idx];
+            cfg[idx] *= -1;
+            f(energy, m);
+        }
+    }
+
+    /// Compute the exact averages at all temperatures in a single pass over the states.
+    pub fn averages(&self, temperatures: &[f64]) -> Vec<Averages> {
+        let n = self.nsite() as f64;
+        // Σ w, Σ w ΔE, Σ w ΔE², Σ w |m|, Σ w m, Σ w m², Σ w m⁴ with ΔE = E - E_0 of each temperature
+        let mut sums = vec![[0_f64; 7]; temperatures.len()];
+        self.for_each(|energy, m| {
+            let (de, m) = (energy - self.ground, m as f64 / n);
+            for (sum, temp) in sums.iter_mut().zip(temperatures) {
+                let w = (-de / temp).exp();
+                for (s, x) in sum.iter_mut().zip([1., de, de*de, m.abs(), m, m*m, m.powi(4)]) {
+                    *s += w*x;
+                }
+            }
+        });
+
+        sums.iter().zip(temperatures).map(|(sum, &temp)| {
+            let beta = 1./temp;
+            let z = sum[0];
+            let [_, de, de2, abs, m, m2, m4] = sum.map(|s| s / z);
+            Averages{energy: (self.ground + de) / n,
+                     specific_heat: beta*beta * (de2 - de*de) / n,
+                     abs_magnetisation: abs,
+                     susceptibility: beta * n * (m2 - m*m),
+                     binder: 1. - m4 / (3.*m2*m2),
+                     free_energy: (self.ground - temp * z.ln()) / n}
+        }).collect()
+    }
+}
+
+/// Enumerate all states of cfg with coupling j and field h and write the exact averages at the given temperatures.
+/**
+ * Writes `exact.dat` with lines `<i>: <T> <energy> <specific heat> <|m|> <susceptibility> <binder> <free energy>`,
+ * see Averages.
+ */
+pub fn run(datadir: &Path, cfg: &Configuration, j: f64, h: f64, temperatures: &[f64]) -> Result<(), Error> {
+    let enumeration = Enumeration::new(cfg, j, h);
+    info!("Enumerated {} states of {} sites, ground state energy per site {}", 1_usize << enumeration.nsite(),
+          enumeration.nsite(), enumeration.ground_state_energy() / enumeration.nsite() as f64);
+    let mut outfile = OutputFile::create(&datadir.join("exact.dat"))?;
+    for (i, (temp, a)) in temperatures.iter().zip(enumeration.averages(temperatures)).enumerate() {
+        info!("T = {}: E = {}, C = {}, |m| = {}, chi = {}", temp, a.energy, a.specific_heat,
+              a.abs_magnetisation, a.susceptibility);
+        writeln!(outfile, "{}: {} {} {} {} {} {} {}", i, temp, a.energy, a.specific_heat, a.abs_magnetisation,
+                 a.susceptibility, a.binder, a.free_energy)?;
+    }
+    Ok(())
+}
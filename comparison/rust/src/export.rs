@@ -0,0 +1,137 @@
+/*!
+ * Export labelled spin configurations as a dataset for machine learning.
+ */
+
+use std::path::Path;
+
+use crate::autocorr::integrated_time;
+use crate::error::{Error, OutputFile};
+use crate::exact::critical_temperature;
+use crate::npy::NpzWriter;
+use crate::{Configuration, Observables, Rng, evolve, hamiltonian, info};
+
+/// Settings of a dataset export.
+pub struct ExportParams {
+    /// Number of configurations per temperature.
+    pub nsample: usize,
+    /// Minimum number of sweeps between two exported configurations, see decorrelation_skip.
+    pub nskip: usize,
+    /// Number of sweeps per temperature used to estimate τ_int.
+    pub npilot: usize,
+    /// Maximum number of configurations per shard.
+    pub shard_size: usize,
+    /// Number of thermalisation sweeps per temperature.
+    pub ntherm: usize,
+}
+
+/// Collect configurations until a shard is full and write it.
+struct ShardWriter<'a> {
+    datadir: &'a Path,
+    index: OutputFile,
+    nx: usize,
+    ny: usize,
+    shard_size: usize,
+    nshard: usize,
+    tc: f64,
+    configs: Vec<i8>,
+    temperatures: Vec<f64>,
+    labels: Vec<i8>,
+}
+
+impl ShardWriter<'_> {
+    /// Add a configuration at given temperature and write the shard if it is full.
+    fn push(&mut self, cfg: &Configuration, temp: f64) -> Result<(), Error> {
+        self.configs.extend(cfg.cfg.iter().map(|&s| s as i8));
+        self.temperatures.push(temp);
+        // the chain has no ordered phase at T > 0
+        self.labels.push(if self.ny > 1 && temp < self.tc { 1 } else { 0 });
+        if self.labels.len() == self.shard_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Write all collected configurations to a new shard.
+    fn flush(&mut self) -> Result<(), Error> {
+        let n = self.labels.len();
+        if n == 0 {
+            return Ok(());
+        }
+
+        let fname = format!("shard_{}.npz", self.nshard);
+        let mut npz = NpzWriter::new();
+        npz.add("configs", &[n, self.ny, self.nx], &self.configs);
+        npz.add("temperature", &[n], &self.temperatures);
+        npz.add("label", &[n], &self.labels);
+        let path = self.datadir.join(&fname);
+        npz.write(&path).map_err(Error::io(&path))?;
+        writeln!(self.index, "{}: {} {}", self.nshard, fname, n)?;
+
+        self.nshard += 1;
+        self.configs.clear();
+        self.temperatures.clear();
+        self.labels.clear();
+        Ok(())
+    }
+}
+
+/// Sample decorrelated configurations at all temperatures and write them as npz shards.
+/**
+ * Each shard `shard_<k>.npz` contains arrays
+ *  - `configs`: int8 spins with shape (n, ny, nx),
+ *  - `temperature`: float64 temperature of each configuration,
+ *  - `label`: int8 phase label, 1 for the ordered phase (T < Tc of the square lattice), 0 otherwise.
+ *
+ * `index.dat` lists lines `<k>: <file name> <n>`, `export.dat` the lattice shape and Tc, and
+ * `skip.dat` lines `<T>: <nskip> <τ_int>` with the sweeps between configurations, see decorrelation_skip.
+ * Configurations of consecutive temperatures share shards, shuffle before training.
+ */
+pub fn run(datadir: &Path, nx: usize, ny: usize, temperatures: &[f64],
+           params: &ExportParams, rng: &mut Rng) -> Result<(), Error> {
+    let tc = critical_temperature(1., 1.);
+    let mut info = OutputFile::create(&datadir.join("export.dat"))?;
+    writeln!(info, "nx: {}\nny: {}\ntc: {}", nx, ny, tc)?;
+    let mut skipfile = OutputFile::create(&datadir.join("skip.dat"))?;
+
+    let mut shards = ShardWriter{
+        datadir,
+        index: OutputFile::create(&datadir.join("index.dat"))?,
+        nx, ny,
+        shard_size: params.shard_size,
+        nshard: 0,
+        tc,
+        configs: Vec::new(),
+        temperatures: Vec::new(),
+        labels: Vec::new(),
+    };
+
+    let mut cfg = Configuration::random(nx, ny, rng);
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    for temp in temperatures.iter() {
+        info!("Exporting {} configurations at temperature {}", params.nsample, temp);
+        let beta = 1./temp;
+        evolve(&mut cfg, &mut energy, beta, 1., 0., rng, params.ntherm, None);
+        let (nskip, tau) = decorrelation_skip(&mut cfg, &mut energy, beta, params, rng);
+        writeln!(skipfile, "{}: {} {}", temp, nskip, tau)?;
+        for _ in 0..params.nsample {
+            evolve(&mut cfg, &mut energy, beta, 1., 0., rng, nskip, None);
+            shards.push(&cfg, *temp)?;
+        }
+    }
+    shards.flush()
+}
+
+/// Run params.npilot sweeps and return the number of sweeps between two samples and τ_int.
+/**
+ * τ_int is the larger integrated autocorrelation time of energy and |magnetisation| in the pilot sweeps,
+ * see autocorr::integrated_time. Samples 2τ_int sweeps apart are effectively independent, so the number of
+ * sweeps is max(params.nskip, ⌈2τ_int⌉). cfg is thermalised and stays in equilibrium during the pilot sweeps.
+ */
+pub fn decorrelation_skip(cfg: &mut Configuration, energy: &mut f64, beta: f64, params: &ExportParams,
+                          rng: &mut Rng) -> (usize, f64) {
+    let mut obs = Observables::new();
+    evolve(cfg, energy, beta, 1., 0., rng, params.npilot, Some(&mut obs));
+    let abs_magnetisation: Vec<f64> = obs.magnetisation.iter().map(|m| m.abs()).collect();
+    let tau = integrated_time(&obs.energy).max(integrated_time(&abs_magnetisation));
+    (params.nskip.max((2.*tau).ceil() as usize), tau)
+}
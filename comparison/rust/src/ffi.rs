@@ -0,0 +1,181 @@
+/*!
+ * C interface to the simulation, built into the cdylib of the crate.
+ *
+ * A simulation is an opaque pointer created by ising_create and released by ising_destroy.
+ * It holds a configuration on a periodic square lattice, its energy, a random number generator, the
+ * update algorithm, and the energy and magnetisation per site measured after each sweep.
+ * The declarations for C and C++ are in include/ising.h.
+ */
+
+use std::ffi::{CStr, c_char};
+use std::ptr;
+use std::slice;
+
+use crate::benchmark::Algorithm;
+use crate::config::expand_seed;
+use crate::{Configuration, Observables, Rng, hamiltonian};
+
+/// State of a simulation behind the pointers of the C interface.
+pub struct Simulation {
+    cfg: Configuration,
+    energy: f64,
+    beta: f64,
+    coupling: f64,
+    field: f64,
+    algorithm: Algorithm,
+    rng: Rng,
+    obs: Observables,
+}
+
+/// Create a simulation on an nx*ny lattice with a hot start at the given temperature.
+/**
+ * Updates use the Metropolis algorithm unless changed with ising_set_algorithm. The random numbers are
+ * drawn from the same generator as the ising program with this seed.
+ * Returns null if nx or ny is zero or the temperature is not positive.
+ */
+#[no_mangle]
+pub extern "C" fn ising_create(nx: usize, ny: usize, temperature: f64, coupling: f64, field: f64,
+                               seed: u64) -> *mut Simulation {
+    // no NaN either
+    if nx == 0 || ny == 0 || temperature.is_nan() || temperature <= 0. {
+        return ptr::null_mut();
+    }
+    let mut rng = Rng::from_seed(expand_seed(seed));
+    let cfg = Configuration::random(nx, ny, &mut rng);
+    let energy = hamiltonian(&cfg, coupling, field);
+    Box::into_raw(Box::new(Simulation{cfg, energy, beta: 1./temperature, coupling, field,
+                                      algorithm: Algorithm::Metropolis, rng, obs: Observables::new()}))
+}
+
+/// Release a simulation, sim may be null.
+/**
+ * # Safety
+ * sim must be null or a pointer returned by ising_create that was not destroyed yet.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn ising_destroy(sim: *mut Simulation) {
+    if !sim.is_null() {
+        drop(Box::from_raw(sim));
+    }
+}
+
+/// Select the update algorithm by its name on the command line of the ising program, e.g. "wolff".
+/**
+ * Returns 0 on success and -1 if the name is unknown, the algorithm is then unchanged.
+ *
+ * # Safety
+ * sim must be a live simulation and name a null terminated string.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn ising_set_algorithm(sim: *mut Simulation, name: *const c_char) -> i32 {
+    let sim = &mut *sim;
+    match CStr::from_ptr(name).to_str().map_err(|err| err.to_string()).and_then(Algorithm::parse) {
+        Ok(algorithm) => {
+            sim.algorithm = algorithm;
+            0
+        }
+        Err(_) => -1,
+    }
+}
+
+/// Change the temperature of the following sweeps, returns -1 if it is not positive and 0 otherwise.
+/**
+ * # Safety
+ * sim must be a live simulation.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn ising_set_temperature(sim: *mut Simulation, temperature: f64) -> i32 {
+    if temperature.is_nan() || temperature <= 0. {
+        return -1;
+    }
+    (*sim).beta = 1./temperature;
+    0
+}
+
+/// Run nsweep sweeps and measure after each if measure is nonzero, returns the number of accepted updates.
+/**
+ * # Safety
+ * sim must be a live simulation.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn ising_sweep(sim: *mut Simulation, nsweep: usize, measure: i32) -> usize {
+    let sim = &mut *sim;
+    let obs = if measure != 0 { Some(&mut sim.obs) } else { None };
+    sim.algorithm.evolve(&mut sim.cfg, &mut sim.energy, sim.beta, sim.coupling, sim.field, &mut sim.rng,
+                         nsweep, obs)
+}
+
+/// Return the current energy, not per site.
+/**
+ * # Safety
+ * sim must be a live simulation.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn ising_energy(sim: *const Simulation) -> f64 {
+    (*sim).energy
+}
+
+/// Return the number of measurements since the simulation was created or its measurements were cleared.
+/**
+ * # Safety
+ * sim must be a live simulation.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn ising_nmeasurement(sim: *const Simulation) -> usize {
+    (*sim).obs.energy.len()
+}
+
+/// Copy up to len measurements of the energy per site to out, returns the number of copied values.
+/**
+ * # Safety
+ * sim must be a live simulation and out must have room for len values.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn ising_read_energy(sim: *const Simulation, out: *mut f64, len: usize) -> usize {
+    let sim = &*sim;
+    let nsite = sim.cfg.nsite() as f64;
+    copy_to(sim.obs.energy.iter().map(|e| e / nsite), out, len)
+}
+
+/// Copy up to len measurements of the magnetisation per site to out, returns the number of copied values.
+/**
+ * # Safety
+ * sim must be a live simulation and out must have room for len values.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn ising_read_magnetisation(sim: *const Simulation, out: *mut f64, len: usize) -> usize {
+    copy_to((*sim).obs.magnetisation.iter().copied(), out, len)
+}
+
+/// Remove all measurements.
+/**
+ * # Safety
+ * sim must be a live simulation.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn ising_clear_measurements(sim: *mut Simulation) {
+    (*sim).obs.clear();
+}
+
+/// Copy up to len spins in row major order to out, returns the number of copied spins.
+/**
+ * # Safety
+ * sim must be a live simulation and out must have room for len values.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn ising_read_spins(sim: *const Simulation, out: *mut i32, len: usize) -> usize {
+    copy_to((*sim).cfg.cfg.iter().copied(), out, len)
+}
+
+/// Copy up to len values to out and return how many were copied.
+/**
+ * # Safety
+ * out must have room for len values.
+ */
+unsafe fn copy_to<T>(values: impl ExactSizeIterator<Item = T>, out: *mut T, len: usize) -> usize {
+    let n = values.len().min(len);
+    if n > 0 {
+        slice::from_raw_parts_mut(out, n).iter_mut().zip(values).for_each(|(slot, value)| *slot = value);
+    }
+    n
+}
@@ -0,0 +1,266 @@
+/*!
+ * Diagnostics for first-order phase transitions.
+ *
+ * At a first-order transition, phases coexist and histograms of the energy
+ * develop two peaks. The energy cumulant V = 1 - ⟨E⁴⟩ / (3⟨E²⟩²) then has a
+ * minimum that stays below 2/3 for large lattices, while it approaches 2/3
+ * everywhere at a continuous transition.
+ */
+
+use std::path::Path;
+
+use crate::error::{Error, OutputFile};
+use crate::{Configuration, Observables, Rng, evolve, hamiltonian, info};
+
+/// Minimum height of the second peak relative to the highest peak.
+const PEAK_FRACTION: f64 = 0.1;
+/// The histogram must drop below this fraction of the lower peak between two peaks.
+const DIP_FRACTION: f64 = 0.8;
+/// The dip must also be this many standard deviations of the counts below the lower peak.
+const DIP_SIGNIFICANCE: f64 = 3.;
+
+/// A histogram with equally sized bins.
+pub struct Histogram {
+    /// Centres of the bins.
+    pub centres: Vec<f64>,
+    /// Number of entries in each bin.
+    pub counts: Vec<f64>,
+}
+
+/// Fill a histogram with at most nbin bins spanning the range of data.
+/**
+ * Observables of small lattices only take a few discrete values.
+ * If there are at most nbin of them (with the smallest observed spacing), every value
+ * gets a bin of its own, centred on the value, so there are no spurious empty bins.
+ */
+pub fn histogram(data: &[f64], nbin: usize) -> Histogram {
+    let mut sorted = data.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let min = sorted[0];
+    let max = sorted[sorted.len()-1];
+    let gap = sorted.windows(2).map(|w| w[1]-w[0])
+        .filter(|&d| d > 1e-9*(max-min))
+        .fold(f64::INFINITY, f64::min);
+
+    let (nbin, start, width) = if max == min {
+        (1, min - 0.5, 1.)
+    }
+    else if (max-min) / gap < (nbin-1) as f64 + 0.5 {
+        let nlevel = ((max-min) / gap).round() as usize + 1;
+        (nlevel, min - gap/2., gap)
+    }
+    else {
+        (nbin, min, (max-min) / nbin as f64)
+    };
+
+    let mut counts = vec![0.; nbin];
+    for x in data.iter() {
+        let bin = (((x-start) / width) as usize).min(nbin-1);
+        counts[bin] += 1.;
+    }
+    let centres = (0..nbin).map(|i| start + (i as f64 + 0.5)*width).collect();
+    Histogram{centres, counts}
+}
+
+/// Average counts over three neighbouring bins.
+fn smoothed(counts: &[f64]) -> Vec<f64> {
+    let n = counts.len();
+    (0..n).map(|i| {
+        let lo = i.saturating_sub(1);
+        let hi = (i+2).min(n);
+        counts[lo..hi].iter().sum::<f64>() / (hi-lo) as f64
+    }).collect()
+}
+
+/// Check whether a histogram has two well separated peaks.
+/**
+ * The counts are smoothed over three neighbouring bins to remove noise.
+ * The histogram is double peaked if there is a local maximum of at least PEAK_FRACTION
+ * times the global maximum and the counts between the two drop below DIP_FRACTION
+ * times the lower one. The drop must also exceed DIP_SIGNIFICANCE times its Poisson error
+ * sqrt(peak + dip), so the noise of a single peak with few samples per bin is not mistaken for a dip.
+ * Autocorrelated samples are noisier than Poisson counts, thin them first.
+ */
+pub fn is_double_peaked(hist: &Histogram) -> bool {
+    let n = hist.counts.len();
+    let smooth = smoothed(&hist.counts);
+
+    let (imax, &max) = smooth.iter().enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1)).unwrap();
+
+    (0..n).filter(|&i| i != imax).any(|i| {
+        let height = smooth[i];
+        let is_local_max = (i == 0 || smooth[i-1] < height) && (i == n-1 || smooth[i+1] <= height);
+        let (lo, hi) = if i < imax { (i, imax) } else { (imax, i) };
+        let dip = smooth[lo..=hi].iter().copied().fold(f64::INFINITY, f64::min);
+        is_local_max && height >= PEAK_FRACTION*max && dip < DIP_FRACTION*height
+            && height - dip > DIP_SIGNIFICANCE*(height + dip).sqrt()
+    })
+}
+
+/// Compute the energy cumulant V = 1 - ⟨E⁴⟩ / (3⟨E²⟩²).
+pub fn energy_cumulant(energy: &[f64]) -> f64 {
+    let n = energy.len() as f64;
+    let e2 = energy.iter().map(|e| e.powi(2)).sum::<f64>() / n;
+    let e4 = energy.iter().map(|e| e.powi(4)).sum::<f64>() / n;
+    1. - e4 / (3.*e2*e2)
+}
+
+/// Location of a first-order transition and its latent heat on a finite lattice.
+pub struct Transition {
+    /// Inverse temperature where both phases have equal weight.
+    pub beta_weight: f64,
+    /// Inverse temperature where both peaks of the energy histogram have equal height.
+    pub beta_height: f64,
+    /// Difference of the mean energies per site of the two phases at beta_weight.
+    pub latent_heat: f64,
+}
+
+/// Compute normalised weights that reweight samples of the energy taken at beta0 to beta.
+fn reweight(energy: &[f64], beta0: f64, beta: f64) -> Vec<f64> {
+    let exponents: Vec<f64> = energy.iter().map(|e| -(beta-beta0)*e).collect();
+    // subtract the maximum to avoid overflow
+    let max = exponents.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let weights: Vec<f64> = exponents.iter().map(|x| (x-max).exp()).collect();
+    let total: f64 = weights.iter().sum();
+    weights.iter().map(|w| w / total).collect()
+}
+
+/// Find the root of a function that increases on [lo, hi] by bisection.
+fn bisect(f: impl Fn(f64) -> f64, mut lo: f64, mut hi: f64) -> Option<f64> {
+    if f(lo) > 0. || f(hi) < 0. {
+        return None;
+    }
+    for _ in 0..100 {
+        let mid = (lo+hi) / 2.;
+        if f(mid) < 0. { lo = mid; } else { hi = mid; }
+    }
+    Some((lo+hi) / 2.)
+}
+
+/// Locate a first-order transition from an energy history measured at inverse temperature beta0.
+/**
+ * The energy histogram must be double peaked at beta0, see is_double_peaked.
+ * Phases are separated at the minimum of the histogram between the two highest peaks.
+ * The history is reweighted to find the inverse temperatures where both phases have
+ * equal weight and where the peaks have equal height. Both are searched in [beta0/2, 2 beta0].
+ * Returns None if the histogram is not double peaked or there is no solution.
+ */
+pub fn transition(energy: &[f64], beta0: f64, size: usize, nbin: usize) -> Option<Transition> {
+    let hist = histogram(energy, nbin);
+    if !is_double_peaked(&hist) {
+        return None;
+    }
+
+    // the two highest local maxima and the minimum between them
+    let smooth = smoothed(&hist.counts);
+    let n = smooth.len();
+    let mut peaks: Vec<usize> = (0..n).filter(|&i| {
+        (i == 0 || smooth[i-1] < smooth[i]) && (i == n-1 || smooth[i+1] <= smooth[i])
+    }).collect();
+    peaks.sort_by(|&a, &b| smooth[b].total_cmp(&smooth[a]));
+    let (lo, hi) = (peaks[0].min(peaks[1]), peaks[0].max(peaks[1]));
+    let split_bin = (lo..=hi).min_by(|&a, &b| smooth[a].total_cmp(&smooth[b])).unwrap();
+    let split = hist.centres[split_bin];
+
+    // fraction of the low energy (ordered) phase, increases with beta
+    let ordered_weight = |beta: f64| -> f64 {
+        let weights = reweight(energy, beta0, beta);
+        energy.iter().zip(weights.iter()).filter(|(e, _)| **e < split).map(|(_, w)| w).sum::<f64>() - 0.5
+    };
+    let beta_weight = bisect(ordered_weight, beta0/2., 2.*beta0)?;
+
+    // difference of the peak heights of the reweighted histogram, increases with beta
+    let height_difference = |beta: f64| -> f64 {
+        let reweighted: Vec<f64> = hist.counts.iter().zip(hist.centres.iter())
+            .map(|(c, e)| c * (-(beta-beta0)*(e-split)).exp())
+            .collect();
+        let max = |range: &[f64]| range.iter().copied().fold(0., f64::max);
+        max(&reweighted[..split_bin]) - max(&reweighted[split_bin+1..])
+    };
+    let beta_height = bisect(height_difference, beta0/2., 2.*beta0)?;
+
+    let weights = reweight(energy, beta0, beta_weight);
+    let phase_mean = |ordered: bool| -> f64 {
+        let (sum, norm) = energy.iter().zip(weights.iter())
+            .filter(|(e, _)| (**e < split) == ordered)
+            .fold((0., 0.), |(s, n), (e, w)| (s + e*w, n + w));
+        sum / norm
+    };
+    let latent_heat = (phase_mean(false) - phase_mean(true)) / size as f64;
+
+    Some(Transition{beta_weight, beta_height, latent_heat})
+}
+
+/// Fit values measured on lattices with given numbers of sites to a + b / size.
+/**
+ * This is the leading finite size correction at a first-order transition.
+ * Returns (a, b) from a least squares fit, i.e. the infinite volume limit a.
+ */
+pub fn extrapolate(sizes: &[usize], values: &[f64]) -> (f64, f64) {
+    let n = sizes.len() as f64;
+    let xs: Vec<f64> = sizes.iter().map(|&s| 1. / s as f64).collect();
+    let xmean = xs.iter().sum::<f64>() / n;
+    let ymean = values.iter().sum::<f64>() / n;
+    let sxy: f64 = xs.iter().zip(values.iter()).map(|(x, y)| (x-xmean)*(y-ymean)).sum();
+    let sxx: f64 = xs.iter().map(|x| (x-xmean).powi(2)).sum();
+    let slope = sxy / sxx;
+    (ymean - slope*xmean, slope)
+}
+
+/// Locate the transition on several lattices and extrapolate to infinite volume.
+/**
+ * For each lattice (nx, ny) in lattices, runs nprod sweeps at temperature temp with
+ * coupling j and field h after ntherm sweeps of thermalisation. temp must be close enough to the transition
+ * for both phases to appear in the energy histogram.
+ *
+ * Writes `latent_heat.dat` with lines `<i>: <nx> <ny> <β_weight> <β_height> <latent heat>`
+ * (NaN if the histogram is not double peaked) and `latent_heat_fit.dat` with lines
+ * `<quantity>: <infinite volume limit> <coefficient of 1/N>` using all lattices with a transition.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn run(datadir: &Path, lattices: &[(usize, usize)], temp: f64, j: f64, h: f64,
+           ntherm: usize, nprod: usize, nbin: usize, rng: &mut Rng) -> Result<(), Error> {
+    let beta = 1./temp;
+    let mut outfile = OutputFile::create(&datadir.join("latent_heat.dat"))?;
+    let mut found = Vec::new();
+
+    for (i, &(nx, ny)) in lattices.iter().enumerate() {
+        info!("Locating the transition on a {}x{} lattice", nx, ny);
+        let mut cfg = Configuration::random(nx, ny, rng);
+        let mut energy = hamiltonian(&cfg, j, h);
+        evolve(&mut cfg, &mut energy, beta, j, h, rng, ntherm, None);
+        let mut obs = Observables::new();
+        evolve(&mut cfg, &mut energy, beta, j, h, rng, nprod, Some(&mut obs));
+
+        match transition(&obs.energy, beta, cfg.size(), nbin) {
+            Some(trans) => {
+                writeln!(outfile, "{}: {} {} {} {} {}", i, nx, ny,
+                         trans.beta_weight, trans.beta_height, trans.latent_heat)?;
+                found.push((cfg.size(), trans));
+            }
+            None => {
+                info!("  Energy histogram is not double peaked");
+                writeln!(outfile, "{}: {} {} NaN NaN NaN", i, nx, ny)?;
+            }
+        }
+    }
+
+    if found.len() < 2 {
+        info!("Need a transition on at least two lattices to extrapolate");
+        return Ok(());
+    }
+    let sizes: Vec<usize> = found.iter().map(|(size, _)| *size).collect();
+    let mut fitfile = OutputFile::create(&datadir.join("latent_heat_fit.dat"))?;
+    let quantities = [
+        ("beta_weight", found.iter().map(|(_, t)| t.beta_weight).collect::<Vec<_>>()),
+        ("beta_height", found.iter().map(|(_, t)| t.beta_height).collect()),
+        ("latent_heat", found.iter().map(|(_, t)| t.latent_heat).collect()),
+    ];
+    for (name, values) in quantities {
+        let (limit, slope) = extrapolate(&sizes, &values);
+        info!("{} extrapolates to {}", name, limit);
+        writeln!(fitfile, "{}: {} {}", name, limit, slope)?;
+    }
+    Ok(())
+}
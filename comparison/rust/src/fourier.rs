@@ -0,0 +1,211 @@
+/*!
+ * Fourier modes of the spin field and derived structure factors.
+ */
+
+use std::f64::consts::PI;
+
+use crate::Configuration;
+
+/// A complex number stored as (real, imaginary) part.
+pub type Complex = (f64, f64);
+
+/// Return mode numbers (n, m) of all wave vectors along the lattice axes.
+/**
+ * The wave vector of mode (n, m) is k = (2π n / nx, 2π m / ny).
+ * Returns k = 0, all (n, 0) for 0 < n <= nx/2 and all (0, m) for 0 < m <= ny/2.
+ */
+pub fn axis_modes(nx: usize, ny: usize) -> Vec<(usize, usize)> {
+    let mut modes = vec![(0, 0)];
+    modes.extend((1..=nx/2).map(|n| (n, 0)));
+    modes.extend((1..=ny/2).map(|m| (0, m)));
+    modes
+}
+
+/// Compute the Fourier mode s(k) = Σ_r s(r) exp(-i k·r) with k given by mode numbers (n, m).
+pub fn fourier_mode(cfg: &Configuration, (n, m): (usize, usize)) -> Complex {
+    let kx = 2.*PI*n as f64 / cfg.nx as f64;
+    let ky = 2.*PI*m as f64 / cfg.ny as f64;
+
+    let mut re = 0.;
+    let mut im = 0.;
+    for y in 0..cfg.ny {
+        for x in 0..cfg.nx {
+            let phase = kx*x as f64 + ky*y as f64;
+            let spin = cfg[y*cfg.nx + x] as f64;
+            re += spin*phase.cos();
+            im -= spin*phase.sin();
+        }
+    }
+    (re, im)
+}
+
+/// Compute the structure factor S(k) = |s(k)|² / N at the smallest non-zero wave vector k = (2π/nx, 0).
+/**
+ * Sums the columns first, so only nx phases are evaluated. N is the number of occupied sites.
+ */
+pub fn smallest_structure_factor(cfg: &Configuration) -> f64 {
+    let k = 2.*PI / cfg.nx as f64;
+    let mut re = 0.;
+    let mut im = 0.;
+    for x in 0..cfg.nx {
+        let column: i32 = (0..cfg.ny).map(|y| cfg[y*cfg.nx + x]).sum();
+        let phase = k*x as f64;
+        re += column as f64 * phase.cos();
+        im -= column as f64 * phase.sin();
+    }
+    (re*re + im*im) / cfg.nsite() as f64
+}
+
+/// Compute the Fourier modes of a configuration for all given mode numbers.
+pub fn fourier_modes(cfg: &Configuration, modes: &[(usize, usize)]) -> Vec<Complex> {
+    modes.iter().map(|&mode| fourier_mode(cfg, mode)).collect()
+}
+
+/// Compute the dynamic structure factor S(k, t) = ⟨s(k, t₀+t) s(-k, t₀)⟩ / N.
+/**
+ * `history` holds the Fourier modes of each measurement, as returned by fourier_modes.
+ * The average runs over all t₀ for which t₀+t is in the history.
+ * Returns S[k][t] for t in 0..ntau. The intermediate scattering function is
+ * F(k, t) = S(k, t) / S(k, 0).
+ */
+pub fn dynamic_structure_factor(history: &[Vec<Complex>], ntau: usize, size: usize) -> Vec<Vec<f64>> {
+    let nmodes = history.first().map_or(0, |modes| modes.len());
+    let ntau = ntau.min(history.len());
+
+    (0..nmodes).map(|k| {
+        (0..ntau).map(|tau| {
+            let nsample = history.len() - tau;
+            let sum: f64 = (0..nsample).map(|t0| {
+                let (re0, im0) = history[t0][k];
+                let (re1, im1) = history[t0+tau][k];
+                // real part of s(k, t₀+t) * conj(s(k, t₀)), the imaginary part averages to 0
+                re1*re0 + im1*im0
+            }).sum();
+            sum / nsample as f64 / size as f64
+        }).collect()
+    }).collect()
+}
+
+/// Compute the discrete Fourier transform of a field on an nx*ny lattice in place.
+/**
+ * data is stored in row major order (x runs fastest).
+ * Computes f(k) = Σ_r f(r) exp(∓i k·r), with the + sign if inverse is true.
+ * The inverse transform is not normalised, divide by nx*ny to undo a forward transform.
+ * Transforms rows and columns separately, so the cost is O(nx ny (nx + ny)).
+ */
+pub fn dft_2d(data: &mut [Complex], nx: usize, ny: usize, inverse: bool) {
+    let sign = if inverse { 1. } else { -1. };
+    let transform = |line: &[Complex]| -> Vec<Complex> {
+        let n = line.len();
+        (0..n).map(|k| {
+            line.iter().enumerate().fold((0., 0.), |(re, im), (j, &(a, b))| {
+                let phase = sign*2.*PI*((k*j) % n) as f64 / n as f64;
+                let (c, s) = (phase.cos(), phase.sin());
+                (re + a*c - b*s, im + a*s + b*c)
+            })
+        }).collect()
+    };
+
+    for y in 0..ny {
+        let row = transform(&data[y*nx..(y+1)*nx]);
+        data[y*nx..(y+1)*nx].copy_from_slice(&row);
+    }
+    for x in 0..nx {
+        let column: Vec<Complex> = (0..ny).map(|y| data[y*nx + x]).collect();
+        for (y, value) in transform(&column).into_iter().enumerate() {
+            data[y*nx + x] = value;
+        }
+    }
+}
+
+/// Compute the discrete Fourier transform of a line in place with a radix-2 FFT.
+/**
+ * The length of line must be a power of two. Same conventions as dft_2d.
+ */
+fn fft_line(line: &mut [Complex], inverse: bool) {
+    let n = line.len();
+    debug_assert!(n.is_power_of_two());
+    let sign = if inverse { 1. } else { -1. };
+
+    // bit reversal permutation
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits().checked_shr(usize::BITS - bits).unwrap_or(0);
+        if i < j {
+            line.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let phase = sign*2.*PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for j in 0..len/2 {
+                let (s, c) = (phase*j as f64).sin_cos();
+                let (a, b) = line[start + j + len/2];
+                let odd = (a*c - b*s, a*s + b*c);
+                let even = line[start + j];
+                line[start + j] = (even.0 + odd.0, even.1 + odd.1);
+                line[start + j + len/2] = (even.0 - odd.0, even.1 - odd.1);
+            }
+        }
+        len *= 2;
+    }
+}
+
+/// Compute the discrete Fourier transform of a field on an nx*ny lattice in place.
+/**
+ * Same as dft_2d but uses a radix-2 FFT with cost O(nx ny log(nx ny)) if nx and ny
+ * are powers of two and falls back to dft_2d otherwise.
+ */
+pub fn fft_2d(data: &mut [Complex], nx: usize, ny: usize, inverse: bool) {
+    if !nx.is_power_of_two() || !ny.is_power_of_two() {
+        dft_2d(data, nx, ny, inverse);
+        return;
+    }
+
+    for row in data.chunks_exact_mut(nx) {
+        fft_line(row, inverse);
+    }
+    let mut column = vec![(0., 0.); ny];
+    for x in 0..nx {
+        for (y, value) in column.iter_mut().enumerate() {
+            *value = data[y*nx + x];
+        }
+        fft_line(&mut column, inverse);
+        for (y, value) in column.iter().enumerate() {
+            data[y*nx + x] = *value;
+        }
+    }
+}
+
+/// Accumulate the static structure factor S(k) = ⟨|s(k)|²⟩ / N for all wave vectors.
+pub struct StructureFactor {
+    /// Σ over measurements of |s(k)|² / N, stored like the configuration with k = (2π n/nx, 2π m/ny) at m*nx + n.
+    sum: Vec<f64>,
+    /// Number of measurements.
+    nmeasure: usize,
+}
+
+impl StructureFactor {
+    /// Create an accumulator for an nx*ny lattice.
+    pub fn new(nx: usize, ny: usize) -> StructureFactor {
+        StructureFactor{sum: vec![0.; nx*ny], nmeasure: 0}
+    }
+
+    /// Add |s(k)|² / N of cfg, computed with fft_2d.
+    pub fn measure(&mut self, cfg: &Configuration) {
+        let mut data: Vec<Complex> = cfg.cfg.iter().map(|&s| (s as f64, 0.)).collect();
+        fft_2d(&mut data, cfg.nx, cfg.ny, false);
+        let size = cfg.nsite() as f64;
+        for (sum, (re, im)) in self.sum.iter_mut().zip(data) {
+            *sum += (re*re + im*im) / size;
+        }
+        self.nmeasure += 1;
+    }
+
+    /// Return S(k) averaged over all measurements, in the layout of a configuration.
+    pub fn average(&self) -> Vec<f64> {
+        self.sum.iter().map(|sum| sum / self.nmeasure as f64).collect()
+    }
+}
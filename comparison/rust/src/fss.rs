@@ -0,0 +1,141 @@
+/*!
+ * Finite-size scaling of temperature scans on several lattice sizes.
+ *
+ * The Binder cumulants of two sizes cross close to Tc, and the maxima of the susceptibility and
+ * specific heat shift towards Tc and grow with the size L as powers of L that involve the critical exponents.
+ * Locating both on the grid of simulated temperatures is the input to fits of the exponents.
+ */
+
+use std::path::Path;
+
+use crate::analysis::{Summary, fits};
+use crate::error::{Error, OutputFile};
+use crate::info;
+
+/// Return the indices of temperatures in increasing order.
+fn sorted_indices(temperatures: &[f64]) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..temperatures.len()).collect();
+    indices.sort_by(|&a, &b| temperatures[a].total_cmp(&temperatures[b]));
+    indices
+}
+
+/// Locate the maximum of values over temperatures, returns its temperature and value.
+/**
+ * Interpolates with a parabola through the largest value and its neighbouring temperatures.
+ * Returns the largest value itself if it is at the edge of the temperature range or the parabola
+ * is not concave. temperatures need not be sorted.
+ */
+pub fn peak(temperatures: &[f64], values: &[f64]) -> (f64, f64) {
+    let indices = sorted_indices(temperatures);
+    let k = (0..indices.len()).max_by(|&a, &b| values[indices[a]].total_cmp(&values[indices[b]])).unwrap();
+    if k == 0 || k+1 == indices.len() {
+        return (temperatures[indices[k]], values[indices[k]]);
+    }
+    let [(x0, y0), (x1, y1), (x2, y2)] = [k-1, k, k+1].map(|k| (temperatures[indices[k]], values[indices[k]]));
+    let denom = (x0-x1) * (x0-x2) * (x1-x2);
+    let a = (x2*(y1-y0) + x1*(y0-y2) + x0*(y2-y1)) / denom;
+    let b = (x2*x2*(y0-y1) + x1*x1*(y2-y0) + x0*x0*(y1-y2)) / denom;
+    let c = (x1*x2*(x1-x2)*y0 + x2*x0*(x2-x0)*y1 + x0*x1*(x0-x1)*y2) / denom;
+    if a >= 0. {
+        return (x1, y1);
+    }
+    (-b / (2.*a), c - b*b / (4.*a))
+}
+
+/// Locate the lowest temperature where the curves a and b cross by linear interpolation.
+/**
+ * Returns None if a - b does not change its sign. temperatures need not be sorted.
+ */
+pub fn crossing(temperatures: &[f64], a: &[f64], b: &[f64]) -> Option<f64> {
+    let indices = sorted_indices(temperatures);
+    indices.windows(2).find_map(|w| {
+        let (d0, d1) = (a[w[0]] - b[w[0]], a[w[1]] - b[w[1]]);
+        if d0 == 0. {
+            Some(temperatures[w[0]])
+        }
+        else if d0*d1 < 0. {
+            let (t0, t1) = (temperatures[w[0]], temperatures[w[1]]);
+            Some(t0 + (t1-t0) * d0 / (d0-d1))
+        }
+        else {
+            None
+        }
+    })
+}
+
+/// Locate the maximum of values over temperatures with errors, returns (value, error) of its temperature and value.
+/**
+ * See peak, the errors of values are propagated linearly, see fits::propagate.
+ */
+pub fn peak_with_errors(temperatures: &[f64], values: &[(f64, f64)]) -> ((f64, f64), (f64, f64)) {
+    let (y, sigma): (Vec<f64>, Vec<f64>) = values.iter().cloned().unzip();
+    let result = fits::propagate(&y, &sigma, |y| {
+        let (t, max) = peak(temperatures, y);
+        vec![t, max]
+    });
+    (result[0], result[1])
+}
+
+/// Return sigma or unit errors if any of sigma is not positive, which would give infinite weights in fits.
+fn fit_errors(sigma: &[f64]) -> Vec<f64> {
+    if sigma.iter().all(|&s| s > 0.) { sigma.to_vec() } else { vec![1.; sigma.len()] }
+}
+
+/// Write the combined finite-size scaling summary of temperature scans on lattices of the given sizes.
+/**
+ * summaries[i] holds the summaries of all temperatures on size sizes[i]. Writes `fss.dat` with lines
+ * `<i>: <L> <T(χ_max)> <error> <χ_max> <error> <T(C_max)> <error> <C_max> <error>`, see peak_with_errors,
+ * and `binder_crossings.dat` with lines `<i>: <L_1> <L_2> <T_cross>` for consecutive sizes, NaN if their
+ * Binder cumulants do not cross.
+ * With at least two sizes, also writes `fss_fits.dat` with lines `<quantity>: <value> <error>` for
+ * γ/ν from χ_max ~ L^(γ/ν) and, with at least three sizes, Tc and 1/ν from T(χ_max) = Tc + a L^(-1/ν),
+ * see fits::power_law and fits::shift. Fit errors are propagated from the errors of the peaks.
+ */
+pub fn write_summary(datadir: &Path, sizes: &[usize], temperatures: &[f64],
+                     summaries: &[Vec<Summary>]) -> Result<(), Error> {
+    let mut fssfile = OutputFile::create(&datadir.join("fss.dat"))?;
+    let mut peaks = Vec::with_capacity(sizes.len());
+    for (i, (size, summary)) in sizes.iter().zip(summaries).enumerate() {
+        let chi: Vec<(f64, f64)> = summary.iter().map(|s| s.susceptibility).collect();
+        let heat: Vec<(f64, f64)> = summary.iter().map(|s| s.specific_heat).collect();
+        let (t_chi, chi_max) = peak_with_errors(temperatures, &chi);
+        let (t_heat, heat_max) = peak_with_errors(temperatures, &heat);
+        writeln!(fssfile, "{}: {} {} {} {} {} {} {} {} {}", i, size, t_chi.0, t_chi.1, chi_max.0, chi_max.1,
+                 t_heat.0, t_heat.1, heat_max.0, heat_max.1)?;
+        peaks.push((t_chi, chi_max));
+    }
+
+    let mut crossingfile = OutputFile::create(&datadir.join("binder_crossings.dat"))?;
+    for (i, (pair, summary)) in sizes.windows(2).zip(summaries.windows(2)).enumerate() {
+        let binder = |summary: &[Summary]| summary.iter().map(|s| s.binder.0).collect::<Vec<f64>>();
+        let cross = crossing(temperatures, &binder(&summary[0]), &binder(&summary[1]));
+        info!("Binder cumulants of L = {} and {} cross at T = {}", pair[0], pair[1], cross.unwrap_or(f64::NAN));
+        writeln!(crossingfile, "{}: {} {} {}", i, pair[0], pair[1], cross.unwrap_or(f64::NAN))?;
+    }
+
+    if sizes.len() < 2 {
+        return Ok(());
+    }
+    let lengths: Vec<f64> = sizes.iter().map(|&l| l as f64).collect();
+    let (chi_max, chi_sigma): (Vec<f64>, Vec<f64>) = peaks.iter().map(|p| p.1).unzip();
+    let chi_sigma = fit_errors(&chi_sigma);
+    let gamma_over_nu = fits::propagate(&chi_max, &chi_sigma, |y| {
+        vec![fits::power_law(&lengths, y, &chi_sigma).exponent]
+    })[0];
+    let mut results = vec![("gamma_over_nu", gamma_over_nu)];
+    if sizes.len() >= 3 {
+        let (t_max, t_sigma): (Vec<f64>, Vec<f64>) = peaks.iter().map(|p| p.0).unzip();
+        let t_sigma = fit_errors(&t_sigma);
+        let shift = fits::propagate(&t_max, &t_sigma, |y| {
+            let fit = fits::shift(&lengths, y, &t_sigma);
+            vec![fit.limit, fit.exponent]
+        });
+        results.extend([("tc", shift[0]), ("inverse_nu", shift[1])]);
+    }
+    let mut fitfile = OutputFile::create(&datadir.join("fss_fits.dat"))?;
+    for (name, (value, error)) in results {
+        info!("{}: {} +- {}", name, value, error);
+        writeln!(fitfile, "{}: {} {}", name, value, error)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,400 @@
+/*!
+ * Classical Heisenberg (O(3)) model H = -J Σ_⟨ij⟩ S_i·S_j - h Σ_i S_i^z with unit vector spins.
+ *
+ * Uses random-rotation Metropolis proposals through model::Model, heat bath updates, or overrelaxation,
+ * and mixtures of them in an UpdateSchedule.
+ * Observables are the lengths of the (staggered) magnetisation vectors per site,
+ * and the structure factor sums over all three components.
+ */
+
+use std::f64::consts::PI;
+use std::path::Path;
+
+use crate::error::{Error, OutputFile};
+use crate::{Configuration, Format, Observables, Rng, info, write_observables};
+use crate::analysis::{self, Autocorrelation, Summary};
+use crate::model::{self, Model};
+
+/// A classical spin, a unit vector (x, y, z).
+pub type Spin = [f64; 3];
+
+/// Compute the scalar product of two vectors.
+pub fn dot(a: &Spin, b: &Spin) -> f64 {
+    a[0]*b[0] + a[1]*b[1] + a[2]*b[2]
+}
+
+/// Compute the cross product of two vectors.
+fn cross(a: &Spin, b: &Spin) -> Spin {
+    [a[1]*b[2] - a[2]*b[1], a[2]*b[0] - a[0]*b[2], a[0]*b[1] - a[1]*b[0]]
+}
+
+/// Return the vector scaled to unit length.
+fn normalise(a: Spin) -> Spin {
+    let norm = dot(&a, &a).sqrt();
+    a.map(|x| x / norm)
+}
+
+/// Draw a unit vector from the uniform distribution on the sphere.
+pub fn random_unit(rng: &mut Rng) -> Spin {
+    let z = 2.*rng.gen_real() - 1.;
+    let (s, c) = (2.*PI*rng.gen_real()).sin_cos();
+    let r = (1. - z*z).sqrt();
+    [r*c, r*s, z]
+}
+
+/// Rotate spin by angle around a unit axis.
+pub fn rotate(spin: &Spin, axis: &Spin, angle: f64) -> Spin {
+    let (s, c) = angle.sin_cos();
+    let k_cross_v = cross(axis, spin);
+    let k_dot_v = dot(axis, spin);
+    // Rodrigues' rotation formula
+    [0, 1, 2].map(|a| spin[a]*c + k_cross_v[a]*s + axis[a]*k_dot_v*(1. - c))
+}
+
+/// Parameters of the Heisenberg model.
+#[derive(Clone, Debug)]
+pub struct Heisenberg {
+    /// Coupling constant, negative for an antiferromagnet.
+    pub j: f64,
+    /// External field along z.
+    pub h: f64,
+    /// Maximum rotation angle of Metropolis proposals, π allows any orientation in one step.
+    pub max_angle: f64,
+}
+
+impl Model for Heisenberg {
+    type Spin = Spin;
+
+    fn random_spin(&self, rng: &mut Rng) -> Spin {
+        random_unit(rng)
+    }
+
+    /// Rotate spin around a random axis by an angle uniform in [-max_angle, max_angle].
+    fn propose(&self, spin: &Spin, rng: &mut Rng) -> Spin {
+        let axis = random_unit(rng);
+        let angle = self.max_angle * (2.*rng.gen_real() - 1.);
+        // normalise to keep rounding errors from accumulating
+        normalise(rotate(spin, &axis, angle))
+    }
+
+    fn pair_energy(&self, a: &Spin, b: &Spin) -> f64 {
+        -self.j*dot(a, b)
+    }
+
+    fn site_energy(&self, _cfg: &Configuration<Spin>, _idx: usize, spin: &Spin) -> f64 {
+        -self.h*spin[2]
+    }
+
+    fn measure(&self, obs: &mut Observables, cfg: &Configuration<Spin>, energy: f64) {
+        obs.energy.push(energy);
+        obs.magnetisation.push(magnetisation(cfg));
+        obs.staggered.push(staggered_magnetisation(cfg));
+        obs.structure.push(smallest_structure_factor(cfg));
+    }
+}
+
+/// Compute the length of the magnetisation per site |Σ_i S_i| / N.
+pub fn magnetisation(cfg: &Configuration<Spin>) -> f64 {
+    let total = cfg.cfg.iter().fold([0.; 3], |acc, s| [acc[0] + s[0], acc[1] + s[1], acc[2] + s[2]]);
+    dot(&total, &total).sqrt() / cfg.size() as f64
+}
+
+/// Compute the length of the staggered magnetisation per site |Σ_i (-1)^(x_i+y_i) S_i| / N.
+pub fn staggered_magnetisation(cfg: &Configuration<Spin>) -> f64 {
+    let mut total = [0.; 3];
+    for (idx, s) in cfg.cfg.iter().enumerate() {
+        let sign = if (idx % cfg.nx + idx / cfg.nx).is_multiple_of(2) { 1. } else { -1. };
+        for a in 0..3 {
+            total[a] += sign*s[a];
+        }
+    }
+    dot(&total, &total).sqrt() / cfg.size() as f64
+}
+
+/// Compute the structure factor Σ_a |S_a(k)|² / N at k = (2π/nx, 0).
+pub fn smallest_structure_factor(cfg: &Configuration<Spin>) -> f64 {
+    let k = 2.*PI / cfg.nx as f64;
+    let mut re = [0.; 3];
+    let mut im = [0.; 3];
+    for (idx, s) in cfg.cfg.iter().enumerate() {
+        let (sin, cos) = (k*(idx % cfg.nx) as f64).sin_cos();
+        for a in 0..3 {
+            re[a] += s[a]*cos;
+            im[a] -= s[a]*sin;
+        }
+    }
+    (dot(&re, &re) + dot(&im, &im)) / cfg.size() as f64
+}
+
+/// Set the spin at a random active site by heat bath.
+/**
+ * Draws the new spin from exp(β S·F) with the local field F = J Σ_neighbours S_j + h ẑ,
+ * the update is always accepted.
+ */
+pub fn heat_bath_step(model: &Heisenberg, cfg: &mut Configuration<Spin>, energy: &mut f64, beta: f64,
+                      rng: &mut Rng) {
+    let active = cfg.active_sites();
+    let idx = active[rng.gen_index(active.len())];
+    let field = local_field(model, cfg, idx);
+    let strength = dot(&field, &field).sqrt();
+
+    let spin = if strength*beta < 1e-12 {
+        random_unit(rng)
+    } else {
+        // invert the cumulative distribution of cos θ ~ exp(β |F| cos θ), 1-u is in (0, 1]
+        let u = 1. - rng.gen_real();
+        let bf = beta*strength;
+        let cos_theta = (1. + (u + (1.-u)*(-2.*bf).exp()).ln() / bf).clamp(-1., 1.);
+        let sin_theta = (1. - cos_theta*cos_theta).sqrt();
+        let (sin_phi, cos_phi) = (2.*PI*rng.gen_real()).sin_cos();
+
+        // orthonormal frame (e1, e2, n) with n along the field
+        let n = field.map(|f| f / strength);
+        let helper = if n[0].abs() < 0.9 { [1., 0., 0.] } else { [0., 1., 0.] };
+        let e1 = normalise(cross(&n, &helper));
+        let e2 = cross(&n, &e1);
+        [0, 1, 2].map(|a| cos_theta*n[a] + sin_theta*(cos_phi*e1[a] + sin_phi*e2[a]))
+    };
+
+    *energy += model.delta_e(cfg, idx, &spin);
+    cfg[idx] = spin;
+}
+
+/// Compute the local field F = J Σ_neighbours S_j + h ẑ at site idx, the energy of the site is -S·F.
+fn local_field(model: &Heisenberg, cfg: &Configuration<Spin>, idx: usize) -> Spin {
+    let mut field = [0., 0., model.h];
+    for &neigh in cfg.neighbours_of(idx) {
+        for (f, s) in field.iter_mut().zip(cfg[neigh].iter()) {
+            *f += model.j*s;
+        }
+    }
+    field
+}
+
+/// Reflect the spin at site idx about its local field, S -> 2 (S·F) F / |F|² - S.
+/**
+ * This keeps S·F and thus the energy (microcanonical update) but moves the spin as far as possible,
+ * so it decorrelates configurations quickly without any random numbers. Spins in a vanishing field
+ * are left alone. The energy is updated anyway to keep track of rounding errors.
+ */
+pub fn overrelaxation_step(model: &Heisenberg, cfg: &mut Configuration<Spin>, energy: &mut f64, idx: usize) {
+    let field = local_field(model, cfg, idx);
+    let strength2 = dot(&field, &field);
+    if strength2 < 1e-24 {
+        return;
+    }
+    let spin = &cfg[idx];
+    let projection = 2.*dot(spin, &field) / strength2;
+    let spin = normalise([0, 1, 2].map(|a| projection*field[a] - spin[a]));
+    *energy += model.delta_e(cfg, idx, &spin);
+    cfg[idx] = spin;
+}
+
+/// Evolve a configuration by overrelaxation sweeps over all active sites in order.
+/**
+ * Measures observables once per sweep if obs is given like model::evolve.
+ * Overrelaxation alone keeps the energy and is not ergodic, combine it with Metropolis or
+ * heat bath updates in an UpdateSchedule.
+ */
+pub fn evolve_overrelaxation(model: &Heisenberg, cfg: &mut Configuration<Spin>, energy: &mut f64,
+                             nsweep: usize, mut obs: Option<&mut Observables>) {
+    for _sweep in 0..nsweep {
+        for i in 0..cfg.active_sites().len() {
+            let idx = cfg.active_sites()[i];
+            overrelaxation_step(model, cfg, energy, idx);
+        }
+        if let Some(o) = &mut obs {
+            model.measure(o, cfg, *energy);
+        }
+    }
+}
+
+/// Evolve a configuration in Monte-Carlo time with heat bath updates.
+/**
+ * Measures observables once per sweep if obs is given like model::evolve.
+ */
+pub fn evolve_heat_bath(model: &Heisenberg, cfg: &mut Configuration<Spin>, energy: &mut f64, beta: f64,
+                        rng: &mut Rng, nsweep: usize, mut obs: Option<&mut Observables>) {
+    for _sweep in 0..nsweep {
+        for _step in 0..cfg.active_sites().len() {
+            heat_bath_step(model, cfg, energy, beta, rng);
+        }
+        if let Some(o) = &mut obs {
+            model.measure(o, cfg, *energy);
+        }
+    }
+}
+
+/// Update algorithm of the Heisenberg model.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Update {
+    /// Metropolis with random rotations, see Heisenberg::propose.
+    Metropolis,
+    /// Heat bath, see heat_bath_step.
+    HeatBath,
+    /// Overrelaxation, see overrelaxation_step, only in an UpdateSchedule with an ergodic update.
+    Overrelaxation,
+}
+
+impl Update {
+    /// Parse an update from its name, `metropolis`, `heat-bath`, or `overrelaxation`.
+    pub fn parse(name: &str) -> Result<Update, String> {
+        match name {
+            "metropolis" => Ok(Update::Metropolis),
+            "heat-bath" => Ok(Update::HeatBath),
+            "overrelaxation" => Ok(Update::Overrelaxation),
+            _ => Err(format!("Unknown Heisenberg update '{}'", name)),
+        }
+    }
+
+    /// Evolve cfg by nsweep sweeps, returns the number of accepted updates.
+    #[allow(clippy::too_many_arguments)]
+    pub fn evolve(&self, model: &Heisenberg, cfg: &mut Configuration<Spin>, energy: &mut f64, beta: f64,
+                  rng: &mut Rng, nsweep: usize, obs: Option<&mut Observables>) -> usize {
+        match self {
+            Update::Metropolis => model::evolve(model, cfg, energy, beta, rng, nsweep, obs),
+            Update::HeatBath => {
+                evolve_heat_bath(model, cfg, energy, beta, rng, nsweep, obs);
+                nsweep*cfg.active_sites().len()
+            }
+            Update::Overrelaxation => {
+                evolve_overrelaxation(model, cfg, energy, nsweep, obs);
+                nsweep*cfg.active_sites().len()
+            }
+        }
+    }
+}
+
+/// Sweeps of several updates that make up one sweep of a run, e.g. 1 heat bath and 5 overrelaxation sweeps.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UpdateSchedule {
+    /// Number of sweeps and update, run in this order.
+    pub steps: Vec<(usize, Update)>,
+}
+
+impl UpdateSchedule {
+    /// Create a schedule of a single sweep of update.
+    pub fn single(update: Update) -> UpdateSchedule {
+        UpdateSchedule{steps: vec![(1, update)]}
+    }
+
+    /// Parse a schedule `<n> <update> + <n> <update> + ...`, e.g. `1 heat-bath + 5 overrelaxation`.
+    /**
+     * Updates are named as in Update::parse, see model::parse_steps for the format. The schedule needs
+     * Metropolis or heat bath sweeps since overrelaxation alone keeps the energy.
+     */
+    pub fn parse(spec: &str) -> Result<UpdateSchedule, String> {
+        let steps = model::parse_steps(spec, Update::parse)?;
+        if steps.iter().all(|(_, update)| *update == Update::Overrelaxation) {
+            return Err(format!("Update schedule '{}' needs metropolis or heat-bath sweeps", spec));
+        }
+        Ok(UpdateSchedule{steps})
+    }
+
+    /// Return the number of single update sweeps in one sweep of the schedule.
+    pub fn nsweep(&self) -> usize {
+        self.steps.iter().map(|(n, _)| n).sum()
+    }
+
+    /// Evolve cfg by nsweep sweeps of the schedule, measure after every one of them if obs is given.
+    /**
+     * Returns the number of accepted updates of all steps.
+     */
+    #[allow(clippy::too_many_arguments)]
+    pub fn evolve(&self, model: &Heisenberg, cfg: &mut Configuration<Spin>, energy: &mut f64, beta: f64,
+                  rng: &mut Rng, nsweep: usize, mut obs: Option<&mut Observables>) -> usize {
+        let mut naccept = 0;
+        for _sweep in 0..nsweep {
+            for &(n, update) in &self.steps {
+                naccept += update.evolve(model, cfg, energy, beta, rng, n, None);
+            }
+            if let Some(o) = &mut obs {
+                model.measure(o, cfg, *energy);
+            }
+        }
+        naccept
+    }
+}
+
+/// Number of sweeps between two adjustments of the proposal width in thermalise.
+const NSWEEP_TUNE: usize = 10;
+
+/// Evolve cfg by nsweep sweeps of updates while tuning model.max_angle to the target acceptance rate.
+/**
+ * After every NSWEEP_TUNE sweeps, max_angle is scaled by the ratio of the Metropolis acceptance rate
+ * in those sweeps to target, by at most a factor of 2 and up to π. Only the Metropolis steps of updates
+ * count, schedules without them keep max_angle. Returns the acceptance rate of the last adjustment
+ * or None if there was none.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn thermalise(model: &mut Heisenberg, updates: &UpdateSchedule, cfg: &mut Configuration<Spin>, energy: &mut f64,
+                  beta: f64, target: f64, rng: &mut Rng, nsweep: usize) -> Option<f64> {
+    if !updates.steps.iter().any(|(_, update)| *update == Update::Metropolis) {
+        updates.evolve(model, cfg, energy, beta, rng, nsweep, None);
+        return None;
+    }
+
+    let mut acceptance = None;
+    let mut remaining = nsweep;
+    while remaining > 0 {
+        let nchunk = remaining.min(NSWEEP_TUNE);
+        let (mut naccept, mut nproposal) = (0, 0);
+        for _sweep in 0..nchunk {
+            for &(n, update) in &updates.steps {
+                let accepted = update.evolve(model, cfg, energy, beta, rng, n, None);
+                if update == Update::Metropolis {
+                    naccept += accepted;
+                    nproposal += n*cfg.active_sites().len();
+                }
+            }
+        }
+        let rate = naccept as f64 / nproposal as f64;
+        model.max_angle = (model.max_angle * (rate / target).clamp(0.5, 2.)).min(PI);
+        acceptance = Some(rate);
+        remaining -= nchunk;
+    }
+    acceptance
+}
+
+/// Simulate the Heisenberg model at all temperatures and write results to datadir.
+/**
+ * Every sweep runs all steps of updates. Thermalisation tunes the Metropolis proposal width, starting
+ * from model.max_angle, to the target acceptance rate, see thermalise. The width is frozen for production
+ * and recorded in `proposal.dat` as `<idx>: <temperature> <max_angle>` lines.
+ * Writes the observables to `<idx>.<ext>` in the given format and `summary.dat` in the format
+ * of a temperature scan, with errors from analysis_rng and nresample bootstrap samples.
+ * The Binder cumulant in the summary uses the normalisation of the Ising model.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn run(datadir: &Path, nx: usize, ny: usize, temperatures: &[f64], model: &Heisenberg, updates: &UpdateSchedule,
+           target: f64, ntherm_init: usize, ntherm: usize, nprod: usize, format: Format, nresample: usize,
+           rng: &mut Rng, analysis_rng: &mut Rng) -> Result<(), Error> {
+    let mut model = model.clone();
+    let mut cfg = model.random(nx, ny, rng);
+    let mut energy = model.hamiltonian(&cfg);
+    thermalise(&mut model, updates, &mut cfg, &mut energy, 1./temperatures[0], target, rng, ntherm_init);
+
+    let mut summary = OutputFile::create(&datadir.join("summary.dat"))?;
+    let mut proposal = OutputFile::create(&datadir.join("proposal.dat"))?;
+    for (i, temp) in temperatures.iter().enumerate() {
+        info!("Running Heisenberg model for temperature {}", temp);
+        let beta = 1./temp;
+        thermalise(&mut model, updates, &mut cfg, &mut energy, beta, target, rng, ntherm);
+        info!("  Proposal width: {}", model.max_angle);
+        writeln!(proposal, "{}: {} {}", i, temp, model.max_angle)?;
+
+        let mut obs = Observables::new();
+        let naccept = updates.evolve(&model, &mut cfg, &mut energy, beta, rng, nprod, Some(&mut obs));
+        info!("  Production acceptance rate: {}", naccept as f64 / (nprod*updates.nsweep()*cfg.size()) as f64);
+        write_observables(&datadir.join(format!("{}.{}", i, format.extension())), &obs, format)?;
+
+        let tau = Autocorrelation::new(&obs.energy).tau.max(Autocorrelation::new(&obs.magnetisation).tau);
+        let binsize = analysis::binsize(tau, obs.energy.len());
+        let result = Summary::new(&obs, beta, nx, cfg.nsite(), binsize, nresample, analysis_rng);
+        let values = [result.energy, result.abs_magnetisation, result.susceptibility, result.specific_heat,
+                      result.binder, result.correlation_length]
+            .map(|(value, error)| format!("{} {}", value, error));
+        writeln!(summary, "{}: {} {}", i, temp, values.join(" "))?;
+    }
+    Ok(())
+}
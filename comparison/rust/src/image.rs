@@ -0,0 +1,331 @@
+/*!
+ * Greyscale images of spin configurations written as PNG files and movies of them.
+ *
+ * See https://www.w3.org/TR/png/
+ * Like .npz files, images are stored without compression, the zlib stream consists of stored
+ * deflate blocks so no compression library is needed.
+ *
+ * Movies are animated GIFs, see https://www.w3.org/Graphics/GIF/spec-gif89a.txt, or streams of binary
+ * PPM frames that ffmpeg reads with `-f image2pipe -c:v ppm -i <file>`, also from a named pipe.
+ */
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::Configuration;
+use crate::error::{Error, write_file};
+use crate::npy::crc32;
+
+/// Smallest width and height of a snapshot in pixels, small lattices are scaled up to at least this size.
+pub const MIN_SIZE: usize = 256;
+
+/// Largest block of a stored deflate stream.
+const MAX_STORED_BLOCK: usize = 65535;
+
+/// Largest number of codes of the LZW compression in GIF files.
+const MAX_LZW_CODES: u16 = 4096;
+
+/// Number of bits of the palette indices of GIF frames, the palette is black, white, grey, and an unused entry.
+const GIF_INDEX_BITS: u8 = 2;
+
+/// Greyscale image with 8 bits per pixel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    /// Width in pixels.
+    pub width: usize,
+    /// Height in pixels.
+    pub height: usize,
+    /// Grey values in row major order, 0 is black and 255 white.
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Return an image of cfg with up spins white, down spins black, and vacancies grey.
+    /**
+     * Every site is a square of scale*scale pixels, row y of the lattice is the y-th row of squares from the top.
+     */
+    pub fn of_configuration(cfg: &Configuration, scale: usize) -> Image {
+        let (width, height) = (cfg.nx*scale, cfg.ny*scale);
+        let mut pixels = Vec::with_capacity(width*height);
+        for y in 0..height {
+            pixels.extend((0..width).map(|x| match cfg[(y/scale)*cfg.nx + x/scale] {
+                1 => 255,
+                -1 => 0,
+                _ => 128,
+            }));
+        }
+        Image{width, height, pixels}
+    }
+
+    /// Return the encoded PNG file of the image.
+    pub fn png_bytes(&self) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // bit depth 8, greyscale, deflate, adaptive filtering, no interlacing
+        header.extend_from_slice(&[8, 0, 0, 0, 0]);
+        push_chunk(&mut png, b"IHDR", &header);
+
+        // every scanline starts with filter type 0 (none)
+        let mut raw = Vec::with_capacity((self.width+1)*self.height);
+        for row in self.pixels.chunks(self.width) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+        push_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+        push_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    /// Write the image to a PNG file.
+    pub fn write_png(&self, fname: &Path) -> Result<(), Error> {
+        write_file(fname, self.png_bytes())
+    }
+}
+
+/// Return the scale of snapshots of an nx*ny lattice such that both sides have at least MIN_SIZE pixels.
+pub fn snapshot_scale(nx: usize, ny: usize) -> usize {
+    MIN_SIZE.div_ceil(nx.min(ny)).max(1)
+}
+
+/// Return the file name of the snapshot of temperature temp after sweep out of nsweep in dir.
+/**
+ * The sweep is padded with zeros to the width of nsweep so the files sort in Monte-Carlo time.
+ */
+pub fn snapshot_name(dir: &Path, temp: f64, sweep: usize, nsweep: usize) -> PathBuf {
+    let width = nsweep.to_string().len();
+    dir.join(format!("T{}_sweep{:0width$}.png", temp, sweep, width = width))
+}
+
+/// Append a PNG chunk with the given type and data to png.
+fn push_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    // the checksum covers type and data
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Return a zlib stream of data made of stored (uncompressed) deflate blocks, data must not be empty.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // deflate with a 32K window, no preset dictionary, header is a multiple of 31
+    let mut stream = vec![0x78, 0x01];
+    let nblock = data.len().div_ceil(MAX_STORED_BLOCK);
+    for (i, block) in data.chunks(MAX_STORED_BLOCK).enumerate() {
+        stream.push((i+1 == nblock) as u8);  // BFINAL, BTYPE 00
+        stream.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        stream.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+/// Compute the Adler-32 checksum used by zlib.
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/// File format of a movie.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MovieFormat {
+    /// Animated GIF that loops forever.
+    Gif,
+    /// Concatenated binary PPM (P6) frames.
+    Ppm,
+}
+
+impl MovieFormat {
+    /// Return the format of a movie file from its extension, .gif or .ppm.
+    pub fn from_path(fname: &Path) -> Result<MovieFormat, String> {
+        match fname.extension().and_then(|ext| ext.to_str()) {
+            Some("gif") => Ok(MovieFormat::Gif),
+            Some("ppm") => Ok(MovieFormat::Ppm),
+            _ => Err(format!("Movie '{}' must be a .gif or .ppm file", fname.display())),
+        }
+    }
+}
+
+/// Writer of a movie of images of equal size, the frames are written as they are added.
+pub struct Movie {
+    file: BufWriter<fs::File>,
+    path: PathBuf,
+    format: MovieFormat,
+    width: usize,
+    height: usize,
+    /// Time between GIF frames in 1/100 s.
+    delay: u16,
+}
+
+impl Movie {
+    /// Create a movie of width*height frames in the format given by the extension of fname, see MovieFormat.
+    /**
+     * delay is the time between GIF frames in 1/100 s, PPM frames carry no timing.
+     */
+    pub fn create(fname: &Path, width: usize, height: usize, delay: u16) -> Result<Movie, Error> {
+        let format = MovieFormat::from_path(fname).map_err(Error::Message)?;
+        if format == MovieFormat::Gif && (width > u16::MAX as usize || height > u16::MAX as usize) {
+            return Err(Error::Message(format!("Movie '{}' is too large for a GIF", fname.display())));
+        }
+        let file = BufWriter::new(fs::File::create(fname).map_err(Error::io(fname))?);
+        let mut movie = Movie{file, path: fname.to_path_buf(), format, width, height, delay};
+        if format == MovieFormat::Gif {
+            let header = movie.gif_header();
+            movie.write(&header)?;
+        }
+        Ok(movie)
+    }
+
+    /// Append a frame, img must have the size of the movie.
+    pub fn add_frame(&mut self, img: &Image) -> Result<(), Error> {
+        assert_eq!((img.width, img.height), (self.width, self.height), "Frames of a movie must have the same size");
+        let frame = match self.format {
+            MovieFormat::Gif => self.gif_frame(img),
+            MovieFormat::Ppm => {
+                let mut frame = format!("P6\n{} {}\n255\n", img.width, img.height).into_bytes();
+                frame.extend(img.pixels.iter().flat_map(|&grey| [grey; 3]));
+                frame
+            }
+        };
+        self.write(&frame)
+    }
+
+    /// Finish the file and flush it.
+    pub fn finish(mut self) -> Result<(), Error> {
+        if self.format == MovieFormat::Gif {
+            self.write(&[0x3b])?;  // trailer
+        }
+        self.file.flush().map_err(Error::io(&self.path))
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.file.write_all(data).map_err(Error::io(&self.path))
+    }
+
+    /// Return the GIF header, logical screen, palette, and the extension to loop the animation.
+    fn gif_header(&self) -> Vec<u8> {
+        let mut header = b"GIF89a".to_vec();
+        header.extend_from_slice(&(self.width as u16).to_le_bytes());
+        header.extend_from_slice(&(self.height as u16).to_le_bytes());
+        // global palette with 2^GIF_INDEX_BITS entries of GIF_INDEX_BITS bits of colour resolution
+        header.extend_from_slice(&[0x80 | (GIF_INDEX_BITS-1) << 4 | (GIF_INDEX_BITS-1), 0, 0]);
+        header.extend_from_slice(&[0, 0, 0, 255, 255, 255, 128, 128, 128, 0, 0, 0]);
+        // loop forever
+        header.extend_from_slice(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00");
+        header
+    }
+
+    /// Return a GIF frame with its delay, descriptor, and LZW compressed palette indices.
+    fn gif_frame(&self, img: &Image) -> Vec<u8> {
+        let mut frame = vec![0x21, 0xf9, 4, 0];
+        frame.extend_from_slice(&self.delay.to_le_bytes());
+        frame.extend_from_slice(&[0, 0]);  // no transparency, end of extension
+        frame.push(0x2c);
+        frame.extend_from_slice(&[0; 4]);  // position
+        frame.extend_from_slice(&(self.width as u16).to_le_bytes());
+        frame.extend_from_slice(&(self.height as u16).to_le_bytes());
+        frame.push(0);  // no local palette, no interlacing
+
+        let indices: Vec<u8> = img.pixels.iter().map(|&grey| match grey {
+            0 => 0,
+            255 => 1,
+            _ => 2,
+        }).collect();
+        frame.push(GIF_INDEX_BITS);
+        for block in lzw(&indices, GIF_INDEX_BITS).chunks(255) {
+            frame.push(block.len() as u8);
+            frame.extend_from_slice(block);
+        }
+        frame.push(0);
+        frame
+    }
+}
+
+/// Bits packed into bytes starting at the least significant bit as in GIF files.
+struct BitWriter {
+    bytes: Vec<u8>,
+    /// Bits that do not fill a byte yet.
+    buffer: u32,
+    nbits: u8,
+}
+
+impl BitWriter {
+    fn push(&mut self, code: u16, width: u8) {
+        self.buffer |= (code as u32) << self.nbits;
+        self.nbits += width;
+        while self.nbits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.nbits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.nbits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Compress indices with the variable width LZW code of GIF files.
+/**
+ * Codes start with min_code_size+1 bits and grow to 12 bits, the table is cleared when it is full.
+ * Returns the packed codes without the sub-block structure of GIF files.
+ */
+pub fn lzw(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear: u16 = 1 << min_code_size;
+    let end = clear + 1;
+    let mut bits = BitWriter{bytes: Vec::new(), buffer: 0, nbits: 0};
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut width = min_code_size + 1;
+    let mut next = end + 1;
+    bits.push(clear, width);
+
+    let mut prefix: Option<u16> = None;
+    for &index in indices {
+        prefix = Some(match prefix {
+            None => index as u16,
+            Some(code) => match table.get(&(code, index)) {
+                Some(&extended) => extended,
+                None => {
+                    bits.push(code, width);
+                    if next == MAX_LZW_CODES {
+                        bits.push(clear, width);
+                        table.clear();
+                        width = min_code_size + 1;
+                        next = end + 1;
+                    } else {
+                        table.insert((code, index), next);
+                        next += 1;
+                        // the decoder adds its entries one code later
+                        if next > 1 << width && width < 12 {
+                            width += 1;
+                        }
+                    }
+                    index as u16
+                }
+            }
+        });
+    }
+    if let Some(code) = prefix {
+        bits.push(code, width);
+        // the decoder adds one more entry after reading the last code
+        if next < MAX_LZW_CODES && next + 1 > 1 << width && width < 12 {
+            width += 1;
+        }
+    }
+    bits.push(end, width);
+    bits.finish()
+}
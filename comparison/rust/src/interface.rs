@@ -0,0 +1,71 @@
+/*!
+ * Interface roughness between pinned antiparallel boundary rows.
+ */
+
+use std::path::Path;
+
+use crate::error::{Error, OutputFile};
+use crate::{Configuration, Format, Observables, Rng, evolve, hamiltonian, info, write_observables, write_row};
+
+/// Return the height of the interface in each column.
+/**
+ * The height of column x is the number of up spins in that column excluding the
+ * pinned bottom row. For a sharp interface without overhangs, this is the
+ * distance of the interface from the bottom boundary.
+ */
+pub fn column_heights(cfg: &Configuration) -> Vec<f64> {
+    (0..cfg.nx).map(|x| {
+        (1..cfg.ny-1).filter(|y| cfg[y*cfg.nx + x] == 1).count() as f64
+    }).collect()
+}
+
+/// Return the squared width W² = ⟨(h(x) - h̄)²⟩ₓ of an interface with given column heights.
+pub fn width_squared(heights: &[f64]) -> f64 {
+    let n = heights.len() as f64;
+    let mean = heights.iter().sum::<f64>() / n;
+    heights.iter().map(|h| (h-mean).powi(2)).sum::<f64>() / n
+}
+
+/// Measure the interface width for all temperatures and write results to datadir.
+/**
+ * Uses a lattice with pinned boundary rows, see Configuration::pin_boundary_rows,
+ * coupling j, and no external field.
+ * Writes the observables to `<idx>.<ext>` in the given format as in a normal run and `<idx>_interface.dat` with the mean
+ * height in the first row and W² in the second, one entry per sweep.
+ * `interface.dat` summarises lines `<idx>: <T> <L> <W>` with W = sqrt(⟨W²⟩) and L = nx.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn run(datadir: &Path, nx: usize, ny: usize, temperatures: &[f64], j: f64,
+           ntherm_init: usize, ntherm: usize, nprod: usize, format: Format, rng: &mut Rng) -> Result<(), Error> {
+    let mut cfg = Configuration::random(nx, ny, rng);
+    cfg.pin_boundary_rows();
+    let mut energy = hamiltonian(&cfg, j, 0.);
+    evolve(&mut cfg, &mut energy, 1./temperatures[0], j, 0., rng, ntherm_init, None);
+
+    let mut summary = OutputFile::create(&datadir.join("interface.dat"))?;
+    for (i, temp) in temperatures.iter().enumerate() {
+        info!("Running interface measurement for temperature {}", temp);
+        let beta = 1./temp;
+        evolve(&mut cfg, &mut energy, beta, j, 0., rng, ntherm, None);
+
+        let mut obs = Observables::new();
+        let mut mean_height = Vec::with_capacity(nprod);
+        let mut width2 = Vec::with_capacity(nprod);
+        for _sweep in 0..nprod {
+            evolve(&mut cfg, &mut energy, beta, j, 0., rng, 1, Some(&mut obs));
+            let heights = column_heights(&cfg);
+            mean_height.push(heights.iter().sum::<f64>() / nx as f64);
+            width2.push(width_squared(&heights));
+        }
+
+        let width = (width2.iter().sum::<f64>() / nprod as f64).sqrt();
+        info!("  Interface width: {}", width);
+        writeln!(summary, "{}: {} {} {}", i, temp, nx, width)?;
+
+        write_observables(&datadir.join(format!("{}.{}", i, format.extension())), &obs, format)?;
+        let mut interfacefile = OutputFile::create(&datadir.join(format!("{}_interface.dat", i)))?;
+        write_row(&mut interfacefile, &mean_height)?;
+        write_row(&mut interfacefile, &width2)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,345 @@
+/*!
+ * Lattice geometries, their nearest neighbours, and boundary conditions.
+ *
+ * All lattices number their sites y*nx + x on an nx*ny grid with periodic boundaries,
+ * so observables that refer to x and y work on any of them. Only the neighbours differ.
+ * Boundaries turn the links that wrap around the grid into open or anti-periodic ones,
+ * Anisotropic scales the couplings of the links along x and y, and NextNearest adds diagonal links.
+ */
+
+/// Link from a site to one of its neighbours.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Link {
+    /// Index of the neighbour.
+    pub site: usize,
+    /// Grid displacement from the site to the neighbour in x direction before wrapping around.
+    pub dx: isize,
+    /// Grid displacement from the site to the neighbour in y direction before wrapping around.
+    pub dy: isize,
+}
+
+/// Geometry of a lattice, defines which sites are nearest neighbours.
+pub trait Lattice {
+    /// Return the extents (nx, ny) of the grid the sites are numbered on.
+    fn extent(&self) -> (usize, usize);
+
+    /// Return the number of nearest neighbours of each site.
+    fn coordination(&self) -> usize;
+
+    /// Return the grid displacements (dx, dy) from site idx to all its nearest neighbours.
+    /**
+     * Lattices that store every link once at the even positions of the neighbours of
+     * its sites, like Square and Triangular, support Swendsen-Wang updates.
+     */
+    fn offsets(&self, idx: usize) -> Vec<(isize, isize)>;
+
+    /// Return the factor of the coupling constant of a link, 1 unless the couplings are anisotropic.
+    fn coupling(&self, _link: &Link) -> f64 {
+        1.
+    }
+
+    /// Return the total number of lattice sites.
+    fn size(&self) -> usize {
+        let (nx, ny) = self.extent();
+        nx*ny
+    }
+
+    /// Return the links from site idx to all its nearest neighbours with periodic boundaries.
+    fn links(&self, idx: usize) -> Vec<Link> {
+        let (nx, ny) = self.extent();
+        let (x, y) = ((idx % nx) as isize, (idx / nx) as isize);
+        self.offsets(idx).into_iter().map(|(dx, dy)| {
+            let xn = (x + dx).rem_euclid(nx as isize) as usize;
+            let yn = (y + dy).rem_euclid(ny as isize) as usize;
+            Link{site: yn*nx + xn, dx, dy}
+        }).collect()
+    }
+
+    /// Return the indices of all nearest neighbours of site idx with periodic boundaries.
+    fn neighbours(&self, idx: usize) -> Vec<usize> {
+        self.links(idx).iter().map(|link| link.site).collect()
+    }
+
+    /// Return the nearest neighbours of all sites, coordination entries per site.
+    fn neighbour_list(&self) -> Vec<usize> {
+        (0..self.size()).flat_map(|idx| self.neighbours(idx)).collect()
+    }
+}
+
+/// Square lattice, or a chain if ny == 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Square {
+    /// Number of sites in x direction.
+    pub nx: usize,
+    /// Number of sites in y direction, 1 for a chain.
+    pub ny: usize,
+}
+
+impl Lattice for Square {
+    fn extent(&self) -> (usize, usize) {
+        (self.nx, self.ny)
+    }
+
+    fn coordination(&self) -> usize {
+        if self.ny == 1 { 2 } else { 4 }
+    }
+
+    /// Neighbours are in the order x+1, x-1, y+1, y-1, or x+1, x-1 on a chain.
+    fn offsets(&self, _idx: usize) -> Vec<(isize, isize)> {
+        if self.ny == 1 {
+            vec![(1, 0), (-1, 0)]
+        } else {
+            vec![(1, 0), (-1, 0), (0, 1), (0, -1)]
+        }
+    }
+}
+
+/// Triangular lattice in the sheared representation with 6 nearest neighbours.
+/**
+ * Each row is shifted by half a lattice spacing relative to the previous one, so the
+ * neighbours in the grid are x±1, y±1, and the diagonal (x+1, y+1), (x-1, y-1).
+ * Needs nx, ny >= 3 so all neighbours are distinct, and multiples of 3 to fit the
+ * three-sublattice ground states of the antiferromagnet.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Triangular {
+    /// Number of sites in x direction.
+    pub nx: usize,
+    /// Number of rows.
+    pub ny: usize,
+}
+
+impl Lattice for Triangular {
+    fn extent(&self) -> (usize, usize) {
+        (self.nx, self.ny)
+    }
+
+    fn coordination(&self) -> usize {
+        6
+    }
+
+    /// Neighbours are in the order x+1, x-1, y+1, y-1, (x+1, y+1), (x-1, y-1).
+    fn offsets(&self, _idx: usize) -> Vec<(isize, isize)> {
+        assert!(self.nx >= 3 && self.ny >= 3, "The triangular lattice needs nx, ny >= 3");
+        vec![(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (-1, -1)]
+    }
+}
+
+/// Honeycomb lattice in the brick wall representation.
+/**
+ * Every site is linked to x+1 and x-1 and to y+1 if x+y is even or to y-1 if x+y is odd.
+ * Needs even nx and ny so these vertical links match across the periodic boundaries.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Honeycomb {
+    /// Number of sites in x direction.
+    pub nx: usize,
+    /// Number of rows.
+    pub ny: usize,
+}
+
+impl Lattice for Honeycomb {
+    fn extent(&self) -> (usize, usize) {
+        (self.nx, self.ny)
+    }
+
+    fn coordination(&self) -> usize {
+        3
+    }
+
+    /// Neighbours are in the order x+1, x-1, and y±1.
+    fn offsets(&self, idx: usize) -> Vec<(isize, isize)> {
+        let (nx, ny) = (self.nx, self.ny);
+        assert!(nx.is_multiple_of(2) && ny.is_multiple_of(2), "The honeycomb lattice needs even nx and ny");
+        let vertical = if (idx % nx + idx / nx).is_multiple_of(2) { 1 } else { -1 };
+        vec![(1, 0), (-1, 0), (0, vertical)]
+    }
+}
+
+/// Lattice with different couplings along the axes of the grid.
+/**
+ * Links in x direction (dy == 0) have the coupling factor x and links in y direction (dx == 0)
+ * the factor y, so a model with coupling J has J_x = J*x and J_y = J*y.
+ * Other links, like the diagonals of Triangular, keep the coupling J.
+ */
+pub struct Anisotropic {
+    /// Underlying lattice.
+    pub lattice: Box<dyn Lattice + Sync>,
+    /// Coupling factor of links in x direction.
+    pub x: f64,
+    /// Coupling factor of links in y direction.
+    pub y: f64,
+}
+
+impl Lattice for Anisotropic {
+    fn extent(&self) -> (usize, usize) {
+        self.lattice.extent()
+    }
+
+    fn coordination(&self) -> usize {
+        self.lattice.coordination()
+    }
+
+    fn offsets(&self, idx: usize) -> Vec<(isize, isize)> {
+        self.lattice.offsets(idx)
+    }
+
+    fn coupling(&self, link: &Link) -> f64 {
+        let factor = if link.dy == 0 {
+            self.x
+        } else if link.dx == 0 {
+            self.y
+        } else {
+            1.
+        };
+        factor * self.lattice.coupling(link)
+    }
+}
+
+/// Square lattice with diagonal next-nearest neighbours, the lattice of the J1-J2 model.
+/**
+ * Appends the diagonals (x+1, y+1), (x-1, y-1), (x+1, y-1), (x-1, y+1) to the neighbours
+ * of lattice, which must be a 2D square lattice, possibly Anisotropic, with nx, ny >= 3.
+ * The diagonal links have the coupling factor j2, so a model with coupling J has J_2 = J*j2.
+ * The striped phase for J_2 < -|J|/2 shows up in stripe_magnetisation.
+ */
+pub struct NextNearest {
+    /// Lattice of the nearest neighbours.
+    pub lattice: Box<dyn Lattice + Sync>,
+    /// Coupling factor of the diagonal links.
+    pub j2: f64,
+}
+
+impl Lattice for NextNearest {
+    fn extent(&self) -> (usize, usize) {
+        self.lattice.extent()
+    }
+
+    fn coordination(&self) -> usize {
+        self.lattice.coordination() + 4
+    }
+
+    fn offsets(&self, idx: usize) -> Vec<(isize, isize)> {
+        let (nx, ny) = self.extent();
+        assert!(nx >= 3 && ny >= 3, "Next-nearest neighbours need nx, ny >= 3");
+        let mut offsets = self.lattice.offsets(idx);
+        offsets.extend([(1, 1), (-1, -1), (1, -1), (-1, 1)]);
+        offsets
+    }
+
+    fn coupling(&self, link: &Link) -> f64 {
+        if link.dx != 0 && link.dy != 0 {
+            self.j2
+        } else {
+            self.lattice.coupling(link)
+        }
+    }
+}
+
+/// Lattice geometry selected at runtime.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Geometry {
+    /// See Square.
+    Square,
+    /// See Triangular.
+    Triangular,
+    /// See Honeycomb.
+    Honeycomb,
+}
+
+impl Geometry {
+    /// Parse a geometry from its name, `square`, `triangular`, or `honeycomb`.
+    pub fn parse(name: &str) -> Result<Geometry, String> {
+        match name {
+            "square" => Ok(Geometry::Square),
+            "triangular" => Ok(Geometry::Triangular),
+            "honeycomb" => Ok(Geometry::Honeycomb),
+            _ => Err(format!("Unknown lattice '{}'", name)),
+        }
+    }
+
+    /// Create the lattice with extents nx*ny, fails if the geometry does not support them.
+    pub fn create(&self, nx: usize, ny: usize) -> Result<Box<dyn Lattice + Sync>, String> {
+        match self {
+            Geometry::Square => Ok(Box::new(Square{nx, ny})),
+            Geometry::Triangular if nx >= 3 && ny >= 3 => Ok(Box::new(Triangular{nx, ny})),
+            Geometry::Triangular => Err("The triangular lattice needs nx, ny >= 3".to_string()),
+            Geometry::Honeycomb if nx.is_multiple_of(2) && ny.is_multiple_of(2) => Ok(Box::new(Honeycomb{nx, ny})),
+            Geometry::Honeycomb => Err("The honeycomb lattice needs even nx and ny".to_string()),
+        }
+    }
+}
+
+/// Boundary condition in one direction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Boundary {
+    /// Links wrap around.
+    Periodic,
+    /// No links across the boundary.
+    Open,
+    /// Links wrap around with the opposite sign of the coupling.
+    Antiperiodic,
+}
+
+impl Boundary {
+    /// Parse a boundary condition from its name, `periodic`, `open`, or `antiperiodic`.
+    pub fn parse(name: &str) -> Result<Boundary, String> {
+        match name {
+            "periodic" => Ok(Boundary::Periodic),
+            "open" => Ok(Boundary::Open),
+            "antiperiodic" => Ok(Boundary::Antiperiodic),
+            _ => Err(format!("Unknown boundary condition '{}'", name)),
+        }
+    }
+}
+
+/// Boundary conditions in x and y direction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Boundaries {
+    /// Boundary condition in x direction.
+    pub x: Boundary,
+    /// Boundary condition in y direction.
+    pub y: Boundary,
+}
+
+impl Boundaries {
+    /// Periodic boundaries in both directions.
+    pub const PERIODIC: Boundaries = Boundaries{x: Boundary::Periodic, y: Boundary::Periodic};
+
+    /// Parse boundaries from `<b>` for both directions or `<x>:<y>`, see Boundary::parse.
+    pub fn parse(spec: &str) -> Result<Boundaries, String> {
+        match spec.split_once(':') {
+            Some((x, y)) => Ok(Boundaries{x: Boundary::parse(x)?, y: Boundary::parse(y)?}),
+            None => {
+                let b = Boundary::parse(spec)?;
+                Ok(Boundaries{x: b, y: b})
+            }
+        }
+    }
+
+    /// Return true if both directions are periodic.
+    pub fn is_periodic(&self) -> bool {
+        *self == Boundaries::PERIODIC
+    }
+
+    /// Return the factor of the coupling of a link from site idx on an nx*ny grid.
+    /**
+     * The factor is 1 for links inside the grid, -1 for each anti-periodic boundary
+     * the link wraps around, and None if it wraps around an open boundary.
+     */
+    pub fn factor(&self, link: &Link, idx: usize, nx: usize, ny: usize) -> Option<f64> {
+        let (x, y) = ((idx % nx) as isize, (idx / nx) as isize);
+        let mut factor = 1.;
+        for (wraps, boundary) in [(!(0..nx as isize).contains(&(x + link.dx)), self.x),
+                                  (!(0..ny as isize).contains(&(y + link.dy)), self.y)] {
+            if wraps {
+                match boundary {
+                    Boundary::Periodic => {}
+                    Boundary::Open => return None,
+                    Boundary::Antiperiodic => factor = -factor,
+                }
+            }
+        }
+        Some(factor)
+    }
+}
@@ -0,0 +1,1399 @@
+/*!
+ * Rust implementation of the Ising Model simulation.
+ *
+ * The crate root provides the lattice (Configuration), the random number generator,
+ * the Hamiltonian, and Metropolis updates (evolve and friends).
+ * Cluster updates are in wolff and swendsen_wang, parallel Metropolis sweeps in checkerboard.
+ * The modules implement measurements and run modes on top of these; the `ising`
+ * binary drives them from the command line.
+ *
+ * A minimal simulation:
+ * ```
+ * use ising::{Configuration, Observables, Rng, evolve, hamiltonian};
+ *
+ * let mut rng = Rng::from_seed([1; 32]);
+ * let mut cfg = Configuration::random(8, 8, &mut rng);
+ * let mut energy = hamiltonian(&cfg, 1., 0.);
+ * evolve(&mut cfg, &mut energy, 1./2.5, 1., 0., &mut rng, 100, None);  // thermalise
+ *
+ * let mut obs = Observables::new();
+ * evolve(&mut cfg, &mut energy, 1./2.5, 1., 0., &mut rng, 100, Some(&mut obs));
+ * assert_eq!(obs.energy.len(), 100);
+ * ```
+ */
+
+use std::fs;
+use std::path::Path;
+use std::ops::{ControlFlow, Index, IndexMut};
+use std::sync::Arc;
+
+use rand::prelude::*;
+use rand::TryRng;
+use rand::rngs::ChaCha12Rng;
+
+pub mod aging;
+pub mod analysis;
+pub mod anneal;
+pub mod autocorr;
+pub mod benchmark;
+pub mod cftp;
+pub mod chains;
+pub mod checkerboard;
+pub mod checkpoint;
+pub mod classifier;
+pub mod clusters;
+pub mod config;
+pub mod correlation;
+pub mod covariance;
+pub mod disorder;
+pub mod distributed;
+pub mod error;
+pub mod exact;
+pub mod export;
+pub mod ffi;
+pub mod firstorder;
+pub mod fourier;
+pub mod fss;
+pub mod heisenberg;
+pub mod image;
+pub mod interface;
+pub mod kawasaki;
+pub mod lattice;
+pub mod logging;
+pub mod model;
+pub mod multicanonical;
+pub mod npy;
+pub mod nucleation;
+pub mod observable;
+pub mod padded;
+pub mod plot;
+pub mod potts;
+pub mod profile;
+pub mod progress;
+pub mod reweighting;
+pub mod schedule;
+pub mod start;
+pub mod stats;
+pub mod stream;
+pub mod surface;
+pub mod swendsen_wang;
+pub mod thermalisation;
+pub mod wang_landau;
+pub mod watch;
+pub mod wolff;
+pub mod xoshiro;
+
+use error::{Error, OutputFile, write_file};
+use lattice::{Boundaries, Lattice, Square};
+use model::{Acceptance, Model, SweepOrder};
+use observable::{Observable, Series};
+use schedule::Schedule;
+use padded::Padded;
+use xoshiro::Xoshiro256PlusPlus;
+
+/// Algorithms behind Rng.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RngKind {
+    /// xoshiro256++, fast with good statistical properties, see xoshiro.
+    #[default]
+    Xoshiro,
+    /// ChaCha12, the algorithm behind rand's StdRng, slower but cryptographically strong.
+    ChaCha,
+}
+
+impl RngKind {
+    /// Parse the name of an algorithm, 'xoshiro' or 'chacha'.
+    pub fn parse(name: &str) -> Result<RngKind, String> {
+        match name {
+            "xoshiro" => Ok(RngKind::Xoshiro),
+            "chacha" => Ok(RngKind::ChaCha),
+            _ => Err(format!("Unknown random number generator '{}', must be 'xoshiro' or 'chacha'", name)),
+        }
+    }
+
+    /// Return the name of the algorithm as accepted by parse.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RngKind::Xoshiro => "xoshiro",
+            RngKind::ChaCha => "chacha",
+        }
+    }
+}
+
+/// Generator behind Rng, passes all draws on to one of the algorithms.
+/**
+ * The state of ChaCha12 includes a buffer of 64 words, boxing it keeps Rng small with xoshiro256++.
+ */
+enum Backend {
+    Xoshiro(Xoshiro256PlusPlus),
+    ChaCha(Box<ChaCha12Rng>),
+}
+
+impl TryRng for Backend {
+    type Error = std::convert::Infallible;
+
+    fn try_next_u32(&mut self) -> Result<u32, Self::Error> {
+        match self {
+            Backend::Xoshiro(rng) => rng.try_next_u32(),
+            Backend::ChaCha(rng) => rng.try_next_u32(),
+        }
+    }
+
+    fn try_next_u64(&mut self) -> Result<u64, Self::Error> {
+        match self {
+            Backend::Xoshiro(rng) => rng.try_next_u64(),
+            Backend::ChaCha(rng) => rng.try_next_u64(),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dst: &mut [u8]) -> Result<(), Self::Error> {
+        match self {
+            Backend::Xoshiro(rng) => rng.try_fill_bytes(dst),
+            Backend::ChaCha(rng) => rng.try_fill_bytes(dst),
+        }
+    }
+}
+
+/// Helper struct to handle a random number generator.
+/**
+ * Uses xoshiro256++ by default, which draws numbers about twice as fast as ChaCha12 and is good enough
+ * for Monte-Carlo.
+ * ChaCha12, the generator of earlier versions, can be selected with RngKind::ChaCha.
+ * Both allow saving and restoring the position in the random stream, see state.
+ * Generators seeded by gen_seed for other Rngs should be created with the same kind, see Rng::new.
+ *
+ * Parallel work draws from streams that are guaranteed not to overlap: Rng::stream creates numbered
+ * streams of a seed, e.g. one per Markov chain, and split divides a stream into substreams, e.g. one
+ * per block of rows of a sweep. Assigning streams to units of work rather than to threads makes the
+ * results independent of the number of threads.
+ */
+pub struct Rng {
+    rng: Backend,
+}
+
+/// Position of an Rng in its random stream.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RngState {
+    /// The four words of state of xoshiro256++.
+    Xoshiro([u64; 4]),
+    /// ChaCha12 as the seed, the stream, and the number of 32 bit words drawn in the stream.
+    ChaCha{seed: [u8; 32], stream: u64, word_pos: u128},
+}
+
+impl Rng {
+    /// Create an instance of Rng from a given seed with the default algorithm.
+    pub fn from_seed(seed: [u8; 32]) -> Rng {
+        Rng::new(RngKind::default(), seed)
+    }
+
+    /// Create an instance of Rng from a given seed with the given algorithm.
+    pub fn new(kind: RngKind, seed: [u8; 32]) -> Rng {
+        match kind {
+            RngKind::Xoshiro => Rng{rng: Backend::Xoshiro(Xoshiro256PlusPlus::from_seed(seed))},
+            RngKind::ChaCha => Rng{rng: Backend::ChaCha(Box::new(ChaCha12Rng::from_seed(seed)))},
+        }
+    }
+
+    /// Create the generator of stream index of a seed, stream 0 is Rng::new(kind, seed).
+    /**
+     * xoshiro256++ streams start 2^192 numbers apart, which costs index long jumps, see
+     * Xoshiro256PlusPlus::long_jump. ChaCha12 streams use its 64 bit stream counter, which is free,
+     * and hold 2^68 words each. Either way, a stream only overlaps with another one after drawing
+     * more numbers than any simulation can.
+     */
+    pub fn stream(kind: RngKind, seed: [u8; 32], index: u64) -> Rng {
+        let mut rng = Rng::new(kind, seed);
+        match &mut rng.rng {
+            Backend::Xoshiro(rng) => (0..index).for_each(|_| rng.long_jump()),
+            Backend::ChaCha(rng) => rng.set_stream(index),
+        }
+        rng
+    }
+
+    /// Split off a generator for a part of the work and move this one past the numbers of the part.
+    /**
+     * For xoshiro256++, the returned generator continues at the current position and this one jumps
+     * 2^128 numbers ahead, see Xoshiro256PlusPlus::jump. So neither overlaps with the other or with
+     * earlier splits unless a part draws 2^128 numbers, and 2^64 splits stay inside a stream of
+     * Rng::stream. Splits of split off generators are not covered by this.
+     * For ChaCha12, the returned generator is seeded with gen_seed, which gives independent streams
+     * as long as ChaCha12 is indistinguishable from random.
+     * Costs about as much as drawing 256 numbers with xoshiro256++.
+     */
+    pub fn split(&mut self) -> Rng {
+        match &mut self.rng {
+            Backend::Xoshiro(rng) => {
+                let part = rng.clone();
+                rng.jump();
+                Rng{rng: Backend::Xoshiro(part)}
+            }
+            Backend::ChaCha(_) => Rng::new(RngKind::ChaCha, self.gen_seed()),
+        }
+    }
+
+    /// Create an instance of Rng that continues at a given state.
+    pub fn from_state(state: RngState) -> Rng {
+        match state {
+            RngState::Xoshiro(s) => Rng{rng: Backend::Xoshiro(Xoshiro256PlusPlus::from_state(s))},
+            RngState::ChaCha{seed, stream, word_pos} => {
+                let mut rng = ChaCha12Rng::from_seed(seed);
+                // setting the stream resets the position
+                rng.set_stream(stream);
+                rng.set_word_pos(word_pos);
+                Rng{rng: Backend::ChaCha(Box::new(rng))}
+            }
+        }
+    }
+
+    /// Return the current state, Rng::from_state continues from it with the same random numbers.
+    pub fn state(&self) -> RngState {
+        match &self.rng {
+            Backend::Xoshiro(rng) => RngState::Xoshiro(rng.state()),
+            Backend::ChaCha(rng) => RngState::ChaCha{seed: rng.get_seed(), stream: rng.get_stream(),
+                                                      word_pos: rng.get_word_pos()},
+        }
+    }
+
+    /// Return the algorithm of the generator.
+    pub fn kind(&self) -> RngKind {
+        match self.rng {
+            Backend::Xoshiro(_) => RngKind::Xoshiro,
+            Backend::ChaCha(_) => RngKind::ChaCha,
+        }
+    }
+
+    /// Generate a random index into a configuration of given size.
+    pub fn gen_index(&mut self, size: usize) -> usize {
+        self.rng.random_range(0..size)
+    }
+
+    /// Generate a random spin, one of {-1, +1}.
+    pub fn gen_spin(&mut self) -> i32 {
+        match self.rng.random_range(0..2) {
+            0 => -1,
+            _ => 1,  // 1 is the only other possibility
+        }
+    }
+
+    /// Generate a random double in [0, 1).
+    pub fn gen_real(&mut self) -> f64 {
+        self.rng.random::<f64>()
+    }
+
+    /// Fill indices with random indices into a configuration of given size.
+    /**
+     * Draws the same numbers as calling gen_index for every element, but dispatches on the algorithm
+     * once for the whole buffer, so the generator is inlined into the loop.
+     */
+    pub fn fill_indices(&mut self, size: usize, indices: &mut [usize]) {
+        match &mut self.rng {
+            Backend::Xoshiro(rng) => indices.fill_with(|| rng.random_range(0..size)),
+            Backend::ChaCha(rng) => indices.fill_with(|| rng.random_range(0..size)),
+        }
+    }
+
+    /// Fill reals with random doubles in [0, 1), the same numbers as calling gen_real for every element.
+    pub fn fill_reals(&mut self, reals: &mut [f64]) {
+        match &mut self.rng {
+            Backend::Xoshiro(rng) => reals.fill_with(|| rng.random::<f64>()),
+            Backend::ChaCha(rng) => reals.fill_with(|| rng.random::<f64>()),
+        }
+    }
+
+    /// Generate a seed for another, independent instance of Rng.
+    pub fn gen_seed(&mut self) -> [u8; 32] {
+        self.rng.random()
+    }
+}
+
+/// Hold a spin configuration on the lattice.
+/**
+ * The spin type S defaults to the Ising spins ±1, other models use their own spins
+ * on the same lattice, see model::Model.
+ */
+#[derive(Clone)]
+pub struct Configuration<S = i32> {
+    /// Number of sites in x direction.
+    pub nx: usize,
+    /// Number of sites in y direction, 1 for a chain.
+    pub ny: usize,
+
+    /// The actual configuration, +1 for spin up, -1 for spin down.
+    /// Holds the states 0, ..., q-1 instead for the Potts model, see potts.
+    pub cfg: Vec<S>,
+
+    /// List nearest neighbour indices for each site.
+    /**
+     * The neighbours of site i are stored at neighbour_start[i]..neighbour_start[i+1].
+     * On a periodic 2D square lattice, these are (4*i+0)...(4*i+3) in the order x+1, x-1, y+1, y-1.
+     * On a periodic chain (ny == 1), they are (2*i+0), (2*i+1) in the order x+1, x-1.
+     * Other lattices store their neighbours in the same order as lattice::Lattice::offsets,
+     * open boundaries drop the links that cross them.
+     */
+    neighbours: Vec<usize>,
+
+    /// Start of the neighbours of each site in neighbours, with the total number of entries at the end.
+    neighbour_start: Vec<usize>,
+
+    /// Factor of the coupling of each entry of neighbours, all 1 if None.
+    /**
+     * Anti-periodic boundaries flip the sign of the links across them.
+     */
+    couplings: Option<Vec<f64>>,
+
+    /// Indices of all sites that are updated by evolve, all sites unless some are pinned or vacant.
+    active: Vec<usize>,
+
+    /// Indices of all vacant sites, see Configuration::remove_sites.
+    vacancies: Vec<usize>,
+
+    /// Additional field acting on each individual site, on top of the uniform field h.
+    pub local_field: Option<Vec<f64>>,
+
+    /// True if neighbours are those of a periodic 2D square lattice, see is_periodic_square.
+    square_links: bool,
+}
+
+impl Configuration {
+    /// Create a random configuration on an nx*ny lattice.
+    pub fn random(nx: usize, ny: usize, rng: &mut Rng) -> Configuration {
+        let mut cfg = Configuration::cold(nx, ny, 0);
+
+        for site in cfg.cfg.iter_mut() {
+            *site = rng.gen_spin();
+        }
+
+        cfg
+    }
+
+    /// Fix the spins in the bottom row (y = 0) to +1 and in the top row (y = ny-1) to -1.
+    /**
+     * Pinned sites are excluded from updates, forcing an interface between them.
+     * The periodic link between the two rows only adds a constant nx to the energy.
+     */
+    pub fn pin_boundary_rows(&mut self) {
+        let (nx, ny) = (self.nx, self.ny);
+        assert!(ny >= 3, "Need at least 3 rows to pin the boundaries");
+
+        self.cfg[..nx].iter_mut().for_each(|s| *s = 1);
+        self.cfg[(ny-1)*nx..].iter_mut().for_each(|s| *s = -1);
+        self.active = (nx..(ny-1)*nx).collect();
+    }
+
+    /// Remove sites from the lattice, leaving vacancies with spin 0.
+    /**
+     * Vacancies are excluded from updates and, having spin 0, do not interact with
+     * their neighbours. Observables per site are normalised to the occupied sites, see nsite.
+     */
+    pub fn remove_sites(&mut self, sites: &[usize]) {
+        let mut vacant = vec![false; self.size()];
+        for &idx in self.vacancies.iter().chain(sites) {
+            vacant[idx] = true;
+            self.cfg[idx] = 0;
+        }
+        self.vacancies = (0..self.size()).filter(|&idx| vacant[idx]).collect();
+        self.active.retain(|&idx| !vacant[idx]);
+    }
+
+    /// Remove a fraction of the active sites chosen uniformly at random, see remove_sites.
+    /**
+     * Removes round(fraction * number of active sites) sites, the same number for every rng.
+     */
+    pub fn dilute(&mut self, fraction: f64, rng: &mut Rng) {
+        let mut candidates = self.active.clone();
+        let nremove = (fraction * candidates.len() as f64).round() as usize;
+        // partial Fisher-Yates shuffle
+        for i in 0..nremove {
+            let k = i + rng.gen_index(candidates.len() - i);
+            candidates.swap(i, k);
+        }
+        self.remove_sites(&candidates[..nremove]);
+    }
+}
+
+impl<S: Clone> Configuration<S> {
+    /// Create a configuration on an nx*ny square lattice with all spins set to spin.
+    pub fn cold(nx: usize, ny: usize, spin: S) -> Configuration<S> {
+        Configuration::on_lattice(&Square{nx, ny}, spin)
+    }
+
+    /// Move the spins to another lattice with the same extents, keeping pinned sites and local fields.
+    pub fn with_lattice(self, lattice: &dyn Lattice) -> Configuration<S> {
+        self.with_boundaries(lattice, Boundaries::PERIODIC)
+    }
+
+    /// Like with_lattice but with the given boundary conditions.
+    /**
+     * The coupling factors of the links are the products of the boundary factors and Lattice::coupling.
+     */
+    pub fn with_boundaries(mut self, lattice: &dyn Lattice, boundaries: Boundaries) -> Configuration<S> {
+        let (nx, ny) = lattice.extent();
+        assert_eq!((nx, ny), (self.nx, self.ny), "The lattice must have the extents of the configuration");
+        let mut neighbours = Vec::with_capacity(lattice.coordination()*self.size());
+        let mut neighbour_start = vec![0];
+        let mut couplings = Vec::with_capacity(lattice.coordination()*self.size());
+        for idx in 0..self.size() {
+            for link in lattice.links(idx) {
+                if let Some(factor) = boundaries.factor(&link, idx, nx, ny) {
+                    neighbours.push(link.site);
+                    couplings.push(factor * lattice.coupling(&link));
+                }
+            }
+            neighbour_start.push(neighbours.len());
+        }
+        self.square_links = are_square_links(nx, ny, &neighbours, &neighbour_start);
+        self.neighbours = neighbours;
+        self.neighbour_start = neighbour_start;
+        self.couplings = if couplings.iter().all(|&c| c == 1.) { None } else { Some(couplings) };
+        self
+    }
+
+    /// Create a configuration on any lattice with all spins set to spin.
+    pub fn on_lattice(lattice: &dyn Lattice, spin: S) -> Configuration<S> {
+        let (nx, ny) = lattice.extent();
+        let neighbours = lattice.neighbour_list();
+        let coordination = lattice.coordination();
+        let couplings: Vec<f64> = (0..nx*ny)
+            .flat_map(|idx| lattice.links(idx).into_iter().map(|link| lattice.coupling(&link)))
+            .collect();
+        let neighbour_start: Vec<usize> = (0..=nx*ny).map(|idx| coordination*idx).collect();
+        Configuration{nx, ny,
+                      cfg: vec![spin; nx*ny],
+                      square_links: are_square_links(nx, ny, &neighbours, &neighbour_start),
+                      neighbour_start,
+                      neighbours,
+                      couplings: if couplings.iter().all(|&c| c == 1.) { None } else { Some(couplings) },
+                      active: (0..nx*ny).collect(),
+                      vacancies: Vec::new(),
+                      local_field: None}
+    }
+
+    /// Multiply the coupling factor of every bond by a factor from draw, e.g. for random bonds.
+    /**
+     * Both links of a bond get the same factor so the couplings stay symmetric.
+     * Factors are drawn in the order of the neighbour lists of the sites for the first link of each bond.
+     */
+    pub fn scale_bonds(&mut self, mut draw: impl FnMut() -> f64) {
+        let mut couplings = self.couplings.take().unwrap_or_else(|| vec![1.; self.neighbours.len()]);
+        let mut scaled = vec![false; couplings.len()];
+        for idx in 0..self.size() {
+            for link in self.neighbour_start[idx]..self.neighbour_start[idx+1] {
+                if scaled[link] {
+                    continue;
+                }
+                let factor = draw();
+                couplings[link] *= factor;
+                scaled[link] = true;
+                let neigh = self.neighbours[link];
+                let reverse = (self.neighbour_start[neigh]..self.neighbour_start[neigh+1])
+                    .find(|&back| self.neighbours[back] == idx && !scaled[back]);
+                if let Some(back) = reverse {
+                    couplings[back] *= factor;
+                    scaled[back] = true;
+                }
+            }
+        }
+        self.couplings = Some(couplings);
+    }
+
+    /// Add h to the local field at site idx, creating the local field if there is none.
+    pub fn add_local_field(&mut self, idx: usize, h: f64) {
+        let size = self.size();
+        self.local_field.get_or_insert_with(|| vec![0.; size])[idx] += h;
+    }
+
+    /// Return the total number of lattice sites.
+    pub fn size(&self) -> usize {
+        self.nx*self.ny
+    }
+
+    /// Return the largest number of nearest neighbours of any site.
+    /**
+     * All sites have this many neighbours unless there are open boundaries.
+     */
+    pub fn coordination(&self) -> usize {
+        self.neighbour_start.windows(2).map(|w| w[1] - w[0]).max().unwrap_or(0)
+    }
+
+    /// Return true if some coupling factors differ from 1, see couplings_of.
+    pub fn has_couplings(&self) -> bool {
+        self.couplings.is_some()
+    }
+
+    /// Return true on a periodic 2D square lattice with uniform couplings where all sites are active.
+    /**
+     * The neighbours of each site are then the sites left and right of it in its row and at the same x
+     * in the rows above and below, so sweeps can sum them over whole rows, see checkerboard.
+     */
+    pub fn is_periodic_square(&self) -> bool {
+        self.square_links && self.couplings.is_none() && self.active.len() == self.size()
+    }
+
+    /// Return the factors of the couplings to the neighbours of site idx, None if they are all 1.
+    pub fn couplings_of(&self, idx: usize) -> Option<&[f64]> {
+        self.couplings.as_ref().map(|c| &c[self.neighbour_start[idx]..self.neighbour_start[idx+1]])
+    }
+
+    /// Return the indices of all sites that are updated by evolve.
+    pub fn active_sites(&self) -> &[usize] {
+        &self.active
+    }
+
+    /// Return the indices of all vacant sites.
+    pub fn vacancies(&self) -> &[usize] {
+        &self.vacancies
+    }
+
+    /// Return the number of occupied sites, i.e. all sites except vacancies.
+    pub fn nsite(&self) -> usize {
+        self.size() - self.vacancies.len()
+    }
+
+    /// Return the indices of all nearest neighbours of site idx.
+    pub fn neighbours_of(&self, idx: usize) -> &[usize] {
+        &self.neighbours[self.neighbour_start[idx]..self.neighbour_start[idx+1]]
+    }
+}
+
+impl<S> Index<usize> for Configuration<S> {
+    type Output = S;
+
+    /// Read spin at site idx.
+    fn index(&self, idx: usize) -> &S {
+        &self.cfg[idx]
+    }
+}
+
+impl<S> IndexMut<usize> for Configuration<S> {
+    /// Modify spin at site idx.
+    fn index_mut(&mut self, idx: usize) -> &mut S {
+        &mut self.cfg[idx]
+    }
+}
+
+/// Store Monte-Carlo history of observables.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Observables {
+    /// Total energy after each measurement.
+    pub energy: Vec<f64>,
+    /// Magnetisation per site after each measurement.
+    pub magnetisation: Vec<f64>,
+    /// Staggered magnetisation per site after each measurement, the order parameter for J < 0.
+    pub staggered: Vec<f64>,
+    /// Structure factor at the smallest wave vector along x after each measurement,
+    /// see fourier::smallest_structure_factor.
+    pub structure: Vec<f64>,
+    /// Histories of additional observables, see observable::Observable.
+    pub custom: Vec<Series>,
+}
+
+impl Observables {
+    /// Create an empty history.
+    pub fn new() -> Observables {
+        Observables::with_observables(&[])
+    }
+
+    /// Create an empty history that also measures the given observables.
+    pub fn with_observables(observables: &[Arc<dyn Observable>]) -> Observables {
+        Observables{energy: Vec::new(), magnetisation: Vec::new(), staggered: Vec::new(), structure: Vec::new(),
+                    custom: observables.iter().cloned().map(Series::new).collect()}
+    }
+
+    /// Return the history of the additional observable with the given name.
+    pub fn series(&self, name: &str) -> Option<&Series> {
+        self.custom.iter().find(|series| series.name() == name)
+    }
+
+    /// Record the observables of cfg with the given energy.
+    pub fn measure(&mut self, cfg: &Configuration, energy: f64) {
+        self.energy.push(energy);
+        self.magnetisation.push(magnetisation(cfg));
+        self.staggered.push(staggered_magnetisation(cfg));
+        self.structure.push(fourier::smallest_structure_factor(cfg));
+        for series in &mut self.custom {
+            series.measure(cfg);
+        }
+    }
+
+    /// Remove all measurements.
+    pub fn clear(&mut self) {
+        self.energy.clear();
+        self.magnetisation.clear();
+        self.staggered.clear();
+        self.structure.clear();
+        for series in &mut self.custom {
+            series.values.clear();
+        }
+    }
+}
+
+impl Default for Observables {
+    fn default() -> Observables {
+        Observables::new()
+    }
+}
+
+/// Return true if neighbours and neighbour_start are those of a periodic 2D square lattice, see Configuration.
+fn are_square_links(nx: usize, ny: usize, neighbours: &[usize], neighbour_start: &[usize]) -> bool {
+    ny > 1 && neighbour_start.iter().enumerate().all(|(idx, &start)| start == 4*idx)
+        && (0..nx*ny).all(|idx| {
+            let (x, y) = (idx % nx, idx / nx);
+            neighbours[4*idx..4*idx+4] == [y*nx + (x+1) % nx, y*nx + (x+nx-1) % nx,
+                                           (y+1) % ny * nx + x, (y+ny-1) % ny * nx + x]
+        })
+}
+
+/// Return a list of nearest neighbour indices for use as neighbours in Configuration.
+/**
+ * Uses periodic boundary conditions. Builds a chain if ny == 1, see lattice::Square.
+ */
+pub fn make_neighbour_list(nx: usize, ny: usize) -> Vec<usize> {
+    Square{nx, ny}.neighbour_list()
+}
+
+/// Create the output data directory.
+/**
+ * An existing empty directory is used as is. A directory with contents is only deleted
+ * and recreated if force is set, otherwise returns Error::NonEmptyDirectory.
+ */
+pub fn create_datadir(dirname: &Path, force: bool) -> Result<(), Error> {
+    if dirname.is_dir() {
+        let empty = fs::read_dir(dirname).map_err(Error::io(dirname))?.next().is_none();
+        if empty {
+            return Ok(());
+        }
+        if !force {
+            return Err(Error::NonEmptyDirectory(dirname.to_path_buf()));
+        }
+        warn!("Data directory '{}' exists, removing!", dirname.display());
+        fs::remove_dir_all(dirname).map_err(Error::io(dirname))?;
+    }
+    fs::create_dir_all(dirname).map_err(Error::io(dirname))
+}
+
+/// Create the output data directory and write the temperature file.
+/**
+ * See create_datadir for how an existing directory is treated.
+ */
+pub fn prepare_datadir(dirname: &Path, temperatures: &[f64], force: bool) -> Result<(), Error> {
+    create_datadir(dirname, force)?;
+
+    let mut tempfile = OutputFile::create(&dirname.join("temperatures.dat"))?;
+    for (i, temp) in temperatures.iter().enumerate() {
+        writeln!(tempfile, "{}: {}", i, temp)?;
+    }
+    Ok(())
+}
+
+/// Write a row of space separated values to a file.
+pub fn write_row(file: &mut OutputFile, values: &[f64]) -> Result<(), Error> {
+    for value in values.iter() {
+        write!(file, "{} ", value)?;
+    }
+    writeln!(file)
+}
+
+/// File formats for observables, see write_observables.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Space separated rows of energy and magnetisation.
+    Dat,
+    /// Columns energy and magnetisation with a header line.
+    Csv,
+    /// Object with arrays energy and magnetisation.
+    Json,
+    /// NumPy array of shape (2, n) with energy in row 0 and magnetisation in row 1.
+    Npy,
+}
+
+impl Format {
+    /// Parse the name of a format, 'dat', 'csv', 'json', or 'npy'.
+    pub fn parse(name: &str) -> Result<Format, String> {
+        match name {
+            "dat" => Ok(Format::Dat),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "npy" => Ok(Format::Npy),
+            _ => Err(format!("Unknown format '{}'", name)),
+        }
+    }
+
+    /// Return the file extension of the format, equal to its name.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Dat => "dat",
+            Format::Csv => "csv",
+            Format::Json => "json",
+            Format::Npy => "npy",
+        }
+    }
+}
+
+/// Write observables to a data file in the given format.
+/**
+ * The dat format has the energy in the first and the magnetisation in the second row.
+ * csv has a header line `energy,magnetisation` followed by one line per measurement.
+ * json is an object `{"energy": [...], "magnetisation": [...]}`.
+ * npy is a binary float64 array with the same rows as dat, see npy::npy_bytes.
+ */
+pub fn write_observables(fname: &Path, obs: &Observables, format: Format) -> Result<(), Error> {
+    match format {
+        Format::Dat => {
+            let mut obsfile = OutputFile::create(fname)?;
+            write_row(&mut obsfile, &obs.energy)?;
+            write_row(&mut obsfile, &obs.magnetisation)
+        }
+        Format::Csv => {
+            let mut text = String::from("energy,magnetisation\n");
+            for (e, m) in obs.energy.iter().zip(obs.magnetisation.iter()) {
+                text.push_str(&format!("{},{}\n", e, m));
+            }
+            write_file(fname, text)
+        }
+        Format::Json => {
+            let list = |values: &[f64]| values.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", ");
+            write_file(fname, format!("{{\"energy\": [{}], \"magnetisation\": [{}]}}\n",
+                                      list(&obs.energy), list(&obs.magnetisation)))
+        }
+        Format::Npy => {
+            let data = [obs.energy.as_slice(), obs.magnetisation.as_slice()].concat();
+            write_file(fname, npy::npy_bytes(&[2, obs.energy.len()], &data))
+        }
+    }
+}
+
+/// Sum the spins of all nearest neighbours of site idx.
+pub fn neighbour_sum(cfg: &Configuration, idx: usize) -> i32 {
+    cfg.neighbours_of(idx).iter().map(|&neigh| cfg[neigh]).sum()
+}
+
+/// Sum the spins of all nearest neighbours of site idx weighted with the factors of their couplings.
+/**
+ * Same as neighbour_sum unless cfg has coupling factors, see Configuration::couplings_of.
+ */
+pub fn coupling_sum(cfg: &Configuration, idx: usize) -> f64 {
+    match cfg.couplings_of(idx) {
+        Some(factors) => cfg.neighbours_of(idx).iter().zip(factors.iter())
+            .map(|(&neigh, factor)| cfg[neigh] as f64 * factor).sum(),
+        None => neighbour_sum(cfg, idx) as f64,
+    }
+}
+
+/// Evaluate the Hamiltonian on a configuration with coupling j in an external field h.
+/**
+ * H = -j Σ_<ik> s_i s_k - h Σ_i s_i, j < 0 is antiferromagnetic.
+ * Includes the local field and the coupling factors of cfg if there are any.
+ */
+pub fn hamiltonian(cfg: &Configuration, j: f64, h: f64) -> f64 {
+    // sum integers if possible so the energy is exact
+    let bonds = if cfg.has_couplings() {
+        cfg.cfg.iter().enumerate()
+            .map(|(idx, &site)| site as f64 * coupling_sum(cfg, idx)).sum::<f64>() / 2.
+    } else {
+        bond_sum(cfg) as f64
+    };
+
+    let local: f64 = match &cfg.local_field {
+        Some(field) => cfg.cfg.iter().zip(field.iter()).map(|(&s, f)| s as f64 * f).sum(),
+        None => 0.,
+    };
+
+    -j*bonds - h*cfg.cfg.iter().sum::<i32>() as f64 - local
+}
+
+/// Compute the bond sum Σ_<ik> s_i s_k over all links of cfg, ignoring coupling factors.
+pub fn bond_sum(cfg: &Configuration) -> i64 {
+    let twice: i64 = cfg.cfg.iter().enumerate()
+        .map(|(idx, &site)| (site * neighbour_sum(cfg, idx)) as i64)
+        .sum();
+    // every link is counted from both ends
+    twice / 2
+}
+
+/// Count the unsatisfied links of cfg, i.e. the length of all domain walls for a ferromagnetic coupling.
+/**
+ * A link <ik> is unsatisfied if s_i s_k times its coupling factor is negative, so the flipped links
+ * of anti-periodic boundaries are unsatisfied between aligned spins, see Configuration::couplings_of.
+ * On the square lattice, every unsatisfied link crosses one edge of a domain wall on the dual lattice.
+ */
+pub fn unsatisfied_bonds(cfg: &Configuration) -> usize {
+    let twice: usize = (0..cfg.size()).map(|idx| {
+        let neighbours = cfg.neighbours_of(idx);
+        match cfg.couplings_of(idx) {
+            Some(factors) => neighbours.iter().zip(factors)
+                .filter(|&(&neigh, factor)| (cfg[idx]*cfg[neigh]) as f64 * factor < 0.).count(),
+            None => neighbours.iter().filter(|&&neigh| cfg[idx]*cfg[neigh] < 0).count(),
+        }
+    }).sum();
+    // every link is counted from both ends
+    twice / 2
+}
+
+/// Return true if the Hamiltonian on cfg in field h is -j times the integer bond sum, see BondEnergy.
+pub fn has_bond_energy(cfg: &Configuration, h: f64) -> bool {
+    h == 0. && cfg.local_field.is_none() && !cfg.has_couplings()
+}
+
+/// Energy -j n of a configuration in zero field with uniform couplings and the integer bond sum n.
+/**
+ * Adding up ΔE in floating point accumulates rounding errors unless j is an integer. Counting the
+ * bond sum as an integer and evaluating -j n on every readout keeps the energy exactly equal to hamiltonian
+ * however long a chain runs.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BondEnergy {
+    /// Coupling constant.
+    j: f64,
+    /// The bond sum n = Σ_<ik> s_i s_k.
+    bonds: i64,
+}
+
+impl BondEnergy {
+    /// Return the bond energy of cfg with coupling j, see has_bond_energy for the requirements on cfg.
+    pub fn new(cfg: &Configuration, j: f64) -> BondEnergy {
+        BondEnergy{j, bonds: bond_sum(cfg)}
+    }
+
+    /// Return the bond energy with coupling j closest to energy.
+    /**
+     * Only takes constant time unlike new, energy must be the energy of a configuration
+     * up to rounding errors much smaller than |j|.
+     */
+    pub fn from_energy(energy: f64, j: f64) -> BondEnergy {
+        let bonds = if j == 0. { 0 } else { (-energy / j).round() as i64 };
+        BondEnergy{j, bonds}
+    }
+
+    /// Record a flip of spin s_i with k = s_i Σ_k s_k before the flip, which changes the bond sum by -2k.
+    pub fn flip(&mut self, k: i32) {
+        self.bonds -= 2 * k as i64;
+    }
+
+    /// Record the change of a running energy since it was before and replace it by the exact energy.
+    /**
+     * The change is rounded to the closest multiple of j, so it may carry rounding errors
+     * from adding up ΔE in floating point, as long as they are much smaller than |j|.
+     */
+    pub fn track(&mut self, before: f64, energy: &mut f64) {
+        if self.j != 0. {
+            self.bonds += ((before - *energy) / self.j).round() as i64;
+        }
+        *energy = self.energy();
+    }
+
+    /// Return the bond sum.
+    pub fn bonds(&self) -> i64 {
+        self.bonds
+    }
+
+    /// Return the energy -j n.
+    pub fn energy(&self) -> f64 {
+        // + 0. turns -0 into 0, which running sums of ΔE also give
+        -self.j * self.bonds as f64 + 0.
+    }
+}
+
+/// Compare the incrementally updated energy to its exact value and reset it, returns the drift energy - exact.
+/**
+ * Returns an error and leaves energy unchanged if the drift per occupied site exceeds tolerance,
+ * which indicates a bug in an update rather than rounding errors.
+ */
+pub fn check_energy(energy: &mut f64, exact: f64, nsite: usize, tolerance: f64) -> Result<f64, String> {
+    let drift = *energy - exact;
+    if drift.is_nan() || drift.abs() > tolerance * nsite as f64 {
+        return Err(format!("Running energy {} drifted by {} from the recomputed energy {}", energy, drift, exact));
+    }
+    *energy = exact;
+    Ok(drift)
+}
+
+/// Compute the magnetisation per occupied site on a configuration.
+pub fn magnetisation(cfg: &Configuration) -> f64 {
+    cfg.cfg.iter().sum::<i32>() as f64 / cfg.nsite() as f64
+}
+
+/// Compute the staggered magnetisation (1/N) Σ_i (-1)^(x_i+y_i) s_i with N occupied sites on a configuration.
+/**
+ * This is the order parameter of the antiferromagnet. Odd extents are frustrated with
+ * periodic boundaries, so it cannot saturate there.
+ */
+pub fn staggered_magnetisation(cfg: &Configuration) -> f64 {
+    let sum: i32 = cfg.cfg.iter().enumerate()
+        .map(|(idx, s)| if (idx % cfg.nx + idx / cfg.nx).is_multiple_of(2) { *s } else { -*s })
+        .sum();
+    sum as f64 / cfg.nsite() as f64
+}
+
+/// Compute the stripe magnetisation √(m(π,0)² + m(0,π)²) on a configuration.
+/**
+ * m(π,0) = (1/N) Σ_i (-1)^x_i s_i is 1 for stripes along y, m(0,π) for stripes along x.
+ * This is the order parameter of the striped phase of the J1-J2 model, see lattice::NextNearest.
+ */
+pub fn stripe_magnetisation(cfg: &Configuration) -> f64 {
+    let (mut mx, mut my) = (0, 0);
+    for (idx, &s) in cfg.cfg.iter().enumerate() {
+        mx += if (idx % cfg.nx).is_multiple_of(2) { s } else { -s };
+        my += if (idx / cfg.nx).is_multiple_of(2) { s } else { -s };
+    }
+    ((mx*mx + my*my) as f64).sqrt() / cfg.nsite() as f64
+}
+
+/// Compute the change in energy if the spin at site idx were flipped with coupling j in an external field h.
+/**
+ * Includes the local field and the coupling factors of cfg if there are any.
+ */
+pub fn delta_e(cfg: &Configuration, idx: usize, j: f64, h: f64) -> f64 {
+    let local = cfg.local_field.as_ref().map_or(0., |field| field[idx]);
+    2.*cfg[idx] as f64 * (j*coupling_sum(cfg, idx) + h + local)
+}
+
+/// The Ising model with coupling j in an external field h as a model::Model.
+/**
+ * Proposals flip the spin. The Hamiltonian and energy differences are those of
+ * hamiltonian and delta_e, including the local field of the configuration.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ising {
+    /// Coupling constant, negative for an antiferromagnet.
+    pub j: f64,
+    /// Uniform external field.
+    pub h: f64,
+}
+
+impl Model for Ising {
+    type Spin = i32;
+
+    fn random_spin(&self, rng: &mut Rng) -> i32 {
+        rng.gen_spin()
+    }
+
+    fn propose(&self, spin: &i32, _rng: &mut Rng) -> i32 {
+        -spin
+    }
+
+    fn pair_energy(&self, a: &i32, b: &i32) -> f64 {
+        -self.j*(a*b) as f64
+    }
+
+    fn site_energy(&self, cfg: &Configuration, idx: usize, spin: &i32) -> f64 {
+        let local = cfg.local_field.as_ref().map_or(0., |field| field[idx]);
+        -(self.h + local)*(*spin as f64)
+    }
+
+    fn measure(&self, obs: &mut Observables, cfg: &Configuration, energy: f64) {
+        obs.measure(cfg, energy);
+    }
+
+    fn hamiltonian(&self, cfg: &Configuration) -> f64 {
+        hamiltonian(cfg, self.j, self.h)
+    }
+
+    fn delta_e(&self, cfg: &Configuration, idx: usize, spin: &i32) -> f64 {
+        if *spin == cfg[idx] { 0. } else { delta_e(cfg, idx, self.j, self.h) }
+    }
+}
+
+/// Propose to flip the spin at a random active site and accept or reject with Metropolis-Hastings.
+/**
+ * Returns true if the flip was accepted, see model::metropolis_step.
+ */
+pub fn metropolis_step(cfg: &mut Configuration, energy: &mut f64, beta: f64, j: f64, h: f64,
+                   rng: &mut Rng) -> bool {
+    model::metropolis_step(&Ising{j, h}, cfg, energy, beta, rng)
+}
+
+/// Acceptance probabilities in zero field.
+/**
+ * Without a (local) field, ΔE = 2 J s_i Σ_j s_j only depends on k = s_i Σ_j s_j which is an
+ * integer in [-coordination, coordination], so the acceptance probability can be computed once per temperature.
+ * Metropolis accepts ΔE <= 0 unconditionally, these are stored as 1 so every step only compares
+ * with a uniform random number.
+ */
+struct AcceptanceTable {
+    /// Number of nearest neighbours, the offset of k in probabilities.
+    coordination: i32,
+    /// Acceptance::probability of ΔE for k = -coordination, ..., coordination.
+    probabilities: Vec<f64>,
+}
+
+impl AcceptanceTable {
+    /// Tabulate acceptance probabilities for inverse temperature beta and coupling j.
+    fn new(beta: f64, j: f64, coordination: usize, acceptance: Acceptance) -> AcceptanceTable {
+        let coordination = coordination as i32;
+        let probabilities = (-coordination..=coordination)
+            .map(|k| {
+                let delta = 2.*(j*k as f64);
+                match acceptance {
+                    Acceptance::Metropolis if delta <= 0. => 1.,
+                    _ => acceptance.probability(beta, delta),
+                }
+            })
+            .collect();
+        AcceptanceTable{coordination, probabilities}
+    }
+}
+
+/// Propose to flip the spin at site idx and accept if its probability in table exceeds uniform.
+/**
+ * Only valid in zero field, records an accepted flip in energy.
+ */
+fn flip_step_table(cfg: &mut Configuration, energy: &mut BondEnergy, table: &AcceptanceTable, idx: usize,
+                   uniform: f64) -> bool {
+    let k = cfg[idx] * neighbour_sum(cfg, idx);
+    if table.probabilities[(k + table.coordination) as usize] > uniform {
+        cfg[idx] *= -1;
+        energy.flip(k);
+        return true;
+    }
+    false  // discard
+}
+
+/// Like flip_step_table for the site at index p of the padded layout.
+fn flip_step_padded(padded: &mut Padded, energy: &mut BondEnergy, table: &AcceptanceTable, p: usize,
+                    uniform: f64) -> bool {
+    let k = padded.spin(p) * padded.neighbour_sum(p);
+    if table.probabilities[(k + table.coordination) as usize] > uniform {
+        padded.flip(p);
+        energy.flip(k);
+        return true;
+    }
+    false  // discard
+}
+
+/// Evolve a configuration in Monte-Carlo time.
+/**
+ * Flips spins at random active sites nsweep*cfg.active.len() times and accepting or
+ * rejecting the change using the Metropolis-Hastings algroithm.
+ * In zero field with uniform couplings, the acceptance probabilities are tabulated, see AcceptanceTable,
+ * the energy is tracked exactly as a BondEnergy, and the sites and uniform random numbers of a sweep are
+ * drawn up front with Rng::fill_indices and Rng::fill_reals, otherwise this is model::evolve for the Ising model.
+ * On periodic square lattices, the sweeps work on a copy of the spins in the padded layout of padded::Padded
+ * instead of going through the neighbour lists.
+ * Measures observables every cfg.active.len() steps, i.e. once per sweep.
+ *
+ * cfg and energy must be set before calling the function.
+ * Upon return, they contain the final configuration and energy.
+ * Returns the number of accepted spin flips.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn evolve(cfg: &mut Configuration, energy: &mut f64, beta: f64, j: f64, h: f64,
+          rng: &mut Rng, nsweep: usize, obs: Option<&mut Observables>) -> usize {
+    evolve_in_order(cfg, energy, beta, j, h, Acceptance::Metropolis, SweepOrder::Random, rng, nsweep, obs)
+}
+
+/// Like evolve but with heat bath (Glauber) acceptance, see model::Acceptance::Glauber.
+/**
+ * The sites are visited in the same order as by evolve, so the two only differ in their dynamics.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn evolve_glauber(cfg: &mut Configuration, energy: &mut f64, beta: f64, j: f64, h: f64,
+                      rng: &mut Rng, nsweep: usize, obs: Option<&mut Observables>) -> usize {
+    evolve_in_order(cfg, energy, beta, j, h, Acceptance::Glauber, SweepOrder::Random, rng, nsweep, obs)
+}
+
+/// Like evolve and evolve_glauber with the given acceptance rule and order of the sites, see model::SweepOrder.
+/**
+ * evolve and evolve_glauber visit random sites, Sequential and Shuffled visit every active site once per sweep.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn evolve_in_order(cfg: &mut Configuration, energy: &mut f64, beta: f64, j: f64, h: f64,
+                       acceptance: Acceptance, order: SweepOrder, rng: &mut Rng, nsweep: usize,
+                       obs: Option<&mut Observables>) -> usize {
+    // a (local) field or coupling factors make ΔE continuous, fall back to computing exp in every step then
+    if !has_bond_energy(cfg, h) {
+        return model::evolve_in_order(&Ising{j, h}, cfg, energy, beta, acceptance, order, rng, nsweep, obs);
+    }
+    match obs {
+        Some(o) => {
+            let mut measure = |_sweep, cfg: &Configuration, energy| {
+                o.measure(cfg, energy);
+                ControlFlow::Continue(())
+            };
+            evolve_tabulated(cfg, energy, beta, j, acceptance, order, rng, nsweep, Some(&mut measure))
+        }
+        None => evolve_tabulated(cfg, energy, beta, j, acceptance, order, rng, nsweep, None),
+    }
+}
+
+/// Buffers of tabulated sweeps in zero field with uniform couplings, built once for many sweeps.
+/**
+ * On periodic square lattices, the sweeps work on a padded copy of the spins, which has to be
+ * written back to the configuration before it is read and read again after it changed elsewhere.
+ */
+struct TabulatedSweeps {
+    /// Positions in cfg.active of all steps of a sweep.
+    indices: Vec<usize>,
+    /// Uniform random numbers of all steps of a sweep.
+    uniforms: Vec<f64>,
+    /// Copy of the spins in the padded layout, all sites are active, so indices are sites.
+    padded: Option<Padded>,
+}
+
+impl TabulatedSweeps {
+    /// Create the buffers for sweeps of cfg.
+    fn new(cfg: &Configuration) -> TabulatedSweeps {
+        TabulatedSweeps{indices: vec![0; cfg.active.len()], uniforms: vec![0.; cfg.active.len()],
+                        padded: cfg.is_periodic_square().then(|| Padded::new(cfg))}
+    }
+
+    /// Run a single sweep in the given order, returns the number of accepted spin flips.
+    fn sweep(&mut self, cfg: &mut Configuration, energy: &mut BondEnergy, table: &AcceptanceTable,
+             order: SweepOrder, rng: &mut Rng) -> usize {
+        order.fill(&mut self.indices, rng);
+        rng.fill_reals(&mut self.uniforms);
+        let mut naccept = 0;
+        match self.padded.as_mut() {
+            Some(padded) => {
+                for (&idx, &uniform) in self.indices.iter().zip(&self.uniforms) {
+                    let p = padded.index(idx);
+                    if flip_step_padded(padded, energy, table, p, uniform) {
+                        naccept += 1;
+                    }
+                }
+            }
+            None => {
+                for (&i, &uniform) in self.indices.iter().zip(&self.uniforms) {
+                    let idx = cfg.active[i];
+                    if flip_step_table(cfg, energy, table, idx, uniform) {
+                        naccept += 1;
+                    }
+                }
+            }
+        }
+        naccept
+    }
+
+    /// Write the spins of the sweeps to cfg.
+    fn write(&self, cfg: &mut Configuration) {
+        if let Some(padded) = &self.padded {
+            padded.write(cfg);
+        }
+    }
+
+    /// Read the spins of cfg after it was changed by other updates.
+    fn read(&mut self, cfg: &Configuration) {
+        if let Some(padded) = &mut self.padded {
+            padded.read(cfg);
+        }
+    }
+}
+
+/// Callback of evolve_tabulated after every sweep, see evolve_observed.
+type SweepCallback<'a> = &'a mut dyn FnMut(usize, &Configuration, f64) -> ControlFlow<()>;
+
+/// Sweeps of evolve_in_order in zero field with uniform couplings, call after_sweep(sweep, cfg, energy) if given.
+/**
+ * The tables, buffers, and padded copy of the spins are built once for all sweeps. The padded copy is only
+ * written back to cfg before calling after_sweep and at the end.
+ */
+#[allow(clippy::too_many_arguments)]
+fn evolve_tabulated(cfg: &mut Configuration, energy: &mut f64, beta: f64, j: f64, acceptance: Acceptance,
+                    order: SweepOrder, rng: &mut Rng, nsweep: usize, mut after_sweep: Option<SweepCallback>) -> usize {
+    let table = AcceptanceTable::new(beta, j, cfg.coordination(), acceptance);
+    let mut bond_energy = BondEnergy::from_energy(*energy, j);
+    let mut sweeps = TabulatedSweeps::new(cfg);
+
+    // running number of accepted spin flips
+    let mut naccept: usize = 0;
+    for sweep in 0..nsweep {
+        naccept += sweeps.sweep(cfg, &mut bond_energy, &table, order, rng);
+        *energy = bond_energy.energy();
+
+        if let Some(callback) = &mut after_sweep {
+            sweeps.write(cfg);
+            if callback(sweep, cfg, *energy).is_break() {
+                break;
+            }
+        }
+    }
+    sweeps.write(cfg);
+
+    naccept
+}
+
+/// Like evolve but call callback(sweep, cfg, energy) after every sweep instead of measuring Observables.
+/**
+ * sweep counts from 0, cfg and energy are the configuration and energy after that sweep.
+ * Returning ControlFlow::Break stops the evolution after the current sweep, e.g. once a measurement
+ * has converged, ControlFlow::Continue runs the next sweep until nsweep sweeps are done.
+ * The sweeps use the same random numbers as evolve, so both give the same chain.
+ * Returns the number of accepted spin flips.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn evolve_observed<F>(cfg: &mut Configuration, energy: &mut f64, beta: f64, j: f64, h: f64,
+                          rng: &mut Rng, nsweep: usize, mut callback: F) -> usize
+where F: FnMut(usize, &Configuration, f64) -> ControlFlow<()> {
+    if has_bond_energy(cfg, h) {
+        return evolve_tabulated(cfg, energy, beta, j, Acceptance::Metropolis, SweepOrder::Random, rng, nsweep,
+                                Some(&mut callback));
+    }
+    let model = Ising{j, h};
+    let mut naccept = 0;
+    for sweep in 0..nsweep {
+        naccept += model::evolve(&model, cfg, energy, beta, rng, 1, None);
+        if callback(sweep, cfg, *energy).is_break() {
+            break;
+        }
+    }
+    naccept
+}
+
+/// Evolve a configuration in Monte-Carlo time while changing the temperature.
+/**
+ * Like evolve but the temperature and field of each sweep are taken from ramp and field.
+ * Measurements after sweep i are taken at temperature ramp.value(i, nsweep).
+ * The acceptance rate of each sweep is reported to both schedules.
+ * The sweeps are those of evolve_field, but the buffers and padded copy of the spins of sweeps
+ * in zero field are built once for the whole ramp and the acceptance table once per temperature.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn evolve_ramp(cfg: &mut Configuration, energy: &mut f64, j: f64, ramp: &dyn Schedule, field: &dyn Schedule,
+               rng: &mut Rng, nsweep: usize, mut obs: Option<&mut Observables>) -> usize {
+    let mut sweeps = TabulatedSweeps::new(cfg);
+    // whether cfg lags behind the spins of sweeps or the other way around
+    let (mut cfg_outdated, mut sweeps_outdated) = (false, false);
+    // acceptance table and the beta it was built for, rebuilt when the temperature changes
+    let mut table: Option<(f64, AcceptanceTable)> = None;
+
+    let mut naccept: usize = 0;
+    for sweep in 0..nsweep {
+        let beta = 1./ramp.value(sweep, nsweep);
+        if cfg_outdated && sweep > 0 && field.value(sweep, nsweep) != field.value(sweep-1, nsweep) {
+            // change_field needs the magnetisation
+            sweeps.write(cfg);
+            cfg_outdated = false;
+        }
+        let h = change_field(cfg, energy, field, sweep, nsweep);
+        let nflip = if has_bond_energy(cfg, h) {
+            if sweeps_outdated {
+                sweeps.read(cfg);
+                sweeps_outdated = false;
+            }
+            if table.as_ref().is_none_or(|&(b, _)| b != beta) {
+                table = Some((beta, AcceptanceTable::new(beta, j, cfg.coordination(), Acceptance::Metropolis)));
+            }
+            let mut bond_energy = BondEnergy::from_energy(*energy, j);
+            let nflip = sweeps.sweep(cfg, &mut bond_energy, &table.as_ref().unwrap().1, SweepOrder::Random, rng);
+            *energy = bond_energy.energy();
+            cfg_outdated = true;
+            nflip
+        }
+        else {
+            if cfg_outdated {
+                sweeps.write(cfg);
+                cfg_outdated = false;
+            }
+            sweeps_outdated = true;
+            model::evolve(&Ising{j, h}, cfg, energy, beta, rng, 1, None)
+        };
+        if let Some(o) = &mut obs {
+            if cfg_outdated {
+                sweeps.write(cfg);
+                cfg_outdated = false;
+            }
+            o.measure(cfg, *energy);
+        }
+
+        let acceptance = nflip as f64 / cfg.active.len() as f64;
+        ramp.record_acceptance(sweep, nsweep, acceptance);
+        field.record_acceptance(sweep, nsweep, acceptance);
+        naccept += nflip;
+    }
+    if cfg_outdated {
+        sweeps.write(cfg);
+    }
+    naccept
+}
+
+/// Evolve a configuration by a single sweep in a time dependent field.
+/**
+ * The field is taken from sweep number `sweep` out of `nsweep` of field.
+ * The energy is updated to account for the change in field before the sweep.
+ * Runs of many sweeps should use evolve_ramp, which sets up the sweeps only once.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn evolve_field(cfg: &mut Configuration, energy: &mut f64, beta: f64, j: f64, field: &dyn Schedule,
+                sweep: usize, nsweep: usize, rng: &mut Rng,
+                obs: Option<&mut Observables>) -> usize {
+    let h = change_field(cfg, energy, field, sweep, nsweep);
+    evolve(cfg, energy, beta, j, h, rng, 1, obs)
+}
+
+/// Return the field at sweep number `sweep` out of `nsweep` and adjust energy to it.
+pub fn change_field(cfg: &Configuration, energy: &mut f64, field: &dyn Schedule,
+                sweep: usize, nsweep: usize) -> f64 {
+    let h = field.value(sweep, nsweep);
+    if sweep > 0 {
+        // H depends linearly on h
+        let dh = h - field.value(sweep-1, nsweep);
+        *energy -= dh * magnetisation(cfg) * cfg.nsite() as f64;
+    }
+    h
+}
+
+/// Count attempted spin flips to take measurements more often than once per sweep.
+pub struct FlipClock {
+    /// Measure every this many attempted flips.
+    pub every: usize,
+    /// Number of flips attempted so far.
+    pub flips: usize,
+    /// Values of flips at each measurement.
+    pub times: Vec<usize>,
+}
+
+impl FlipClock {
+    /// Create a clock that measures every `every` attempted flips.
+    pub fn new(every: usize) -> FlipClock {
+        FlipClock{every, flips: 0, times: Vec::new()}
+    }
+}
+
+/// Evolve a configuration by a single sweep in a time dependent field with sub-sweep measurements.
+/**
+ * Like evolve_field but measures observables every clock.every attempted flips instead
+ * of once per sweep. The clock keeps running across calls, so measurements stay
+ * equidistant even if clock.every does not divide the number of active sites.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn evolve_field_flips(cfg: &mut Configuration, energy: &mut f64, beta: f64, j: f64, field: &dyn Schedule,
+                      sweep: usize, nsweep: usize, rng: &mut Rng,
+                      clock: &mut FlipClock, obs: &mut Observables) -> usize {
+    let h = change_field(cfg, energy, field, sweep, nsweep);
+    let mut naccept: usize = 0;
+    for _step in 0..cfg.active.len() {
+        if metropolis_step(cfg, energy, beta, j, h, rng) {
+            naccept += 1;
+        }
+        clock.flips += 1;
+        if clock.flips.is_multiple_of(clock.every) {
+            clock.times.push(clock.flips);
+            obs.measure(cfg, *energy);
+        }
+    }
+    naccept
+}
+
+/// Compute the mean of a series and its error from nbin bins.
+/**
+ * Needs at least 2 bins of at least one value each.
+ */
+pub fn binned_mean(data: &[f64], nbin: usize) -> (f64, f64) {
+    assert!(nbin >= 2 && data.len() >= nbin, "Binning needs at least 2 bins and one value per bin");
+    let binsize = data.len() / nbin;
+    let bins: Vec<f64> = data.chunks_exact(binsize).take(nbin)
+        .map(|bin| bin.iter().sum::<f64>() / binsize as f64)
+        .collect();
+
+    let mean = bins.iter().sum::<f64>() / nbin as f64;
+    let var = bins.iter().map(|b| (b-mean).powi(2)).sum::<f64>() / (nbin*(nbin-1)) as f64;
+    (mean, var.sqrt())
+}
+
+/// Compute the specific heat per site from an energy history and its jackknife error from nbin bins.
+/**
+ * Needs at least 2 bins of at least one value each.
+ */
+pub fn binned_specific_heat(energy: &[f64], beta: f64, size: usize, nbin: usize) -> (f64, f64) {
+    assert!(nbin >= 2 && energy.len() >= nbin, "Binning needs at least 2 bins and one value per bin");
+    analysis::jackknife(energy, energy.len() / nbin, |data| analysis::specific_heat(data, beta, size))
+}
@@ -0,0 +1,196 @@
+/*!
+ * Log messages with levels as plain text or JSON lines.
+ *
+ * The macros error!, warn!, info!, and debug! format a message like println! and pass it to log.
+ * Text output prints the bare message, to stdout for info and debug and to stderr for warnings and errors.
+ * JSON output prints one object per line to stdout with the time, level, message, the numeric fields of the
+ * record, and the names and fields of all open spans, so cluster jobs produce machine-parsable logs.
+ * Until init is called, messages up to info are printed as text.
+ */
+
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::sync::OnceLock;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::config::json_string;
+
+/// Severity of a message, messages are printed if their level is at most the configured one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl Level {
+    /// Parse the name of a level, 'error', 'warn', 'info', or 'debug'.
+    pub fn parse(name: &str) -> Result<Level, String> {
+        match name {
+            "error" => Ok(Level::Error),
+            "warn" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            _ => Err(format!("Unknown log level '{}', expected error, warn, info, or debug", name)),
+        }
+    }
+
+    /// Return the name of the level.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        }
+    }
+}
+
+/// Output format of log messages.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogFormat {
+    /// The bare message.
+    Text,
+    /// One JSON object per message.
+    Json,
+}
+
+impl LogFormat {
+    /// Parse the name of a format, 'text' or 'json'.
+    pub fn parse(name: &str) -> Result<LogFormat, String> {
+        match name {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Unknown log format '{}', expected text or json", name)),
+        }
+    }
+}
+
+/// Level and format set by init.
+static LOGGER: OnceLock<(Level, LogFormat)> = OnceLock::new();
+
+/// Name and fields of an open span.
+type SpanRecord = (&'static str, Vec<(String, f64)>);
+
+thread_local! {
+    /// The open spans of this thread, outermost first.
+    static SPANS: RefCell<Vec<SpanRecord>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Set the level and format of all following messages, only the first call has an effect.
+pub fn init(level: Level, format: LogFormat) {
+    let _ = LOGGER.set((level, format));
+}
+
+/// Return the configured level and format.
+fn logger() -> (Level, LogFormat) {
+    *LOGGER.get().unwrap_or(&(Level::Info, LogFormat::Text))
+}
+
+/// Return true if messages of level are printed.
+pub fn enabled(level: Level) -> bool {
+    level <= logger().0
+}
+
+/// Write a field as `"name": value` with null for values that are not finite.
+fn write_field(record: &mut String, name: &str, value: f64) {
+    if value.is_finite() {
+        write!(record, ", {}: {}", json_string(name), value).unwrap();
+    }
+    else {
+        write!(record, ", {}: null", json_string(name)).unwrap();
+    }
+}
+
+/// Print a message of level with numeric fields, which are only shown in JSON output.
+pub fn log(level: Level, message: &str, fields: &[(&str, f64)]) {
+    let (max_level, format) = logger();
+    if level > max_level {
+        return;
+    }
+    match format {
+        LogFormat::Text if level <= Level::Warn => eprintln!("{}", message),
+        LogFormat::Text => println!("{}", message),
+        LogFormat::Json => {
+            let time = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0., |t| t.as_secs_f64());
+            let mut record = format!("{{\"time\": {:.3}, \"level\": \"{}\"", time, level.name());
+            SPANS.with(|spans| {
+                let spans = spans.borrow();
+                if !spans.is_empty() {
+                    let names: Vec<&str> = spans.iter().map(|(name, _)| *name).collect();
+                    write!(record, ", \"span\": {}", json_string(&names.join("/"))).unwrap();
+                }
+                for (name, value) in spans.iter().flat_map(|(_, fields)| fields) {
+                    write_field(&mut record, name, *value);
+                }
+            });
+            for (name, value) in fields {
+                write_field(&mut record, name, *value);
+            }
+            write!(record, ", \"message\": {}}}", json_string(message.trim_start())).unwrap();
+            println!("{}", record);
+        }
+    }
+}
+
+/// A named section of a run, e.g. one temperature, whose fields are attached to all messages inside it.
+/**
+ * The span is open from enter until it is closed or dropped and spans of one thread nest.
+ */
+pub struct Span {
+    /// Time when the span was entered.
+    start: Instant,
+}
+
+impl Span {
+    /// Open a span with the given name and fields.
+    pub fn enter(name: &'static str, fields: &[(&str, f64)]) -> Span {
+        SPANS.with(|spans| spans.borrow_mut()
+                   .push((name, fields.iter().map(|(name, value)| (name.to_string(), *value)).collect())));
+        log(Level::Debug, &format!("Entering {}", name), &[]);
+        Span{start: Instant::now()}
+    }
+
+    /// Return the time in seconds since the span was entered.
+    pub fn elapsed(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+
+    /// Close the span with an info message carrying fields and the duration of the span in seconds.
+    pub fn close(self, message: &str, fields: &[(&str, f64)]) {
+        let mut fields = fields.to_vec();
+        fields.push(("duration", self.elapsed()));
+        log(Level::Info, message, &fields);
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        SPANS.with(|spans| spans.borrow_mut().pop());
+    }
+}
+
+/// Log an error message, see log.
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::Level::Error, &format!($($arg)*), &[]) };
+}
+
+/// Log a warning, see log.
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::Level::Warn, &format!($($arg)*), &[]) };
+}
+
+/// Log an informational message, see log.
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::Level::Info, &format!($($arg)*), &[]) };
+}
+
+/// Log a debug message, see log.
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::Level::Debug, &format!($($arg)*), &[]) };
+}
@@ -1,16 +1,50 @@
-/**
+/*!
  * Rust implementation of the Ising Model simulation.
  */
 
-use std::io::prelude::*;
 use std::fs;
-use std::path::Path;
-use std::ops::{Index, IndexMut};
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 use std::env;
+use std::process;
+use std::sync::Arc;
+use std::thread;
 
-extern crate rand;
-use rand::prelude::*;
+use ising::{Configuration, FlipClock, Format, Observables, Rng, RngKind, error, info, warn};
+use ising::{binned_mean, binned_specific_heat, change_field, check_energy, create_datadir, evolve, evolve_field_flips,
+            evolve_ramp, hamiltonian, prepare_datadir, write_observables, write_row};
+use ising::{aging, analysis, anneal, autocorr, benchmark, cftp, chains, checkerboard, covariance, exact, export, firstorder, fourier, fss,
+            image, interface, model, multicanonical, nucleation, profile, schedule, surface, thermalisation, wang_landau};
+use ising::analysis::{Autocorrelation, Summary};
+use ising::benchmark::Algorithm;
+use ising::chains::Chain;
+use ising::checkpoint::Checkpoint;
+use ising::disorder::{CorrelatedDisorder, RandomBonds};
+use ising::distributed::{self, Ring};
+use ising::error::{Error, OutputFile, write_file};
+use ising::heisenberg::{self, Heisenberg};
+use ising::image::{Image, Movie, MovieFormat};
+use ising::lattice::{Anisotropic, Boundaries, Geometry, Lattice, NextNearest};
+use ising::logging::{self, Level, LogFormat, Span};
+use ising::model::{Acceptance, Model, SweepOrder};
+use ising::observable::{self, Observable, Series};
+use ising::plot::Plot;
+use ising::potts::Potts;
+use ising::fourier::StructureFactor;
+use ising::profile::Profile;
+use ising::progress::Progress;
+use ising::reweighting::{self, Reweighting};
+use ising::schedule::Schedule;
+use ising::stream::{Sink, StreamWriter};
+use ising::start::{Start, write_configuration};
+use ising::stats::{self, TemperatureStats};
+use ising::surface::SurfaceField;
+use ising::wang_landau::WangLandauParams;
+use ising::watch::Watch;
+use ising::classifier::Classifier;
+use ising::clusters::ClusterDistribution;
+use ising::correlation::Correlation;
+use ising::config::{self, Constants, RunParams, derive_seed, expand_seed};
 
 
 //--------------------------
@@ -21,8 +55,90 @@ const NTHERM: usize = 1000;  // number of thermalisation sweeps per temperature
 const NPROD: usize = 10000;  // number of production sweeps (with measurements) per temperature
 
 const NX: usize = 4;  // number of lattice sites in x direction
-const NY: usize = 3;  // number of lattice sites in y direction
-const LATSIZE: usize = NX*NY;  // total lattice size
+const NY: usize = 3;  // number of lattice sites in y direction, use 1 for a chain
+
+const SEED: [u8; 32] = [138; 32];  // seed of the random number generator
+
+const J: f64 = 1.0;  // default coupling constant, negative for an antiferromagnet
+const H: f64 = 0.0;  // default external magnetic field
+
+const NRAMP: usize = 10000;  // default number of sweeps of a temperature ramp
+const NANNEAL: usize = 100;  // default number of sweeps per step of an annealing schedule
+
+const NTAU_SKT: usize = 100;  // number of time separations (in sweeps) of the dynamic structure factor
+
+const T_QUENCH: f64 = 1.5;  // temperature to quench to in aging mode
+const NSWEEP_AGING: usize = 10000;  // number of sweeps after each quench
+const NREPEAT_AGING: usize = 20;  // number of quenches to average over
+const H_RESPONSE: f64 = 0.05;  // strength of the random field used to measure the response
+
+/// Return a vector of waiting times (in sweeps after the quench) for aging mode.
+fn list_waiting_times() -> Vec<usize> {
+    vec![10, 100, 1000]
+}
+
+const NBIN_HISTOGRAM: usize = 50;  // number of bins of energy and magnetisation histograms
+const NREWEIGHT: usize = 200;  // number of temperatures of reweighted curves
+
+const T_LATENT: f64 = 2.3;  // temperature close to the transition for the latent heat measurement
+
+/// Return a vector of lattice sizes (nx, ny) to locate the transition on with --latent-heat.
+fn list_latent_lattices() -> Vec<(usize, usize)> {
+    vec![(4, 4), (6, 6), (8, 8)]
+}
+
+const NBOOTSTRAP: usize = 1000;  // number of bootstrap samples for errors of nonlinear observables
+
+const NPILOT_THIN: usize = 1000;  // number of production sweeps used to estimate tau_int for --auto-thin
+
+const ENERGY_TOLERANCE: f64 = 1e-9;  // largest accepted drift of the running energy per site with --check-energy
+
+const NBLOCK_AUTO_THERM: usize = 100;  // number of sweeps between two checks of equilibration with --auto-therm
+const NMAX_AUTO_THERM: usize = 100000;  // maximum number of thermalisation sweeps per temperature with --auto-therm
+
+const T_BENCHMARK: f64 = 2.3;  // temperature of the algorithm benchmark, close to Tc
+const NSWEEP_BENCHMARK: usize = 10000;  // number of timed sweeps per algorithm in the benchmark
+
+const NSKIP_CLASSIFIER: usize = 10;  // number of sweeps between snapshots sent to the classifier
+
+const FRAME_DELAY_MOVIE: u16 = 4;  // time between frames of GIF movies of the quench in 1/100 s
+
+const MAX_ANGLE_HEISENBERG: f64 = 1.0;  // initial maximum rotation angle of Metropolis proposals for --heisenberg
+const ACCEPTANCE_HEISENBERG: f64 = 0.5;  // acceptance rate the proposal width is tuned to during thermalisation
+
+const NSAMPLE_EXPORT: usize = 1000;  // number of exported configurations per temperature
+const NSKIP_EXPORT: usize = 10;  // minimum number of sweeps between exported configurations
+const NPILOT_EXPORT: usize = 1000;  // number of sweeps per temperature used to estimate tau_int for --export
+const SHARD_SIZE_EXPORT: usize = 10000;  // maximum number of configurations per exported file
+
+const NREPEAT_NUCLEATION: usize = 100;  // number of decays per temperature and field in nucleation mode
+const NMAX_NUCLEATION: usize = 100000;  // maximum number of sweeps for a single decay
+
+/// Return a vector of temperatures for nucleation mode, should be below Tc.
+fn list_nucleation_temperatures() -> Vec<f64> {
+    vec![1.0, 1.5]
+}
+
+/// Return a vector of (weak) fields for nucleation mode.
+fn list_nucleation_fields() -> Vec<f64> {
+    vec![0.1, 0.2, 0.4]
+}
+
+const NSAMPLE_CFTP: usize = 1000;  // number of exact samples per temperature with coupling from the past
+const NMAX_CFTP: usize = 1 << 16;  // maximum number of sweeps into the past for a single exact sample
+
+const T_MULTICANONICAL: f64 = 2.0;  // temperature of multicanonical sampling, below Tc for the double peak
+const NITER_MULTICANONICAL: usize = 100;  // number of iterations of the multicanonical weights
+const NSWEEP_MULTICANONICAL: usize = 1000;  // number of sweeps per iteration of the multicanonical weights
+
+const LN_F_WANG_LANDAU: f64 = 1e-8;  // final logarithm of the Wang-Landau modification factor
+const FLATNESS_WANG_LANDAU: f64 = 0.8;  // minimum fraction of the mean count for a flat energy histogram
+const NCHECK_WANG_LANDAU: usize = 1000;  // number of sweeps between two checks of the flatness
+
+const NBIN_VALIDATE: usize = 20;  // number of bins for error estimates in validation mode
+const MAX_DEVIATION: f64 = 4.;  // largest accepted deviation from exact results in validation mode, in units of the error
+
+const WIDTH_REFINE: f64 = 0.1;  // half width of the window around Tc that --refine-tc adds temperatures in
 
 /// Return a vector of temperatures to run the simulation with.
 fn list_temperatures() -> Vec<f64> {
@@ -30,257 +146,2231 @@ fn list_temperatures() -> Vec<f64> {
     for i in 0..12 {
         temperatures.push((i as f64 + 1.)*0.5);
     }
-    return temperatures
+    temperatures
 }
 
 // End of run parameters.
 //------------------------
 
-/// Helper struct to handle a random number generator.
-struct Rng {
-    rng: StdRng,
+/// Return the run parameters set above, they can be overwritten with --config.
+fn default_params() -> RunParams {
+    RunParams{nx: NX, ny: NY, ntherm_init: NTHERM_INIT, ntherm: NTHERM, nprod: NPROD,
+              temperatures: list_temperatures(), seed: SEED, rng: RngKind::default(), updates: None,
+              observables: Vec::new(), datadir: PathBuf::from("./data"), constants: default_constants()}
+}
+
+/// Return the constants of the modes set above, they can be overwritten with --config and --<name> arguments.
+fn default_constants() -> Constants {
+    Constants{ntau_skt: NTAU_SKT, t_quench: T_QUENCH, nsweep_aging: NSWEEP_AGING, nrepeat_aging: NREPEAT_AGING,
+              h_response: H_RESPONSE, waiting_times: list_waiting_times(), nbin_histogram: NBIN_HISTOGRAM,
+              nreweight: NREWEIGHT, t_latent: T_LATENT, latent_lattices: list_latent_lattices(),
+              npilot_thin: NPILOT_THIN, energy_tolerance: ENERGY_TOLERANCE, nblock_auto_therm: NBLOCK_AUTO_THERM,
+              nmax_auto_therm: NMAX_AUTO_THERM, t_benchmark: T_BENCHMARK, nsweep_benchmark: NSWEEP_BENCHMARK,
+              nskip_classifier: NSKIP_CLASSIFIER, frame_delay_movie: FRAME_DELAY_MOVIE,
+              max_angle_heisenberg: MAX_ANGLE_HEISENBERG, acceptance_heisenberg: ACCEPTANCE_HEISENBERG,
+              nsample_export: NSAMPLE_EXPORT, nskip_export: NSKIP_EXPORT, npilot_export: NPILOT_EXPORT,
+              shard_size_export: SHARD_SIZE_EXPORT,
+              nrepeat_nucleation: NREPEAT_NUCLEATION, nmax_nucleation: NMAX_NUCLEATION,
+              nucleation_temperatures: list_nucleation_temperatures(), nucleation_fields: list_nucleation_fields(),
+              nsample_cftp: NSAMPLE_CFTP, nmax_cftp: NMAX_CFTP, t_multicanonical: T_MULTICANONICAL,
+              niter_multicanonical: NITER_MULTICANONICAL, nsweep_multicanonical: NSWEEP_MULTICANONICAL,
+              ln_f_wang_landau: LN_F_WANG_LANDAU, flatness_wang_landau: FLATNESS_WANG_LANDAU,
+              ncheck_wang_landau: NCHECK_WANG_LANDAU, nbin_validate: NBIN_VALIDATE, max_deviation: MAX_DEVIATION,
+              width_refine: WIDTH_REFINE}
 }
 
-impl Rng {
-    /// Create an instance of Rng from a given seed.
-    fn from_seed(seed: [u8; 32]) -> Rng {
-        Rng{rng: StdRng::from_seed(seed)}
+/// Random stream of the bootstrap analysis, see derive_seed.
+const STREAM_BOOTSTRAP: u64 = 1;
+/// Random stream of the first disorder realisation, the others use the following streams.
+const STREAM_REALISATION: u64 = 1 << 32;
+/// Random stream of the first lattice size of finite-size scaling, the others use the following streams.
+const STREAM_SIZE: u64 = 2 << 32;
+
+/// Keep only every stride-th measurement, starting with measurement stride-1.
+fn thin_observables(obs: &mut Observables, stride: usize) {
+    let thin = |data: &mut Vec<f64>| {
+        *data = data.iter().skip(stride-1).step_by(stride).copied().collect();
+    };
+    thin(&mut obs.energy);
+    thin(&mut obs.magnetisation);
+    thin(&mut obs.staggered);
+    thin(&mut obs.structure);
+    for series in &mut obs.custom {
+        series.values = series.values.iter().skip(stride-1).step_by(stride).cloned().collect();
     }
+}
 
-    /// Generate a random index into a configuration.
-    fn gen_index(&mut self) -> usize {
-        use rand::Rng;
-        self.rng.gen_range(0, LATSIZE)
+/// Write observables of a temperature ramp to a data file.
+/**
+ * Same format as write_observables but with an additional first row
+ * listing the temperature in each sweep.
+ */
+fn write_ramp_observables(fname: &Path, temperatures: &[f64], obs: &Observables) -> Result<(), Error> {
+    let mut obsfile = OutputFile::create(fname)?;
+    write_row(&mut obsfile, temperatures)?;
+    write_row(&mut obsfile, &obs.energy)?;
+    write_row(&mut obsfile, &obs.magnetisation)?;
+    Ok(())
+}
+
+/// Write the staggered magnetisation as a single row to a data file.
+fn write_staggered(fname: &Path, obs: &Observables) -> Result<(), Error> {
+    let mut staggeredfile = OutputFile::create(fname)?;
+    write_row(&mut staggeredfile, &obs.staggered)?;
+    Ok(())
+}
+
+/// Write the history of an additional observable to a data file with one row per measurement.
+fn write_series(fname: &Path, series: &Series) -> Result<(), Error> {
+    let mut seriesfile = OutputFile::create(fname)?;
+    for values in &series.values {
+        write_row(&mut seriesfile, values)?;
     }
+    Ok(())
+}
 
-    /// Generate a random spin, one of {-1, +1}.
-    fn gen_spin(&mut self) -> i32 {
-        use rand::Rng;
-        match self.rng.gen_range(0, 2) {
-            0 => -1,
-            _ => 1,  // 1 is the only other possibility
-        }
+/// Plot the energy per site after each measurement and the histogram of the magnetisation of temperature i to dir.
+fn plot_temperature(dir: &Path, i: usize, temp: f64, obs: &Observables, nsite: usize, nbin: usize) -> Result<(), Error> {
+    let measurements: Vec<f64> = (0..obs.energy.len()).map(|k| k as f64).collect();
+    let energy: Vec<f64> = obs.energy.iter().map(|e| e / nsite as f64).collect();
+    Plot::new(&format!("Energy at T = {}", temp), "measurement", "E/N").line(&measurements, &energy)
+        .write(&dir.join(format!("{}_energy.svg", i)))?;
+    let hist = firstorder::histogram(&obs.magnetisation, nbin);
+    Plot::new(&format!("Magnetisation at T = {}", temp), "m", "count").bars(&hist.centres, &hist.counts)
+        .write(&dir.join(format!("{}_magnetisation_histogram.svg", i)))
+}
+
+/// Plot the susceptibility and specific heat with their errors against the temperature to dir.
+fn plot_summaries(dir: &Path, temperatures: &[f64], summaries: &[Summary]) -> Result<(), Error> {
+    let columns = |quantity: fn(&Summary) -> (f64, f64)| -> (Vec<f64>, Vec<f64>) {
+        summaries.iter().map(quantity).unzip()
+    };
+    let (chi, chi_error) = columns(|summary| summary.susceptibility);
+    Plot::new("Magnetic susceptibility", "T", "χ").points(temperatures, &chi, &chi_error)
+        .write(&dir.join("susceptibility.svg"))?;
+    let (heat, heat_error) = columns(|summary| summary.specific_heat);
+    Plot::new("Specific heat", "T", "C").points(temperatures, &heat, &heat_error)
+        .write(&dir.join("specific_heat.svg"))
+}
+
+/// Write the field and drive phase of each sweep to a data file.
+/**
+ * First row is the field, second row the phase of the drive.
+ */
+fn write_drive(fname: &Path, field: &dyn Schedule, nsweep: usize) -> Result<(), Error> {
+    let mut drivefile = OutputFile::create(fname)?;
+    let values: Vec<f64> = (0..nsweep).map(|sweep| field.value(sweep, nsweep)).collect();
+    write_row(&mut drivefile, &values)?;
+    let phases: Vec<f64> = (0..nsweep).map(|sweep| field.phase(sweep, nsweep)).collect();
+    write_row(&mut drivefile, &phases)?;
+    Ok(())
+}
+
+/// Write classifier outputs to a data file.
+/**
+ * Row j holds output j of the classifier for all snapshots.
+ */
+fn write_predictions(fname: &Path, predictions: &[Vec<f64>]) -> Result<(), Error> {
+    let mut predfile = OutputFile::create(fname)?;
+    let noutput = predictions.first().map_or(0, |output| output.len());
+    for j in 0..noutput {
+        let row: Vec<f64> = predictions.iter().map(|output| output[j]).collect();
+        write_row(&mut predfile, &row)?;
     }
+    Ok(())
+}
 
-    /// Generate a random double in [0, 1].
-    fn gen_real(&mut self) -> f64 {
-        use rand::Rng;
-        self.rng.gen_range(0., 1.)
+/// Write the list of Fourier modes of an nx*ny lattice as lines `<index>: <kx> <ky>`.
+fn write_modes(fname: &Path, modes: &[(usize, usize)], nx: usize, ny: usize) -> Result<(), Error> {
+    let mut modefile = OutputFile::create(fname)?;
+    for (i, (n, m)) in modes.iter().enumerate() {
+        writeln!(modefile, "{}: {} {}", i,
+                 2.*std::f64::consts::PI*(*n as f64)/nx as f64,
+                 2.*std::f64::consts::PI*(*m as f64)/ny as f64)?;
     }
+    Ok(())
 }
 
-/// Hold a spin configuration on the lattice.
-struct Configuration {
-    /// The actual configuration, +1 for spin up, -1 for spin down.
-    cfg: [i32; LATSIZE],
+/// Run the simulation on a periodic lattice with coupling j and compare against the exact solution.
+/**
+ * Simulates a chain of params.nx sites or, if square, the params.nx*params.ny square lattice.
+ * Compares the energy and specific heat per site at all temperatures of params, and the absolute
+ * magnetisation if the lattice is small enough for an exact::Enumeration,
+ * and returns true if all deviations are below max_deviation standard deviations of the constants of params.
+ */
+fn validate(params: &RunParams, algorithm: Algorithm, j: f64, square: bool) -> bool {
+    let temperatures = &params.temperatures;
+    let (nx, ny) = (params.nx, if square { params.ny } else { 1 });
+    let mut rng = Rng::new(params.rng, params.seed);
+    let mut cfg = Configuration::random(nx, ny, &mut rng);
+    let mut energy = hamiltonian(&cfg, j, 0.);
+    algorithm.evolve(&mut cfg, &mut energy, 1./temperatures[0], j, 0., &mut rng, params.ntherm_init, None);
+    let enumeration = (nx*ny <= exact::MAX_ENUMERATION_SITES)
+        .then(|| exact::Enumeration::new(&cfg, j, 0.).averages(temperatures));
+
+    if square {
+        info!("Validating {} against exact solution of a periodic {}x{} square lattice and J = {}",
+              algorithm.name(), nx, ny, j);
+    }
+    else {
+        info!("Validating {} against exact solution of a periodic chain with {} sites and J = {}",
+              algorithm.name(), nx, j);
+    }
+    let mut header = format!("{:>6} {:>24} {:>10} {:>24} {:>10}", "T", "E", "E exact", "C", "C exact");
+    if enumeration.is_some() {
+        header.push_str(&format!(" {:>24} {:>10}", "|m|", "|m| exact"));
+    }
+    info!("{}", header);
+
+    let mut passed = true;
+    for (i, temp) in temperatures.iter().enumerate() {
+        let beta = 1./temp;
+        algorithm.evolve(&mut cfg, &mut energy, beta, j, 0., &mut rng, params.ntherm, None);
+        let mut obs = Observables::new();
+        algorithm.evolve(&mut cfg, &mut energy, beta, j, 0., &mut rng, params.nprod, Some(&mut obs));
 
-    /// List nearest neighbour indices for each site.
+        let per_site: Vec<f64> = obs.energy.iter().map(|e| e / (nx*ny) as f64).collect();
+        let (e, e_err) = binned_mean(&per_site, params.constants.nbin_validate);
+        let (c, c_err) = binned_specific_heat(&obs.energy, beta, nx*ny, params.constants.nbin_validate);
+        let (e_exact, c_exact) = if square {
+            (exact::square_energy(nx, ny, j, *temp), exact::square_specific_heat(nx, ny, j, *temp))
+        }
+        else {
+            (exact::chain_energy(nx, j, *temp), exact::chain_specific_heat(nx, j, *temp))
+        };
+
+        let mut ok = (e-e_exact).abs() <= params.constants.max_deviation*e_err.max(f64::EPSILON)
+            && (c-c_exact).abs() <= params.constants.max_deviation*c_err.max(f64::EPSILON);
+        let mut line = format!("{:>6} {:>11.6} +- {:>8.6} {:>10.6} {:>11.6} +- {:>8.6} {:>10.6}",
+                               temp, e, e_err, e_exact, c, c_err, c_exact);
+        let mut fields = vec![("temperature", *temp), ("energy", e), ("energy_error", e_err), ("energy_exact", e_exact),
+                              ("specific_heat", c), ("specific_heat_error", c_err), ("specific_heat_exact", c_exact)];
+        if let Some(enumeration) = &enumeration {
+            let abs_magnetisation: Vec<f64> = obs.magnetisation.iter().map(|m| m.abs()).collect();
+            let (m, m_err) = binned_mean(&abs_magnetisation, params.constants.nbin_validate);
+            let m_exact = enumeration[i].abs_magnetisation;
+            ok &= (m-m_exact).abs() <= params.constants.max_deviation*m_err.max(f64::EPSILON);
+            line.push_str(&format!(" {:>11.6} +- {:>8.6} {:>10.6}", m, m_err, m_exact));
+            fields.extend([("abs_magnetisation", m), ("abs_magnetisation_error", m_err),
+                           ("abs_magnetisation_exact", m_exact)]);
+        }
+        fields.push(("passed", ok as i32 as f64));
+        logging::log(Level::Info, &format!("{}  {}", line, if ok { "ok" } else { "FAILED" }), &fields);
+        passed &= ok;
+    }
+
+    passed
+}
+
+/// Command line arguments.
+struct Args {
+    /// Lattice, sweeps, temperatures, seed, and output directory.
+    params: RunParams,
+    /// Configuration file the parameters were read from.
+    config: Option<PathBuf>,
+    /// Mode of the run, selected by the mode arguments, see Mode.
+    mode: Mode,
+    /// Validate on the nx*ny square lattice instead of a chain.
+    validate_square: bool,
+    /// Run a single temperature ramp instead of a temperature scan.
+    ramp: Option<Box<dyn Schedule>>,
+    /// Number of sweeps of the ramp.
+    nramp: usize,
+    /// Run simulated annealing through these inverse temperatures instead of a temperature scan.
+    anneal: Option<Vec<f64>>,
+    /// Number of sweeps per step of the annealing schedule.
+    nanneal: usize,
+    /// Coupling constant J.
+    coupling: f64,
+    /// Coupling constant in y direction if it differs from coupling, which then applies in x direction.
+    coupling_y: Option<f64>,
+    /// Coupling constant of diagonal next-nearest neighbours.
+    coupling_nnn: Option<f64>,
+    /// External field during production.
+    field: Box<dyn Schedule + Sync>,
+    /// Measure the spin-spin correlation function every this many production sweeps.
+    correlation: Option<usize>,
+    /// Measure the static structure factor every this many production sweeps.
+    structure_factor: Option<usize>,
+    /// Measure the distribution of geometric cluster sizes every this many production sweeps.
+    clusters: Option<usize>,
+    /// Measure the dynamic structure factor during production.
+    skt: bool,
+    /// Also measure the response function in aging mode.
+    response: bool,
+    /// Fields acting on the edges of the lattice.
+    surface_fields: Vec<SurfaceField>,
+    /// Deterministic field profiles h(x, y).
+    profiles: Vec<Profile>,
+    /// Spatially correlated random field.
+    disorder: Option<CorrelatedDisorder>,
+    /// Distribution of random bonds of temperature scans and ramps.
+    random_bonds: Option<RandomBonds>,
+    /// Number of disorder realisations to average over in temperature scans.
+    realisations: usize,
+    /// Lattice sizes L of finite-size scaling, runs a temperature scan on each L*L lattice if not empty.
+    sizes: Vec<usize>,
+    /// Fraction of vacant sites in temperature scans and ramps.
+    dilution: Option<f64>,
+    /// Measure every this many attempted flips during production instead of once per sweep.
+    measure_flips: Option<usize>,
+    /// Measure every this many sweeps during production.
+    measure_every: Option<usize>,
+    /// Measure this many times per sweep during production, counting attempted flips like measure_flips.
+    measures_per_sweep: Option<usize>,
+    /// Choose the measurement stride from τ_int during production.
+    auto_thin: bool,
+    /// Recompute the energy every this many production sweeps of temperature scans to check the running energy.
+    check_energy: Option<usize>,
+    /// Thermalise each temperature until MSER judges the energy equilibrated instead of for a fixed number of sweeps.
+    auto_therm: bool,
+    /// Write a PNG image of the configuration every this many production sweeps of temperature scans.
+    snapshots: Option<usize>,
+    /// Record the initial thermalisation of temperature scans as a movie to this file.
+    movie: Option<PathBuf>,
+    /// Draw the lattice and observables in the terminal every this many production sweeps of temperature scans.
+    watch: Option<usize>,
+    /// Plot the observables and summaries of temperature scans as SVG files.
+    plots: bool,
+    /// Bin size of jackknife errors in the summary, derived from τ_int if None.
+    binsize: Option<usize>,
+    /// Number of bootstrap samples for errors in the summary.
+    nbootstrap: usize,
+    /// Number of independent Markov chains in temperature scans.
+    chains: usize,
+    /// File format of the observables of each temperature.
+    format: Format,
+    /// Initial configuration of temperature scans and ramps.
+    start: Start,
+    /// Lattice of temperature scans and ramps.
+    lattice: Geometry,
+    /// Boundary conditions of temperature scans and ramps.
+    boundaries: Boundaries,
+    /// Seed given on the command line, overrides the seed of params.
+    seed: Option<u64>,
+    /// Random number generator given on the command line, overrides the one of params.
+    rng: Option<RngKind>,
+    /// Update schedule of the Heisenberg model given on the command line, overrides the one of params.
+    updates: Option<String>,
+    /// Names of additional observables given on the command line, override the observables of params.
+    observables: Option<Vec<String>>,
+    /// Number of lattice sites in x direction given on the command line, overrides params.
+    nx: Option<usize>,
+    /// Number of lattice sites in y direction given on the command line, overrides params.
+    ny: Option<usize>,
+    /// Number of initial thermalisation sweeps given on the command line, overrides params.
+    ntherm_init: Option<usize>,
+    /// Number of thermalisation sweeps per temperature given on the command line, overrides params.
+    ntherm: Option<usize>,
+    /// Number of production sweeps per temperature given on the command line, overrides params.
+    nprod: Option<usize>,
+    /// Temperatures given on the command line, override params.
+    temperatures: Option<Vec<f64>>,
+    /// File to read the temperatures from, overrides params.
+    temperatures_file: Option<PathBuf>,
+    /// Constants given on the command line as (name, value), override params.
+    constants: Vec<(String, String)>,
+    /// Number of temperatures to add around the exact Tc of the square lattice.
+    refine_tc: Option<usize>,
+    /// Largest number of threads of checkerboard sweeps, all available cores if None.
+    threads: Option<usize>,
+    /// Addresses of all processes of a distributed run, one per rank, empty to run in a single process.
+    peers: Vec<String>,
+    /// Index of this process in peers.
+    rank: usize,
+    /// Do not report the progress of production sweeps.
+    quiet: bool,
+    /// Delete datadir if it is not empty.
+    force: bool,
+    /// Most verbose level of log messages.
+    log_level: Level,
+    /// Format of log messages.
+    log_format: LogFormat,
+    /// Write the observables of temperature scans to disk every this many measurements.
+    stream: Option<usize>,
+    /// Write a checkpoint every this many production sweeps of temperature scans.
+    checkpoint: Option<usize>,
+    /// Checkpoint to resume a temperature scan from.
+    resume: Option<PathBuf>,
+    /// Write histograms and diagnostics for first-order transitions.
+    first_order: bool,
+    /// Reweight the temperature scan to nreweight temperatures of the constants with this method.
+    reweight: Option<Reweighting>,
+    /// Update algorithm for temperature scans and validation.
+    algorithm: Algorithm,
+    /// Updates of one sweep of temperature scans of the Ising model, algorithm unless params has updates.
+    schedule: benchmark::UpdateSchedule,
+    /// Order in which the single flip algorithms visit the sites in temperature scans.
+    sweep_order: SweepOrder,
+    /// Algorithms to compare in benchmark mode.
+    benchmark: Option<Vec<Algorithm>>,
+    /// Command of an external classifier to feed snapshots to during production.
+    classifier: Option<String>,
+    /// Number of states of the Potts model to simulate in temperature scans instead of the Ising model.
+    potts: Option<usize>,
+    /// Simulate the Heisenberg model with these updates in a temperature scan.
     /**
-     * Neighbours for site i are stored at (4*i+0)...(4*i+3) in the order
-     * x+1, x-1, y+1, y-1.
+     * A single sweep of the update given with --heisenberg or the updates of params.
      */
-    neighbours: [usize; 4*LATSIZE],
+    heisenberg: Option<heisenberg::UpdateSchedule>,
+    /// Additional observables to measure in temperature scans, selected by the observables of params.
+    measured: Vec<Arc<dyn Observable>>,
+}
+
+/// Mode of a run, selected by at most one mode argument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Scan,
+    Validate,
+    Ramp,
+    Anneal,
+    Nucleation,
+    Interface,
+    Aging,
+    Export,
+    Covariance,
+    LatentHeat,
+    Cftp,
+    WangLandau,
+    Multicanonical,
+    Enumerate,
+    Benchmark,
+    Heisenberg,
+    Distributed,
 }
 
-impl Configuration {
-    /// Create a random configuration.
-    fn random(rng: &mut Rng) -> Configuration {
-        let mut cfg = Configuration{cfg: [0; LATSIZE],
-                                    neighbours: make_neighbour_list()};
+impl Mode {
+    const ALL: [Mode; 17] = [Mode::Scan, Mode::Validate, Mode::Ramp, Mode::Anneal, Mode::Nucleation, Mode::Interface,
+                             Mode::Aging, Mode::Export, Mode::Covariance, Mode::LatentHeat, Mode::Cftp,
+                             Mode::WangLandau, Mode::Multicanonical, Mode::Enumerate, Mode::Benchmark,
+                             Mode::Heisenberg, Mode::Distributed];
 
-        for site in &mut cfg.cfg.iter_mut() {
-            *site = rng.gen_spin();
+    /// Return the argument that selects the mode.
+    fn argument(&self) -> &'static str {
+        match self {
+            Mode::Scan => "temperature scans",
+            Mode::Validate => "--validate",
+            Mode::Ramp => "--ramp",
+            Mode::Anneal => "--anneal",
+            Mode::Nucleation => "--nucleation",
+            Mode::Interface => "--interface",
+            Mode::Aging => "--aging",
+            Mode::Export => "--export",
+            Mode::Covariance => "--covariance",
+            Mode::LatentHeat => "--latent-heat",
+            Mode::Cftp => "--cftp",
+            Mode::WangLandau => "--wang-landau",
+            Mode::Multicanonical => "--multicanonical",
+            Mode::Enumerate => "--enumerate",
+            Mode::Benchmark => "--benchmark",
+            Mode::Heisenberg => "--heisenberg",
+            Mode::Distributed => "--peers",
         }
+    }
 
-        cfg
+    /// Return true if the mode uses feature.
+    /**
+     * Together with Feature::conflicts, this decides which arguments can be given together, check_mode
+     * rejects all arguments that the selected mode would ignore. parse_args, check_schedule, and check_extents
+     * only add checks that depend on the values of arguments, e.g. of the couplings, algorithm, or extents.
+     */
+    fn supports(&self, feature: Feature) -> bool {
+        use Feature::*;
+        const LATTICE: [Feature; 12] = [Lattice, Boundary, CouplingY, CouplingNnn, RandomBonds, Dilution, SurfaceField,
+                                        FieldProfile, CorrelatedField, Field, Coupling, Start];
+        match self {
+            Mode::Scan => feature != Response,
+            Mode::Validate | Mode::Distributed => matches!(feature, Algorithm | Coupling | RefineTc),
+            Mode::Ramp => LATTICE.contains(&feature) || feature == DrivenField,
+            Mode::Anneal => LATTICE.contains(&feature) || feature == Algorithm,
+            Mode::Multicanonical => LATTICE.contains(&feature),
+            Mode::Enumerate => LATTICE.contains(&feature) || feature == RefineTc,
+            Mode::WangLandau => matches!(feature, Lattice | Start | Coupling | RefineTc),
+            Mode::LatentHeat | Mode::Benchmark => matches!(feature, Field | Coupling),
+            Mode::Cftp => matches!(feature, Field | Coupling | RefineTc),
+            Mode::Heisenberg => matches!(feature, Field | Coupling | Updates),
+            Mode::Nucleation => feature == Coupling,
+            Mode::Interface => matches!(feature, Coupling | RefineTc),
+            Mode::Aging => matches!(feature, Coupling | Response),
+            Mode::Export | Mode::Covariance => feature == RefineTc,
+        }
+    }
+}
+
+/// Select mode in args, fails if args already select another mode.
+fn select_mode(args: &mut Args, mode: Mode) -> Result<(), String> {
+    if args.mode != Mode::Scan && args.mode != mode {
+        return Err(format!("Arguments {} and {} cannot be combined", args.mode.argument(), mode.argument()));
     }
+    args.mode = mode;
+    Ok(())
 }
 
-impl Index<usize> for Configuration {
-    type Output = i32;
+/// Arguments that only some modes use, see Mode::supports, and that may exclude each other, see conflicts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Feature {
+    Potts,
+    Lattice,
+    Boundary,
+    CouplingY,
+    CouplingNnn,
+    RandomBonds,
+    Dilution,
+    SurfaceField,
+    FieldProfile,
+    CorrelatedField,
+    Field,
+    DrivenField,
+    Coupling,
+    Start,
+    Algorithm,
+    Updates,
+    SweepOrder,
+    MeasureFlips,
+    MeasureEvery,
+    MeasureSubsweep,
+    AutoThin,
+    AutoTherm,
+    CheckEnergy,
+    Chains,
+    Realisations,
+    Sizes,
+    Stream,
+    Checkpoint,
+    Resume,
+    Snapshots,
+    Movie,
+    Watch,
+    Plots,
+    Observables,
+    Skt,
+    Classifier,
+    Correlation,
+    StructureFactor,
+    Clusters,
+    FirstOrder,
+    Reweight,
+    Response,
+    RefineTc,
+}
+
+impl Feature {
+    const ALL: [Feature; 43] = {
+        use Feature::*;
+        [Potts, Lattice, Boundary, CouplingY, CouplingNnn, RandomBonds, Dilution, SurfaceField, FieldProfile,
+         CorrelatedField, Field, DrivenField, Coupling, Start, Algorithm, Updates, SweepOrder, MeasureFlips,
+         MeasureEvery, MeasureSubsweep, AutoThin, AutoTherm, CheckEnergy, Chains, Realisations, Sizes, Stream,
+         Checkpoint, Resume, Snapshots, Movie, Watch, Plots, Observables, Skt, Classifier, Correlation,
+         StructureFactor, Clusters, FirstOrder, Reweight, Response, RefineTc]
+    };
+
+    /// Return the arguments that request the feature for error messages.
+    fn name(&self) -> &'static str {
+        match self {
+            Feature::Potts => "--potts",
+            Feature::Lattice => "--lattice",
+            Feature::Boundary => "--boundary",
+            Feature::CouplingY => "--coupling-y",
+            Feature::CouplingNnn => "--coupling-nnn",
+            Feature::RandomBonds => "--random-bonds",
+            Feature::Dilution => "--dilution",
+            Feature::SurfaceField => "--surface-field",
+            Feature::FieldProfile => "--field-profile",
+            Feature::CorrelatedField => "--correlated-field",
+            Feature::Field => "--field",
+            Feature::DrivenField => "a time-dependent --field",
+            Feature::Coupling => "--coupling",
+            Feature::Start => "--start",
+            Feature::Algorithm => "--algorithm",
+            Feature::Updates => "an update schedule",
+            Feature::SweepOrder => "--sweep-order",
+            Feature::MeasureFlips => "--measure-flips",
+            Feature::MeasureEvery => "--measure-every",
+            Feature::MeasureSubsweep => "--measure-every 1/<m>",
+            Feature::AutoThin => "--auto-thin",
+            Feature::AutoTherm => "--auto-therm",
+            Feature::CheckEnergy => "--check-energy",
+            Feature::Chains => "--chains",
+            Feature::Realisations => "--realisations",
+            Feature::Sizes => "--sizes",
+            Feature::Stream => "--stream",
+            Feature::Checkpoint => "--checkpoint",
+            Feature::Resume => "--resume",
+            Feature::Snapshots => "--snapshots",
+            Feature::Movie => "--movie",
+            Feature::Watch => "--watch",
+            Feature::Plots => "--plots",
+            Feature::Observables => "additional observables",
+            Feature::Skt => "--skt",
+            Feature::Classifier => "--classifier",
+            Feature::Correlation => "--correlation",
+            Feature::StructureFactor => "--structure-factor",
+            Feature::Clusters => "--clusters",
+            Feature::FirstOrder => "--first-order",
+            Feature::Reweight => "--reweight",
+            Feature::Response => "--response",
+            Feature::RefineTc => "--refine-tc",
+        }
+    }
+
+    /// Return the name as the subject of an error message, e.g. "Argument --potts".
+    fn describe(&self) -> String {
+        let name = self.name();
+        if name.starts_with("--") {
+            return format!("Argument {}", name);
+        }
+        name[..1].to_uppercase() + &name[1..]
+    }
+
+    /// Return the features that cannot be combined with this one.
+    /**
+     * The relation is symmetric, a pair of features only needs to be listed for one of them.
+     */
+    fn conflicts(&self) -> &'static [Feature] {
+        use Feature::*;
+        match self {
+            Potts => &[Algorithm, Start, Field, DrivenField, SurfaceField, FieldProfile, CorrelatedField, Dilution,
+                       Chains, MeasureFlips, MeasureSubsweep, Skt, Classifier, Correlation, StructureFactor, Clusters,
+                       Snapshots, Movie, Watch, Updates, Observables, RefineTc],
+            Chains => &[MeasureFlips, MeasureSubsweep, MeasureEvery, AutoThin, AutoTherm, Skt, Classifier,
+                        Correlation, StructureFactor, Clusters, Observables],
+            Checkpoint | Resume => &[Chains, MeasureFlips, MeasureSubsweep, AutoThin, Skt, Classifier, FirstOrder,
+                                     Correlation, StructureFactor, Clusters, RandomBonds, Realisations, Sizes,
+                                     Observables],
+            Stream => &[Chains, AutoThin, FirstOrder, Checkpoint, Resume, Realisations, Sizes, Reweight, Plots,
+                        Observables],
+            AutoThin => &[MeasureFlips, MeasureSubsweep, MeasureEvery],
+            MeasureEvery | MeasureSubsweep => &[MeasureFlips],
+            Algorithm | Updates | SweepOrder => &[MeasureFlips, MeasureSubsweep],
+            Realisations => &[Sizes],
+            Reweight => &[DrivenField, Resume],
+            Movie => &[AutoTherm, Resume],
+            RefineTc => &[Lattice, CouplingNnn],
+            _ => &[],
+        }
+    }
+
+    /// Return true if args request the feature.
+    fn given(&self, args: &Args) -> bool {
+        match self {
+            Feature::Potts => args.potts.is_some(),
+            Feature::Lattice => args.lattice != Geometry::Square,
+            Feature::Boundary => !args.boundaries.is_periodic(),
+            Feature::CouplingY => args.coupling_y.is_some(),
+            Feature::CouplingNnn => args.coupling_nnn.is_some(),
+            Feature::RandomBonds => args.random_bonds.is_some(),
+            Feature::Dilution => args.dilution.is_some(),
+            Feature::SurfaceField => !args.surface_fields.is_empty(),
+            Feature::FieldProfile => !args.profiles.is_empty(),
+            Feature::CorrelatedField => args.disorder.is_some(),
+            Feature::Field => args.field.start() != 0.,
+            Feature::DrivenField => !args.field.is_constant(),
+            Feature::Coupling => args.coupling != J,
+            Feature::Start => args.start != Start::Hot,
+            Feature::Algorithm => args.algorithm != Algorithm::Metropolis,
+            Feature::Updates => args.params.updates.is_some(),
+            Feature::SweepOrder => args.sweep_order != SweepOrder::Random,
+            Feature::MeasureFlips => args.measure_flips.is_some(),
+            Feature::MeasureEvery => args.measure_every.is_some(),
+            Feature::MeasureSubsweep => args.measures_per_sweep.is_some(),
+            Feature::AutoThin => args.auto_thin,
+            Feature::AutoTherm => args.auto_therm,
+            Feature::CheckEnergy => args.check_energy.is_some(),
+            Feature::Chains => args.chains > 1,
+            Feature::Realisations => args.realisations > 1,
+            Feature::Sizes => !args.sizes.is_empty(),
+            Feature::Stream => args.stream.is_some(),
+            Feature::Checkpoint => args.checkpoint.is_some(),
+            Feature::Resume => args.resume.is_some(),
+            Feature::Snapshots => args.snapshots.is_some(),
+            Feature::Movie => args.movie.is_some(),
+            Feature::Watch => args.watch.is_some(),
+            Feature::Plots => args.plots,
+            Feature::Observables => !args.params.observables.is_empty(),
+            Feature::Skt => args.skt,
+            Feature::Classifier => args.classifier.is_some(),
+            Feature::Correlation => args.correlation.is_some(),
+            Feature::StructureFactor => args.structure_factor.is_some(),
+            Feature::Clusters => args.clusters.is_some(),
+            Feature::FirstOrder => args.first_order,
+            Feature::Reweight => args.reweight.is_some(),
+            Feature::Response => args.response,
+            Feature::RefineTc => args.refine_tc.is_some(),
+        }
+    }
+}
 
-    /// Read spin at site idx.
-    fn index(&self, idx: usize) -> &i32 {
-        return &self.cfg[idx];
+/// Check that the mode selected by args uses all features that args request and that none of them conflict.
+fn check_mode(args: &Args) -> Result<(), String> {
+    for feature in Feature::ALL {
+        if feature.given(args) && !args.mode.supports(feature) {
+            let modes: Vec<&str> = Mode::ALL.iter().filter(|mode| mode.supports(feature))
+                .map(|mode| mode.argument()).collect();
+            let modes = match modes.split_last() {
+                None => "no mode".to_string(),
+                Some((last, [])) => last.to_string(),
+                Some((last, rest)) => format!("{}, or {}", rest.join(", "), last),
+            };
+            return Err(format!("{} only applies to {}", feature.describe(), modes));
+        }
+    }
+    for (i, a) in Feature::ALL.iter().enumerate() {
+        for b in &Feature::ALL[i+1..] {
+            if (a.conflicts().contains(b) || b.conflicts().contains(a)) && a.given(args) && b.given(args) {
+                return Err(format!("{} cannot be combined with {}", a.describe(), b.name()));
+            }
+        }
     }
+    Ok(())
 }
 
-impl IndexMut<usize> for Configuration {
-    /// Modify spin at site idx.
-    fn index_mut(&mut self, idx: usize) -> &mut i32 {
-        return &mut self.cfg[idx];
+/// Print usage information.
+fn print_usage() {
+    println!("Usage: ising [options] [datadir]
+
+datadir defaults to ./data. An existing empty datadir is reused, a datadir with contents
+is only deleted with --force.
+
+Options:
+  --config <file>      Read run parameters (nx, ny, ntherm_init, ntherm, nprod, temperatures,
+                       seed, rng, updates, datadir, and the constants below) from a TOML file, see
+                       config.rs. A positional datadir and the arguments of constants take precedence
+                       over the file.
+  --start <start>      Initial configuration of temperature scans and ramps, one of
+                         hot (default): random spins
+                         cold-up, cold-down: all spins +1 or -1
+                         file:<path>: spins from a file, e.g. datadir/final.dat of a previous run
+  --lattice <name>     Lattice of temperature scans and ramps, square (default), triangular, or
+                       honeycomb, see lattice.rs.
+  --boundary <spec>    Boundary conditions of temperature scans and ramps, <spec> is <b> for both
+                       directions or <x>:<y> with <b> one of periodic (default), open, or antiperiodic.
+  --format <fmt>       Format of the observables of each temperature, dat (default), csv, json,
+                       or npy.
+  --nx <n>, --ny <n>   Number of lattice sites in x and y direction (default {} and {}), --ny 1 for a chain.
+  --ntherm-init <n>    Number of thermalisation sweeps in the beginning (default {}).
+  --ntherm <n>         Number of thermalisation sweeps per temperature (default {}).
+  --nprod <n>          Number of production sweeps per temperature (default {}).
+  --temps <spec>       Temperatures to run at, a comma separated list or <start>:<stop>:<n> for
+                       <n> evenly spaced temperatures from <start> to <stop>.
+  --temps-file <file>  Read the temperatures from a file with numbers separated by whitespace,
+                       commas, or newlines and # comments.
+                       The run parameters above take precedence over the config file.
+  --refine-tc <n>      Add <n> evenly spaced temperatures within +-{} of the exact Tc of the
+                       square lattice with the couplings of --coupling and --coupling-y.
+  --threads <n>        Largest number of threads of checkerboard sweeps (default all cores).
+  --peers <list>       Distribute checkerboard sweeps of one lattice over processes, <list> is a comma
+                       separated host:port per rank that the rank listens on.
+  --rank <r>           Index of this process in --peers (default 0), only rank 0 writes output.
+  --seed <s>           Seed of the random number generator, a non-negative integer or 'random'
+                       to draw one from the OS, takes precedence over the config file.
+  --rng <name>         Random number generator, xoshiro (default) for xoshiro256++ or chacha for the
+                       slower ChaCha12 of earlier versions, takes precedence over the config file.
+  --quiet              Do not report the progress of the production sweeps of temperature scans,
+                       which is redrawn on stderr with sweeps/s, acceptance rate, and ETA otherwise.
+  --force              Delete datadir and all its contents if it is not empty, runs stop with
+                       an error otherwise.
+  --log-level <level>  Most verbose log messages to print, error, warn, info (default), or debug.
+  --log-format <fmt>   Print log messages as text (default) or as one JSON object per line with
+                       numeric fields, e.g. acceptance rates and durations of each temperature.
+  --validate           Compare against the exact solution of an nx*1 chain.
+  --validate-square    Compare against the exact solution of the periodic nx*ny square lattice.
+  --ramp <spec>        Run a single temperature ramp, <spec> is one of
+                         linear:<start>:<stop>
+                         exp:<start>:<stop>
+                         power:<start>:<stop>:<exponent>
+                         piecewise:<sweep>=<T>,<sweep>=<T>,...
+                         adaptive:<start>:<stop>:<target acceptance rate>
+  --ramp-sweeps <n>    Number of sweeps of the ramp (default {}).
+  --anneal <spec>      Run simulated annealing in a single chain through the inverse temperatures
+                       of <spec> and keep the lowest energy configuration, <spec> is one of
+                         linear:<start>:<stop>:<nstep>
+                         geometric:<start>:<stop>:<nstep>
+                         list:<beta>,<beta>,...
+  --anneal-sweeps <n>  Number of sweeps per annealing step (default {}).
+  --nucleation         Measure lifetimes of metastable states, see list_nucleation_*.
+  --interface          Pin the bottom/top rows to +1/-1 and measure the interface width.
+  --aging              Measure C(t, t_w) after quenches to T = {}.
+  --response           In aging mode, also measure the integrated response.
+  --export             Export labelled configurations as npz shards.
+  --covariance         Export the site-site covariance matrix for PCA.
+  --correlation <k>    Measure the spin-spin correlation function along x and y every <k>
+                       production sweeps.
+  --structure-factor <k>
+                       Measure the static structure factor S(k) for all wave vectors every <k>
+                       production sweeps.
+  --clusters <k>       Measure the distribution of geometric cluster sizes and the largest cluster
+                       every <k> production sweeps.
+  --skt                Measure the dynamic structure factor S(k, t) during production.
+  --classifier <cmd>   Send a snapshot every {} production sweeps to the
+                       shell command <cmd> and record its output, see classifier.rs.
+  --coupling <J>       Coupling constant (default {}), negative for an antiferromagnet.
+  --coupling-y <Jy>    Coupling constant in y direction of temperature scans and ramps, --coupling
+                       then only applies in x direction. Requires the metropolis or heatbath
+                       algorithm, prints the exact critical temperature of the square lattice.
+  --coupling-nnn <J2>  Add diagonal next-nearest neighbours with coupling <J2> to the square lattice
+                       of temperature scans and ramps (J1-J2 model), stripes form for J2 < -|J|/2.
+                       Requires the metropolis or heatbath algorithm.
+  --field <spec>       External field during production (default {}), <spec> is one of
+                         <h> or const:<h>
+                         linear:<start>:<stop>
+                         power:<start>:<stop>:<exponent>
+                         piecewise:<sweep>=<h>,<sweep>=<h>,...
+                         sin:<amplitude>:<period>[:<offset>]
+  --surface-field <spec>
+                       Comma separated fields <edge>=<h> on the edges bottom, top, left, right
+                       during temperature scans and ramps.
+  --field-profile <spec>
+                       Add a field profile during temperature scans and ramps, can be repeated,
+                       <spec> is one of
+                         gradient:<x|y>:<start>:<stop>
+                         step:<x|y>:<position>:<below>:<above>
+                         gauss:<h>:<x0>:<y0>:<width>
+  --correlated-field <strength>:<a>
+                       Random field with standard deviation <strength> and correlations
+                       decaying like r^-<a> during temperature scans and ramps.
+  --random-bonds <dist>
+                       Multiply the coupling of each bond by a random number from <dist>, binary
+                       (+-1) or gaussian, during temperature scans and ramps (Edwards-Anderson
+                       spin glass). Requires the metropolis or heatbath algorithm.
+  --realisations <n>   Repeat temperature scans for <n> realisations of the random bonds or
+                       correlated field in datadir/realisation_<r> and write their average to
+                       datadir/disorder_average.dat.
+  --sizes <list>       Run the temperature scan on L*L lattices (L*1 chains if ny = 1) for a comma
+                       separated list of sizes L in datadir/size_<L> and write finite-size scaling
+                       summaries to datadir/fss.dat and datadir/binder_crossings.dat.
+  --dilution <p>       Remove a fraction <p> of the sites at random during temperature scans and
+                       ramps, observables are per occupied site. The vacancies are realisations
+                       of the disorder for --realisations.
+  --measure-flips <k>  Measure every <k> attempted flips during production instead of every sweep.
+  --measure-every <k>  Measure every <k> production sweeps of temperature scans instead of every sweep,
+                       or <m> times per sweep with 1/<m>, which measures like --measure-flips every
+                       nx*ny/<m> attempted flips, rounded up.
+  --auto-thin          Measure every ~tau_int/2 sweeps, tau_int is estimated from the
+                       first {} production sweeps.
+  --auto-therm         Thermalise each temperature in blocks of {} sweeps until the MSER
+                       criterion finds the energy equilibrated, at most {} sweeps,
+                       instead of the --ntherm-init and --ntherm sweeps, see datadir/thermalisation.dat.
+  --check-energy <k>   Recompute the energy from scratch every <k> production sweeps of temperature scans,
+                       replace the running energy by it, and stop with an error if they differ by more
+                       than {:e} per site.
+  --snapshots <k>      Write an image of the spins every <k> production sweeps of temperature scans
+                       to datadir/snapshots/T<temperature>_sweep<sweep>.png, up spins white, down black.
+  --movie <file>       Record the quench from the start configuration to the first temperature during the
+                       --ntherm-init initial thermalisation sweeps with a frame after every sweep, as an
+                       animated GIF if <file> ends in .gif or as a stream of PPM frames for ffmpeg
+                       if it ends in .ppm, <file> may be a named pipe.
+  --watch <k>          Draw the lattice and sparklines of the energy and magnetisation in the terminal
+                       every <k> production sweeps of temperature scans instead of the progress report.
+  --plots              Plot the energy and a histogram of the magnetisation of each temperature and
+                       the susceptibility and specific heat of temperature scans to datadir/plots/*.svg.
+  --binsize <n>        Bin size of jackknife and bootstrap errors in summary.dat
+                       (default derived from tau_int).
+  --bootstrap <n>      Number of bootstrap samples for errors in summary.dat (default {}).
+  --first-order        Write energy and magnetisation histograms and look for signs
+                       of a first-order transition.
+  --reweight <method>  Write the energy histogram of each temperature and reweight the scan to
+                       {} temperatures, <method> is single or multi (Ferrenberg-Swendsen).
+  --latent-heat        Locate a first-order transition from energy histograms at T = {}
+                       on list_latent_lattices and extrapolate the latent heat.
+  --cftp               Draw {} exact samples per temperature with coupling from the past.
+  --wang-landau        Estimate the density of states g(E) with Wang-Landau sampling on the lattice
+                       of --lattice and --start and evaluate the thermodynamics at all temperatures.
+  --multicanonical     Sample the magnetisation distribution at T = {} with iterated
+                       multicanonical weights and measure the interface tension from its double peak.
+  --enumerate          Compute exact averages at all temperatures by enumerating all states of the
+                       lattice of --lattice, --start, fields, and disorder, at most 25 occupied sites.
+  --algorithm <alg>    Update algorithm for temperature scans and --validate,
+                       metropolis (default), wolff, swendsen-wang, checkerboard, heatbath, or
+                       kawasaki (spin exchanges with conserved magnetisation).
+  --sweep-order <order>
+                       Order in which metropolis and heatbath visit the sites in temperature scans,
+                       random (default, independent uniform sites), sequential (every site once,
+                       row by row), or shuffled (every site once in a new random permutation per sweep).
+  --benchmark <algs>   Compare comma separated update algorithms at T = {},
+                       available: metropolis, wolff, swendsen-wang, checkerboard, heatbath, kawasaki.
+  --potts <q>          Simulate the q-state Potts model H = -J sum delta(s_i, s_j) in temperature
+                       scans, requires the metropolis algorithm and a hot start without fields.
+  --heisenberg <update>
+                       Simulate the classical Heisenberg model in a temperature scan with the
+                       coupling and the initial --field along z, <update> is metropolis (random
+                       rotations by up to {} rad initially, tuned to an acceptance rate of {}
+                       during thermalisation) or heat-bath.
+  --updates <schedule> Updates of one sweep of --heisenberg instead of a single <update>, e.g.
+                       '1 heat-bath + 5 overrelaxation' (microcanonical reflections about the
+                       local field), or of temperature scans of the Ising model instead of a single
+                       --algorithm, e.g. 'metropolis: 1, wolff: 2'. Takes precedence over 'updates'
+                       in the config file.
+  --observables <names>
+                       Comma separated additional observables to measure in temperature scans of the
+                       Ising model, written to <idx>_<name>.dat with one row per measurement,
+                       available: abs_magnetisation, stripe_magnetisation, bond_correlation,
+                       domain_wall_length, largest_cluster, row_magnetisation. Takes precedence over 'observables' in the config file.
+  --chains <n>         Run <n> independent Markov chains in parallel threads during temperature
+                       scans and merge their measurements, see <idx>_chains.dat.
+  --stream <k>         Write the observables of temperature scans to disk every <k> measurements
+                       instead of keeping them in memory, requires --format csv or npy and
+                       skips summary.dat, autocorr.dat, and <idx>_staggered.dat.
+  --checkpoint <k>     Write datadir/checkpoint.bin every <k> production sweeps and after every
+                       temperature of temperature scans.
+  --resume <file>      Continue a temperature scan from a checkpoint, all other arguments
+                       must be the same as in the original run.
+  --help               Show this message.", NX, NY, NTHERM_INIT, NTHERM, NPROD, WIDTH_REFINE, NRAMP, NANNEAL, T_QUENCH,
+             NSKIP_CLASSIFIER, J, H, NPILOT_THIN, NBLOCK_AUTO_THERM, NMAX_AUTO_THERM, ENERGY_TOLERANCE, NBOOTSTRAP,
+             NREWEIGHT, T_LATENT, NSAMPLE_CFTP, T_MULTICANONICAL, T_BENCHMARK, MAX_ANGLE_HEISENBERG,
+             ACCEPTANCE_HEISENBERG);
+
+    println!("
+Constants of the modes, also keys of the config file with _ in place of -. Lists are comma
+separated, lattice sizes in --latent-lattices are given as <nx>x<ny>.");
+    for (name, value) in default_constants().values() {
+        println!("  --{} <value>\n                       {} (default {})",
+                 name.replace('_', "-"), Constants::describe(name).unwrap(), value);
     }
 }
 
-/// Store Monte-Carlo history of observables.
-struct Observables {
-    energy: Vec<f64>,
-    magnetisation: Vec<f64>,
+/// Parse command line arguments.
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args{params: default_params(), config: None, mode: Mode::Scan, validate_square: false,
+                        ramp: None, nramp: NRAMP, anneal: None, nanneal: NANNEAL, coupling: J, coupling_y: None, coupling_nnn: None, field: Box::new(schedule::Constant(H)),
+                        correlation: None, structure_factor: None, clusters: None,
+                        skt: false,
+                        response: false, surface_fields: Vec::new(), profiles: Vec::new(), disorder: None, random_bonds: None, realisations: 1, sizes: Vec::new(),
+                        dilution: None,
+                        measure_flips: None, measure_every: None, measures_per_sweep: None,
+                        auto_thin: false, auto_therm: false, check_energy: None, snapshots: None, movie: None, watch: None, plots: false, binsize: None, nbootstrap: NBOOTSTRAP, chains: 1,
+                        format: Format::Dat, stream: None, start: Start::Hot, lattice: Geometry::Square, boundaries: Boundaries::PERIODIC, seed: None, rng: None, updates: None,
+                        nx: None, ny: None, ntherm_init: None, ntherm: None, nprod: None, temperatures: None,
+                        temperatures_file: None, constants: Vec::new(), refine_tc: None, threads: None, peers: Vec::new(), rank: 0,
+                        quiet: false, force: false, log_level: Level::Info,
+                        log_format: LogFormat::Text, checkpoint: None, resume: None,
+                        first_order: false, reweight: None,
+                        algorithm: Algorithm::Metropolis, schedule: benchmark::UpdateSchedule::single(Algorithm::Metropolis),
+                        sweep_order: SweepOrder::Random, benchmark: None,
+                        classifier: None, potts: None, heisenberg: None, observables: None, measured: Vec::new()};
+    let mut positional = Vec::new();
+
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| iter.next()
+            .ok_or_else(|| format!("Missing value for argument {}", name));
+
+        match arg.as_str() {
+            "--help" | "-h" => {
+                print_usage();
+                process::exit(0);
+            }
+            "--validate" => select_mode(&mut args, Mode::Validate)?,
+            "--validate-square" => {
+                select_mode(&mut args, Mode::Validate)?;
+                args.validate_square = true;
+            }
+            "--nucleation" => select_mode(&mut args, Mode::Nucleation)?,
+            "--interface" => select_mode(&mut args, Mode::Interface)?,
+            "--correlation" => {
+                let every: usize = value("--correlation")?.parse()
+                    .map_err(|_| "Argument --correlation must be a number".to_string())?;
+                if every == 0 {
+                    return Err("Argument --correlation must be positive".to_string());
+                }
+                args.correlation = Some(every);
+            }
+            "--structure-factor" => {
+                let every: usize = value("--structure-factor")?.parse()
+                    .map_err(|_| "Argument --structure-factor must be a number".to_string())?;
+                if every == 0 {
+                    return Err("Argument --structure-factor must be positive".to_string());
+                }
+                args.structure_factor = Some(every);
+            }
+            "--clusters" => {
+                let every: usize = value("--clusters")?.parse()
+                    .map_err(|_| "Argument --clusters must be a number".to_string())?;
+                if every == 0 {
+                    return Err("Argument --clusters must be positive".to_string());
+                }
+                args.clusters = Some(every);
+            }
+            "--skt" => args.skt = true,
+            "--aging" => select_mode(&mut args, Mode::Aging)?,
+            "--response" => args.response = true,
+            "--export" => select_mode(&mut args, Mode::Export)?,
+            "--auto-thin" => args.auto_thin = true,
+            "--auto-therm" => args.auto_therm = true,
+            "--check-energy" => {
+                args.check_energy = Some(value("--check-energy")?.parse().ok().filter(|&k| k > 0)
+                    .ok_or_else(|| "Argument --check-energy must be a positive number".to_string())?);
+            }
+            "--snapshots" => {
+                args.snapshots = Some(value("--snapshots")?.parse().ok().filter(|&k| k > 0)
+                    .ok_or_else(|| "Argument --snapshots must be a positive number".to_string())?);
+            }
+            "--movie" => {
+                let fname = PathBuf::from(value("--movie")?);
+                MovieFormat::from_path(&fname)?;
+                args.movie = Some(fname);
+            }
+            "--watch" => {
+                args.watch = Some(value("--watch")?.parse().ok().filter(|&k| k > 0)
+                    .ok_or_else(|| "Argument --watch must be a positive number".to_string())?);
+            }
+            "--plots" => args.plots = true,
+            "--first-order" => args.first_order = true,
+            "--reweight" => args.reweight = Some(Reweighting::parse(&value("--reweight")?)?),
+            "--latent-heat" => select_mode(&mut args, Mode::LatentHeat)?,
+            "--cftp" => select_mode(&mut args, Mode::Cftp)?,
+            "--wang-landau" => select_mode(&mut args, Mode::WangLandau)?,
+            "--multicanonical" => select_mode(&mut args, Mode::Multicanonical)?,
+            "--enumerate" => select_mode(&mut args, Mode::Enumerate)?,
+            "--covariance" => select_mode(&mut args, Mode::Covariance)?,
+            "--ramp" => {
+                args.ramp = Some(schedule::parse_temperature(&value("--ramp")?)?);
+                select_mode(&mut args, Mode::Ramp)?;
+            }
+            "--anneal" => {
+                args.anneal = Some(schedule::parse_annealing(&value("--anneal")?)?);
+                select_mode(&mut args, Mode::Anneal)?;
+            }
+            "--field" => args.field = schedule::parse_field(&value("--field")?)?,
+            "--algorithm" => args.algorithm = Algorithm::parse(&value("--algorithm")?)?,
+            "--sweep-order" => args.sweep_order = SweepOrder::parse(&value("--sweep-order")?)?,
+            "--benchmark" => {
+                args.benchmark = Some(Algorithm::parse_list(&value("--benchmark")?)?);
+                select_mode(&mut args, Mode::Benchmark)?;
+            }
+            "--classifier" => args.classifier = Some(value("--classifier")?),
+            "--config" => args.config = Some(PathBuf::from(value("--config")?)),
+            "--start" => args.start = Start::parse(&value("--start")?)?,
+            "--lattice" => args.lattice = Geometry::parse(&value("--lattice")?)?,
+            "--boundary" => args.boundaries = Boundaries::parse(&value("--boundary")?)?,
+            "--format" => args.format = Format::parse(&value("--format")?)?,
+            "--quiet" => args.quiet = true,
+            "--force" => args.force = true,
+            "--log-level" => args.log_level = Level::parse(&value("--log-level")?)?,
+            "--log-format" => args.log_format = LogFormat::parse(&value("--log-format")?)?,
+            "--seed" => args.seed = Some(match value("--seed")?.as_str() {
+                "random" => config::random_seed(),
+                seed => seed.parse()
+                    .map_err(|_| "Argument --seed must be a non-negative integer or 'random'".to_string())?,
+            }),
+            "--rng" => args.rng = Some(RngKind::parse(&value("--rng")?)?),
+            "--updates" => args.updates = Some(value("--updates")?),
+            "--observables" => {
+                args.observables = Some(value("--observables")?.split(',').map(|name| name.trim().to_string())
+                                        .collect());
+            }
+            "--nx" => {
+                args.nx = Some(value("--nx")?.parse().ok().filter(|&n| n > 0)
+                    .ok_or_else(|| "Argument --nx must be a positive number".to_string())?);
+            }
+            "--ny" => {
+                args.ny = Some(value("--ny")?.parse().ok().filter(|&n| n > 0)
+                    .ok_or_else(|| "Argument --ny must be a positive number".to_string())?);
+            }
+            "--ntherm-init" => {
+                args.ntherm_init = Some(value("--ntherm-init")?.parse()
+                    .map_err(|_| "Argument --ntherm-init must be a number".to_string())?);
+            }
+            "--ntherm" => {
+                args.ntherm = Some(value("--ntherm")?.parse()
+                    .map_err(|_| "Argument --ntherm must be a number".to_string())?);
+            }
+            "--nprod" => {
+                args.nprod = Some(value("--nprod")?.parse().ok().filter(|&n| n > 0)
+                    .ok_or_else(|| "Argument --nprod must be a positive number".to_string())?);
+            }
+            "--temps" => args.temperatures = Some(config::parse_temperatures(&value("--temps")?)?),
+            "--temps-file" => args.temperatures_file = Some(PathBuf::from(value("--temps-file")?)),
+            "--refine-tc" => {
+                args.refine_tc = Some(value("--refine-tc")?.parse().ok().filter(|&n| n > 0)
+                    .ok_or_else(|| "Argument --refine-tc must be a positive number".to_string())?);
+            }
+            "--peers" => {
+                args.peers = value("--peers")?.split(',').map(str::to_string).collect();
+                select_mode(&mut args, Mode::Distributed)?;
+            }
+            "--rank" => {
+                args.rank = value("--rank")?.parse().map_err(|_| "Argument --rank must be a number".to_string())?;
+            }
+            "--threads" => {
+                args.threads = Some(value("--threads")?.parse().ok().filter(|&n| n > 0)
+                    .ok_or_else(|| "Argument --threads must be a positive number".to_string())?);
+            }
+            "--surface-field" => {
+                args.surface_fields = SurfaceField::parse_list(&value("--surface-field")?)?;
+            }
+            "--field-profile" => args.profiles.push(Profile::parse(&value("--field-profile")?)?),
+            "--correlated-field" => {
+                args.disorder = Some(CorrelatedDisorder::parse(&value("--correlated-field")?)?);
+            }
+            "--random-bonds" => args.random_bonds = Some(RandomBonds::parse(&value("--random-bonds")?)?),
+            "--dilution" => {
+                let fraction: f64 = value("--dilution")?.parse()
+                    .map_err(|_| "Argument --dilution must be a number".to_string())?;
+                if !(0. ..1.).contains(&fraction) {
+                    return Err("Argument --dilution must be in [0, 1)".to_string());
+                }
+                args.dilution = Some(fraction);
+            }
+            "--sizes" => {
+                args.sizes = value("--sizes")?.split(',').map(|size| match size.trim().parse::<usize>() {
+                    Ok(l) if l >= 2 => Ok(l),
+                    _ => Err(format!("Invalid lattice size '{}' in --sizes, must be an integer >= 2", size)),
+                }).collect::<Result<_, _>>()?;
+            }
+            "--realisations" => {
+                args.realisations = value("--realisations")?.parse()
+                    .map_err(|_| "Argument --realisations must be a number".to_string())?;
+                if args.realisations == 0 {
+                    return Err("Argument --realisations must be at least 1".to_string());
+                }
+            }
+            "--measure-flips" => {
+                let every: usize = value("--measure-flips")?.parse()
+                    .map_err(|_| "Argument --measure-flips must be a number".to_string())?;
+                if every == 0 {
+                    return Err("Argument --measure-flips must be positive".to_string());
+                }
+                args.measure_flips = Some(every);
+            }
+            "--measure-every" => {
+                let spec = value("--measure-every")?;
+                let invalid = || "Argument --measure-every must be a positive number of sweeps or 1/<m> to \
+                                  measure m times per sweep".to_string();
+                match spec.strip_prefix("1/") {
+                    Some(m) => args.measures_per_sweep = Some(m.parse().ok().filter(|&m| m > 0).ok_or_else(invalid)?),
+                    None => args.measure_every = Some(spec.parse().ok().filter(|&k| k > 0).ok_or_else(invalid)?),
+                }
+            }
+            "--binsize" => {
+                let binsize: usize = value("--binsize")?.parse()
+                    .map_err(|_| "Argument --binsize must be a number".to_string())?;
+                if binsize == 0 {
+                    return Err("Argument --binsize must be positive".to_string());
+                }
+                args.binsize = Some(binsize);
+            }
+            "--chains" => {
+                args.chains = value("--chains")?.parse()
+                    .map_err(|_| "Argument --chains must be a number".to_string())?;
+                if args.chains == 0 {
+                    return Err("Argument --chains must be positive".to_string());
+                }
+            }
+            "--checkpoint" => {
+                let every: usize = value("--checkpoint")?.parse()
+                    .map_err(|_| "Argument --checkpoint must be a number".to_string())?;
+                if every == 0 {
+                    return Err("Argument --checkpoint must be positive".to_string());
+                }
+                args.checkpoint = Some(every);
+            }
+            "--stream" => {
+                let every: usize = value("--stream")?.parse()
+                    .map_err(|_| "Argument --stream must be a number".to_string())?;
+                if every == 0 {
+                    return Err("Argument --stream must be positive".to_string());
+                }
+                args.stream = Some(every);
+            }
+            "--potts" => {
+                let q: usize = value("--potts")?.parse()
+                    .map_err(|_| "Argument --potts must be a number".to_string())?;
+                if q < 2 {
+                    return Err("Argument --potts must be at least 2".to_string());
+                }
+                args.potts = Some(q);
+            }
+            "--heisenberg" => {
+                let update = heisenberg::Update::parse(&value("--heisenberg")?)?;
+                args.heisenberg = Some(heisenberg::UpdateSchedule::single(update));
+                select_mode(&mut args, Mode::Heisenberg)?;
+            }
+            "--resume" => args.resume = Some(PathBuf::from(value("--resume")?)),
+            "--bootstrap" => {
+                args.nbootstrap = value("--bootstrap")?.parse()
+                    .map_err(|_| "Argument --bootstrap must be a number".to_string())?;
+                if args.nbootstrap < 2 {
+                    return Err("Argument --bootstrap must be at least 2".to_string());
+                }
+            }
+            "--coupling" => {
+                args.coupling = value("--coupling")?.parse()
+                    .map_err(|_| "Argument --coupling must be a number".to_string())?;
+            }
+            "--coupling-y" => {
+                args.coupling_y = Some(value("--coupling-y")?.parse()
+                    .map_err(|_| "Argument --coupling-y must be a number".to_string())?);
+            }
+            "--coupling-nnn" => {
+                args.coupling_nnn = Some(value("--coupling-nnn")?.parse()
+                    .map_err(|_| "Argument --coupling-nnn must be a number".to_string())?);
+            }
+            "--ramp-sweeps" => {
+                args.nramp = value("--ramp-sweeps")?.parse()
+                    .map_err(|_| "Argument --ramp-sweeps must be a number".to_string())?;
+            }
+            "--anneal-sweeps" => {
+                args.nanneal = value("--anneal-sweeps")?.parse().ok().filter(|&n| n > 0)
+                    .ok_or_else(|| "Argument --anneal-sweeps must be a positive number".to_string())?;
+            }
+            _ if arg.starts_with("--") => match Constants::key(&arg) {
+                Some(key) => args.constants.push((key, value(&arg)?)),
+                None => return Err(format!("Unknown argument {}", arg)),
+            },
+            _ => positional.push(arg),
+        }
+    }
+
+    if args.stream.is_some() && !StreamWriter::supports(args.format) {
+        return Err("Argument --stream requires --format csv or npy".to_string());
+    }
+    if !args.boundaries.is_periodic() && !args.algorithm.is_single_flip() {
+        return Err("Argument --boundary requires the metropolis or heatbath algorithm".to_string());
+    }
+    if (args.coupling_y.is_some() || args.coupling_nnn.is_some())
+        && (!args.algorithm.is_single_flip() || args.coupling == 0.) {
+        return Err("Arguments --coupling-y and --coupling-nnn require the metropolis or heatbath algorithm and \
+                    a nonzero --coupling".to_string());
+    }
+    if args.random_bonds.is_some() && !args.algorithm.is_single_flip() {
+        return Err("Argument --random-bonds requires the metropolis or heatbath algorithm".to_string());
+    }
+    if args.realisations > 1 && args.random_bonds.is_none() && args.disorder.is_none() && args.dilution.is_none() {
+        return Err("Argument --realisations requires --random-bonds, --correlated-field, or --dilution".to_string());
+    }
+    if args.algorithm == Algorithm::Kawasaki && (args.mode == Mode::Validate || matches!(args.start, Start::Cold(_))) {
+        return Err("The kawasaki algorithm conserves the magnetisation and cannot be combined with --validate \
+                    or a cold start".to_string());
+    }
+    if args.sweep_order != SweepOrder::Random && !args.algorithm.is_single_flip() {
+        return Err("Argument --sweep-order requires the metropolis or heatbath algorithm".to_string());
+    }
+    if args.mode == Mode::WangLandau && args.coupling == 0. {
+        return Err("Argument --wang-landau requires a nonzero --coupling".to_string());
+    }
+    if args.temperatures.is_some() && args.temperatures_file.is_some() {
+        return Err("Arguments --temps and --temps-file cannot be combined".to_string());
+    }
+    if args.refine_tc.is_some() && (args.coupling == 0. || args.coupling_y == Some(0.)) {
+        return Err("Argument --refine-tc requires nonzero couplings".to_string());
+    }
+    if args.mode == Mode::Cftp && args.coupling <= 0. {
+        return Err("Argument --cftp requires a positive coupling".to_string());
+    }
+    if args.mode == Mode::Distributed && (args.rank >= args.peers.len() || args.algorithm != Algorithm::Checkerboard) {
+        return Err("Argument --peers requires a --rank below the number of peers and the checkerboard algorithm"
+                   .to_string());
+    }
+    if args.rank != 0 && args.peers.is_empty() {
+        return Err("Argument --rank requires --peers".to_string());
+    }
+
+    if let Some(config) = &args.config {
+        args.params.read(config)?;
+    }
+    match positional.len() {
+        0 => {}
+        1 => args.params.datadir = PathBuf::from(&positional[0]),
+        _ => return Err("Too many positional arguments".to_string()),
+    }
+    if let Some(seed) = args.seed {
+        args.params.seed = expand_seed(seed);
+    }
+    args.params.rng = args.rng.unwrap_or(args.params.rng);
+    if args.updates.is_some() {
+        args.params.updates = args.updates.clone();
+    }
+    if let Some(observables) = &args.observables {
+        args.params.observables = observables.clone();
+    }
+    check_mode(&args)?;
+    args.schedule = benchmark::UpdateSchedule::single(args.algorithm);
+    if let Some(spec) = &args.params.updates {
+        if args.heisenberg.is_some() {
+            args.heisenberg = Some(heisenberg::UpdateSchedule::parse(spec)?);
+        }
+        else {
+            args.schedule = benchmark::UpdateSchedule::parse(spec)?;
+            check_schedule(&args)?;
+        }
+    }
+    if !args.params.observables.is_empty() {
+        args.measured = observable::Registry::new().select(&args.params.observables)?;
+    }
+    if args.heisenberg.as_ref().is_some_and(|updates| updates.steps == [(1, heisenberg::Update::Overrelaxation)]) {
+        return Err("Overrelaxation keeps the energy, combine it with metropolis or heat-bath sweeps with --updates"
+                   .to_string());
+    }
+    let params = &mut args.params;
+    params.nx = args.nx.unwrap_or(params.nx);
+    params.ny = args.ny.unwrap_or(params.ny);
+    params.ntherm_init = args.ntherm_init.unwrap_or(params.ntherm_init);
+    params.ntherm = args.ntherm.unwrap_or(params.ntherm);
+    params.nprod = args.nprod.unwrap_or(params.nprod);
+    if let Some(temperatures) = &args.temperatures {
+        params.temperatures = temperatures.clone();
+    }
+    if let Some(fname) = &args.temperatures_file {
+        params.temperatures = config::read_temperatures(fname)?;
+    }
+    for (key, value) in &args.constants {
+        params.constants.parse_arg(key, value)?;
+    }
+    params.constants.check()?;
+    if args.mode == Mode::Validate && params.nprod < params.constants.nbin_validate {
+        return Err(format!("Argument --validate needs at least nbin_validate = {} production sweeps",
+                           params.constants.nbin_validate));
+    }
+    if params.ny == 1 && params.nx < 2 {
+        return Err("A chain needs at least 2 sites".to_string());
+    }
+    if let Some(n) = args.refine_tc {
+        if params.ny == 1 {
+            return Err("Argument --refine-tc requires a 2D lattice, chains have no phase transition".to_string());
+        }
+        let tc = exact::critical_temperature(args.coupling, args.coupling_y.unwrap_or(args.coupling));
+        params.temperatures = config::refine(&params.temperatures, tc, params.constants.width_refine, n);
+    }
+    if args.sizes.is_empty() {
+        check_extents(&args, args.params.nx, args.params.ny)?;
+    }
+    for &size in &args.sizes {
+        let (nx, ny) = size_extents(&args.params, size);
+        check_extents(&args, nx, ny)?;
+    }
+    Ok(args)
 }
 
-/// Return a list of nearest neighbour indices for use as neighbours in Configuration.
-fn make_neighbour_list() -> [usize; 4*LATSIZE] {
-    let mut indices: [usize; 4*LATSIZE] = [0; LATSIZE*4];
+/// Check that the update schedule of the Ising model given by --updates or the config file fits the other arguments.
+fn check_schedule(args: &Args) -> Result<(), String> {
+    let uniform = args.boundaries.is_periodic() && args.coupling_y.is_none() && args.coupling_nnn.is_none()
+        && args.random_bonds.is_none();
+    if !uniform && !args.schedule.algorithms().all(|algorithm| algorithm.is_single_flip()) {
+        return Err("Only the metropolis and heatbath algorithms of an update schedule support --boundary, \
+                    --coupling-y, --coupling-nnn, and --random-bonds".to_string());
+    }
+    if args.schedule.algorithms().any(|algorithm| algorithm == Algorithm::Kawasaki)
+        && matches!(args.start, Start::Cold(_)) {
+        return Err("The kawasaki algorithm conserves the magnetisation and cannot start cold".to_string());
+    }
+    Ok(())
+}
 
-    for y in 0..NY {
-        for x in 0..NX {
-            indices[(y*NX+x)*4 + 0] = if x == NX-1 { y*NX } else { y*NX + x+1 };
-            indices[(y*NX+x)*4 + 1] = if x == 0 { y*NX + NX-1 } else { y*NX + x-1 };
-            indices[(y*NX+x)*4 + 2] = if y == NY-1 { x } else { (y+1)*NX + x };
-            indices[(y*NX+x)*4 + 3] = if y == 0 { (NY-1)*NX + x } else { (y-1)*NX + x };
+/// Check that the lattice and algorithm selected by args support an nx*ny lattice.
+fn check_extents(args: &Args, nx: usize, ny: usize) -> Result<(), String> {
+    let checkerboard = args.schedule.algorithms().any(|algorithm| algorithm == Algorithm::Checkerboard)
+        || args.benchmark.as_ref().is_some_and(|algs| algs.contains(&Algorithm::Checkerboard));
+    let ny = if args.mode == Mode::Validate && !args.validate_square { 1 } else { ny };
+    if checkerboard && !checkerboard::is_supported(nx, ny) {
+        return Err("The checkerboard algorithm needs a 2D lattice with even nx and ny and cannot validate chains"
+                   .to_string());
+    }
+    if args.validate_square && (nx < 2 || ny < 2 || (args.coupling < 0. && (!nx.is_multiple_of(2) || !ny.is_multiple_of(2)))) {
+        return Err("Argument --validate-square needs nx, ny >= 2, both even for a negative --coupling".to_string());
+    }
+    if args.lattice != Geometry::Square {
+        args.lattice.create(nx, ny)?;
+        let uses = |algorithm| args.schedule.algorithms().any(|a| a == algorithm);
+        if uses(Algorithm::Checkerboard) || (args.lattice == Geometry::Honeycomb && uses(Algorithm::SwendsenWang)) {
+            return Err("Argument --lattice cannot be combined with the checkerboard algorithm, \
+                        and the honeycomb lattice not with the swendsen-wang algorithm".to_string());
         }
     }
+    let nvacancy = args.dilution.map_or(0, |fraction| (fraction * (nx*ny) as f64).round() as usize);
+    if args.mode == Mode::Covariance {
+        covariance::check_size(nx.saturating_mul(ny), args.params.temperatures.len())?;
+    }
+    if args.mode == Mode::Enumerate && nx*ny - nvacancy > exact::MAX_ENUMERATION_SITES {
+        return Err(format!("Argument --enumerate supports at most {} occupied sites", exact::MAX_ENUMERATION_SITES));
+    }
+    if args.coupling_nnn.is_some() && (args.lattice != Geometry::Square || nx < 3 || ny < 3) {
+        return Err("Argument --coupling-nnn needs the square lattice with nx, ny >= 3".to_string());
+    }
+    Ok(())
+}
 
-    indices
+/// Return the extents of the lattice of size L in finite-size scaling, L*1 if params have a chain and L*L otherwise.
+fn size_extents(params: &RunParams, size: usize) -> (usize, usize) {
+    (size, if params.ny == 1 { 1 } else { size })
 }
 
-/// Create the output data directory and write the temperature file.
+/// Return the lattice selected by args, its extents are checked by parse_args.
 /**
- * Deletes the directory and all its contents if it exists.
+ * The lattice is Anisotropic if args have a coupling in y direction and has NextNearest
+ * neighbours if they have a next-nearest neighbour coupling.
+ * Its coupling factors are relative to args.coupling.
  */
-fn prepare_datadir(dirname: &Path, temperatures: &Vec<f64>) {
-    if dirname.exists() {
-        println!("Data directory '{}' exists, removing!", dirname.display());
-        fs::remove_dir_all(dirname).unwrap();
+fn lattice(args: &Args) -> Box<dyn Lattice + Sync> {
+    let mut lattice = args.lattice.create(args.params.nx, args.params.ny).unwrap();
+    if let Some(jy) = args.coupling_y {
+        lattice = Box::new(Anisotropic{lattice, x: 1., y: jy / args.coupling});
     }
-    fs::create_dir_all(dirname).unwrap();
-
-    let mut tempfile = fs::File::create(dirname.join("temperatures.dat")).unwrap();
-    for (i, temp) in temperatures.iter().enumerate() {
-        write!(tempfile, "{}: {}\n", i, temp);
+    if let Some(j2) = args.coupling_nnn {
+        lattice = Box::new(NextNearest{lattice, j2: j2 / args.coupling});
     }
+    lattice
 }
 
-/// Write observables to a data file.
-fn write_observables(fname: &Path, obs: &Observables) {
-    let mut obsfile = fs::File::create(fname).unwrap();
+/// Create the initial configuration selected by the start, lattice, and boundaries of args.
+/**
+ * Creates random Potts states if args select the Potts model.
+ * Returns an error if the start configuration cannot be read.
+ */
+fn initial_configuration(args: &Args, rng: &mut Rng) -> Result<Configuration, Error> {
+    let cfg = match args.potts {
+        Some(q) => Potts{q, j: args.coupling}.random(args.params.nx, args.params.ny, rng),
+        None => args.start.create(args.params.nx, args.params.ny, rng)?,
+    };
+    Ok(cfg.with_boundaries(lattice(args).as_ref(), args.boundaries))
+}
 
-    for energy in obs.energy.iter() {
-        write!(obsfile, "{} ", energy);
+/// Evaluate the Hamiltonian of the model selected by args, Ising or Potts.
+fn model_energy(args: &Args, cfg: &Configuration, j: f64, h: f64) -> f64 {
+    match args.potts {
+        Some(q) => Potts{q, j}.hamiltonian(cfg),
+        None => hamiltonian(cfg, j, h),
     }
-    write!(obsfile, "\n");
+}
 
-    for magn in obs.magnetisation.iter() {
-        write!(obsfile, "{} ", magn);
+/// Run the NTHERM_INIT initial sweeps like evolve_model and record them to a movie with a frame after every sweep.
+/**
+ * The first frame shows the start configuration, so a hot start shows the coarsening after a quench.
+ * Returns the number of accepted updates.
+ */
+#[allow(clippy::too_many_arguments)]
+fn record_quench(args: &Args, fname: &Path, cfg: &mut Configuration, energy: &mut f64, beta: f64, j: f64, h: f64,
+                 rng: &mut Rng) -> Result<usize, Error> {
+    let scale = image::snapshot_scale(cfg.nx, cfg.ny);
+    let mut movie = Movie::create(fname, cfg.nx*scale, cfg.ny*scale, args.params.constants.frame_delay_movie)?;
+    movie.add_frame(&Image::of_configuration(cfg, scale))?;
+    let mut naccept = 0;
+    for _sweep in 0..args.params.ntherm_init {
+        naccept += evolve_model(args, cfg, energy, beta, j, h, rng, 1, None);
+        movie.add_frame(&Image::of_configuration(cfg, scale))?;
     }
-    write!(obsfile, "\n");
+    movie.finish()?;
+    info!("Recorded {} frames of the quench to {}", args.params.ntherm_init+1, fname.display());
+    Ok(naccept)
 }
 
-/// Evaluate the Hamiltonian on a configuration.
-fn hamiltonian(cfg: &Configuration) -> i32 {
-    let mut energy: i32 = 0;
-
-    for (idx, site) in cfg.cfg.iter().enumerate() {
-        energy += site * (cfg[cfg.neighbours[4*idx]]
-                          + cfg[cfg.neighbours[4*idx+1]]
-                          + cfg[cfg.neighbours[4*idx+2]]
-                          + cfg[cfg.neighbours[4*idx+3]]);
+/// Evolve cfg with the model and update schedule selected by args, see UpdateSchedule::evolve_in_order.
+#[allow(clippy::too_many_arguments)]
+fn evolve_model(args: &Args, cfg: &mut Configuration, energy: &mut f64, beta: f64, j: f64, h: f64,
+                rng: &mut Rng, nsweep: usize, obs: Option<&mut Observables>) -> usize {
+    match args.potts {
+        Some(q) => model::evolve_in_order(&Potts{q, j}, cfg, energy, beta, Acceptance::Metropolis,
+                                          args.sweep_order, rng, nsweep, obs),
+        None => args.schedule.evolve_in_order(args.sweep_order, cfg, energy, beta, j, h, rng, nsweep, obs),
     }
+}
 
-    return -energy;
+/// Add surface fields, field profiles, and correlated disorder of args to the local field of cfg.
+/**
+ * Draws the disorder from rng and writes it to datadir/disorder.dat as a single row.
+ * Also draws the random bonds of args from rng and writes their coupling factors
+ * to datadir/bonds.dat with one row per site in the order of its neighbours.
+ * Finally removes the sites for the dilution of args and writes their indices to datadir/vacancies.dat.
+ */
+fn apply_local_fields(cfg: &mut Configuration, args: &Args, rng: &mut Rng) -> Result<(), Error> {
+    let params = &args.params;
+    surface::apply(cfg, &args.surface_fields);
+    profile::apply(cfg, &args.profiles);
+    if let Some(disorder) = &args.disorder {
+        let field = disorder.generate(cfg.nx, cfg.ny, rng);
+        for (idx, h) in field.iter().enumerate() {
+            cfg.add_local_field(idx, *h);
+        }
+        let mut disorderfile = OutputFile::create(&params.datadir.join("disorder.dat"))?;
+        write_row(&mut disorderfile, &field)?;
+    }
+    if let Some(bonds) = &args.random_bonds {
+        bonds.apply(cfg, rng);
+        let mut bondfile = OutputFile::create(&params.datadir.join("bonds.dat"))?;
+        for idx in 0..cfg.size() {
+            write_row(&mut bondfile, cfg.couplings_of(idx).unwrap())?;
+        }
+    }
+    if let Some(fraction) = args.dilution {
+        cfg.dilute(fraction, rng);
+        let mut vacancyfile = OutputFile::create(&params.datadir.join("vacancies.dat"))?;
+        write_row(&mut vacancyfile, &cfg.vacancies().iter().map(|&idx| idx as f64).collect::<Vec<f64>>())?;
+    }
+    Ok(())
 }
 
-/// Compute the magnetisation on a configuration.
-fn magnetisation(cfg: &Configuration) -> f64 {
-    return cfg.cfg.iter().sum::<i32>() as f64 / LATSIZE as f64;
+/// Open an index file with lines '<idx>: ...' for appending, dropping all lines with idx >= keep.
+fn open_index_file(fname: &Path, keep: usize) -> Result<OutputFile, Error> {
+    let kept: String = fs::read_to_string(fname).unwrap_or_default().lines()
+        .filter(|line| line.split(':').next().and_then(|idx| idx.parse::<usize>().ok())
+                .is_some_and(|idx| idx < keep))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    write_file(fname, kept)?;
+    OutputFile::append(fname)
 }
 
-/// Compute the change in energy if the spin at site idx were flipped.
-fn delta_e(cfg: &Configuration, idx: usize) -> i32 {
-    return 2*cfg[idx] * (cfg[cfg.neighbours[4*idx]]
-                         + cfg[cfg.neighbours[4*idx+1]]
-                         + cfg[cfg.neighbours[4*idx+2]]
-                         + cfg[cfg.neighbours[4*idx+3]]);
+/// Write the state of a temperature scan to datadir/checkpoint.bin.
+/**
+ * temperature and sweep are the index of the current temperature and the number
+ * of production sweeps completed at that temperature, obs holds their measurements.
+ */
+#[allow(clippy::too_many_arguments)]
+fn write_checkpoint(params: &RunParams, temperature: usize, sweep: usize, cfg: &Configuration, energy: f64,
+                    rng: &Rng, analysis_rng: &Rng, obs: &Observables) -> Result<(), Error> {
+    let checkpoint = Checkpoint{params: params.to_toml(), temperature, sweep, spins: cfg.cfg.clone(),
+                                local_field: cfg.local_field.clone(), energy, rng: rng.state(),
+                                analysis_rng: analysis_rng.state(), obs: obs.clone()};
+    Ok(checkpoint.write(&params.datadir.join("checkpoint.bin"))?)
 }
 
-/// Evolve a configuration in Monte-Carlo time.
+/// Run a single temperature ramp and write the observables to datadir/ramp.dat.
 /**
- * Flips spins at random sites nsweep*NX*NY times and accepting or
- * rejecting the change using the Metropolis-Hastings algroithm.
- * Measures observables every NX*NY steps, i.e. once per sweep.
- *
- * cfg and energy must be set before calling the function.
- * Upon return, they contain the final configuration and energy.
- * Returns the number of accepted spin flips.
+ * Besides the ramp, uses params, coupling, field, surface_fields, profiles, disorder, start, lattice,
+ * boundaries, coupling_y, coupling_nnn, random_bonds, dilution, and nramp of args.
+ * Also writes the staggered magnetisation to datadir/ramp_staggered.dat if the coupling is negative
+ * and the final configuration to datadir/final.dat.
  */
-fn evolve(cfg: &mut Configuration, energy: &mut f64, beta: f64,
-          rng: &mut Rng, nsweep: usize, mut obs: Option<&mut Observables>) -> usize {
-    // running number of accepted spin flips
-    let mut naccept: usize = 0;
+fn run_ramp(args: &Args, ramp: &dyn Schedule) -> Result<(), Error> {
+    let params = &args.params;
+    let datadir = params.datadir.as_path();
+    let field = args.field.as_ref();
+    let nsweep = args.nramp;
 
-    for _sweep in 0..nsweep {
-        for _step in 0..LATSIZE {
-            let idx = rng.gen_index();  // flip spin at this site
+    let mut rng = Rng::new(params.rng, params.seed);
+    // read the initial configuration before creating datadir, which may contain it
+    let mut cfg = initial_configuration(args, &mut rng)?;
+    create_datadir(datadir, args.force)?;
+    apply_local_fields(&mut cfg, args, &mut rng)?;
+    let latsize = cfg.size();
+    let j = args.coupling;
+    let mut energy = hamiltonian(&cfg, j, field.start());
 
-            let delta = delta_e(&cfg, idx);  // proposed change in energy
+    let start_time = Instant::now();
 
-            // Metropolis-Hastings accept-reject
-            // The first check is not necessary for this to be correct but avoids
-            // evaluating the costly exponential and RNG.
-            if delta <= 0 || (-beta*(delta as f64)).exp() > rng.gen_real() {
-                cfg[idx] *= -1;
-                *energy += delta as f64;
-                naccept += 1;
-            }
-            // else: discard
-        }
+    // thermalise at the initial temperature of the ramp
+    let naccept = evolve(&mut cfg, &mut energy, 1./ramp.start(), j, field.start(), &mut rng,
+                         params.ntherm_init, None);
+    info!("Initial thermalisation acceptance rate: {}",
+          (naccept as f64)/((params.ntherm_init*latsize) as f64));
 
-        // measure observables if an instance of Observables is given.
-        if let Some(o) = &mut obs {
-            o.energy.push(*energy);
-            o.magnetisation.push(magnetisation(&cfg));
-        }
+    let mut obs = Observables::new();
+    let naccept = evolve_ramp(&mut cfg, &mut energy, j, ramp, field, &mut rng, nsweep, Some(&mut obs));
+    info!("Ramp acceptance rate: {}", naccept as f64 / (nsweep*latsize) as f64);
+
+    write_ramp_observables(&datadir.join("ramp.dat"), &ramp.values(nsweep), &obs)?;
+    if j < 0. {
+        write_staggered(&datadir.join("ramp_staggered.dat"), &obs)?;
+    }
+    if !field.is_constant() {
+        write_drive(&datadir.join("ramp_drive.dat"), field, nsweep)?;
     }
+    write_configuration(&datadir.join("final.dat"), &cfg)?;
 
-    return naccept;
+    let duration = start_time.elapsed();
+    info!("Duration in wall clock time: {}s", duration.as_secs() as f64
+          + (0.001*duration.subsec_millis() as f64));
+    Ok(())
 }
 
-fn main() {
-    // parse command line arguments
-    let args: Vec<String> = env::args().collect();
-    let datadir = if args.len() == 2 {
-        Path::new(&args[1])
+/// Run simulated annealing through the inverse temperatures betas and write the energies to datadir/anneal.dat.
+/**
+ * Uses params, algorithm, coupling, field, surface_fields, profiles, disorder, start, lattice, boundaries,
+ * coupling_y, coupling_nnn, random_bonds, dilution, and nanneal of args.
+ * anneal.dat has one line `<i>: <beta> <mean energy> <lowest energy> <acceptance rate>` per step
+ * with total energies. Also writes the lowest energy configuration to datadir/ground_state.dat
+ * and the final configuration to datadir/final.dat.
+ */
+fn run_anneal(args: &Args, betas: &[f64]) -> Result<(), Error> {
+    let params = &args.params;
+    let datadir = params.datadir.as_path();
+
+    let mut rng = Rng::new(params.rng, params.seed);
+    // read the initial configuration before creating datadir, which may contain it
+    let mut cfg = initial_configuration(args, &mut rng)?;
+    create_datadir(datadir, args.force)?;
+    apply_local_fields(&mut cfg, args, &mut rng)?;
+    let (j, h) = (args.coupling, args.field.start());
+    let mut energy = hamiltonian(&cfg, j, h);
+
+    let start_time = Instant::now();
+
+    let (steps, ground_state) = anneal::anneal(&mut cfg, &mut energy, j, h, args.algorithm, betas,
+                                               args.nanneal, &mut rng);
+    let mut annealfile = OutputFile::create(&datadir.join("anneal.dat"))?;
+    for (i, step) in steps.iter().enumerate() {
+        writeln!(annealfile, "{}: {} {} {} {}", i, step.beta, step.mean_energy, step.min_energy,
+                 step.acceptance)?;
     }
-    else {
-        Path::new("./data")
-    };
+    let lowest = hamiltonian(&ground_state, j, h);
+    info!("Lowest energy: {} ({} per site)", lowest, lowest / cfg.nsite() as f64);
+    write_configuration(&datadir.join("ground_state.dat"), &ground_state)?;
+    write_configuration(&datadir.join("final.dat"), &cfg)?;
 
-    // prepare output directory
-    let temperatures = list_temperatures();
-    prepare_datadir(&datadir, &temperatures);
+    let duration = start_time.elapsed();
+    info!("Duration in wall clock time: {}s", duration.as_secs() as f64
+          + (0.001*duration.subsec_millis() as f64));
+    Ok(())
+}
+
+/// Run the simulation for all temperatures and write the observables to datadir.
+/**
+ * Uses the production settings of args, i.e. params, schedule, sweep_order, coupling, field, surface_fields,
+ * profiles, disorder, correlation, structure_factor, clusters, skt, measure_flips, measure_every, measures_per_sweep,
+ * auto_thin, auto_therm, check_energy, snapshots, movie, watch, plots, first_order, classifier, chains, format, stream, start, lattice, boundaries,
+ * coupling_y, coupling_nnn, random_bonds, dilution, checkpoint, resume, reweight, potts, and measured.
+ * Additional chains share the lattice and local fields with the main chain and their
+ * measurements are appended to those of the main chain. They use their own random spins for a hot
+ * start and the initial configuration of the main chain for any other start.
+ * Writes the final configuration of the main chain to datadir/final.dat.
+ * When resuming, the first temperatures are skipped as recorded in the checkpoint and their
+ * results in datadir are kept.
+ * Returns the summaries of all temperatures that were run, or none if the observables are streamed.
+ */
+fn run_temperatures(args: &Args) -> Result<Vec<Summary>, Error> {
+    let params = &args.params;
+    let datadir = params.datadir.as_path();
+    let j = args.coupling;
+    let field: &(dyn Schedule + Sync) = args.field.as_ref();
+
+    let resume = args.resume.as_deref().map(|fname| {
+        Checkpoint::read(fname)
+            .and_then(|ckp| if ckp.params == params.to_toml() && ckp.spins.len() == params.nx*params.ny {
+                Ok(ckp)
+            } else {
+                Err(format!("Checkpoint '{}' was written with different run parameters", fname.display()))
+            })
+    }).transpose()?;
+    // index of the first temperature to run
+    let first_temperature = resume.as_ref().map_or(0, |ckp| ckp.temperature);
 
     // one rng for all purposes
-    let mut rng = Rng::from_seed([138; 32]);
+    let mut rng = Rng::new(params.rng, params.seed);
+    // separate stream for the analysis so it does not affect the Markov chain
+    let mut bootstrap_rng = Rng::new(params.rng, derive_seed(params.seed, STREAM_BOOTSTRAP));
+
+    // read the initial configuration before preparing datadir, which may contain it
+    let mut initial = resume.is_none().then(|| initial_configuration(args, &mut rng)).transpose()?;
 
-    // initial condition (hot start)
-    let mut cfg = Configuration::random(&mut rng);
+    // prepare output directory, keeping the results of completed temperatures when resuming
+    let temperatures = &params.temperatures;
+    if resume.is_none() {
+        prepare_datadir(datadir, temperatures, args.force)?;
+    }
+    let modes = fourier::axis_modes(params.nx, params.ny);
+    if args.skt {
+        write_modes(&datadir.join("modes.dat"), &modes, params.nx, params.ny)?;
+    }
+    let mut summaryfile = open_index_file(&datadir.join("summary.dat"), first_temperature)?;
+    let mut autocorrfile = open_index_file(&datadir.join("autocorr.dat"), first_temperature)?;
+    let mut statsfile = stats::open(&datadir.join("stats.csv"), first_temperature)?;
+    let mut thinfile = if args.auto_thin {
+        Some(OutputFile::create(&datadir.join("thinning.dat"))?)
+    } else { None };
+    let mut thermfile = if args.auto_therm {
+        Some(open_index_file(&datadir.join("thermalisation.dat"), first_temperature)?)
+    } else { None };
+    let snapshotdir = datadir.join("snapshots");
+    if args.snapshots.is_some() {
+        fs::create_dir_all(&snapshotdir).map_err(Error::io(&snapshotdir))?;
+    }
+    let snapshot_scale = image::snapshot_scale(params.nx, params.ny);
+    let mut watch = args.watch.map(Watch::new);
+    let plotdir = datadir.join("plots");
+    if args.plots {
+        fs::create_dir_all(&plotdir).map_err(Error::io(&plotdir))?;
+    }
+    let mut firstfile = if args.first_order {
+        Some(OutputFile::create(&datadir.join("first_order.dat"))?)
+    } else { None };
+    let mut summaries = Vec::new();
+    let mut runs = Vec::new();
+    let mut cumulants = Vec::new();
+    let mut double_peaked = false;
+    let mut classifier = args.classifier.as_deref().map(|command| {
+        Classifier::spawn(command, params.nx, params.ny)
+            .map_err(|err| Error::Message(format!("Failed to start classifier '{}': {}", command, err)))
+    }).transpose()?;
+
+    // initial condition
+    let mut cfg;
     let mut energy = 0.0;  // does not matter for initial thermalisation
+    // completed production sweeps and measurements of the first temperature when resuming
+    let mut resumed_production = None;
+    match resume {
+        Some(ckp) => {
+            cfg = Configuration::cold(params.nx, params.ny, 1)
+                .with_boundaries(lattice(args).as_ref(), args.boundaries);
+            surface::apply(&mut cfg, &args.surface_fields);
+            profile::apply(&mut cfg, &args.profiles);
+            cfg.cfg = ckp.spins;
+            cfg.local_field = ckp.local_field;
+            if args.dilution.is_some() {
+                // vacancies are the only sites with spin 0
+                let vacancies: Vec<usize> = (0..cfg.size()).filter(|&idx| cfg[idx] == 0).collect();
+                cfg.remove_sites(&vacancies);
+            }
+            rng = Rng::from_state(ckp.rng);
+            bootstrap_rng = Rng::from_state(ckp.analysis_rng);
+            if ckp.sweep > 0 {
+                energy = ckp.energy;
+                resumed_production = Some((ckp.sweep, ckp.obs));
+            }
+        }
+        None => {
+            cfg = initial.take().unwrap();
+            apply_local_fields(&mut cfg, args, &mut rng)?;
+        }
+    }
+    let latsize = cfg.size();
+    let mut chains: Vec<Chain> = (1..args.chains)
+        .map(|c| {
+            // the main chain draws from stream 0
+            let rng = Rng::stream(params.rng, params.seed, c as u64);
+            match args.start {
+                Start::Hot => Chain::new(&cfg, rng),
+                _ => Chain::from_configuration(cfg.clone(), rng),
+            }
+        })
+        .collect();
 
     // start measuring time, the above doesn't count
     let start_time = Instant::now();
 
     // initial thermalisation
-    let naccept = evolve(&mut cfg, &mut energy, 1./temperatures[0], &mut rng, NTHERM_INIT, None);
-    println!("Initial thermalisation acceptance rate: {}", (naccept as f64)/((NTHERM_INIT*LATSIZE) as f64));
+    let h0 = field.start();
+    if first_temperature == 0 && resumed_production.is_none() && !args.auto_therm {
+        let naccept = match args.movie.as_deref() {
+            Some(fname) => record_quench(args, fname, &mut cfg, &mut energy, 1./temperatures[0], j, h0, &mut rng)?,
+            None => evolve_model(args, &mut cfg, &mut energy, 1./temperatures[0], j, h0,
+                                 &mut rng, params.ntherm_init, None),
+        };
+        info!("Initial thermalisation acceptance rate: {}",
+              (naccept as f64)/((params.ntherm_init*latsize) as f64));
+    }
 
-    for (i, temp) in temperatures.iter().enumerate() {
-        println!("Running for temperature {}", temp);
+    for (i, temp) in temperatures.iter().enumerate().skip(first_temperature) {
+        let span = Span::enter("temperature", &[("index", i as f64), ("temperature", *temp)]);
+        info!("Running for temperature {}", temp);
         let beta = 1./temp;
-        energy = hamiltonian(&cfg) as f64;
-
-        // re-thermalise
-        let naccept = evolve(&mut cfg, &mut energy, beta, &mut rng, NTHERM, None);
-        println!("  Thermalisation acceptance rate: {}", (naccept as f64)/((NTHERM*LATSIZE) as f64));
 
         // measure
-        let mut obs = Observables{energy: Vec::new(), magnetisation: Vec::new()};
-        let naccept = evolve(&mut cfg, &mut energy, beta, &mut rng, NPROD, Some(&mut obs));
-        println!("  Production acceptance rate: {}", naccept as f64 / (NPROD*LATSIZE) as f64);
+        let (first_sweep, mut obs) = resumed_production.take().unwrap_or_else(|| {
+            energy = model_energy(args, &cfg, j, h0);
+            (0, Observables::with_observables(&args.measured))
+        });
+        let mut correlation = Correlation::new(params.nx, params.ny);
+        let mut structure_factor = StructureFactor::new(params.nx, params.ny);
+        let mut clusters = ClusterDistribution::new(cfg.nsite());
+        let mut mode_history = Vec::new();
+        let mut predictions: Vec<Vec<f64>> = Vec::new();
+        let mut clock = args.measure_flips
+            .or(args.measures_per_sweep.map(|m| (params.nx*params.ny).div_ceil(m)))
+            .map(FlipClock::new);
+        let obsname = datadir.join(format!("{}.{}", i, args.format.extension()));
+        let mut sink = args.stream.map(|_| StreamWriter::create(&obsname, args.format).map_err(Error::io(&obsname)))
+            .transpose()?;
+        // the additional chains run in parallel to the main chain
+        let (updates, order, nprod) = (&args.schedule, args.sweep_order, params.nprod);
+        let ntherm = if i == 0 { params.ntherm_init + params.ntherm } else { params.ntherm };
+        let mut therm_acceptance = f64::NAN;
+        let mut ntherm_run = 0;
+        let (mut naccept, extra, production_time) = thread::scope(|scope| -> Result<_, Error> {
+            let extra = scope.spawn(|| chains::run_parallel(&mut chains, |chain| {
+                chain.run(updates, order, beta, j, field, ntherm, nprod)
+            }));
+
+            // re-thermalise
+            if first_sweep == 0 && args.auto_therm {
+                let therm = thermalisation::thermalise(params.constants.nblock_auto_therm, params.constants.nmax_auto_therm, |nsweep, energies| {
+                    let mut obs = Observables::new();
+                    let naccept = evolve_model(args, &mut cfg, &mut energy, beta, j, h0, &mut rng, nsweep,
+                                               Some(&mut obs));
+                    energies.extend(obs.energy);
+                    naccept
+                });
+                therm_acceptance = (therm.naccept as f64)/((therm.nsweep*latsize) as f64);
+                ntherm_run = therm.nsweep;
+                match therm.nequilibrate {
+                    Some(nequilibrate) => logging::log(
+                        Level::Info,
+                        &format!("  Thermalised after {} sweeps, equilibrated after ~{} sweeps, acceptance rate: {}",
+                                 therm.nsweep, nequilibrate, therm_acceptance),
+                        &[("thermalisation_sweeps", therm.nsweep as f64),
+                          ("equilibration_sweeps", nequilibrate as f64)]),
+                    None => warn!("  Energy did not equilibrate within {} sweeps, starting production anyway",
+                                  therm.nsweep),
+                }
+                writeln!(thermfile.as_mut().unwrap(), "{}: {} {} {}", i, temp, therm.nsweep,
+                         therm.nequilibrate.map_or(f64::NAN, |n| n as f64))?;
+            }
+            else if first_sweep == 0 {
+                let naccept = evolve_model(args, &mut cfg, &mut energy, beta, j, h0, &mut rng,
+                                           params.ntherm, None);
+                therm_acceptance = (naccept as f64)/((params.ntherm*latsize) as f64);
+                ntherm_run = params.ntherm;
+                info!("  Thermalisation acceptance rate: {}", therm_acceptance);
+            }
+
+            let mut stride = args.measure_every.unwrap_or(1);  // measure every stride sweeps
+            let mut naccept = 0;
+            let production_start = Instant::now();
+            let mut max_drift: f64 = 0.;  // largest energy drift found by --check-energy
+            let mut progress = Progress::new("  Production", params.nprod, first_sweep, cfg.active_sites().len(),
+                                             !args.quiet && watch.is_none());
+            for sweep in first_sweep..params.nprod {
+                let measure = (sweep+1) % stride == 0;
+                naccept += match clock.as_mut() {
+                    Some(clock) => evolve_field_flips(&mut cfg, &mut energy, beta, j, field, sweep,
+                                                      params.nprod, &mut rng, clock, &mut obs),
+                    None => {
+                        let h = change_field(&cfg, &mut energy, field, sweep, params.nprod);
+                        evolve_model(args, &mut cfg, &mut energy, beta, j, h, &mut rng, 1,
+                                     if measure { Some(&mut obs) } else { None })
+                    }
+                };
+                if args.auto_thin && sweep+1 == params.constants.npilot_thin {
+                    let tau = autocorr::integrated_time(&obs.energy)
+                        .max(autocorr::integrated_time(&obs.magnetisation));
+                    stride = ((tau/2.).round() as usize).max(1);
+                    thin_observables(&mut obs, stride);
+                    info!("  Thinning to every {} sweeps, tau_int = {}", stride, tau);
+                    writeln!(thinfile.as_mut().unwrap(), "{}: {} {}", i, stride, tau)?;
+                }
+                if args.check_energy.is_some_and(|every| (sweep+1) % every == 0) {
+                    let exact = model_energy(args, &cfg, j, field.value(sweep, params.nprod));
+                    let drift = check_energy(&mut energy, exact, cfg.nsite(), params.constants.energy_tolerance)
+                        .map_err(|err| format!("{} after production sweep {} at T = {}", err, sweep+1, temp))?;
+                    max_drift = max_drift.max(drift.abs());
+                    logging::log(Level::Debug, &format!("  Energy drift after sweep {}: {}", sweep+1, drift),
+                                 &[("sweep", (sweep+1) as f64), ("drift", drift)]);
+                }
+                if args.snapshots.is_some_and(|every| (sweep+1) % every == 0) {
+                    Image::of_configuration(&cfg, snapshot_scale)
+                        .write_png(&image::snapshot_name(&snapshotdir, *temp, sweep+1, params.nprod))?;
+                }
+                if let Some(watch) = watch.as_mut() {
+                    watch.update(&cfg, *temp, sweep+1, params.nprod, &obs);
+                }
+                if args.correlation.is_some_and(|every| (sweep+1) % every == 0) {
+                    correlation.measure(&cfg);
+                }
+                if args.structure_factor.is_some_and(|every| (sweep+1) % every == 0) {
+                    structure_factor.measure(&cfg);
+                }
+                if args.clusters.is_some_and(|every| (sweep+1) % every == 0) {
+                    clusters.measure(&cfg);
+                }
+                if args.skt {
+                    mode_history.push(fourier::fourier_modes(&cfg, &modes));
+                }
+                if let Some(classifier) = classifier.as_mut() {
+                    if (sweep+1) % params.constants.nskip_classifier == 0 {
+                        predictions.push(classifier.classify(&cfg)
+                            .map_err(|err| Error::Message(format!("Classifier failed: {}", err)))?);
+                    }
+                }
+                if let (Some(every), Some(sink)) = (args.stream, sink.as_mut()) {
+                    if obs.energy.len() >= every {
+                        sink.take(&mut obs).map_err(Error::io(&obsname))?;
+                    }
+                }
+                if args.checkpoint.is_some_and(|every| (sweep+1) % every == 0) && sweep+1 < params.nprod {
+                    write_checkpoint(params, i, sweep+1, &cfg, energy, &rng, &bootstrap_rng, &obs)?;
+                }
+                progress.update(sweep+1, naccept);
+            }
+            progress.finish();
+            if args.check_energy.is_some() {
+                logging::log(Level::Info, &format!("  Largest energy drift: {}", max_drift),
+                             &[("max_drift", max_drift)]);
+            }
+            let extra = extra.join().unwrap();
+            Ok((naccept, extra, production_start.elapsed().as_secs_f64()))
+        })?;
+        let mut histories = vec![obs];
+        for (chain_obs, chain_naccept) in extra {
+            histories.push(chain_obs);
+            naccept += chain_naccept;
+        }
+        let (mut obs, chain_ids) = chains::merge(histories);
+        let acceptance = naccept as f64 / (args.chains*(params.nprod-first_sweep)*latsize) as f64;
+        info!("  Production acceptance rate: {}", acceptance);
+
+        // streamed measurements are already on disk and not kept for the analysis
+        if let Some(sink) = sink.as_mut() {
+            sink.take(&mut obs).and_then(|_| sink.finish()).map_err(Error::io(&obsname))?;
+        } else {
+            write_observables(&obsname, &obs, args.format)?;
+            if args.chains > 1 {
+                let mut chainfile = OutputFile::create(&datadir.join(format!("{}_chains.dat", i)))?;
+                write_row(&mut chainfile, &chain_ids.iter().map(|&id| id as f64).collect::<Vec<f64>>())?;
+            }
+            let tau_e = Autocorrelation::new(&obs.energy);
+            let tau_m = Autocorrelation::new(&obs.magnetisation);
+            logging::log(Level::Info,
+                         &format!("  Effective samples: energy {:.0} (tau_int = {:.2}), magnetisation {:.0} \
+                                   (tau_int = {:.2})", tau_e.effective_samples(), tau_e.tau,
+                                  tau_m.effective_samples(), tau_m.tau),
+                         &[("tau_energy", tau_e.tau), ("tau_magnetisation", tau_m.tau)]);
+            let binsize = args.binsize
+                .unwrap_or_else(|| analysis::binsize(tau_e.tau.max(tau_m.tau), obs.energy.len()));
+            writeln!(autocorrfile, "{}: {} {} {} {} {} {} {} {}", i, temp, tau_e.tau, tau_e.error,
+                     tau_e.effective_samples(), tau_m.tau, tau_m.error, tau_m.effective_samples(), binsize)?;
+            let summary = Summary::new(&obs, beta, params.nx, cfg.nsite(), binsize, args.nbootstrap,
+                                       &mut bootstrap_rng);
+            let values = [summary.energy, summary.abs_magnetisation, summary.susceptibility, summary.specific_heat,
+                          summary.binder, summary.correlation_length]
+                .map(|(value, error)| format!("{} {}", value, error));
+            writeln!(summaryfile, "{}: {} {}", i, temp, values.join(" "))?;
+            summaries.push(summary);
+            if args.plots {
+                plot_temperature(&plotdir, i, *temp, &obs, cfg.nsite(), params.constants.nbin_histogram)?;
+            }
+            if args.reweight.is_some() {
+                reweighting::write_histogram(&datadir.join(format!("{}_energy_histogram.dat", i)), &obs.energy)?;
+                runs.push(reweighting::Run{beta, energy: obs.energy.clone(), magnetisation: obs.magnetisation.clone()});
+            }
+            if j < 0. {
+                write_staggered(&datadir.join(format!("{}_staggered.dat", i)), &obs)?;
+            }
+            for series in &obs.custom {
+                write_series(&datadir.join(format!("{}_{}.dat", i, series.name())), series)?;
+            }
+        }
+        if let Some(clock) = &clock {
+            let mut clockfile = OutputFile::create(&datadir.join(format!("{}_clock.dat", i)))?;
+            let times: Vec<f64> = clock.times.iter().map(|&t| t as f64).collect();
+            write_row(&mut clockfile, &times)?;
+        }
+        if args.correlation.is_some() {
+            let mut corrfile = OutputFile::create(&datadir.join(format!("{}_corr_x.dat", i)))?;
+            write_row(&mut corrfile, &correlation.x())?;
+            if params.ny > 1 {
+                let mut corrfile = OutputFile::create(&datadir.join(format!("{}_corr_y.dat", i)))?;
+                write_row(&mut corrfile, &correlation.y())?;
+            }
+        }
+        if args.structure_factor.is_some() {
+            let mut skfile = OutputFile::create(&datadir.join(format!("{}_sk.dat", i)))?;
+            for row in structure_factor.average().chunks(params.nx) {
+                write_row(&mut skfile, row)?;
+            }
+        }
+        if args.clusters.is_some() {
+            let mut clusterfile = OutputFile::create(&datadir.join(format!("{}_clusters.dat", i)))?;
+            write_row(&mut clusterfile, &clusters.distribution())?;
+            write_row(&mut clusterfile, clusters.largest())?;
+        }
+        if args.skt {
+            let sfactor = fourier::dynamic_structure_factor(&mode_history, params.constants.ntau_skt, latsize);
+            let mut sktfile = OutputFile::create(&datadir.join(format!("{}_skt.dat", i)))?;
+            for row in sfactor.iter() {
+                write_row(&mut sktfile, row)?;
+            }
+        }
+        if let Some(firstfile) = firstfile.as_mut() {
+            let ehist = firstorder::histogram(&obs.energy, params.constants.nbin_histogram);
+            let mhist = firstorder::histogram(&obs.magnetisation, params.constants.nbin_histogram);
+            let mut histfile = OutputFile::create(&datadir.join(format!("{}_histogram.dat", i)))?;
+            for row in [&ehist.centres, &ehist.counts, &mhist.centres, &mhist.counts] {
+                write_row(&mut histfile, row)?;
+            }
+
+            let cumulant = firstorder::energy_cumulant(&obs.energy);
+            let edouble = firstorder::is_double_peaked(&ehist);
+            let mdouble = firstorder::is_double_peaked(&mhist);
+            writeln!(firstfile, "{}: {} {} {}", i, cumulant, edouble as i32, mdouble as i32)?;
+            cumulants.push(cumulant);
+            double_peaked |= edouble;
+        }
+        if classifier.is_some() {
+            write_predictions(&datadir.join(format!("{}_classifier.dat", i)), &predictions)?;
+        }
+        if !field.is_constant() {
+            write_drive(&datadir.join(format!("{}_drive.dat", i)), field, params.nprod)?;
+        }
+        if args.checkpoint.is_some() {
+            write_checkpoint(params, i+1, 0, &cfg, energy, &rng, &bootstrap_rng, &Observables::new())?;
+        }
+        let elapsed = span.elapsed();
+        let nprod_run = args.chains*(params.nprod-first_sweep);
+        TemperatureStats{index: i, temperature: *temp, ntherm: ntherm_run, therm_acceptance, nprod: nprod_run,
+                         acceptance, flips_per_sweep: naccept as f64 / nprod_run as f64, wall_time: elapsed,
+                         production_time}.write(&mut statsfile)?;
+        span.close(&format!("  Finished temperature {} in {:.3}s", temp, elapsed),
+                   &[("thermalisation_acceptance", therm_acceptance), ("production_acceptance", acceptance)]);
+    }
+    write_configuration(&datadir.join("final.dat"), &cfg)?;
+    if args.plots {
+        plot_summaries(&plotdir, &temperatures[first_temperature..], &summaries)?;
+    }
+
+    if let Some(method) = args.reweight {
+        let points = reweighting::reweight(&runs, method, cfg.nsite(), params.constants.nreweight);
+        reweighting::write_points(&datadir.join("reweighted.dat"), &points)?;
+    }
 
-        write_observables(&datadir.join(format!("{}.dat", i)), &obs);
+    if args.first_order {
+        let (imin, vmin) = cumulants.iter().enumerate()
+            .min_by(|a, b| a.1.total_cmp(b.1)).unwrap();
+        info!("Minimum energy cumulant {} at temperature {}", vmin, temperatures[imin]);
+        if double_peaked {
+            info!("Found a double peaked energy histogram, the transition may be first-order");
+        }
     }
 
     let duration = start_time.elapsed();
-    println!("Duration in wall clock time: {}s", duration.as_secs() as f64
-             + (0.001*duration.subsec_millis() as f64));
+    info!("Duration in wall clock time: {}s", duration.as_secs() as f64
+          + (0.001*duration.subsec_millis() as f64));
+    Ok(summaries)
+}
+
+fn main() {
+    let mut args = match parse_args() {
+        Ok(args) => args,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            print_usage();
+            process::exit(1);
+        }
+    };
+    logging::init(args.log_level, args.log_format);
+    if let Some(threads) = args.threads {
+        checkerboard::set_max_threads(threads);
+    }
+
+    if args.mode == Mode::Validate {
+        if validate(&args.params, args.algorithm, args.coupling, args.validate_square) {
+            info!("Validation passed");
+            return;
+        }
+        error!("Validation failed");
+        process::exit(1);
+    }
+
+    if let Some(seed) = args.seed {
+        info!("Using seed {}", seed);
+    }
+    let result = if !args.sizes.is_empty() {
+        run_sizes(&mut args)
+    } else if args.realisations > 1 {
+        run_realisations(&mut args)
+    } else {
+        run_mode(&args)
+    };
+    // the other ranks of a distributed run have no datadir
+    if let Err(err) = result.and_then(|_| if args.rank == 0 { write_manifest(&args) } else { Ok(()) }) {
+        error!("{}", err);
+        process::exit(1);
+    }
+}
+
+/// Write datadir/manifest.json and, if the parameters were read from a config file, datadir/run.toml.
+fn write_manifest(args: &Args) -> Result<(), Error> {
+    if args.config.is_some() {
+        // echo the parameters for reproducibility
+        args.params.write(&args.params.datadir.join("run.toml"))?;
+    }
+    let command: Vec<String> = env::args().collect();
+    write_file(&args.params.datadir.join("manifest.json"), config::manifest(&args.params, args.seed, &command))
+}
+
+/// Run the mode selected in args, all modes write their output to args.params.datadir.
+fn run_mode(args: &Args) -> Result<(), Error> {
+    let params = &args.params;
+
+    if !args.peers.is_empty() {
+        if args.rank == 0 {
+            prepare_datadir(&params.datadir, &params.temperatures, args.force)?;
+        }
+        let mut ring = Ring::connect(args.rank, &args.peers)?;
+        let mut rng = Rng::new(params.rng, params.seed);
+        distributed::run(&params.datadir, params.nx, params.ny, &params.temperatures, args.coupling,
+                         params.ntherm_init, params.ntherm, params.nprod, args.format, &mut ring, &mut rng)?;
+        return Ok(());
+    }
+
+    if let Some(algorithms) = &args.benchmark {
+        create_datadir(&params.datadir, args.force)?;
+        benchmark::run(&params.datadir, params.nx, params.ny, params.constants.t_benchmark, args.coupling,
+                       args.field.start(), algorithms, params.ntherm_init, params.constants.nsweep_benchmark, params.rng,
+                       params.seed)?;
+        return Ok(());
+    }
+
+    if args.mode == Mode::LatentHeat {
+        create_datadir(&params.datadir, args.force)?;
+        let mut rng = Rng::new(params.rng, params.seed);
+        firstorder::run(&params.datadir, &params.constants.latent_lattices, params.constants.t_latent, args.coupling,
+                        args.field.start(), params.ntherm_init, params.nprod, params.constants.nbin_histogram, &mut rng)?;
+        return Ok(());
+    }
+
+    if args.mode == Mode::Cftp {
+        prepare_datadir(&params.datadir, &params.temperatures, args.force)?;
+        let mut rng = Rng::new(params.rng, params.seed);
+        cftp::run(&params.datadir, params.nx, params.ny, &params.temperatures, args.coupling,
+                  args.field.start(), params.constants.nsample_cftp, params.constants.nmax_cftp, &mut rng)?;
+        return Ok(());
+    }
+
+    if args.mode == Mode::Enumerate {
+        let mut rng = Rng::new(params.rng, params.seed);
+        // read the initial configuration before creating datadir, which may contain it
+        let mut cfg = initial_configuration(args, &mut rng)?;
+        create_datadir(&params.datadir, args.force)?;
+        apply_local_fields(&mut cfg, args, &mut rng)?;
+        exact::run(&params.datadir, &cfg, args.coupling, args.field.start(), &params.temperatures)?;
+        return Ok(());
+    }
+
+    if args.mode == Mode::WangLandau {
+        let mut rng = Rng::new(params.rng, params.seed);
+        // read the initial configuration before creating datadir, which may contain it
+        let mut cfg = initial_configuration(args, &mut rng)?;
+        create_datadir(&params.datadir, args.force)?;
+        let wang_landau_params = WangLandauParams{ln_f_final: params.constants.ln_f_wang_landau, flatness: params.constants.flatness_wang_landau,
+                                                  ncheck: params.constants.ncheck_wang_landau};
+        wang_landau::run(&params.datadir, &mut cfg, args.coupling, &params.temperatures, &wang_landau_params,
+                         &mut rng)?;
+        return Ok(());
+    }
+
+    if args.mode == Mode::Multicanonical {
+        let mut rng = Rng::new(params.rng, params.seed);
+        // read the initial configuration before creating datadir, which may contain it
+        let mut cfg = initial_configuration(args, &mut rng)?;
+        create_datadir(&params.datadir, args.force)?;
+        apply_local_fields(&mut cfg, args, &mut rng)?;
+        multicanonical::run(&params.datadir, &mut cfg, params.constants.t_multicanonical, args.coupling, args.field.start(),
+                            params.constants.niter_multicanonical, params.constants.nsweep_multicanonical, params.nprod, &mut rng)?;
+        return Ok(());
+    }
+
+    if args.mode == Mode::Nucleation {
+        create_datadir(&params.datadir, args.force)?;
+        let mut rng = Rng::new(params.rng, params.seed);
+        nucleation::run(&params.datadir, params.nx, params.ny, &params.constants.nucleation_temperatures, args.coupling,
+                        &params.constants.nucleation_fields, params.constants.nrepeat_nucleation, params.constants.nmax_nucleation, &mut rng)?;
+        return Ok(());
+    }
+
+    if matches!(args.mode, Mode::Export | Mode::Covariance) {
+        create_datadir(&params.datadir, args.force)?;
+        let mut rng = Rng::new(params.rng, params.seed);
+        let export_params = export::ExportParams{nsample: params.constants.nsample_export, nskip: params.constants.nskip_export,
+                                                 npilot: params.constants.npilot_export,
+                                                 shard_size: params.constants.shard_size_export, ntherm: params.ntherm};
+        if args.mode == Mode::Export {
+            export::run(&params.datadir, params.nx, params.ny, &params.temperatures, &export_params, &mut rng)?;
+        }
+        else {
+            covariance::run(&params.datadir, params.nx, params.ny, &params.temperatures,
+                            &export_params, &mut rng)?;
+        }
+        return Ok(());
+    }
+
+    if args.mode == Mode::Aging {
+        create_datadir(&params.datadir, args.force)?;
+        let mut rng = Rng::new(params.rng, params.seed);
+        aging::run(&params.datadir, params.nx, params.ny, params.constants.t_quench, args.coupling, &params.constants.waiting_times,
+                   params.constants.nsweep_aging, params.constants.nrepeat_aging,
+                   if args.response { Some(params.constants.h_response) } else { None }, &mut rng)?;
+        return Ok(());
+    }
+
+    if args.mode == Mode::Interface {
+        prepare_datadir(&params.datadir, &params.temperatures, args.force)?;
+        let mut rng = Rng::new(params.rng, params.seed);
+        interface::run(&params.datadir, params.nx, params.ny, &params.temperatures, args.coupling,
+                       params.ntherm_init, params.ntherm, params.nprod, args.format, &mut rng)?;
+        return Ok(());
+    }
+
+    if let Some(updates) = &args.heisenberg {
+        prepare_datadir(&params.datadir, &params.temperatures, args.force)?;
+        let mut rng = Rng::new(params.rng, params.seed);
+        let mut analysis_rng = Rng::new(params.rng, derive_seed(params.seed, STREAM_BOOTSTRAP));
+        let model = Heisenberg{j: args.coupling, h: args.field.start(), max_angle: params.constants.max_angle_heisenberg};
+        heisenberg::run(&params.datadir, params.nx, params.ny, &params.temperatures, &model, updates,
+                        params.constants.acceptance_heisenberg, params.ntherm_init, params.ntherm, params.nprod, args.format,
+                        args.nbootstrap, &mut rng, &mut analysis_rng)?;
+        return Ok(());
+    }
+
+    if let Some(jy) = args.coupling_y {
+        if args.lattice == Geometry::Square && args.coupling_nnn.is_none() && args.potts.is_none()
+            && params.ny > 1 {
+            info!("Exact critical temperature: {}", exact::critical_temperature(args.coupling, jy));
+        }
+    }
+    if let Some(betas) = &args.anneal {
+        return run_anneal(args, betas);
+    }
+    match &args.ramp {
+        Some(ramp) => run_ramp(args, ramp.as_ref()),
+        None => run_temperatures(args).map(|_| ()),
+    }
+}
+
+/// Run a temperature scan for each lattice size and write the finite-size scaling summary to datadir.
+/**
+ * Size L runs in datadir/size_<L> with the seed of the random stream STREAM_SIZE + i where i is
+ * the index of L in sizes, see fss::write_summary for the output.
+ */
+fn run_sizes(args: &mut Args) -> Result<(), Error> {
+    let (datadir, seed) = (args.params.datadir.clone(), args.params.seed);
+    let (nx, ny) = (args.params.nx, args.params.ny);
+    prepare_datadir(&datadir, &args.params.temperatures, args.force)?;
+    let mut summaries = Vec::with_capacity(args.sizes.len());
+    for (i, &size) in args.sizes.iter().enumerate() {
+        (args.params.nx, args.params.ny) = size_extents(&args.params, size);
+        let _span = Span::enter("size", &[("nx", args.params.nx as f64), ("ny", args.params.ny as f64)]);
+        info!("Running lattice size {}x{}", args.params.nx, args.params.ny);
+        args.params.datadir = datadir.join(format!("size_{}", size));
+        args.params.seed = derive_seed(seed, STREAM_SIZE + i as u64);
+        summaries.push(run_temperatures(args)?);
+    }
+    (args.params.nx, args.params.ny) = (nx, ny);
+    args.params.datadir = datadir;
+    args.params.seed = seed;
+
+    fss::write_summary(&args.params.datadir, &args.sizes, &args.params.temperatures, &summaries)
+}
+
+/// Run a temperature scan for each disorder realisation and write the disorder averages to datadir.
+/**
+ * Realisation r runs in datadir/realisation_<r> with the seed of the random stream
+ * STREAM_REALISATION + r, which draws its disorder and drives its Markov chain.
+ * datadir/disorder_average.dat has the columns of summary.dat with the mean over all
+ * realisations and its standard error, see analysis::disorder_average.
+ */
+fn run_realisations(args: &mut Args) -> Result<(), Error> {
+    let (datadir, seed) = (args.params.datadir.clone(), args.params.seed);
+    prepare_datadir(&datadir, &args.params.temperatures, args.force)?;
+    let mut summaries = Vec::with_capacity(args.realisations);
+    for r in 0..args.realisations {
+        let _span = Span::enter("realisation", &[("realisation", r as f64)]);
+        info!("Running disorder realisation {}", r);
+        args.params.datadir = datadir.join(format!("realisation_{}", r));
+        args.params.seed = derive_seed(seed, STREAM_REALISATION + r as u64);
+        summaries.push(run_temperatures(args)?);
+    }
+    args.params.datadir = datadir;
+    args.params.seed = seed;
+
+    let mut averagefile = OutputFile::create(&args.params.datadir.join("disorder_average.dat"))?;
+    for (i, temp) in args.params.temperatures.iter().enumerate() {
+        let quantities: [fn(&Summary) -> f64; 6] = [|s| s.energy.0, |s| s.abs_magnetisation.0,
+                                                    |s| s.susceptibility.0, |s| s.specific_heat.0,
+                                                    |s| s.binder.0, |s| s.correlation_length.0];
+        let values = quantities.map(|quantity| {
+            let samples: Vec<f64> = summaries.iter().map(|summary| quantity(&summary[i])).collect();
+            let (mean, error) = analysis::disorder_average(&samples);
+            format!("{} {}", mean, error)
+        });
+        writeln!(averagefile, "{}: {} {}", i, temp, values.join(" "))?;
+    }
+    Ok(())
 }
@@ -0,0 +1,272 @@
+/*!
+ * Generic spin models with nearest neighbour interactions.
+ *
+ * A Model defines the spins, the interaction between neighbouring spins, the energy of
+ * individual sites, and the proposals of the Metropolis algorithm.
+ * Its spins live on a Configuration with the same lattice and neighbour lists as Ising spins,
+ * and the single spin update loop in this module works for any model with Metropolis or
+ * heat bath (Glauber) acceptance and visits the sites in any SweepOrder.
+ */
+
+use crate::{Configuration, Observables, Rng};
+
+/// Spin model with Hamiltonian H = Σ_⟨ij⟩ pair_energy(s_i, s_j) + Σ_i site_energy(s_i).
+pub trait Model {
+    /// Type of a single spin.
+    type Spin: Clone;
+
+    /// Draw a spin from the uniform distribution, used for hot starts.
+    fn random_spin(&self, rng: &mut Rng) -> Self::Spin;
+
+    /// Propose a new spin to replace spin in a Metropolis update.
+    /**
+     * The proposal must be symmetric, i.e. proposing a from b must be as likely as b from a.
+     */
+    fn propose(&self, spin: &Self::Spin, rng: &mut Rng) -> Self::Spin;
+
+    /// Return the interaction energy of two neighbouring spins.
+    fn pair_energy(&self, a: &Self::Spin, b: &Self::Spin) -> f64;
+
+    /// Return the energy of spin at site idx of cfg without its interactions, e.g. in a field.
+    fn site_energy(&self, _cfg: &Configuration<Self::Spin>, _idx: usize, _spin: &Self::Spin) -> f64 {
+        0.
+    }
+
+    /// Record the observables of cfg with the given energy.
+    fn measure(&self, obs: &mut Observables, cfg: &Configuration<Self::Spin>, energy: f64);
+
+    /// Create a configuration with random spins on an nx*ny lattice.
+    fn random(&self, nx: usize, ny: usize, rng: &mut Rng) -> Configuration<Self::Spin> {
+        let mut cfg = Configuration::cold(nx, ny, self.random_spin(rng));
+        for idx in 1..cfg.size() {
+            cfg[idx] = self.random_spin(rng);
+        }
+        cfg
+    }
+
+    /// Evaluate the Hamiltonian on a configuration.
+    /**
+     * Pair energies are scaled by the coupling factors of cfg, see Configuration::couplings_of.
+     */
+    fn hamiltonian(&self, cfg: &Configuration<Self::Spin>) -> f64 {
+        let mut energy = 0.;
+        for idx in 0..cfg.size() {
+            // /2 to count each link only once
+            energy += bond_energy(self, cfg, idx, &cfg[idx]) / 2.;
+            energy += self.site_energy(cfg, idx, &cfg[idx]);
+        }
+        energy
+    }
+
+    /// Compute the change in energy if the spin at site idx were replaced by spin.
+    fn delta_e(&self, cfg: &Configuration<Self::Spin>, idx: usize, spin: &Self::Spin) -> f64 {
+        let old = &cfg[idx];
+        bond_energy(self, cfg, idx, spin) - bond_energy(self, cfg, idx, old)
+            + self.site_energy(cfg, idx, spin) - self.site_energy(cfg, idx, old)
+    }
+}
+
+/// Sum the pair energies of spin at site idx with all its neighbours, scaled by the coupling factors of cfg.
+fn bond_energy<M: Model + ?Sized>(model: &M, cfg: &Configuration<M::Spin>, idx: usize, spin: &M::Spin) -> f64 {
+    let neighbours = cfg.neighbours_of(idx);
+    match cfg.couplings_of(idx) {
+        Some(factors) => neighbours.iter().zip(factors.iter())
+            .map(|(&neigh, factor)| factor*model.pair_energy(spin, &cfg[neigh])).sum(),
+        None => neighbours.iter().map(|&neigh| model.pair_energy(spin, &cfg[neigh])).sum(),
+    }
+}
+
+/// Rule to accept or reject a proposed update.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Acceptance {
+    /// Metropolis-Hastings, accept with probability min(1, exp(-β ΔE)).
+    Metropolis,
+    /// Heat bath or Glauber, accept with probability 1 / (1 + exp(β ΔE)).
+    Glauber,
+}
+
+impl Acceptance {
+    /// Return the probability to accept a proposal that changes the energy by delta, not capped at 1.
+    pub fn probability(&self, beta: f64, delta: f64) -> f64 {
+        match self {
+            Acceptance::Metropolis => (-beta*delta).exp(),
+            Acceptance::Glauber => 1. / (1. + (beta*delta).exp()),
+        }
+    }
+
+    /// Decide on a proposal that changes the energy by delta with the given probability.
+    /**
+     * Metropolis accepts proposals with delta <= 0 without drawing a random number.
+     */
+    pub fn accept(&self, delta: f64, probability: f64, rng: &mut Rng) -> bool {
+        match self {
+            Acceptance::Metropolis => delta <= 0. || probability > rng.gen_real(),
+            Acceptance::Glauber => probability > rng.gen_real(),
+        }
+    }
+}
+
+/// Order in which the updates of a sweep visit the active sites.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SweepOrder {
+    /// Draw every site independently and uniformly, so a sweep may visit some sites several times and others not.
+    #[default]
+    Random,
+    /// Visit every site once in the order of the active sites, i.e. row by row (typewriter order).
+    Sequential,
+    /// Visit every site once in a new random permutation in every sweep.
+    Shuffled,
+}
+
+impl SweepOrder {
+    /// Parse the name of a sweep order, 'random', 'sequential', or 'shuffled'.
+    pub fn parse(name: &str) -> Result<SweepOrder, String> {
+        match name {
+            "random" => Ok(SweepOrder::Random),
+            "sequential" => Ok(SweepOrder::Sequential),
+            "shuffled" => Ok(SweepOrder::Shuffled),
+            _ => Err(format!("Unknown sweep order '{}', must be 'random', 'sequential', or 'shuffled'", name)),
+        }
+    }
+
+    /// Return the name of the sweep order as accepted by parse.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SweepOrder::Random => "random",
+            SweepOrder::Sequential => "sequential",
+            SweepOrder::Shuffled => "shuffled",
+        }
+    }
+
+    /// Fill positions with the positions in the active sites of the steps of one sweep.
+    /**
+     * Random draws all positions from rng with Rng::fill_indices, Shuffled shuffles 0..positions.len()
+     * with the Fisher-Yates algorithm, and Sequential draws no random numbers.
+     */
+    pub fn fill(&self, positions: &mut [usize], rng: &mut Rng) {
+        match self {
+            SweepOrder::Random => rng.fill_indices(positions.len(), positions),
+            SweepOrder::Sequential | SweepOrder::Shuffled => {
+                positions.iter_mut().enumerate().for_each(|(i, position)| *position = i);
+                if *self == SweepOrder::Shuffled {
+                    for i in (1..positions.len()).rev() {
+                        positions.swap(i, rng.gen_index(i+1));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Parse the steps of an update schedule, a list of updates with their numbers of sweeps.
+/**
+ * Steps are separated by '+' or ',' and are either `<n> <update>` or `<update>: <n>`, e.g.
+ * `1 heat-bath + 5 overrelaxation` or `metropolis: 1, wolff: 2`. A missing count means 1.
+ * parse parses the name of an update.
+ */
+pub fn parse_steps<U>(spec: &str, parse: impl Fn(&str) -> Result<U, String>) -> Result<Vec<(usize, U)>, String> {
+    spec.split(['+', ',']).map(|step| {
+        let (count, name) = match step.split_once(':') {
+            Some((name, count)) => (Some(count.trim()), name.trim()),
+            None => match step.split_whitespace().collect::<Vec<&str>>()[..] {
+                [name] => (None, name),
+                [count, name] => (Some(count), name),
+                _ => return Err(format!("Invalid update '{}' in '{}', must be '<n> <update>' or '<update>: <n>'",
+                                        step.trim(), spec)),
+            },
+        };
+        let n = match count.map(|count| count.parse::<usize>()) {
+            None => 1,
+            Some(Ok(n)) if n > 0 => n,
+            _ => return Err(format!("Invalid number of sweeps '{}' in '{}', must be a positive integer",
+                                    count.unwrap(), spec)),
+        };
+        Ok((n, parse(name)?))
+    }).collect()
+}
+
+/// Propose a new spin at a random active site and accept or reject with the acceptance rule.
+/**
+ * Returns true if the new spin was accepted.
+ */
+pub fn update_step<M: Model>(model: &M, cfg: &mut Configuration<M::Spin>, energy: &mut f64, beta: f64,
+                             acceptance: Acceptance, rng: &mut Rng) -> bool {
+    let active = cfg.active_sites();
+    let idx = active[rng.gen_index(active.len())];
+    update_site(model, cfg, energy, beta, acceptance, idx, rng)
+}
+
+/// Like update_step at site idx.
+pub fn update_site<M: Model>(model: &M, cfg: &mut Configuration<M::Spin>, energy: &mut f64, beta: f64,
+                             acceptance: Acceptance, idx: usize, rng: &mut Rng) -> bool {
+    let spin = model.propose(&cfg[idx], rng);
+
+    let delta = model.delta_e(cfg, idx, &spin);
+    if acceptance.accept(delta, acceptance.probability(beta, delta), rng) {
+        cfg[idx] = spin;
+        *energy += delta;
+        return true;
+    }
+    false
+}
+
+/// Propose a new spin at a random active site and accept or reject with Metropolis-Hastings.
+/**
+ * Returns true if the new spin was accepted.
+ */
+pub fn metropolis_step<M: Model>(model: &M, cfg: &mut Configuration<M::Spin>, energy: &mut f64,
+                                 beta: f64, rng: &mut Rng) -> bool {
+    update_step(model, cfg, energy, beta, Acceptance::Metropolis, rng)
+}
+
+/// Evolve a configuration of any model in Monte-Carlo time with Metropolis updates.
+/**
+ * A sweep makes one update attempt per active site. Measures observables once per sweep
+ * if obs is given, see Model::measure. Returns the number of accepted updates.
+ */
+pub fn evolve<M: Model>(model: &M, cfg: &mut Configuration<M::Spin>, energy: &mut f64, beta: f64,
+                        rng: &mut Rng, nsweep: usize, obs: Option<&mut Observables>) -> usize {
+    evolve_with(model, cfg, energy, beta, Acceptance::Metropolis, rng, nsweep, obs)
+}
+
+/// Like evolve but with the given acceptance rule.
+#[allow(clippy::too_many_arguments)]
+pub fn evolve_with<M: Model>(model: &M, cfg: &mut Configuration<M::Spin>, energy: &mut f64, beta: f64,
+                             acceptance: Acceptance, rng: &mut Rng, nsweep: usize,
+                             obs: Option<&mut Observables>) -> usize {
+    evolve_in_order(model, cfg, energy, beta, acceptance, SweepOrder::Random, rng, nsweep, obs)
+}
+
+/// Like evolve_with but visiting the sites in the given order.
+/**
+ * With SweepOrder::Random, every step draws its site right before its proposal as in update_step.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn evolve_in_order<M: Model>(model: &M, cfg: &mut Configuration<M::Spin>, energy: &mut f64, beta: f64,
+                                 acceptance: Acceptance, order: SweepOrder, rng: &mut Rng, nsweep: usize,
+                                 mut obs: Option<&mut Observables>) -> usize {
+    let mut positions = vec![0; if order == SweepOrder::Random { 0 } else { cfg.active_sites().len() }];
+    let mut naccept: usize = 0;
+    for _sweep in 0..nsweep {
+        if order == SweepOrder::Random {
+            for _step in 0..cfg.active_sites().len() {
+                if update_step(model, cfg, energy, beta, acceptance, rng) {
+                    naccept += 1;
+                }
+            }
+        }
+        else {
+            order.fill(&mut positions, rng);
+            for &i in &positions {
+                let idx = cfg.active_sites()[i];
+                if update_site(model, cfg, energy, beta, acceptance, idx, rng) {
+                    naccept += 1;
+                }
+            }
+        }
+        if let Some(o) = &mut obs {
+            model.measure(o, cfg, *energy);
+        }
+    }
+    naccept
+}
@@ -0,0 +1,175 @@
+/*!
+ * Multicanonical sampling in the magnetisation at a fixed temperature.
+ *
+ * Configurations are sampled with the weight exp(-βE - W(M)) where the weight array W is
+ * iterated until the histogram of the total magnetisation M is flat. The chain then tunnels freely
+ * between the ordered phases below Tc, and the canonical distribution P(M) ∝ H(M) exp(W(M))
+ * resolves the suppressed region between its peaks, where the configurations contain two interfaces.
+ */
+
+use std::path::Path;
+
+use crate::analysis::reweighted_mean;
+use crate::error::{Error, OutputFile};
+use crate::{Configuration, Rng, delta_e, hamiltonian, info};
+
+/// Multicanonical weights W(M) on all levels of the total magnetisation M = -N, -N+2, ..., N.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Weights {
+    /// W of each level, indexed by the number of up spins (M + N) / 2.
+    pub w: Vec<f64>,
+    /// Number of occupied sites N.
+    pub nsite: usize,
+}
+
+impl Weights {
+    /// Create zero weights, i.e. canonical sampling, on a lattice with nsite occupied sites.
+    pub fn new(nsite: usize) -> Weights {
+        Weights{w: vec![0.; nsite+1], nsite}
+    }
+
+    /// Return the index of the level with total magnetisation m.
+    pub fn level(&self, m: i32) -> usize {
+        ((m + self.nsite as i32) / 2) as usize
+    }
+
+    /// Return the magnetisation per site of level index.
+    pub fn magnetisation(&self, index: usize) -> f64 {
+        (2.*index as f64 - self.nsite as f64) / self.nsite as f64
+    }
+
+    /// Apply the recursion W(M) += ln H(M) to all levels that were visited in histogram.
+    /**
+     * Shifts W such that its maximum is 0 afterwards.
+     */
+    pub fn update(&mut self, histogram: &[u64]) {
+        for (w, &count) in self.w.iter_mut().zip(histogram) {
+            if count > 0 {
+                *w += (count as f64).ln();
+            }
+        }
+        let max = self.w.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        self.w.iter_mut().for_each(|w| *w -= max);
+    }
+}
+
+/// Total energy and magnetisation per site of each sweep of a multicanonical run.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Samples {
+    /// Total energy after each sweep.
+    pub energy: Vec<f64>,
+    /// Magnetisation per site after each sweep.
+    pub magnetisation: Vec<f64>,
+    /// Histogram of the levels of all steps, see Weights::level.
+    pub histogram: Vec<u64>,
+}
+
+/// Perform nsweep sweeps of multicanonical single spin flips with the given weights.
+/**
+ * The energy of cfg with coupling j and field h is tracked in energy. Records a sample after
+ * every sweep and the level after every step.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn evolve(cfg: &mut Configuration, energy: &mut f64, beta: f64, j: f64, h: f64, weights: &Weights,
+              rng: &mut Rng, nsweep: usize) -> Samples {
+    let mut samples = Samples{histogram: vec![0; weights.w.len()], ..Samples::default()};
+    let mut m: i32 = cfg.cfg.iter().sum();
+    for _sweep in 0..nsweep {
+        for _step in 0..cfg.active_sites().len() {
+            let active = cfg.active_sites();
+            let idx = active[rng.gen_index(active.len())];
+            let delta = delta_e(cfg, idx, j, h);
+            let proposed = m - 2*cfg[idx];
+            let exponent = beta*delta + weights.w[weights.level(proposed)] - weights.w[weights.level(m)];
+            if exponent <= 0. || (-exponent).exp() > rng.gen_real() {
+                cfg[idx] *= -1;
+                *energy += delta;
+                m = proposed;
+            }
+            samples.histogram[weights.level(m)] += 1;
+        }
+        samples.energy.push(*energy);
+        samples.magnetisation.push(m as f64 / weights.nsite as f64);
+    }
+    samples
+}
+
+/// Iterate the weights niter times with nsweep sweeps each, starting from canonical sampling.
+#[allow(clippy::too_many_arguments)]
+pub fn iterate_weights(cfg: &mut Configuration, energy: &mut f64, beta: f64, j: f64, h: f64,
+                       rng: &mut Rng, niter: usize, nsweep: usize) -> Weights {
+    let mut weights = Weights::new(cfg.nsite());
+    for _iter in 0..niter {
+        let samples = evolve(cfg, energy, beta, j, h, &weights, rng, nsweep);
+        weights.update(&samples.histogram);
+    }
+    weights
+}
+
+/// Return ln P(M) of the canonical distribution from the production histogram, NaN for unvisited levels.
+pub fn canonical_distribution(weights: &Weights, histogram: &[u64]) -> Vec<f64> {
+    histogram.iter().zip(&weights.w)
+        .map(|(&count, w)| if count > 0 { (count as f64).ln() + w } else { f64::NAN })
+        .collect()
+}
+
+/// Compute ln(P_max / P_min) of a double peaked distribution ln P over the levels of the magnetisation.
+/**
+ * P_max is the smaller of the largest values for M < 0 and M > 0, P_min the smallest value between the
+ * two peaks. NaN levels are ignored. Returns 0 if P has a single peak.
+ */
+pub fn barrier(ln_p: &[f64]) -> f64 {
+    let argmax = |range: std::ops::Range<usize>| range.filter(|&i| !ln_p[i].is_nan())
+        .max_by(|&a, &b| ln_p[a].total_cmp(&ln_p[b]));
+    let half = ln_p.len() / 2;
+    match (argmax(0..half), argmax(ln_p.len()-half..ln_p.len())) {
+        (Some(left), Some(right)) => {
+            let min = ln_p[left..=right].iter().filter(|x| !x.is_nan()).cloned().fold(f64::INFINITY, f64::min);
+            ln_p[left].min(ln_p[right]) - min
+        }
+        _ => 0.,
+    }
+}
+
+/// Iterate the weights, run a multicanonical production, and reweight to the canonical ensemble.
+/**
+ * Runs niter weight iterations of nsweep_iter sweeps and nprod production sweeps at temperature temp,
+ * all with coupling j and field h. Writes `multicanonical.dat` with lines `<i>: <m> <ln P(m)> <W(m)>` for the
+ * levels that were visited in production, normalised to Σ P = 1, and `multicanonical_summary.dat` with lines
+ * `<quantity>: <value>` for the canonical energy per site and absolute magnetisation, the barrier
+ * ln(P_max / P_min), and the interface tension ln(P_max / P_min) / (2 L) for two interfaces of
+ * length L = min(nx, ny) on a periodic lattice.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn run(datadir: &Path, cfg: &mut Configuration, temp: f64, j: f64, h: f64, niter: usize, nsweep_iter: usize,
+           nprod: usize, rng: &mut Rng) -> Result<(), Error> {
+    let beta = 1./temp;
+    let mut energy = hamiltonian(cfg, j, h);
+    let weights = iterate_weights(cfg, &mut energy, beta, j, h, rng, niter, nsweep_iter);
+    let samples = evolve(cfg, &mut energy, beta, j, h, &weights, rng, nprod);
+
+    let mut ln_p = canonical_distribution(&weights, &samples.histogram);
+    let max = ln_p.iter().filter(|x| !x.is_nan()).cloned().fold(f64::NEG_INFINITY, f64::max);
+    let ln_z = max + ln_p.iter().filter(|x| !x.is_nan()).map(|x| (x - max).exp()).sum::<f64>().ln();
+    ln_p.iter_mut().for_each(|x| *x -= ln_z);
+    let mut distfile = OutputFile::create(&datadir.join("multicanonical.dat"))?;
+    for (i, (index, x)) in ln_p.iter().enumerate().filter(|(_, x)| !x.is_nan()).enumerate() {
+        writeln!(distfile, "{}: {} {} {}", i, weights.magnetisation(index), x, weights.w[index])?;
+    }
+
+    // undo the multicanonical weight of each sample
+    let log_weights: Vec<f64> = samples.magnetisation.iter()
+        .map(|&m| weights.w[weights.level((m * weights.nsite as f64).round() as i32)])
+        .collect();
+    let abs_magnetisation: Vec<f64> = samples.magnetisation.iter().map(|m| m.abs()).collect();
+    let barrier = barrier(&ln_p);
+    let length = cfg.nx.min(cfg.ny) as f64;
+    let mut summaryfile = OutputFile::create(&datadir.join("multicanonical_summary.dat"))?;
+    for (name, value) in [("energy", reweighted_mean(&samples.energy, &log_weights) / weights.nsite as f64),
+                          ("abs_magnetisation", reweighted_mean(&abs_magnetisation, &log_weights)),
+                          ("barrier", barrier), ("interface_tension", barrier / (2.*length))] {
+        info!("{}: {}", name, value);
+        writeln!(summaryfile, "{}: {}", name, value)?;
+    }
+    Ok(())
+}
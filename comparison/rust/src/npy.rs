@@ -0,0 +1,173 @@
+/*!
+ * Writers for NumPy's .npy and .npz file formats.
+ *
+ * See https://numpy.org/doc/stable/reference/generated/numpy.lib.format.html
+ * .npz files are zip archives of .npy files, stored without compression.
+ */
+
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+/// Element types that can be stored in .npy files.
+pub trait NpyElement: Copy {
+    /// NumPy type description, e.g. '<f8'.
+    const DESCR: &'static str;
+
+    /// Append the little endian bytes of self to out.
+    fn write_le(self, out: &mut Vec<u8>);
+}
+
+impl NpyElement for i8 {
+    const DESCR: &'static str = "|i1";
+    fn write_le(self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl NpyElement for i32 {
+    const DESCR: &'static str = "<i4";
+    fn write_le(self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl NpyElement for i64 {
+    const DESCR: &'static str = "<i8";
+    fn write_le(self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl NpyElement for f64 {
+    const DESCR: &'static str = "<f8";
+    fn write_le(self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+/// Encode the header of an .npy file, i.e. everything before the data.
+/**
+ * The header is padded to at least min_len bytes, which allows rewriting it with a different
+ * shape in place, e.g. when the number of elements is only known after writing the data.
+ */
+pub fn npy_header(descr: &str, shape: &[usize], fortran_order: bool, min_len: usize) -> Vec<u8> {
+    let shape_str = match shape {
+        [n] => format!("({},)", n),
+        _ => format!("({})", shape.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(", ")),
+    };
+    let mut header = format!("{{'descr': '{}', 'fortran_order': {}, 'shape': {}, }}",
+                             descr, if fortran_order { "True" } else { "False" }, shape_str);
+    // magic (6) + version (2) + header length (2) + header + '\n' must be a multiple of 64
+    let unpadded = 10 + header.len() + 1;
+    let padded = unpadded.max(min_len).next_multiple_of(64);
+    header.push_str(&" ".repeat(padded - unpadded));
+    header.push('\n');
+
+    let mut bytes = Vec::with_capacity(padded);
+    bytes.extend_from_slice(b"\x93NUMPY\x01\x00");
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    bytes
+}
+
+/// Encode an array with given shape in C order as the contents of an .npy file.
+pub fn npy_bytes<T: NpyElement>(shape: &[usize], data: &[T]) -> Vec<u8> {
+    assert_eq!(shape.iter().product::<usize>(), data.len(), "Shape does not match data");
+
+    let mut bytes = npy_header(T::DESCR, shape, false, 0);
+    bytes.reserve(std::mem::size_of_val(data));
+    for &x in data {
+        x.write_le(&mut bytes);
+    }
+    bytes
+}
+
+/// Collect arrays and write them as an .npz archive.
+#[derive(Default)]
+pub struct NpzWriter {
+    /// File names in the archive and .npy contents.
+    entries: Vec<(String, Vec<u8>)>,
+}
+
+impl NpzWriter {
+    /// Create an empty archive.
+    pub fn new() -> NpzWriter {
+        NpzWriter::default()
+    }
+
+    /// Add an array that is loaded as `name` by numpy.load.
+    pub fn add<T: NpyElement>(&mut self, name: &str, shape: &[usize], data: &[T]) {
+        self.entries.push((format!("{}.npy", name), npy_bytes(shape, data)));
+    }
+
+    /// Write the archive to a file.
+    pub fn write(&self, fname: &Path) -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(fname)?);
+        let mut central = Vec::new();
+        let mut offset: usize = 0;
+
+        for (name, data) in self.entries.iter() {
+            assert!(offset + data.len() < u32::MAX as usize, "npz archive too large");
+            let crc = crc32(data);
+
+            let mut local = Vec::new();
+            local.extend_from_slice(&0x04034b50u32.to_le_bytes());
+            push_entry_info(&mut local, crc, data.len(), name.len());
+            local.extend_from_slice(&0u16.to_le_bytes());  // extra field length
+            local.extend_from_slice(name.as_bytes());
+            file.write_all(&local)?;
+            file.write_all(data)?;
+
+            central.extend_from_slice(&0x02014b50u32.to_le_bytes());
+            central.extend_from_slice(&20u16.to_le_bytes());  // version made by
+            push_entry_info(&mut central, crc, data.len(), name.len());
+            central.extend_from_slice(&[0; 12]);  // extra, comment, disk, attributes
+            central.extend_from_slice(&(offset as u32).to_le_bytes());
+            central.extend_from_slice(name.as_bytes());
+
+            offset += local.len() + data.len();
+        }
+
+        file.write_all(&central)?;
+        let mut end = Vec::new();
+        end.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        end.extend_from_slice(&[0; 4]);  // disk numbers
+        end.extend_from_slice(&(self.entries.len() as u16).to_le_bytes());
+        end.extend_from_slice(&(self.entries.len() as u16).to_le_bytes());
+        end.extend_from_slice(&(central.len() as u32).to_le_bytes());
+        end.extend_from_slice(&(offset as u32).to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());  // comment length
+        file.write_all(&end)?;
+        file.flush()
+    }
+}
+
+/// Append the part of a zip header that is shared between local and central headers.
+/**
+ * Everything from 'version needed' to 'file name length' for an uncompressed entry.
+ */
+fn push_entry_info(header: &mut Vec<u8>, crc: u32, size: usize, name_len: usize) {
+    header.extend_from_slice(&20u16.to_le_bytes());  // version needed
+    header.extend_from_slice(&0u16.to_le_bytes());  // flags
+    header.extend_from_slice(&0u16.to_le_bytes());  // compression: stored
+    header.extend_from_slice(&0u16.to_le_bytes());  // modification time
+    header.extend_from_slice(&0x21u16.to_le_bytes());  // modification date, 1980-01-01
+    header.extend_from_slice(&crc.to_le_bytes());
+    header.extend_from_slice(&(size as u32).to_le_bytes());  // compressed size
+    header.extend_from_slice(&(size as u32).to_le_bytes());  // uncompressed size
+    header.extend_from_slice(&(name_len as u16).to_le_bytes());
+}
+
+/// Compute the CRC-32 checksum used by zip.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,99 @@
+/*!
+ * Study the decay of metastable states through nucleation.
+ */
+
+use std::path::Path;
+
+use crate::clusters::largest_cluster;
+use crate::error::{Error, OutputFile};
+use crate::{Configuration, Rng, evolve, hamiltonian, info, magnetisation};
+
+/// Outcome of a single decay of a metastable state.
+pub struct Decay {
+    /// Number of sweeps until the magnetisation changed sign.
+    pub lifetime: usize,
+    /// Estimate of the size of the critical droplet.
+    /**
+     * This is the size of the largest cluster of spins aligned with the field
+     * at the last sweep before the reversal in which that size did not grow.
+     * From then on, the droplet grows without shrinking until the magnetisation
+     * has reversed, so it was the last droplet that could still have decayed.
+     */
+    pub droplet: usize,
+}
+
+/// Prepare the metastable state with all spins against field h and evolve it with coupling j until it decays.
+/**
+ * The state is only metastable for a ferromagnetic coupling j > 0.
+ * Returns None if the magnetisation has not reversed after maxsweep sweeps.
+ */
+pub fn decay(nx: usize, ny: usize, beta: f64, j: f64, h: f64, rng: &mut Rng,
+             maxsweep: usize) -> Option<Decay> {
+    // all spins point against the field
+    let spin = if h > 0. { 1 } else { -1 };
+    let mut cfg = Configuration::cold(nx, ny, -spin);
+    let mut energy = hamiltonian(&cfg, j, h);
+
+    // size of the largest cluster the last time it did not grow
+    let mut droplet = 0;
+    let mut previous = 0;
+    for sweep in 1..=maxsweep {
+        evolve(&mut cfg, &mut energy, beta, j, h, rng, 1, None);
+
+        if magnetisation(&cfg) * spin as f64 > 0. {
+            return Some(Decay{lifetime: sweep, droplet});
+        }
+
+        let size = largest_cluster(&cfg, spin);
+        if size <= previous {
+            droplet = size;
+        }
+        previous = size;
+    }
+
+    None
+}
+
+/// Run repeated decays with coupling j for all pairs of temperatures and fields and write results to datadir.
+/**
+ * Writes `nucleation.dat` with lines `<iT>_<ih>: <T> <h>` and for each pair a file
+ * `<iT>_<ih>.dat` with lifetimes in the first row and droplet sizes in the second.
+ * Runs that did not decay within maxsweep sweeps are stored as -1 in both rows.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn run(datadir: &Path, nx: usize, ny: usize, temperatures: &[f64], j: f64, fields: &[f64],
+           nrepeat: usize, maxsweep: usize, rng: &mut Rng) -> Result<(), Error> {
+    let mut indexfile = OutputFile::create(&datadir.join("nucleation.dat"))?;
+
+    for (it, temp) in temperatures.iter().enumerate() {
+        for (ih, h) in fields.iter().enumerate() {
+            info!("Running nucleation for temperature {} and field {}", temp, h);
+            writeln!(indexfile, "{}_{}: {} {}", it, ih, temp, h)?;
+
+            let decays: Vec<Option<Decay>> = (0..nrepeat)
+                .map(|_| decay(nx, ny, 1./temp, j, *h, rng, maxsweep))
+                .collect();
+
+            let decayed: Vec<&Decay> = decays.iter().flatten().collect();
+            if decayed.is_empty() {
+                info!("  No decay within {} sweeps", maxsweep);
+            } else {
+                let mean = decayed.iter().map(|d| d.lifetime as f64).sum::<f64>()
+                    / decayed.len() as f64;
+                info!("  {} of {} runs decayed, mean lifetime {} sweeps",
+                      decayed.len(), nrepeat, mean);
+            }
+
+            let mut outfile = OutputFile::create(&datadir.join(format!("{}_{}.dat", it, ih)))?;
+            for d in decays.iter() {
+                write!(outfile, "{} ", d.as_ref().map_or(-1, |d| d.lifetime as i64))?;
+            }
+            writeln!(outfile)?;
+            for d in decays.iter() {
+                write!(outfile, "{} ", d.as_ref().map_or(-1, |d| d.droplet as i64))?;
+            }
+            writeln!(outfile)?;
+        }
+    }
+    Ok(())
+}
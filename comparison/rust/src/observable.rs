@@ -0,0 +1,216 @@
+/*!
+ * Observables that are measured by name in addition to those stored in Observables.
+ *
+ * An Observable computes a number or a vector of numbers from a configuration.
+ * The Registry holds the built-in observables and any that are registered through the library;
+ * the `observables` key of a config file and `--observables` select from it by name.
+ * Observables::with_observables creates a history that measures the selected observables
+ * alongside energy and magnetisation in every evolve.
+ */
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{Configuration, bond_sum, magnetisation, stripe_magnetisation, unsatisfied_bonds};
+use crate::clusters::largest_cluster;
+
+/// A quantity that is measured on an Ising configuration.
+pub trait Observable: Send + Sync {
+    /// Return the name under which the observable is registered and its measurements are written.
+    fn name(&self) -> &str;
+
+    /// Measure the observable on cfg.
+    fn measure(&self, cfg: &Configuration) -> f64;
+
+    /// Measure all components of a vector valued observable on cfg.
+    /**
+     * Defaults to the single component returned by measure. Vector valued observables override this,
+     * their measure should return a summary such as the first component.
+     */
+    fn measure_vector(&self, cfg: &Configuration) -> Vec<f64> {
+        vec![self.measure(cfg)]
+    }
+}
+
+/// Absolute value of the magnetisation per site.
+pub struct AbsMagnetisation;
+
+impl Observable for AbsMagnetisation {
+    fn name(&self) -> &str {
+        "abs_magnetisation"
+    }
+
+    fn measure(&self, cfg: &Configuration) -> f64 {
+        magnetisation(cfg).abs()
+    }
+}
+
+/// Stripe magnetisation, see stripe_magnetisation.
+pub struct StripeMagnetisation;
+
+impl Observable for StripeMagnetisation {
+    fn name(&self) -> &str {
+        "stripe_magnetisation"
+    }
+
+    fn measure(&self, cfg: &Configuration) -> f64 {
+        stripe_magnetisation(cfg)
+    }
+}
+
+/// Nearest neighbour correlation, the mean of s_i s_j over all links <ij>.
+pub struct BondCorrelation;
+
+impl Observable for BondCorrelation {
+    fn name(&self) -> &str {
+        "bond_correlation"
+    }
+
+    fn measure(&self, cfg: &Configuration) -> f64 {
+        let nlink: usize = (0..cfg.size()).map(|idx| cfg.neighbours_of(idx).len()).sum::<usize>() / 2;
+        if nlink == 0 { 0. } else { bond_sum(cfg) as f64 / nlink as f64 }
+    }
+}
+
+/// Total length of the domain walls, the number of unsatisfied links, see unsatisfied_bonds.
+/**
+ * The difference of its mean between anti-periodic and periodic boundaries along one direction
+ * is the length of the interface that the boundaries force into the ordered phase.
+ */
+pub struct DomainWallLength;
+
+impl Observable for DomainWallLength {
+    fn name(&self) -> &str {
+        "domain_wall_length"
+    }
+
+    fn measure(&self, cfg: &Configuration) -> f64 {
+        unsatisfied_bonds(cfg) as f64
+    }
+}
+
+/// Size of the largest geometric cluster of either spin per occupied site, see clusters.
+pub struct LargestCluster;
+
+impl Observable for LargestCluster {
+    fn name(&self) -> &str {
+        "largest_cluster"
+    }
+
+    fn measure(&self, cfg: &Configuration) -> f64 {
+        largest_cluster(cfg, 1).max(largest_cluster(cfg, -1)) as f64 / cfg.nsite() as f64
+    }
+}
+
+/// Magnetisation per site of each row, a vector with ny components.
+/**
+ * measure returns the largest absolute value, which is close to 1 inside a domain spanning a row
+ * even if the total magnetisation vanishes.
+ */
+pub struct RowMagnetisation;
+
+impl Observable for RowMagnetisation {
+    fn name(&self) -> &str {
+        "row_magnetisation"
+    }
+
+    fn measure(&self, cfg: &Configuration) -> f64 {
+        self.measure_vector(cfg).into_iter().fold(0., |max, m| f64::max(max, m.abs()))
+    }
+
+    fn measure_vector(&self, cfg: &Configuration) -> Vec<f64> {
+        cfg.cfg.chunks(cfg.nx)
+            .map(|row| row.iter().sum::<i32>() as f64 / cfg.nx as f64)
+            .collect()
+    }
+}
+
+/// Look up observables by name.
+#[derive(Clone)]
+pub struct Registry {
+    observables: Vec<Arc<dyn Observable>>,
+}
+
+impl Registry {
+    /// Create a registry with the built-in observables.
+    pub fn new() -> Registry {
+        Registry{observables: vec![Arc::new(AbsMagnetisation), Arc::new(StripeMagnetisation),
+                                   Arc::new(BondCorrelation), Arc::new(DomainWallLength), Arc::new(LargestCluster),
+                                   Arc::new(RowMagnetisation)]}
+    }
+
+    /// Add an observable, fails if there already is one with the same name.
+    pub fn register(&mut self, observable: Arc<dyn Observable>) -> Result<(), String> {
+        if self.get(observable.name()).is_some() {
+            return Err(format!("Observable '{}' is already registered", observable.name()));
+        }
+        self.observables.push(observable);
+        Ok(())
+    }
+
+    /// Return the observable with the given name.
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Observable>> {
+        self.observables.iter().find(|observable| observable.name() == name).cloned()
+    }
+
+    /// Return the observables with the given names in that order, fails on unknown or repeated names.
+    pub fn select(&self, names: &[String]) -> Result<Vec<Arc<dyn Observable>>, String> {
+        names.iter().enumerate().map(|(i, name)| {
+            if names[..i].contains(name) {
+                return Err(format!("Observable '{}' is selected more than once", name));
+            }
+            self.get(name).ok_or_else(|| format!("Unknown observable '{}', expected one of: {}",
+                                                 name, self.names().join(", ")))
+        }).collect()
+    }
+
+    /// Return the names of all registered observables.
+    pub fn names(&self) -> Vec<&str> {
+        self.observables.iter().map(|observable| observable.name()).collect()
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Registry {
+        Registry::new()
+    }
+}
+
+/// Monte-Carlo history of an observable, see Observables::custom.
+#[derive(Clone)]
+pub struct Series {
+    /// The measured observable.
+    pub observable: Arc<dyn Observable>,
+    /// All components of the observable after each measurement.
+    pub values: Vec<Vec<f64>>,
+}
+
+impl Series {
+    /// Create an empty history of observable.
+    pub fn new(observable: Arc<dyn Observable>) -> Series {
+        Series{observable, values: Vec::new()}
+    }
+
+    /// Return the name of the observable.
+    pub fn name(&self) -> &str {
+        self.observable.name()
+    }
+
+    /// Record the observable on cfg.
+    pub fn measure(&mut self, cfg: &Configuration) {
+        self.values.push(self.observable.measure_vector(cfg));
+    }
+}
+
+impl fmt::Debug for Series {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Series").field("name", &self.name()).field("values", &self.values).finish()
+    }
+}
+
+impl PartialEq for Series {
+    /// Series are equal if their observables have the same name and they hold the same values.
+    fn eq(&self, other: &Series) -> bool {
+        self.name() == other.name() && self.values == other.values
+    }
+}
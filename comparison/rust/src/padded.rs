@@ -0,0 +1,93 @@
+/*!
+ * Spins of periodic square lattices in a padded array with ghost rows and columns.
+ *
+ * The nx*ny spins are stored in an (nx+2)*(ny+2) array whose outermost rows and columns are copies of
+ * the opposite boundaries. The four neighbours of a site are then at fixed offsets, ±1 and ±(nx+2),
+ * so the neighbour sum needs no lookups in the neighbour lists of Configuration, which take more memory
+ * than the spins themselves. Single flip sweeps in zero field use this layout on periodic square
+ * lattices, see Configuration::is_periodic_square, while other lattices keep using the neighbour lists.
+ */
+
+use crate::Configuration;
+
+/// Ising spins of a periodic square lattice padded by ghost rows and columns.
+pub struct Padded {
+    /// Number of sites in x direction, without the ghosts.
+    nx: usize,
+    /// Number of sites in y direction, without the ghosts.
+    ny: usize,
+    /// Spins of row y and column x at (y+1)*(nx+2) + x+1, ghosts in rows 0 and ny+1 and columns 0 and nx+1.
+    spins: Vec<i32>,
+}
+
+impl Padded {
+    /// Copy the spins of cfg, which must be on a periodic square lattice.
+    pub fn new(cfg: &Configuration) -> Padded {
+        assert!(cfg.is_periodic_square(), "The padded layout needs a periodic square lattice");
+        let mut padded = Padded{nx: cfg.nx, ny: cfg.ny, spins: vec![0; (cfg.nx+2)*(cfg.ny+2)]};
+        padded.read(cfg);
+        padded
+    }
+
+    /// Return the index in the padded array of site idx of the configuration.
+    pub fn index(&self, idx: usize) -> usize {
+        let (y, x) = (idx / self.nx, idx % self.nx);
+        (y+1)*(self.nx+2) + x+1
+    }
+
+    /// Return the spin at padded index p.
+    pub fn spin(&self, p: usize) -> i32 {
+        self.spins[p]
+    }
+
+    /// Return the sum of the spins of the four neighbours of the site at padded index p.
+    pub fn neighbour_sum(&self, p: usize) -> i32 {
+        let width = self.nx + 2;
+        self.spins[p-1] + self.spins[p+1] + self.spins[p-width] + self.spins[p+width]
+    }
+
+    /// Flip the spin at padded index p and its ghost copies.
+    /**
+     * Sites on the boundary have a copy in the ghost column or row on the opposite side, corners in both
+     * and in the ghost corner. Keeping them up to date with every flip lets single flip updates visit the
+     * sites in any order.
+     */
+    pub fn flip(&mut self, p: usize) {
+        let (nx, ny, width) = (self.nx, self.ny, self.nx + 2);
+        self.spins[p] *= -1;
+        let (y, x) = (p / width, p % width);
+        // with nx = 1 or ny = 1, a site is on both boundaries
+        let ghost_xs = [(x == 1).then_some(nx+1), (x == nx).then_some(0)];
+        let ghost_ys = [(y == 1).then_some(ny+1), (y == ny).then_some(0)];
+        for gx in ghost_xs.into_iter().flatten() {
+            self.spins[y*width + gx] *= -1;
+        }
+        for gy in ghost_ys.into_iter().flatten() {
+            self.spins[gy*width + x] *= -1;
+            for gx in ghost_xs.into_iter().flatten() {
+                self.spins[gy*width + gx] *= -1;
+            }
+        }
+    }
+
+    /// Copy the spins of cfg, which must be on the same lattice, and fill the ghosts.
+    pub fn read(&mut self, cfg: &Configuration) {
+        let (nx, ny, width) = (self.nx, self.ny, self.nx + 2);
+        for y in 0..ny {
+            let row = &mut self.spins[(y+1)*width..(y+2)*width];
+            row[1..=nx].copy_from_slice(&cfg.cfg[y*nx..(y+1)*nx]);
+            row[0] = row[nx];
+            row[nx+1] = row[1];
+        }
+        self.spins.copy_within(ny*width..(ny+1)*width, 0);
+        self.spins.copy_within(width..2*width, (ny+1)*width);
+    }
+
+    /// Copy the spins without the ghosts to cfg, which must be on the same lattice.
+    pub fn write(&self, cfg: &mut Configuration) {
+        let (nx, width) = (self.nx, self.nx + 2);
+        for y in 0..self.ny {
+            cfg.cfg[y*nx..(y+1)*nx].copy_from_slice(&self.spins[(y+1)*width + 1..][..nx]);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+/*!
+ * Simple plots of results written as SVG files.
+ *
+ * Plots have linear axes with ticks at multiples of 1, 2, or 5 times a power of ten and show any number of
+ * series as lines, points with error bars, or bars. They are meant for a quick look at a run,
+ * publication plots are better made from the data files.
+ */
+
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::error::{Error, write_file};
+
+/// Width of a plot in pixels.
+pub const WIDTH: f64 = 640.;
+
+/// Height of a plot in pixels.
+pub const HEIGHT: f64 = 420.;
+
+/// Space between the edges of the image and the axes in pixels, left, right, top, bottom.
+const MARGINS: [f64; 4] = [80., 20., 40., 50.];
+
+/// Approximate number of ticks per axis.
+const NTICK: usize = 5;
+
+/// Colours of consecutive series.
+const COLOURS: [&str; 4] = ["#1f77b4", "#d62728", "#2ca02c", "#ff7f0e"];
+
+/// How a series is drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Style {
+    /// Connected by straight lines.
+    Line,
+    /// Markers with error bars.
+    Points,
+    /// Bars of a histogram centred on x, the width is the spacing of the first two x.
+    Bars,
+}
+
+/// Data of one series of a plot.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Series {
+    /// Horizontal coordinates.
+    pub x: Vec<f64>,
+    /// Vertical coordinates.
+    pub y: Vec<f64>,
+    /// Errors of y, only drawn for Style::Points.
+    pub error: Option<Vec<f64>>,
+    /// How the series is drawn.
+    pub style: Style,
+}
+
+/// A plot with labelled axes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plot {
+    /// Title above the plot.
+    pub title: String,
+    /// Label of the horizontal axis.
+    pub xlabel: String,
+    /// Label of the vertical axis.
+    pub ylabel: String,
+    /// Series in the order they are drawn.
+    pub series: Vec<Series>,
+}
+
+impl Plot {
+    /// Create an empty plot.
+    pub fn new(title: &str, xlabel: &str, ylabel: &str) -> Plot {
+        Plot{title: title.to_string(), xlabel: xlabel.to_string(), ylabel: ylabel.to_string(), series: Vec::new()}
+    }
+
+    /// Add a series drawn as a line.
+    pub fn line(mut self, x: &[f64], y: &[f64]) -> Plot {
+        self.series.push(Series{x: x.to_vec(), y: y.to_vec(), error: None, style: Style::Line});
+        self
+    }
+
+    /// Add a series drawn as points with error bars.
+    pub fn points(mut self, x: &[f64], y: &[f64], error: &[f64]) -> Plot {
+        self.series.push(Series{x: x.to_vec(), y: y.to_vec(), error: Some(error.to_vec()), style: Style::Points});
+        self
+    }
+
+    /// Add a histogram with the given bin centres and counts.
+    pub fn bars(mut self, centres: &[f64], counts: &[f64]) -> Plot {
+        self.series.push(Series{x: centres.to_vec(), y: counts.to_vec(), error: None, style: Style::Bars});
+        self
+    }
+
+    /// Return the ranges of x and y of all series, including error bars, bars, and zero for bars.
+    fn ranges(&self) -> ((f64, f64), (f64, f64)) {
+        let mut x = (f64::INFINITY, f64::NEG_INFINITY);
+        let mut y = x;
+        let extend = |range: &mut (f64, f64), value: f64| if value.is_finite() {
+            *range = (range.0.min(value), range.1.max(value));
+        };
+        for series in &self.series {
+            let half_width = bar_width(series) / 2.;
+            for (i, (&xi, &yi)) in series.x.iter().zip(series.y.iter()).enumerate() {
+                let err = series.error.as_ref().map_or(0., |error| error[i]);
+                extend(&mut x, xi - half_width);
+                extend(&mut x, xi + half_width);
+                extend(&mut y, yi - err);
+                extend(&mut y, yi + err);
+                if series.style == Style::Bars {
+                    extend(&mut y, 0.);
+                }
+            }
+        }
+        (pad(x), pad(y))
+    }
+
+    /// Return the plot as an SVG document.
+    pub fn svg(&self) -> String {
+        let ((xmin, xmax), (ymin, ymax)) = self.ranges();
+        let [left, right, top, bottom] = MARGINS;
+        let (x0, x1, y0, y1) = (left, WIDTH - right, HEIGHT - bottom, top);
+        let px = |x: f64| x0 + (x - xmin) / (xmax - xmin) * (x1 - x0);
+        let py = |y: f64| y0 + (y - ymin) / (ymax - ymin) * (y1 - y0);
+
+        let mut svg = String::new();
+        writeln!(svg, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+                       font-family=\"sans-serif\" font-size=\"13\">", WIDTH, HEIGHT).unwrap();
+        writeln!(svg, "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>").unwrap();
+        writeln!(svg, "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\" font-size=\"15\">{}</text>",
+                 (x0 + x1) / 2., top / 2. + 5., escape(&self.title)).unwrap();
+
+        // axes, ticks, and labels
+        writeln!(svg, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"black\"/>",
+                 x0, y1, x1 - x0, y0 - y1).unwrap();
+        for (tick, label) in ticks(xmin, xmax) {
+            writeln!(svg, "<line x1=\"{0:.1}\" y1=\"{1}\" x2=\"{0:.1}\" y2=\"{2}\" stroke=\"black\"/>\
+                           <text x=\"{0:.1}\" y=\"{3}\" text-anchor=\"middle\">{4}</text>",
+                     px(tick), y0, y0 - 5., y0 + 18., label).unwrap();
+        }
+        for (tick, label) in ticks(ymin, ymax) {
+            writeln!(svg, "<line x1=\"{0}\" y1=\"{1:.1}\" x2=\"{2}\" y2=\"{1:.1}\" stroke=\"black\"/>\
+                           <text x=\"{3}\" y=\"{4:.1}\" text-anchor=\"end\">{5}</text>",
+                     x0, py(tick), x0 + 5., x0 - 6., py(tick) + 4., label).unwrap();
+        }
+        writeln!(svg, "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+                 (x0 + x1) / 2., HEIGHT - 12., escape(&self.xlabel)).unwrap();
+        writeln!(svg, "<text transform=\"translate(18 {}) rotate(-90)\" text-anchor=\"middle\">{}</text>",
+                 (y0 + y1) / 2., escape(&self.ylabel)).unwrap();
+
+        for (series, colour) in self.series.iter().zip(COLOURS.iter().cycle()) {
+            // skip NaN, e.g. the correlation length of disordered lattices
+            let points = series.x.iter().zip(series.y.iter()).enumerate()
+                .filter(|(_, (x, y))| x.is_finite() && y.is_finite());
+            match series.style {
+                Style::Line => {
+                    let coordinates: Vec<String> = points.map(|(_, (&x, &y))| format!("{:.1},{:.1}", px(x), py(y)))
+                        .collect();
+                    writeln!(svg, "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\"/>",
+                             coordinates.join(" "), colour).unwrap();
+                }
+                Style::Points => {
+                    for (i, (&x, &y)) in points {
+                        let err = series.error.as_ref().map_or(0., |error| error[i]);
+                        if err > 0. {
+                            writeln!(svg, "<line x1=\"{0:.1}\" y1=\"{1:.1}\" x2=\"{0:.1}\" y2=\"{2:.1}\" \
+                                           stroke=\"{3}\"/>", px(x), py(y - err), py(y + err), colour).unwrap();
+                        }
+                        writeln!(svg, "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"{}\"/>",
+                                 px(x), py(y), colour).unwrap();
+                    }
+                }
+                Style::Bars => {
+                    let half_width = bar_width(series) / 2.;
+                    for (_, (&x, &y)) in points {
+                        let (top, base) = (py(y.max(0.)), py(y.min(0.)));
+                        writeln!(svg, "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"/>",
+                                 px(x - half_width), top, px(x + half_width) - px(x - half_width), base - top,
+                                 colour).unwrap();
+                    }
+                }
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Write the plot to an SVG file.
+    pub fn write(&self, fname: &Path) -> Result<(), Error> {
+        write_file(fname, self.svg())
+    }
+}
+
+/// Return the width of the bars of a series, 0 unless it is drawn as bars.
+fn bar_width(series: &Series) -> f64 {
+    match (series.style, series.x.as_slice()) {
+        (Style::Bars, [first, second, ..]) => second - first,
+        (Style::Bars, _) => 1.,
+        _ => 0.,
+    }
+}
+
+/// Widen a range by 5% on both sides, empty or zero width ranges become unit ranges.
+fn pad(range: (f64, f64)) -> (f64, f64) {
+    let (min, max) = if range.0 <= range.1 { range } else { (0., 1.) };
+    let margin = if max > min { 0.05 * (max - min) } else { 0.5 * min.abs().max(1.) };
+    (min - margin, max + margin)
+}
+
+/// Return ticks between min and max at a multiple of 1, 2, or 5 times a power of ten, with their labels.
+pub fn ticks(min: f64, max: f64) -> Vec<(f64, String)> {
+    let rough = (max - min) / NTICK as f64;
+    let magnitude = 10_f64.powf(rough.log10().floor());
+    // the step whose number of ticks is closest to NTICK
+    let step = [1., 2., 5., 10.].iter().map(|m| m * magnitude)
+        .min_by(|a, b| (rough / a - 1.).abs().total_cmp(&(rough / b - 1.).abs())).unwrap();
+    let decimals = (-step.log10().floor()).max(0.) as usize;
+    ((min / step).ceil() as i64..=(max / step).floor() as i64)
+        .map(|k| {
+            let tick = k as f64 * step;
+            (tick, format!("{:.*}", decimals, tick + 0.))
+        })
+        .collect()
+}
+
+/// Escape the characters of text that have a meaning in XML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
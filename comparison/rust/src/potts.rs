@@ -0,0 +1,119 @@
+/*!
+ * q-state Potts model H = -J Σ_⟨ij⟩ δ(s_i, s_j) on the lattices of Configuration.
+ *
+ * Spins are stored in Configuration::cfg as states 0, ..., q-1. Observables use the
+ * representation exp(2πi s/q) of each spin, which reduces to Ising spins ±1 for q = 2.
+ * For q = 2, the energy is that of the Ising model with coupling J/2 shifted by -J/2 per bond.
+ * Potts implements model::Model, so model::evolve runs the Metropolis algorithm.
+ */
+
+use std::f64::consts::PI;
+
+use crate::{Configuration, Observables, Rng};
+use crate::fourier::Complex;
+use crate::model::Model;
+
+/// Parameters of the Potts model.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Potts {
+    /// Number of states.
+    pub q: usize,
+    /// Coupling constant.
+    pub j: f64,
+}
+
+/// Count the neighbours of site idx in the given state, weighted with the coupling factors of cfg.
+fn count_equal(cfg: &Configuration, idx: usize, state: i32) -> f64 {
+    let neighbours = cfg.neighbours_of(idx);
+    match cfg.couplings_of(idx) {
+        Some(factors) => neighbours.iter().zip(factors.iter())
+            .filter(|(&neigh, _)| cfg[neigh] == state).map(|(_, factor)| factor).sum(),
+        None => neighbours.iter().filter(|&&neigh| cfg[neigh] == state).count() as f64,
+    }
+}
+
+impl Model for Potts {
+    type Spin = i32;
+
+    fn random_spin(&self, rng: &mut Rng) -> i32 {
+        rng.gen_index(self.q) as i32
+    }
+
+    /// Draw the new state uniformly from the q-1 states that differ from spin.
+    fn propose(&self, spin: &i32, rng: &mut Rng) -> i32 {
+        ((*spin as usize + 1 + rng.gen_index(self.q-1)) % self.q) as i32
+    }
+
+    fn pair_energy(&self, a: &i32, b: &i32) -> f64 {
+        if a == b { -self.j } else { 0. }
+    }
+
+    fn measure(&self, obs: &mut Observables, cfg: &Configuration, energy: f64) {
+        measure(obs, cfg, energy, self.q);
+    }
+
+    /// Count equal neighbours instead of summing pair energies, which is exact for uniform couplings.
+    fn hamiltonian(&self, cfg: &Configuration) -> f64 {
+        let equal: f64 = (0..cfg.size()).map(|idx| count_equal(cfg, idx, cfg[idx])).sum();
+        // /2 to count each link only once
+        -self.j*(equal/2.)
+    }
+
+    fn delta_e(&self, cfg: &Configuration, idx: usize, spin: &i32) -> f64 {
+        -self.j*(count_equal(cfg, idx, *spin) - count_equal(cfg, idx, cfg[idx]))
+    }
+}
+
+/// Return the spin at site idx as exp(2πi s/q).
+fn spin_vector(cfg: &Configuration, idx: usize, q: usize) -> Complex {
+    let (s, c) = (2.*PI*cfg[idx] as f64 / q as f64).sin_cos();
+    (c, s)
+}
+
+/// Compute the order parameter |Σ_i exp(2πi s_i/q)| / N.
+pub fn order_parameter(cfg: &Configuration, q: usize) -> f64 {
+    let (re, im) = (0..cfg.size()).map(|idx| spin_vector(cfg, idx, q))
+        .fold((0., 0.), |(re, im), (a, b)| (re + a, im + b));
+    (re*re + im*im).sqrt() / cfg.size() as f64
+}
+
+/// Compute the staggered order parameter |Σ_i (-1)^(x_i+y_i) exp(2πi s_i/q)| / N.
+pub fn staggered_order_parameter(cfg: &Configuration, q: usize) -> f64 {
+    let (re, im) = (0..cfg.size()).map(|idx| {
+        let (a, b) = spin_vector(cfg, idx, q);
+        if (idx % cfg.nx + idx / cfg.nx).is_multiple_of(2) { (a, b) } else { (-a, -b) }
+    }).fold((0., 0.), |(re, im), (a, b)| (re + a, im + b));
+    (re*re + im*im).sqrt() / cfg.size() as f64
+}
+
+/// Compute the structure factor of the spin vectors at k = (2π/nx, 0).
+/**
+ * Same as fourier::smallest_structure_factor for q = 2.
+ */
+pub fn smallest_structure_factor(cfg: &Configuration, q: usize) -> f64 {
+    let k = 2.*PI / cfg.nx as f64;
+    let mut re = 0.;
+    let mut im = 0.;
+    for y in 0..cfg.ny {
+        for x in 0..cfg.nx {
+            let (a, b) = spin_vector(cfg, y*cfg.nx + x, q);
+            let (s, c) = (k*x as f64).sin_cos();
+            // (a + ib) exp(-ikx)
+            re += a*c + b*s;
+            im += b*c - a*s;
+        }
+    }
+    (re*re + im*im) / cfg.size() as f64
+}
+
+/// Record the observables of a Potts configuration with the given energy.
+/**
+ * The magnetisation and staggered magnetisation of obs hold the order parameters,
+ * which are never negative, unlike the Ising magnetisation.
+ */
+pub fn measure(obs: &mut Observables, cfg: &Configuration, energy: f64, q: usize) {
+    obs.energy.push(energy);
+    obs.magnetisation.push(order_parameter(cfg, q));
+    obs.staggered.push(staggered_order_parameter(cfg, q));
+    obs.structure.push(smallest_structure_factor(cfg, q));
+}
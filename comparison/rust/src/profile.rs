@@ -0,0 +1,100 @@
+/*!
+ * Deterministic, spatially varying field profiles h(x, y).
+ */
+
+use crate::Configuration;
+
+/// Lattice direction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Axis {
+    /// Along rows.
+    X,
+    /// Along columns.
+    Y,
+}
+
+/// Shape of a field profile.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Profile {
+    /// Interpolate linearly from start at coordinate 0 to stop at the last row/column along axis.
+    Gradient { axis: Axis, start: f64, stop: f64 },
+    /// Field below for coordinates < position along axis and above otherwise.
+    Step { axis: Axis, position: f64, below: f64, above: f64 },
+    /// Gaussian spot h exp(-r² / (2 width²)) around (x0, y0) with periodic distances.
+    Gaussian { h: f64, x0: f64, y0: f64, width: f64 },
+}
+
+impl Profile {
+    /// Parse a field profile from a string.
+    /**
+     * Accepted formats are
+     *  - `gradient:<x|y>:<start>:<stop>`
+     *  - `step:<x|y>:<position>:<below>:<above>`
+     *  - `gauss:<h>:<x0>:<y0>:<width>`
+     */
+    pub fn parse(spec: &str) -> Result<Profile, String> {
+        let parts: Vec<&str> = spec.split(':').collect();
+        let number = |s: &str| s.trim().parse::<f64>()
+            .map_err(|_| format!("Invalid number '{}' in field profile '{}'", s, spec));
+        let axis = |s: &str| match s {
+            "x" => Ok(Axis::X),
+            "y" => Ok(Axis::Y),
+            _ => Err(format!("Invalid axis '{}' in field profile '{}'", s, spec)),
+        };
+
+        match parts.as_slice() {
+            ["gradient", ax, start, stop] => Ok(Profile::Gradient{
+                axis: axis(ax)?, start: number(start)?, stop: number(stop)?}),
+            ["step", ax, position, below, above] => Ok(Profile::Step{
+                axis: axis(ax)?, position: number(position)?,
+                below: number(below)?, above: number(above)?}),
+            ["gauss", h, x0, y0, width] => {
+                let width = number(width)?;
+                if width <= 0. {
+                    return Err("Width of a Gaussian field profile must be positive".to_string());
+                }
+                Ok(Profile::Gaussian{h: number(h)?, x0: number(x0)?, y0: number(y0)?, width})
+            }
+            _ => Err(format!("Invalid field profile '{}'", spec)),
+        }
+    }
+
+    /// Return the field at site (x, y) of an nx*ny lattice.
+    pub fn value(&self, x: usize, y: usize, nx: usize, ny: usize) -> f64 {
+        let coordinate = |axis: &Axis| match axis {
+            Axis::X => (x as f64, nx),
+            Axis::Y => (y as f64, ny),
+        };
+        match self {
+            Profile::Gradient{axis, start, stop} => {
+                let (c, n) = coordinate(axis);
+                let frac = if n > 1 { c / (n-1) as f64 } else { 0. };
+                start + (stop-start)*frac
+            }
+            Profile::Step{axis, position, below, above} => {
+                if coordinate(axis).0 < *position { *below } else { *above }
+            }
+            Profile::Gaussian{h, x0, y0, width} => {
+                let periodic = |d: f64, n: usize| {
+                    let d = d.abs() % n as f64;
+                    d.min(n as f64 - d)
+                };
+                let dx = periodic(x as f64 - x0, nx);
+                let dy = periodic(y as f64 - y0, ny);
+                h * (-(dx*dx + dy*dy) / (2.*width*width)).exp()
+            }
+        }
+    }
+}
+
+/// Add field profiles to the local field of cfg.
+pub fn apply(cfg: &mut Configuration, profiles: &[Profile]) {
+    let (nx, ny) = (cfg.nx, cfg.ny);
+    for profile in profiles.iter() {
+        for y in 0..ny {
+            for x in 0..nx {
+                cfg.add_local_field(y*nx + x, profile.value(x, y, nx, ny));
+            }
+        }
+    }
+}
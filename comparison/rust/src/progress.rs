@@ -0,0 +1,74 @@
+/*!
+ * Progress reports of long production runs.
+ *
+ * The report is a single line on stderr that is redrawn at most every REFRESH seconds,
+ * so runs that finish quickly never show it.
+ */
+
+use std::io::prelude::*;
+use std::time::{Duration, Instant};
+
+/// Minimum time between two redraws of the report.
+const REFRESH: Duration = Duration::from_millis(500);
+
+/// Progress of a run of a fixed number of sweeps.
+pub struct Progress {
+    /// Label at the start of the line.
+    label: String,
+    /// Total number of sweeps of the run.
+    total: usize,
+    /// Completed sweeps when the report started, e.g. when resuming from a checkpoint.
+    first: usize,
+    /// Number of active sites, to normalise the acceptance rate.
+    nsite: usize,
+    /// Time when the report started.
+    start: Instant,
+    /// Time of the last redraw, None if nothing was drawn yet.
+    last: Option<Instant>,
+    /// Report nothing if false.
+    enabled: bool,
+}
+
+impl Progress {
+    /// Start reporting the progress of a run of total sweeps on nsite sites with first sweeps completed.
+    pub fn new(label: &str, total: usize, first: usize, nsite: usize, enabled: bool) -> Progress {
+        Progress{label: label.to_string(), total, first, nsite, start: Instant::now(), last: None, enabled}
+    }
+
+    /// Report that sweep sweeps are completed with naccept accepted updates since the report started.
+    /**
+     * Redraws the report if at least REFRESH passed since the last redraw.
+     */
+    pub fn update(&mut self, sweep: usize, naccept: usize) {
+        if !self.enabled || self.last.is_some_and(|last| last.elapsed() < REFRESH)
+            || (self.last.is_none() && self.start.elapsed() < REFRESH) {
+            return;
+        }
+        self.last = Some(Instant::now());
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let done = sweep - self.first;
+        let rate = done as f64 / elapsed;
+        let acceptance = naccept as f64 / (done*self.nsite).max(1) as f64;
+        let eta = (self.total - sweep) as f64 / rate;
+        eprint!("\r{} {:>3}% ({}/{} sweeps), {:.0} sweeps/s, acceptance {:.4}, ETA {}  ", self.label,
+                100*sweep / self.total.max(1), sweep, self.total, rate, acceptance, format_duration(eta));
+        std::io::stderr().flush().unwrap();
+    }
+
+    /// Clear the report if it was drawn.
+    pub fn finish(&mut self) {
+        if self.last.take().is_some() {
+            eprint!("\r{}\r", " ".repeat(100));
+            std::io::stderr().flush().unwrap();
+        }
+    }
+}
+
+/// Format a duration in seconds as `h:mm:ss`, or `?` if it is not finite.
+pub fn format_duration(seconds: f64) -> String {
+    if !seconds.is_finite() {
+        return "?".to_string();
+    }
+    let seconds = seconds.round() as u64;
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
@@ -0,0 +1,194 @@
+/*!
+ * Single and multiple histogram reweighting of temperature scans (Ferrenberg-Swendsen).
+ *
+ * Samples drawn at β_r carry the energy distribution P_r(E) ∝ g(E) exp(-β_r E), so reweighting them with
+ * exp(-(β - β_r) E) gives expectation values at a nearby β. Multiple histogram reweighting combines
+ * all temperatures by estimating their partition functions self-consistently, which is reliable
+ * everywhere between the simulated temperatures as long as neighbouring energy distributions overlap.
+ * The sums run over the distinct energies of all samples instead of energy bins, which is exact for discrete
+ * energies and avoids binning errors otherwise. Samples are weighted equally regardless of their autocorrelation.
+ */
+
+use std::path::Path;
+
+use crate::analysis::reweighted_mean;
+use crate::error::{Error, OutputFile};
+
+/// Method of reweighting.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reweighting {
+    /// Reweight the samples of the simulated temperature closest to the target.
+    Single,
+    /// Combine the samples of all simulated temperatures.
+    Multiple,
+}
+
+impl Reweighting {
+    /// Parse the name of a method, 'single' or 'multi'.
+    pub fn parse(name: &str) -> Result<Reweighting, String> {
+        match name {
+            "single" => Ok(Reweighting::Single),
+            "multi" => Ok(Reweighting::Multiple),
+            _ => Err(format!("Unknown reweighting '{}', expected single or multi", name)),
+        }
+    }
+}
+
+/// Measurements of one simulated temperature.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Run {
+    /// Inverse temperature of the simulation.
+    pub beta: f64,
+    /// Total energy of each measurement.
+    pub energy: Vec<f64>,
+    /// Magnetisation per site of each measurement.
+    pub magnetisation: Vec<f64>,
+}
+
+/// Thermodynamic quantities at one temperature, see analysis::Summary for their definitions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point {
+    /// Target temperature.
+    pub temperature: f64,
+    /// Energy per site.
+    pub energy: f64,
+    /// Specific heat per site.
+    pub specific_heat: f64,
+    /// Absolute magnetisation per site.
+    pub abs_magnetisation: f64,
+    /// Magnetic susceptibility.
+    pub susceptibility: f64,
+}
+
+/// Count how often each distinct energy occurs, sorted by energy.
+pub fn histogram(energy: &[f64]) -> Vec<(f64, usize)> {
+    let mut sorted = energy.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let mut counts: Vec<(f64, usize)> = Vec::new();
+    for e in sorted {
+        match counts.last_mut() {
+            Some((last, count)) if *last == e => *count += 1,
+            _ => counts.push((e, 1)),
+        }
+    }
+    counts
+}
+
+/// Return ln(Σ exp(x)) without overflow.
+fn log_sum_exp(x: impl Iterator<Item = f64> + Clone) -> f64 {
+    let max = x.clone().fold(f64::NEG_INFINITY, f64::max);
+    max + x.map(|x| (x - max).exp()).sum::<f64>().ln()
+}
+
+/// Self-consistent partition functions of several runs.
+pub struct MultiHistogram {
+    /// ln Z_r of every run relative to the first one.
+    pub ln_z: Vec<f64>,
+    /// Distinct energies of all runs with their number of samples, see histogram.
+    levels: Vec<(f64, usize)>,
+    /// ln Σ_r n_r exp(-β_r E - ln Z_r) for every level.
+    ln_denominator: Vec<f64>,
+}
+
+impl MultiHistogram {
+    /// Iterate the Ferrenberg-Swendsen equations until ln Z changes by less than tolerance.
+    /**
+     * ln Z_r = ln Σ_E H(E) exp(-β_r E) / Σ_m n_m exp(-β_m E - ln Z_m) with the histogram H of all samples.
+     * Stops after maxiter iterations if the tolerance is not reached.
+     */
+    pub fn new(runs: &[Run], tolerance: f64, maxiter: usize) -> MultiHistogram {
+        let energies: Vec<f64> = runs.iter().flat_map(|run| run.energy.iter().cloned()).collect();
+        let ln_n: Vec<f64> = runs.iter().map(|run| (run.energy.len() as f64).ln()).collect();
+        let mut multi = MultiHistogram{ln_z: vec![0.; runs.len()], levels: histogram(&energies),
+                                       ln_denominator: Vec::new()};
+        for _iter in 0..maxiter {
+            multi.ln_denominator = multi.levels.iter()
+                .map(|(e, _)| log_sum_exp(runs.iter().zip(&multi.ln_z).zip(&ln_n)
+                                          .map(|((run, ln_z), ln_n)| ln_n - run.beta*e - ln_z)))
+                .collect();
+            let mut ln_z: Vec<f64> = runs.iter().map(|run| multi.ln_partition_function(run.beta)).collect();
+            let offset = ln_z[0];
+            ln_z.iter_mut().for_each(|x| *x -= offset);
+            let change = ln_z.iter().zip(&multi.ln_z).map(|(a, b)| (a - b).abs()).fold(0., f64::max);
+            multi.ln_z = ln_z;
+            if change < tolerance {
+                break;
+            }
+        }
+        multi
+    }
+
+    /// Return ln Z(beta) with the current estimates of ln Z_r.
+    pub fn ln_partition_function(&self, beta: f64) -> f64 {
+        log_sum_exp(self.levels.iter().zip(&self.ln_denominator)
+                    .map(|(&(e, count), ln_d)| (count as f64).ln() - beta*e - ln_d))
+    }
+
+    /// Return the log weights ln(exp(-β E_s) / Σ_m n_m exp(-β_m E_s - ln Z_m)) of the given samples at beta.
+    pub fn log_weights(&self, beta: f64, energy: &[f64]) -> Vec<f64> {
+        energy.iter().map(|e| {
+            let level = self.levels.binary_search_by(|(x, _)| x.total_cmp(e))
+                .expect("Samples must come from the runs of the histogram");
+            -beta*e - self.ln_denominator[level]
+        }).collect()
+    }
+}
+
+/// Compute the thermodynamic quantities at temp from samples with the given log weights.
+fn evaluate(temp: f64, energy: &[f64], magnetisation: &[f64], log_weights: &[f64], nsite: usize) -> Point {
+    let (beta, n) = (1./temp, nsite as f64);
+    let mean = |values: &[f64]| reweighted_mean(values, log_weights);
+    let e = mean(energy);
+    let m = mean(magnetisation);
+    let abs: Vec<f64> = magnetisation.iter().map(|m| m.abs()).collect();
+    let de2: Vec<f64> = energy.iter().map(|x| (x - e).powi(2)).collect();
+    let dm2: Vec<f64> = magnetisation.iter().map(|x| (x - m).powi(2)).collect();
+    Point{temperature: temp, energy: e / n, specific_heat: beta*beta*mean(&de2) / n,
+          abs_magnetisation: mean(&abs), susceptibility: beta*n*mean(&dm2)}
+}
+
+/// Reweight runs to npoint temperatures evenly spaced between the lowest and highest simulated one.
+/**
+ * nsite is the number of sites the energy is normalised to.
+ */
+pub fn reweight(runs: &[Run], method: Reweighting, nsite: usize, npoint: usize) -> Vec<Point> {
+    let temps: Vec<f64> = runs.iter().map(|run| 1./run.beta).collect();
+    let tmin = temps.iter().cloned().fold(f64::INFINITY, f64::min);
+    let tmax = temps.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let targets = (0..npoint).map(|k| if npoint > 1 { tmin + (tmax-tmin)*k as f64 / (npoint-1) as f64 } else { tmin });
+
+    match method {
+        Reweighting::Single => targets.map(|temp| {
+            let run = runs.iter().min_by(|a, b| (1./a.beta - temp).abs().total_cmp(&(1./b.beta - temp).abs()))
+                .unwrap();
+            let log_weights: Vec<f64> = run.energy.iter().map(|e| -(1./temp - run.beta)*e).collect();
+            evaluate(temp, &run.energy, &run.magnetisation, &log_weights, nsite)
+        }).collect(),
+        Reweighting::Multiple => {
+            let multi = MultiHistogram::new(runs, 1e-10, 10000);
+            let energy: Vec<f64> = runs.iter().flat_map(|run| run.energy.iter().cloned()).collect();
+            let magnetisation: Vec<f64> = runs.iter().flat_map(|run| run.magnetisation.iter().cloned()).collect();
+            targets.map(|temp| evaluate(temp, &energy, &magnetisation, &multi.log_weights(1./temp, &energy), nsite))
+                .collect()
+        }
+    }
+}
+
+/// Write the energy histogram of a run with lines `<k>: <E> <count>`.
+pub fn write_histogram(fname: &Path, energy: &[f64]) -> Result<(), Error> {
+    let mut file = OutputFile::create(fname)?;
+    for (k, (e, count)) in histogram(energy).iter().enumerate() {
+        writeln!(file, "{}: {} {}", k, e, count)?;
+    }
+    Ok(())
+}
+
+/// Write reweighted points with lines `<i>: <T> <energy> <specific heat> <|m|> <susceptibility>`.
+pub fn write_points(fname: &Path, points: &[Point]) -> Result<(), Error> {
+    let mut file = OutputFile::create(fname)?;
+    for (i, p) in points.iter().enumerate() {
+        writeln!(file, "{}: {} {} {} {} {}", i, p.temperature, p.energy, p.specific_heat,
+                 p.abs_magnetisation, p.susceptibility)?;
+    }
+    Ok(())
+}
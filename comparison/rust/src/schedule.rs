@@ -0,0 +1,324 @@
+/*!
+ * Schedules for parameters that change over the sweeps of a single Monte-Carlo run,
+ * e.g. the temperature in ramps and the external field, or over the steps of an annealing run.
+ *
+ * Protocols only use the Schedule trait, so new schedules can be added here
+ * without changing the protocols.
+ */
+
+use std::cell::RefCell;
+use std::f64::consts::PI;
+
+/// A parameter that changes over the sweeps of a run.
+pub trait Schedule {
+    /// Return the value in sweep number `sweep` out of `nsweep`.
+    fn value(&self, sweep: usize, nsweep: usize) -> f64;
+
+    /// Return the value at the beginning of the run.
+    fn start(&self) -> f64 {
+        self.value(0, 1)
+    }
+
+    /// Return the values of all sweeps of a run with `nsweep` sweeps.
+    fn values(&self, nsweep: usize) -> Vec<f64> {
+        (0..nsweep).map(|sweep| self.value(sweep, nsweep)).collect()
+    }
+
+    /// Return the phase of the schedule in sweep number `sweep` out of `nsweep`, in [0, 1].
+    /**
+     * This is the fraction of the run by default and the fraction of the current period
+     * for periodic schedules.
+     */
+    fn phase(&self, sweep: usize, nsweep: usize) -> f64 {
+        run_fraction(sweep, nsweep)
+    }
+
+    /// Return true if the value does not change over time.
+    fn is_constant(&self) -> bool {
+        false
+    }
+
+    /// Report the acceptance rate of sweep number `sweep` out of `nsweep`.
+    /**
+     * Must be called after every sweep, in order. Adaptive schedules use it to
+     * determine their value in the next sweep, all others ignore it.
+     */
+    fn record_acceptance(&self, _sweep: usize, _nsweep: usize, _acceptance: f64) {}
+}
+
+/// Keep the value fixed.
+pub struct Constant(pub f64);
+
+impl Schedule for Constant {
+    fn value(&self, _sweep: usize, _nsweep: usize) -> f64 {
+        self.0
+    }
+
+    fn phase(&self, _sweep: usize, _nsweep: usize) -> f64 {
+        0.
+    }
+
+    fn is_constant(&self) -> bool {
+        true
+    }
+}
+
+/// Interpolate linearly from start to stop.
+pub struct Linear {
+    pub start: f64,
+    pub stop: f64,
+}
+
+impl Schedule for Linear {
+    fn value(&self, sweep: usize, nsweep: usize) -> f64 {
+        self.start + (self.stop-self.start)*run_fraction(sweep, nsweep)
+    }
+}
+
+/// Interpolate geometrically from start to stop, i.e. linearly in the logarithm.
+pub struct Exponential {
+    pub start: f64,
+    pub stop: f64,
+}
+
+impl Schedule for Exponential {
+    fn value(&self, sweep: usize, nsweep: usize) -> f64 {
+        self.start * (self.stop/self.start).powf(run_fraction(sweep, nsweep))
+    }
+}
+
+/// Interpolate from start to stop with a power of the run fraction, start + (stop-start) f^exponent.
+pub struct PowerLaw {
+    pub start: f64,
+    pub stop: f64,
+    pub exponent: f64,
+}
+
+impl Schedule for PowerLaw {
+    fn value(&self, sweep: usize, nsweep: usize) -> f64 {
+        self.start + (self.stop-self.start)*run_fraction(sweep, nsweep).powf(self.exponent)
+    }
+}
+
+/// Interpolate linearly between knots (sweep, value), sorted by sweep.
+/**
+ * Before the first and after the last knot, the value is held constant.
+ */
+pub struct Piecewise(pub Vec<(usize, f64)>);
+
+impl Schedule for Piecewise {
+    fn value(&self, sweep: usize, _nsweep: usize) -> f64 {
+        let knots = &self.0;
+        match knots.iter().position(|&(s, _)| s > sweep) {
+            Some(0) => knots[0].1,
+            None => knots[knots.len()-1].1,
+            Some(i) => {
+                let (s0, v0) = knots[i-1];
+                let (s1, v1) = knots[i];
+                v0 + (v1-v0) * (sweep-s0) as f64 / (s1-s0) as f64
+            }
+        }
+    }
+}
+
+/// Periodic driving offset + amplitude*sin(2π t / period) with t in sweeps.
+pub struct Sinusoidal {
+    pub amplitude: f64,
+    pub period: f64,
+    pub offset: f64,
+}
+
+impl Schedule for Sinusoidal {
+    fn value(&self, sweep: usize, _nsweep: usize) -> f64 {
+        self.offset + self.amplitude*(2.*PI*sweep as f64/self.period).sin()
+    }
+
+    fn phase(&self, sweep: usize, _nsweep: usize) -> f64 {
+        (sweep as f64 / self.period).fract()
+    }
+}
+
+/// Move geometrically from start towards stop with a speed that depends on the acceptance rate.
+/**
+ * The value of sweep n+1 is v_n (stop/start)^(a_n / (target (nsweep-1))) where a_n is
+ * the acceptance rate of sweep n. So the schedule is exponential if the acceptance rate
+ * stays at target, it slows down when fewer moves are accepted and speeds up otherwise.
+ * The value never goes past stop. Since it depends on the run, the value is only known
+ * for sweeps up to the last recorded acceptance rate plus one.
+ */
+pub struct Adaptive {
+    start: f64,
+    stop: f64,
+    target: f64,
+    /// Values of all sweeps so far.
+    history: RefCell<Vec<f64>>,
+}
+
+impl Adaptive {
+    /// Create an adaptive schedule aiming for acceptance rate target.
+    pub fn new(start: f64, stop: f64, target: f64) -> Adaptive {
+        Adaptive{start, stop, target, history: RefCell::new(vec![start])}
+    }
+}
+
+impl Schedule for Adaptive {
+    fn value(&self, sweep: usize, _nsweep: usize) -> f64 {
+        let history = self.history.borrow();
+        history[sweep.min(history.len()-1)]
+    }
+
+    fn record_acceptance(&self, sweep: usize, nsweep: usize, acceptance: f64) {
+        let mut history = self.history.borrow_mut();
+        if sweep+1 != history.len() || nsweep < 2 {
+            return;
+        }
+        let current = history[sweep];
+        let next = current * (self.stop/self.start)
+            .powf(acceptance / self.target / (nsweep-1) as f64);
+        // do not overshoot
+        history.push(if self.stop < self.start { next.max(self.stop) } else { next.min(self.stop) });
+    }
+}
+
+/// Fraction of a run that is completed in sweep, 1 in the last sweep.
+fn run_fraction(sweep: usize, nsweep: usize) -> f64 {
+    if nsweep > 1 { sweep as f64 / (nsweep-1) as f64 } else { 0. }
+}
+
+/// Parse a temperature schedule from a string.
+/**
+ * Accepted formats are
+ *  - `linear:<start>:<stop>`
+ *  - `exp:<start>:<stop>`
+ *  - `power:<start>:<stop>:<exponent>`
+ *  - `piecewise:<sweep>=<T>,<sweep>=<T>,...`
+ *  - `adaptive:<start>:<stop>:<target acceptance rate>`
+ *
+ * All temperatures must be positive.
+ */
+pub fn parse_temperature(spec: &str) -> Result<Box<dyn Schedule>, String> {
+    let (kind, args) = spec.split_once(':')
+        .ok_or_else(|| format!("Invalid ramp '{}', expected <kind>:<arguments>", spec))?;
+    let temperature = |temp: &str| match temp.trim().parse::<f64>() {
+        Ok(t) if t > 0. => Ok(t),
+        _ => Err(format!("Invalid temperature '{}', must be a positive number", temp)),
+    };
+    let parts: Vec<&str> = args.split(':').collect();
+
+    match (kind, parts.as_slice()) {
+        ("linear", [start, stop]) => Ok(Box::new(Linear{start: temperature(start)?,
+                                                         stop: temperature(stop)?})),
+        ("exp", [start, stop]) => Ok(Box::new(Exponential{start: temperature(start)?,
+                                                           stop: temperature(stop)?})),
+        ("power", [start, stop, exponent]) => Ok(Box::new(PowerLaw{
+            start: temperature(start)?, stop: temperature(stop)?,
+            exponent: parse_positive(exponent, "exponent")?})),
+        ("adaptive", [start, stop, target]) => {
+            let target = parse_positive(target, "target acceptance rate")?;
+            if target > 1. {
+                return Err("Target acceptance rate must not exceed 1".to_string());
+            }
+            Ok(Box::new(Adaptive::new(temperature(start)?, temperature(stop)?, target)))
+        }
+        ("piecewise", _) => Ok(Box::new(parse_piecewise(args, temperature)?)),
+        ("linear" | "exp" | "power" | "adaptive", _) => {
+            Err(format!("Wrong number of arguments for ramp '{}'", kind))
+        }
+        _ => Err(format!("Unknown ramp kind '{}'", kind)),
+    }
+}
+
+/// Parse the inverse temperatures of the steps of an annealing schedule from a string.
+/**
+ * Accepted formats are
+ *  - `linear:<start>:<stop>:<nstep>`
+ *  - `geometric:<start>:<stop>:<nstep>`
+ *  - `list:<beta>,<beta>,...`
+ *
+ * where start and stop are the inverse temperatures of the first and last step.
+ * All inverse temperatures must be positive and finite.
+ */
+pub fn parse_annealing(spec: &str) -> Result<Vec<f64>, String> {
+    let (kind, args) = spec.split_once(':')
+        .ok_or_else(|| format!("Invalid annealing schedule '{}', expected <kind>:<arguments>", spec))?;
+    let beta = |beta: &str| match beta.trim().parse::<f64>() {
+        Ok(b) if b > 0. && b.is_finite() => Ok(b),
+        _ => Err(format!("Invalid inverse temperature '{}', must be a positive number", beta)),
+    };
+    let nstep = |nstep: &str| match nstep.trim().parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("Invalid number of steps '{}', must be a positive integer", nstep)),
+    };
+    let parts: Vec<&str> = args.split(':').collect();
+
+    match (kind, parts.as_slice()) {
+        ("linear", [start, stop, n]) => Ok(Linear{start: beta(start)?, stop: beta(stop)?}.values(nstep(n)?)),
+        ("geometric", [start, stop, n]) => Ok(Exponential{start: beta(start)?, stop: beta(stop)?}
+                                              .values(nstep(n)?)),
+        ("list", _) => args.split(',').map(beta).collect(),
+        ("linear" | "geometric", _) => {
+            Err(format!("Wrong number of arguments for annealing schedule '{}'", kind))
+        }
+        _ => Err(format!("Unknown annealing schedule kind '{}'", kind)),
+    }
+}
+
+/// Parse a field schedule from a string.
+/**
+ * Accepted formats are
+ *  - `<h>` or `const:<h>`
+ *  - `linear:<start>:<stop>`
+ *  - `power:<start>:<stop>:<exponent>`
+ *  - `piecewise:<sweep>=<h>,<sweep>=<h>,...`
+ *  - `sin:<amplitude>:<period>[:<offset>]`
+ *
+ * Field schedules are Sync so that parallel chains can share them.
+ */
+pub fn parse_field(spec: &str) -> Result<Box<dyn Schedule + Sync>, String> {
+    let parts: Vec<&str> = spec.split(':').collect();
+    let number = |s: &str| s.trim().parse::<f64>()
+        .map_err(|_| format!("Invalid number '{}' in field '{}'", s, spec));
+
+    match parts.as_slice() {
+        [h] | ["const", h] => Ok(Box::new(Constant(number(h)?))),
+        ["linear", start, stop] => Ok(Box::new(Linear{start: number(start)?, stop: number(stop)?})),
+        ["power", start, stop, exponent] => Ok(Box::new(PowerLaw{
+            start: number(start)?, stop: number(stop)?,
+            exponent: parse_positive(exponent, "exponent")?})),
+        ["piecewise", ..] => Ok(Box::new(parse_piecewise(&spec["piecewise:".len()..], number)?)),
+        ["sin", amplitude, period, rest @ ..] if rest.len() <= 1 => {
+            let period = number(period)?;
+            if period <= 0. {
+                return Err("Period of a sinusoidal field must be positive".to_string());
+            }
+            Ok(Box::new(Sinusoidal{amplitude: number(amplitude)?, period,
+                                   offset: rest.first().map_or(Ok(0.), |o| number(o))?}))
+        }
+        _ => Err(format!("Invalid field '{}'", spec)),
+    }
+}
+
+/// Parse knots `<sweep>=<value>,...` of a piecewise schedule, using parse_value for the values.
+fn parse_piecewise(knots: &str, parse_value: impl Fn(&str) -> Result<f64, String>)
+                   -> Result<Piecewise, String> {
+    let mut parsed = Vec::new();
+    for knot in knots.split(',') {
+        let (sweep, value) = knot.split_once('=')
+            .ok_or_else(|| format!("Invalid knot '{}', expected <sweep>=<value>", knot))?;
+        let sweep = sweep.trim().parse::<usize>()
+            .map_err(|_| format!("Invalid sweep number '{}'", sweep))?;
+        parsed.push((sweep, parse_value(value)?));
+    }
+    if parsed.windows(2).any(|w| w[0].0 >= w[1].0) {
+        return Err("Knots of a piecewise schedule must be sorted by sweep".to_string());
+    }
+    Ok(Piecewise(parsed))
+}
+
+/// Parse a single positive number, name is used in the error message.
+fn parse_positive(value: &str, name: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(x) if x > 0. => Ok(x),
+        _ => Err(format!("Invalid {} '{}', must be a positive number", name, value)),
+    }
+}
@@ -0,0 +1,86 @@
+/*!
+ * Initial configurations of the Markov chain.
+ *
+ * Configuration files have one row per y and one space separated spin per x,
+ * the format of write_row, so the final configuration of a run can start the next one.
+ */
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, OutputFile};
+use crate::{Configuration, Rng, write_row};
+
+/// Initial configuration of a run.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Start {
+    /// Random spins.
+    Hot,
+    /// All spins set to the given value.
+    Cold(i32),
+    /// Spins read from a configuration file.
+    File(PathBuf),
+}
+
+impl Start {
+    /// Parse a start from a string, one of `hot`, `cold-up`, `cold-down`, or `file:<path>`.
+    pub fn parse(spec: &str) -> Result<Start, String> {
+        match spec {
+            "hot" => Ok(Start::Hot),
+            "cold-up" => Ok(Start::Cold(1)),
+            "cold-down" => Ok(Start::Cold(-1)),
+            _ => match spec.strip_prefix("file:") {
+                Some(path) if !path.is_empty() => Ok(Start::File(PathBuf::from(path))),
+                _ => Err(format!("Invalid start '{}'", spec)),
+            },
+        }
+    }
+
+    /// Create the initial configuration on an nx*ny lattice.
+    /**
+     * Only a hot start draws from rng.
+     */
+    pub fn create(&self, nx: usize, ny: usize, rng: &mut Rng) -> Result<Configuration, String> {
+        match self {
+            Start::Hot => Ok(Configuration::random(nx, ny, rng)),
+            Start::Cold(spin) => Ok(Configuration::cold(nx, ny, *spin)),
+            Start::File(fname) => read_configuration(fname, nx, ny),
+        }
+    }
+}
+
+/// Read the spins of an nx*ny configuration from a file.
+pub fn read_configuration(fname: &Path, nx: usize, ny: usize) -> Result<Configuration, String> {
+    let text = fs::read_to_string(fname)
+        .map_err(|err| format!("Cannot read configuration '{}': {}", fname.display(), err))?;
+    let rows: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).collect();
+    if rows.len() != ny {
+        return Err(format!("Configuration '{}' has {} rows, expected {}", fname.display(), rows.len(), ny));
+    }
+
+    let mut cfg = Configuration::cold(nx, ny, 1);
+    for (y, row) in rows.iter().enumerate() {
+        let spins: Vec<&str> = row.split_whitespace().collect();
+        if spins.len() != nx {
+            return Err(format!("Row {} of configuration '{}' has {} spins, expected {}",
+                               y, fname.display(), spins.len(), nx));
+        }
+        for (x, spin) in spins.iter().enumerate() {
+            cfg[y*nx + x] = match *spin {
+                "1" => 1,
+                "-1" => -1,
+                _ => return Err(format!("Invalid spin '{}' in configuration '{}'", spin, fname.display())),
+            };
+        }
+    }
+    Ok(cfg)
+}
+
+/// Write the spins of cfg to a file that can be read by read_configuration.
+pub fn write_configuration(fname: &Path, cfg: &Configuration) -> Result<(), Error> {
+    let mut file = OutputFile::create(fname)?;
+    for row in cfg.cfg.chunks(cfg.nx) {
+        write_row(&mut file, &row.iter().map(|&s| s as f64).collect::<Vec<f64>>())?;
+    }
+    Ok(())
+}
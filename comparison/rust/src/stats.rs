@@ -0,0 +1,67 @@
+/*!
+ * Statistics of the Markov chain at each temperature of a scan, written to a CSV file.
+ *
+ * The file has one line per temperature, so benchmarking and tuning runs do not need to scrape the log.
+ */
+
+use std::fs;
+use std::path::Path;
+
+use crate::error::{Error, OutputFile, write_file};
+
+/// Header line of a statistics file.
+pub const HEADER: &str = "index,temperature,thermalisation_sweeps,thermalisation_acceptance,production_sweeps,\
+                          production_acceptance,flips_per_sweep,wall_time,production_time,sweeps_per_second";
+
+/// Statistics of one temperature.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemperatureStats {
+    /// Index of the temperature.
+    pub index: usize,
+    /// The temperature.
+    pub temperature: f64,
+    /// Number of thermalisation sweeps at this temperature.
+    pub ntherm: usize,
+    /// Acceptance rate of the thermalisation sweeps, NaN if there were none.
+    pub therm_acceptance: f64,
+    /// Number of production sweeps of all chains.
+    pub nprod: usize,
+    /// Acceptance rate of the production sweeps.
+    pub acceptance: f64,
+    /// Number of flipped spins per production sweep, the mean flipped cluster size for Wolff updates.
+    pub flips_per_sweep: f64,
+    /// Wall clock time in seconds of the whole temperature, including thermalisation and output.
+    pub wall_time: f64,
+    /// Wall clock time in seconds of the production sweeps.
+    pub production_time: f64,
+}
+
+impl TemperatureStats {
+    /// Return the number of production sweeps of all chains per second of wall clock time.
+    pub fn sweeps_per_second(&self) -> f64 {
+        self.nprod as f64 / self.production_time
+    }
+
+    /// Write the statistics as a line of a statistics file.
+    pub fn write(&self, file: &mut OutputFile) -> Result<(), Error> {
+        writeln!(file, "{},{},{},{},{},{},{},{},{},{}", self.index, self.temperature, self.ntherm,
+                 self.therm_acceptance, self.nprod, self.acceptance, self.flips_per_sweep, self.wall_time,
+                 self.production_time, self.sweeps_per_second())
+    }
+}
+
+/// Open a statistics file for appending, keeping the lines of temperatures with index < keep.
+/**
+ * The first line is always the header.
+ */
+pub fn open(fname: &Path, keep: usize) -> Result<OutputFile, Error> {
+    let mut kept = format!("{}\n", HEADER);
+    for line in fs::read_to_string(fname).unwrap_or_default().lines().skip(1) {
+        if line.split(',').next().and_then(|idx| idx.parse::<usize>().ok()).is_some_and(|idx| idx < keep) {
+            kept.push_str(line);
+            kept.push('\n');
+        }
+    }
+    write_file(fname, kept)?;
+    OutputFile::append(fname)
+}
@@ -0,0 +1,108 @@
+/*!
+ * Writing measurements to disk while the Markov chain runs.
+ *
+ * A Sink receives measurements in chunks, so the full history never has to be held in memory.
+ * StreamWriter writes the same csv and npy files as write_observables.
+ */
+
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+use crate::{Format, Observables};
+use crate::npy::npy_header;
+
+/// Destination of measurements.
+pub trait Sink {
+    /// Append all measurements of obs.
+    fn append(&mut self, obs: &Observables) -> io::Result<()>;
+
+    /// Complete the output after the last measurement.
+    fn finish(&mut self) -> io::Result<()>;
+
+    /// Append all measurements of obs and remove them from obs.
+    fn take(&mut self, obs: &mut Observables) -> io::Result<()> {
+        self.append(obs)?;
+        obs.clear();
+        Ok(())
+    }
+}
+
+/// Collect measurements in memory.
+impl Sink for Observables {
+    fn append(&mut self, obs: &Observables) -> io::Result<()> {
+        self.energy.extend_from_slice(&obs.energy);
+        self.magnetisation.extend_from_slice(&obs.magnetisation);
+        self.staggered.extend_from_slice(&obs.staggered);
+        self.structure.extend_from_slice(&obs.structure);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Write energy and magnetisation to a file as they arrive.
+/**
+ * Supports the csv and npy formats. The npy array is stored in Fortran order with
+ * shape (2, n), so it loads exactly like one written by write_observables, and the
+ * shape in its header is filled in by finish.
+ */
+pub struct StreamWriter {
+    /// Output file.
+    file: io::BufWriter<fs::File>,
+    /// Format of file, Csv or Npy.
+    format: Format,
+    /// Number of measurements written so far.
+    nmeasure: usize,
+    /// Length of the npy header which is reserved for the largest possible shape.
+    header_len: usize,
+}
+
+impl StreamWriter {
+    /// Return true if measurements can be streamed in format.
+    pub fn supports(format: Format) -> bool {
+        matches!(format, Format::Csv | Format::Npy)
+    }
+
+    /// Create fname and write the header of the format.
+    pub fn create(fname: &Path, format: Format) -> io::Result<StreamWriter> {
+        assert!(StreamWriter::supports(format), "Cannot stream format {}", format.extension());
+        let mut file = io::BufWriter::new(fs::File::create(fname)?);
+        let mut header_len = 0;
+        match format {
+            Format::Csv => writeln!(file, "energy,magnetisation")?,
+            _ => {
+                header_len = npy_header("<f8", &[2, usize::MAX], true, 0).len();
+                file.write_all(&npy_header("<f8", &[2, 0], true, header_len))?;
+            }
+        }
+        Ok(StreamWriter{file, format, nmeasure: 0, header_len})
+    }
+}
+
+impl Sink for StreamWriter {
+    fn append(&mut self, obs: &Observables) -> io::Result<()> {
+        for (e, m) in obs.energy.iter().zip(obs.magnetisation.iter()) {
+            match self.format {
+                Format::Csv => writeln!(self.file, "{},{}", e, m)?,
+                _ => {
+                    self.file.write_all(&e.to_le_bytes())?;
+                    self.file.write_all(&m.to_le_bytes())?;
+                }
+            }
+        }
+        self.nmeasure += obs.energy.len();
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if self.format == Format::Npy {
+            self.file.seek(io::SeekFrom::Start(0))?;
+            self.file.write_all(&npy_header("<f8", &[2, self.nmeasure], true, self.header_len))?;
+        }
+        self.file.flush()
+    }
+}
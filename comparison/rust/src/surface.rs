@@ -0,0 +1,75 @@
+/*!
+ * Fields acting only on the boundary rows and columns of the lattice.
+ */
+
+use crate::Configuration;
+
+/// An edge of the lattice.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Edge {
+    /// Row y = 0.
+    Bottom,
+    /// Row y = ny-1.
+    Top,
+    /// Column x = 0.
+    Left,
+    /// Column x = nx-1.
+    Right,
+}
+
+/// A field h acting on all sites of one edge.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SurfaceField {
+    /// Edge the field acts on.
+    pub edge: Edge,
+    /// Strength of the field.
+    pub h: f64,
+}
+
+impl SurfaceField {
+    /// Parse a comma separated list of surface fields `<edge>=<h>`.
+    /**
+     * Edges are bottom, top, left, right, e.g. `bottom=0.5,top=-0.5`.
+     */
+    pub fn parse_list(spec: &str) -> Result<Vec<SurfaceField>, String> {
+        spec.split(',').map(|item| {
+            let (edge, h) = item.split_once('=')
+                .ok_or_else(|| format!("Invalid surface field '{}', expected <edge>=<h>", item))?;
+            let edge = match edge.trim() {
+                "bottom" => Edge::Bottom,
+                "top" => Edge::Top,
+                "left" => Edge::Left,
+                "right" => Edge::Right,
+                _ => return Err(format!("Unknown edge '{}'", edge)),
+            };
+            let h = h.trim().parse::<f64>()
+                .map_err(|_| format!("Invalid number '{}' in surface field '{}'", h, item))?;
+            Ok(SurfaceField{edge, h})
+        }).collect()
+    }
+
+    /// Return the indices of all sites on the edge of cfg.
+    pub fn sites(&self, cfg: &Configuration) -> Vec<usize> {
+        let (nx, ny) = (cfg.nx, cfg.ny);
+        match self.edge {
+            Edge::Bottom => (0..nx).collect(),
+            Edge::Top => ((ny-1)*nx..ny*nx).collect(),
+            Edge::Left => (0..ny).map(|y| y*nx).collect(),
+            Edge::Right => (0..ny).map(|y| y*nx + nx-1).collect(),
+        }
+    }
+}
+
+/// Add surface fields to the local field of cfg.
+/**
+ * Fields of overlapping edges add up in the corners.
+ * With periodic boundary conditions, opposite edges are neighbours, so the fields are
+ * a pair of adjacent lines of field rather than true surfaces.
+ */
+pub fn apply(cfg: &mut Configuration, fields: &[SurfaceField]) {
+    for field in fields.iter() {
+        for idx in field.sites(cfg) {
+            cfg.add_local_field(idx, field.h);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+/*!
+ * Swendsen-Wang multi cluster updates.
+ *
+ * Every satisfied link, i.e. between aligned spins for J_ij > 0 and anti-aligned ones for J_ij < 0,
+ * is activated with probability 1 - exp(-2β|J_ij|), the resulting clusters are found with
+ * union-find and each is flipped or not independently of the others.
+ */
+
+use crate::{Configuration, Observables, Rng, hamiltonian};
+
+/// Disjoint sets of lattice sites.
+struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl UnionFind {
+    /// Create n sets with one element each.
+    fn new(n: usize) -> UnionFind {
+        UnionFind{parent: (0..n).collect(), size: vec![1; n]}
+    }
+
+    /// Return the representative of the set containing i.
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            // path halving
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
+
+    /// Merge the sets containing i and j.
+    fn union(&mut self, i: usize, j: usize) {
+        let (mut a, mut b) = (self.find(i), self.find(j));
+        if a == b {
+            return;
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+    }
+}
+
+/// Perform one Swendsen-Wang update of the whole lattice with coupling j.
+/**
+ * The coupling of each link is j times its factor in cfg.couplings_of, links with factor 0 are never activated.
+ * In an external field h (and local field of cfg), a cluster C is flipped with probability
+ * 1 / (1 + exp(2β Σ_C s_i (h + h_i))), i.e. by heat bath, which reduces to a fair coin in zero field.
+ * Clusters that contain a pinned site keep their spin.
+ * energy is recomputed after the update.
+ * Returns the number of flipped spins.
+ */
+pub fn swendsen_wang_update(cfg: &mut Configuration, energy: &mut f64, beta: f64, j: f64, h: f64,
+                            rng: &mut Rng) -> usize {
+    let size = cfg.size();
+    let bond_padd = |factor: f64| 1. - (-2.*beta*(j*factor).abs()).exp();
+    let padd = bond_padd(1.);
+    let mut clusters = UnionFind::new(size);
+
+    // Every link is in the neighbour lists of both its sites, so testing it from the site with the smaller
+    // index tests every link exactly once, also when open boundaries remove some neighbours.
+    for idx in 0..size {
+        let couplings = cfg.couplings_of(idx);
+        for (p, &neigh) in cfg.neighbours_of(idx).iter().enumerate() {
+            if neigh <= idx {
+                continue;
+            }
+            let factor = couplings.map_or(1., |c| c[p]);
+            let padd = if factor == 1. { padd } else { bond_padd(factor) };
+            if j * factor * (cfg[idx]*cfg[neigh]) as f64 > 0. && rng.gen_real() < padd {
+                clusters.union(idx, neigh);
+            }
+        }
+    }
+
+    let roots: Vec<usize> = (0..size).map(|idx| clusters.find(idx)).collect();
+    // Σ_C s_i (h + h_i) for every cluster C
+    let mut field = vec![0.; size];
+    let mut frozen = vec![true; size];
+    cfg.active_sites().iter().for_each(|&idx| frozen[idx] = false);
+    let mut pinned_roots = vec![false; size];
+    for idx in 0..size {
+        field[roots[idx]] += cfg[idx] as f64 * (h + cfg.local_field.as_ref().map_or(0., |local| local[idx]));
+        pinned_roots[roots[idx]] |= frozen[idx];
+    }
+
+    let mut flip = vec![false; size];
+    for idx in 0..size {
+        if roots[idx] == idx && !pinned_roots[idx] {
+            flip[idx] = rng.gen_real() < 1. / (1. + (2.*beta*field[idx]).exp());
+        }
+    }
+
+    let mut nflip = 0;
+    for idx in 0..size {
+        if flip[roots[idx]] {
+            cfg[idx] *= -1;
+            nflip += 1;
+        }
+    }
+    *energy = hamiltonian(cfg, j, h);
+    nflip
+}
+
+/// Evolve a configuration in Monte-Carlo time with Swendsen-Wang updates with coupling j.
+/**
+ * Each sweep is one update of the whole lattice.
+ * Measures observables once per sweep if obs is given.
+ * Returns the number of flipped spins.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn evolve_swendsen_wang(cfg: &mut Configuration, energy: &mut f64, beta: f64, j: f64, h: f64,
+                            rng: &mut Rng, nsweep: usize, mut obs: Option<&mut Observables>) -> usize {
+    let mut nflip: usize = 0;
+
+    for _sweep in 0..nsweep {
+        nflip += swendsen_wang_update(cfg, energy, beta, j, h, rng);
+
+        if let Some(o) = &mut obs {
+            o.measure(cfg, *energy);
+        }
+    }
+
+    nflip
+}
@@ -0,0 +1,75 @@
+/*!
+ * Automatic detection of the end of thermalisation with the MSER criterion.
+ *
+ * The marginal standard error rule (MSER, White 1997) truncates a time series at the point d that
+ * minimises Σ_{i>d} (y_i - ȳ_d)² / (n-d)², the squared standard error of the mean of the remaining data
+ * without correlations. Dropping the initial transient reduces the variance faster than the shorter series
+ * increases the error, so the minimum marks the end of the transient. Batch means of BATCH measurements
+ * (MSER-5) smooth the series.
+ */
+
+/// Number of measurements per batch mean.
+pub const BATCH: usize = 5;
+
+/// Return the number of measurements at the start of data to discard according to MSER-5.
+/**
+ * Only truncation points in the first half of the batches are considered because the estimate is noisy
+ * close to the end of the series. Returns None if the minimum is at the end of the first half, i.e. the
+ * series has not settled yet, or if data has fewer than two batches.
+ */
+pub fn mser(data: &[f64]) -> Option<usize> {
+    let means: Vec<f64> = data.chunks_exact(BATCH).map(|batch| batch.iter().sum::<f64>() / BATCH as f64).collect();
+    let nbatch = means.len();
+    if nbatch < 2 {
+        return None;
+    }
+
+    // sums of the batch means and their squares from d to the end
+    let (mut sum, mut sum_sq) = (0., 0.);
+    for &y in &means[nbatch/2..] {
+        sum += y;
+        sum_sq += y*y;
+    }
+    let mut best = (f64::INFINITY, nbatch/2);
+    for d in (0..=nbatch/2).rev() {
+        if d < nbatch/2 {
+            sum += means[d];
+            sum_sq += means[d]*means[d];
+        }
+        let n = (nbatch - d) as f64;
+        let statistic = (sum_sq - sum*sum/n).max(0.) / (n*n);
+        if statistic <= best.0 {
+            best = (statistic, d);
+        }
+    }
+    (best.1 < nbatch/2).then_some(best.1 * BATCH)
+}
+
+/// Result of an automatic thermalisation, see thermalise.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Thermalisation {
+    /// Number of sweeps that ran, all are discarded.
+    pub nsweep: usize,
+    /// Number of sweeps until equilibrium estimated by MSER, None if it was not reached within the maximum.
+    pub nequilibrate: Option<usize>,
+    /// Number of accepted updates of all sweeps.
+    pub naccept: usize,
+}
+
+/// Run sweeps in blocks of nblock until MSER judges the energy equilibrated or nmax sweeps ran.
+/**
+ * sweep(n, energies) runs n sweeps, appends the energy after each to energies, and returns
+ * the number of accepted updates. MSER is evaluated on the whole history after each block.
+ */
+pub fn thermalise<F>(nblock: usize, nmax: usize, mut sweep: F) -> Thermalisation
+where F: FnMut(usize, &mut Vec<f64>) -> usize {
+    let mut energies = Vec::new();
+    let mut naccept = 0;
+    while energies.len() < nmax {
+        naccept += sweep(nblock.min(nmax - energies.len()), &mut energies);
+        if let Some(nequilibrate) = mser(&energies) {
+            return Thermalisation{nsweep: energies.len(), nequilibrate: Some(nequilibrate), naccept};
+        }
+    }
+    Thermalisation{nsweep: energies.len(), nequilibrate: None, naccept}
+}
@@ -0,0 +1,159 @@
+/*!
+ * Wang-Landau sampling of the density of states g(E) of the Ising model in zero field.
+ *
+ * A random walk in configuration space flips single spins with probability min(1, g(E)/g(E'))
+ * and multiplies g(E) of the current energy by a modification factor f after every step.
+ * Once the energy histogram of the walk is flat, f is replaced by its square root and the histogram
+ * is reset, until ln f falls below a threshold. Thermodynamic quantities then follow at any
+ * temperature from g(E) alone.
+ *
+ * With uniform couplings, the energy levels are E = -J (nbond - 2a) with the number a of
+ * antiparallel bonds, which labels the bins of the histogram.
+ */
+
+use std::path::Path;
+
+use crate::error::{Error, OutputFile};
+use crate::{Configuration, Rng, bond_sum, info, neighbour_sum};
+
+/// Parameters of the modification factor schedule and flatness criterion.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WangLandauParams {
+    /// Stop once ln f is smaller than this.
+    pub ln_f_final: f64,
+    /// The histogram is flat if every visited level has at least this fraction of the mean count.
+    pub flatness: f64,
+    /// Number of sweeps between two checks of the flatness.
+    pub ncheck: usize,
+}
+
+/// Logarithm of the density of states on the energy levels that were visited.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DensityOfStates {
+    /// Energy levels in increasing order.
+    pub energies: Vec<f64>,
+    /// ln g(E) of each level, normalised to Σ_E g(E) = 2^nsite.
+    pub ln_g: Vec<f64>,
+    /// Number of sites of the lattice.
+    pub nsite: usize,
+}
+
+/// Thermodynamic quantities per site at one temperature.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Thermodynamics {
+    /// Internal energy ⟨E⟩ / N.
+    pub energy: f64,
+    /// Specific heat (⟨E²⟩ - ⟨E⟩²) / (N T²).
+    pub specific_heat: f64,
+    /// Free energy -T ln Z / N.
+    pub free_energy: f64,
+    /// Entropy (⟨E⟩ - F) / (N T).
+    pub entropy: f64,
+}
+
+impl DensityOfStates {
+    /// Compute the thermodynamic quantities at temperature temp.
+    pub fn thermodynamics(&self, temp: f64) -> Thermodynamics {
+        let exponents: Vec<f64> = self.energies.iter().zip(&self.ln_g).map(|(e, ln_g)| ln_g - e/temp).collect();
+        let max = exponents.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let weights: Vec<f64> = exponents.iter().map(|x| (x - max).exp()).collect();
+        let z: f64 = weights.iter().sum();
+        let mean = |f: &dyn Fn(f64) -> f64| self.energies.iter().zip(&weights)
+            .map(|(&e, w)| w*f(e)).sum::<f64>() / z;
+
+        let n = self.nsite as f64;
+        let energy = mean(&|e| e);
+        let variance = mean(&|e| (e - energy).powi(2));
+        let free_energy = -temp*(max + z.ln());
+        Thermodynamics{energy: energy/n, specific_heat: variance/(temp*temp)/n,
+                       free_energy: free_energy/n, entropy: (energy - free_energy)/temp/n}
+    }
+}
+
+/// Estimate the density of states of the Ising model with coupling j on the lattice of cfg.
+/**
+ * Starts from ln f = 1 and flips spins at random sites of cfg. The couplings must be uniform, i.e.
+ * cfg has neither coupling factors nor a local field. Only levels that were visited at least once
+ * are subject to the flatness check and returned.
+ */
+pub fn sample(cfg: &mut Configuration, j: f64, params: &WangLandauParams, rng: &mut Rng) -> DensityOfStates {
+    assert!(!cfg.has_couplings() && cfg.local_field.is_none(), "Wang-Landau sampling needs uniform couplings");
+    let nbond = (0..cfg.size()).map(|idx| cfg.neighbours_of(idx).len()).sum::<usize>() / 2;
+    // number of antiparallel bonds
+    let mut level = ((nbond as i64 - bond_sum(cfg)) / 2) as usize;
+
+    let mut ln_g = vec![0_f64; nbond+1];
+    let mut histogram = vec![0_u64; nbond+1];
+    let mut visited = vec![false; nbond+1];
+    let mut ln_f = 1.;
+    let mut nsweep: usize = 0;
+    while ln_f > params.ln_f_final {
+        for _sweep in 0..params.ncheck {
+            for _step in 0..cfg.size() {
+                let idx = rng.gen_index(cfg.size());
+                let proposed = (level as i64 + (cfg[idx] * neighbour_sum(cfg, idx)) as i64) as usize;
+                let log_ratio = ln_g[level] - ln_g[proposed];
+                if log_ratio >= 0. || log_ratio.exp() > rng.gen_real() {
+                    cfg[idx] *= -1;
+                    level = proposed;
+                }
+                ln_g[level] += ln_f;
+                histogram[level] += 1;
+                visited[level] = true;
+            }
+        }
+        nsweep += params.ncheck;
+
+        if is_flat(&histogram, &visited, params.flatness) {
+            info!("  Histogram is flat after {} sweeps with ln f = {}", nsweep, ln_f);
+            ln_f /= 2.;
+            histogram.iter_mut().for_each(|count| *count = 0);
+        }
+    }
+
+    let levels: Vec<usize> = (0..=nbond).filter(|&a| visited[a]).collect();
+    let mut ln_g: Vec<f64> = levels.iter().map(|&a| ln_g[a]).collect();
+    let energies = levels.iter().map(|&a| -j*(nbond as f64 - 2.*a as f64)).collect();
+    // normalise to the total number of states
+    let max = ln_g.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let ln_z = max + ln_g.iter().map(|x| (x - max).exp()).sum::<f64>().ln();
+    let shift = cfg.size() as f64 * 2_f64.ln() - ln_z;
+    ln_g.iter_mut().for_each(|x| *x += shift);
+
+    let mut dos = DensityOfStates{energies, ln_g, nsite: cfg.size()};
+    if j < 0. {
+        // levels are sorted by the number of antiparallel bonds
+        dos.energies.reverse();
+        dos.ln_g.reverse();
+    }
+    dos
+}
+
+/// Return true if all visited levels have at least flatness times the mean count of visited levels.
+fn is_flat(histogram: &[u64], visited: &[bool], flatness: f64) -> bool {
+    let counts: Vec<u64> = histogram.iter().zip(visited).filter(|(_, &v)| v).map(|(&c, _)| c).collect();
+    let mean = counts.iter().sum::<u64>() as f64 / counts.len() as f64;
+    counts.iter().all(|&c| c as f64 >= flatness*mean)
+}
+
+/// Estimate the density of states and evaluate the thermodynamics at the given temperatures.
+/**
+ * Writes `dos.dat` with lines `<i>: <E> <ln g(E)>` and `wang_landau.dat` with lines
+ * `<i>: <T> <energy> <specific heat> <free energy> <entropy>`, all per site, see sample.
+ */
+pub fn run(datadir: &Path, cfg: &mut Configuration, j: f64, temperatures: &[f64], params: &WangLandauParams,
+           rng: &mut Rng) -> Result<(), Error> {
+    let dos = sample(cfg, j, params, rng);
+
+    let mut dosfile = OutputFile::create(&datadir.join("dos.dat"))?;
+    for (i, (e, ln_g)) in dos.energies.iter().zip(&dos.ln_g).enumerate() {
+        writeln!(dosfile, "{}: {} {}", i, e, ln_g)?;
+    }
+    let mut outfile = OutputFile::create(&datadir.join("wang_landau.dat"))?;
+    for (i, &temp) in temperatures.iter().enumerate() {
+        let thermo = dos.thermodynamics(temp);
+        writeln!(outfile, "{}: {} {} {} {} {}", i, temp, thermo.energy, thermo.specific_heat,
+                 thermo.free_energy, thermo.entropy)?;
+    }
+    Ok(())
+}
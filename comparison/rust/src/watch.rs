@@ -0,0 +1,131 @@
+/*!
+ * Live view of a running simulation in the terminal.
+ *
+ * The lattice is drawn with Unicode half blocks, two sites per character cell, with the energy and
+ * magnetisation as sparklines below it. The view is plain text with ANSI escape codes for greyscale colours
+ * and is redrawn in place on stderr, so no terminal library is needed.
+ * Lattices larger than the view are shown as averages of the spins in square blocks of sites.
+ */
+
+use std::io::prelude::*;
+use std::time::{Duration, Instant};
+
+use crate::{Configuration, Observables};
+
+/// Largest number of character columns of the lattice.
+pub const MAX_COLUMNS: usize = 80;
+
+/// Largest number of lattice rows of the view, two rows share one line of the terminal.
+pub const MAX_ROWS: usize = 48;
+
+/// Number of values in the sparklines of the energy and magnetisation.
+pub const SPARKLINE_WIDTH: usize = 60;
+
+/// Minimum time between two redraws.
+const REFRESH: Duration = Duration::from_millis(50);
+
+/// Characters of sparklines from low to high.
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Live view that is redrawn every few sweeps.
+pub struct Watch {
+    /// Number of sweeps between redraws.
+    every: usize,
+    /// Time of the last redraw, None if nothing was drawn yet.
+    last: Option<Instant>,
+}
+
+impl Watch {
+    /// Start a view that is redrawn every given number of sweeps.
+    pub fn new(every: usize) -> Watch {
+        Watch{every, last: None}
+    }
+
+    /// Redraw the view at temperature temp after sweep out of nsweep sweeps with the history obs.
+    /**
+     * Only draws if sweep is a multiple of the number of sweeps between redraws and
+     * at least REFRESH passed since the last redraw.
+     */
+    pub fn update(&mut self, cfg: &Configuration, temp: f64, sweep: usize, nsweep: usize, obs: &Observables) {
+        if !sweep.is_multiple_of(self.every) || self.last.is_some_and(|last| last.elapsed() < REFRESH) {
+            return;
+        }
+        eprint!("{}", render(cfg, temp, sweep, nsweep, obs));
+        std::io::stderr().flush().unwrap();
+        self.last = Some(Instant::now());
+    }
+}
+
+/// Average the spins of cfg in square blocks so the result has at most max_columns columns and max_rows rows.
+/**
+ * Returns the number of columns, the number of rows, and the mean spins in row major order.
+ * Blocks at the right and bottom edges may be smaller.
+ */
+pub fn downsample(cfg: &Configuration, max_columns: usize, max_rows: usize) -> (usize, usize, Vec<f64>) {
+    let block = cfg.nx.div_ceil(max_columns).max(cfg.ny.div_ceil(max_rows));
+    let (ncol, nrow) = (cfg.nx.div_ceil(block), cfg.ny.div_ceil(block));
+    let mut means = Vec::with_capacity(ncol*nrow);
+    for row in 0..nrow {
+        for col in 0..ncol {
+            let ys = row*block..((row+1)*block).min(cfg.ny);
+            let xs = col*block..((col+1)*block).min(cfg.nx);
+            let n = ys.len() * xs.len();
+            let sum: i32 = ys.flat_map(|y| xs.clone().map(move |x| y*cfg.nx + x)).map(|idx| cfg[idx]).sum();
+            means.push(sum as f64 / n as f64);
+        }
+    }
+    (ncol, nrow, means)
+}
+
+/// Return a sparkline of the last width values, scaled between their minimum and maximum.
+pub fn sparkline(values: &[f64], width: usize) -> String {
+    let values = &values[values.len().saturating_sub(width)..];
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    values.iter().map(|&value| {
+        let level = if max > min { (value - min) / (max - min) * (SPARKS.len() - 1) as f64 } else { 0. };
+        SPARKS[level.round() as usize]
+    }).collect()
+}
+
+/// Return the ANSI 256-colour greyscale index of a mean spin, black for -1 and white for +1.
+fn grey(mean: f64) -> u8 {
+    232 + ((mean + 1.) / 2. * 23.).round() as u8
+}
+
+/// Return the view of cfg at temperature temp after sweep out of nsweep sweeps with the history obs.
+/**
+ * Starts with the escape codes that move the cursor to the top left corner and clear the screen.
+ */
+pub fn render(cfg: &Configuration, temp: f64, sweep: usize, nsweep: usize, obs: &Observables) -> String {
+    let (ncol, nrow, means) = downsample(cfg, MAX_COLUMNS, MAX_ROWS);
+    let mut view = format!("\x1b[H\x1b[J T = {}, sweep {}/{}\n", temp, sweep, nsweep);
+    for row in (0..nrow).step_by(2) {
+        // only emit colours that change to keep redraws small
+        let mut colours = None;
+        for col in 0..ncol {
+            // foreground is the upper half, background the lower one, the last line of odd rows has none
+            let upper = grey(means[row*ncol + col]);
+            let lower = (row+1 < nrow).then(|| grey(means[(row+1)*ncol + col]));
+            if colours != Some((upper, lower)) {
+                view += &format!("\x1b[38;5;{}m", upper);
+                if let Some(lower) = lower {
+                    view += &format!("\x1b[48;5;{}m", lower);
+                }
+                colours = Some((upper, lower));
+            }
+            view.push('▀');
+        }
+        view += "\x1b[0m\n";
+    }
+
+    let recent = obs.energy.len().saturating_sub(SPARKLINE_WIDTH);
+    let energy: Vec<f64> = obs.energy[recent..].iter().map(|e| e / cfg.nsite() as f64).collect();
+    for (label, values) in [("E/N", &energy), ("m  ", &obs.magnetisation)] {
+        match values.last() {
+            Some(last) => view += &format!(" {} {} {:.4}\n", label, sparkline(values, SPARKLINE_WIDTH), last),
+            None => view += &format!(" {}\n", label),
+        }
+    }
+    view
+}
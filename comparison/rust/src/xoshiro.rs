@@ -0,0 +1,131 @@
+/*!
+ * The xoshiro256++ random number generator by Blackman and Vigna, see https://prng.di.unimi.it/.
+ *
+ * rand provides the same generator as rngs::Xoshiro256PlusPlus with the same seeding, but hides its state,
+ * which checkpoints need to continue a run. This implementation produces the same numbers and exposes
+ * the four words of state.
+ *
+ * jump and long_jump advance the generator by 2^128 and 2^192 numbers at the cost of drawing
+ * a few hundred numbers. Generators that are jumps apart draw non-overlapping sequences, which
+ * makes them independent random streams for threads or chains.
+ */
+
+use std::convert::Infallible;
+
+use rand::TryRng;
+
+/// Jump polynomial of x^(2^128) modulo the characteristic polynomial, by Blackman and Vigna.
+const JUMP: [u64; 4] = [0x180ec6d33cfd0aba, 0xd5a61266f0c9392c, 0xa9582618e03fc9aa, 0x39abdc4529b1661c];
+
+/// Jump polynomial of x^(2^192) modulo the characteristic polynomial, by Blackman and Vigna.
+const LONG_JUMP: [u64; 4] = [0x76e15d3efefdcbbf, 0xc5004e441c522fb3, 0x77710069854ee241, 0x39109bb02acbe635];
+
+/// xoshiro256++ with a state of four 64 bit words.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Xoshiro256PlusPlus {
+    s: [u64; 4],
+}
+
+impl Xoshiro256PlusPlus {
+    /// Create a generator from 32 bytes read as four little endian words.
+    /**
+     * The all zero state never leaves zero, so an all zero seed is replaced by
+     * SplitMix64 output like in rand.
+     */
+    pub fn from_seed(seed: [u8; 32]) -> Xoshiro256PlusPlus {
+        let mut s = [0; 4];
+        for (word, bytes) in s.iter_mut().zip(seed.chunks_exact(8)) {
+            *word = u64::from_le_bytes(bytes.try_into().unwrap());
+        }
+        if s == [0; 4] {
+            return Xoshiro256PlusPlus{s: splitmix64(0)};
+        }
+        Xoshiro256PlusPlus{s}
+    }
+
+    /// Create a generator that continues at a state returned by state.
+    /**
+     * The state must not be all zero.
+     */
+    pub fn from_state(s: [u64; 4]) -> Xoshiro256PlusPlus {
+        assert_ne!(s, [0; 4], "The state of xoshiro256++ must not be all zero");
+        Xoshiro256PlusPlus{s}
+    }
+
+    /// Return the current state.
+    pub fn state(&self) -> [u64; 4] {
+        self.s
+    }
+
+    /// Advance the state by 2^128 numbers.
+    pub fn jump(&mut self) {
+        self.jump_by(&JUMP);
+    }
+
+    /// Advance the state by 2^192 numbers.
+    pub fn long_jump(&mut self) {
+        self.jump_by(&LONG_JUMP);
+    }
+
+    /// Replace the state by the jump polynomial evaluated at the transition matrix applied to the state.
+    fn jump_by(&mut self, polynomial: &[u64; 4]) {
+        let mut s = [0; 4];
+        for &word in polynomial {
+            for bit in 0..64 {
+                if word & (1 << bit) != 0 {
+                    s.iter_mut().zip(self.s).for_each(|(a, b)| *a ^= b);
+                }
+                self.advance();
+            }
+        }
+        self.s = s;
+    }
+
+    /// Return the next 64 random bits.
+    fn advance(&mut self) -> u64 {
+        let s = &mut self.s;
+        let result = s[0].wrapping_add(s[3]).rotate_left(23).wrapping_add(s[0]);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+}
+
+impl TryRng for Xoshiro256PlusPlus {
+    type Error = Infallible;
+
+    fn try_next_u32(&mut self) -> Result<u32, Infallible> {
+        // the lowest bits are the weakest
+        Ok((self.advance() >> 32) as u32)
+    }
+
+    fn try_next_u64(&mut self) -> Result<u64, Infallible> {
+        Ok(self.advance())
+    }
+
+    fn try_fill_bytes(&mut self, dst: &mut [u8]) -> Result<(), Infallible> {
+        for chunk in dst.chunks_mut(8) {
+            let bytes = self.advance().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+        Ok(())
+    }
+}
+
+/// Return four words of the SplitMix64 sequence started at state.
+fn splitmix64(mut state: u64) -> [u64; 4] {
+    let mut s = [0; 4];
+    for word in s.iter_mut() {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        *word = z ^ (z >> 31);
+    }
+    s
+}
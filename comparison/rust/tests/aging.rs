@@ -0,0 +1,92 @@
+use ising::Rng;
+use ising::aging::{self, overlap};
+
+#[test]
+fn overlap_of_equal_and_opposite_configurations() {
+    let a = [1, -1, 1, 1];
+    assert_eq!(overlap(&a, &a), 1.);
+    assert_eq!(overlap(&a, &[-1, 1, -1, -1]), -1.);
+    assert_eq!(overlap(&a, &[1, 1, -1, 1]), 0.);
+}
+
+#[test]
+fn correlation_starts_at_one_for_all_waiting_times() {
+    let mut rng = Rng::from_seed([3; 32]);
+    let waiting_times = [0, 4, 10];
+    let result = aging::quench(8, 8, 1./1.5, 1., &waiting_times, 20, None, &mut rng);
+    assert!(result.response.iter().all(Vec::is_empty));
+    for (correlation, tw) in result.correlation.iter().zip(waiting_times) {
+        assert_eq!(correlation.len(), 20 - tw + 1);
+        assert_eq!(correlation[0], 1.);
+        assert!(correlation.iter().all(|c| c.abs() <= 1.));
+    }
+}
+
+#[test]
+fn correlation_decays_faster_at_high_temperature() {
+    let mean_correlation = |temp: f64, rng: &mut Rng| {
+        (0..20).map(|_| aging::quench(16, 16, 1./temp, 1., &[10], 20, None, rng).correlation[0][10])
+            .sum::<f64>() / 20.
+    };
+    let mut rng = Rng::from_seed([5; 32]);
+    let cold = mean_correlation(1., &mut rng);
+    let hot = mean_correlation(10., &mut rng);
+    assert!(hot < 0.2 && cold > 0.5, "C(20, 10) = {} at T = 1, {} at T = 10", cold, hot);
+}
+
+#[test]
+fn run_writes_correlation_for_all_waiting_times() {
+    let dir = std::env::temp_dir().join(format!("ising-aging-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut rng = Rng::from_seed([7; 32]);
+    aging::run(&dir, 6, 6, 1.5, 1., &[0, 5], 10, 3, None, &mut rng).unwrap();
+
+    assert_eq!(std::fs::read_to_string(dir.join("waiting_times.dat")).unwrap(), "0: 0\n1: 5\n");
+    for (iw, len) in [(0, 11), (1, 6)] {
+        let text = std::fs::read_to_string(dir.join(format!("aging_{}.dat", iw))).unwrap();
+        let rows: Vec<Vec<f64>> = text.lines()
+            .map(|line| line.split_whitespace().map(|x| x.parse().unwrap()).collect()).collect();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].len(), len);
+        assert!((rows[0][0] - 1.).abs() < 1e-12);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn response_starts_at_zero_and_matches_equilibrium_fdt() {
+    let mut rng = Rng::from_seed([9; 32]);
+    let result = aging::quench(8, 8, 1./2., 1., &[0, 5], 10, Some(0.05), &mut rng);
+    for (response, correlation) in result.response.iter().zip(&result.correlation) {
+        assert_eq!(response.len(), correlation.len());
+        assert_eq!(response[0], 0.);
+    }
+
+    // a quench to a high temperature equilibrates at once and after the correlations have decayed,
+    // T χ reaches the static local susceptibility T χ = 1 - C = 1 of the paramagnet
+    let (temp, field, nrepeat, tau) = (5., 0.5, 400, 10);
+    let (mut tchi, mut c) = (0., 0.);
+    for _ in 0..nrepeat {
+        let result = aging::quench(16, 16, 1./temp, 1., &[5], 5 + tau, Some(field), &mut rng);
+        tchi += temp*result.response[0][tau] / nrepeat as f64;
+        c += result.correlation[0][tau] / nrepeat as f64;
+    }
+    assert!(c.abs() < 0.05, "C = {}", c);
+    assert!((tchi - 1.).abs() < 0.15, "T chi = {}", tchi);
+}
+
+#[test]
+fn run_writes_response_row_with_field() {
+    let dir = std::env::temp_dir().join(format!("ising-aging-response-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut rng = Rng::from_seed([11; 32]);
+    aging::run(&dir, 6, 6, 1.5, 1., &[2], 8, 2, Some(0.1), &mut rng).unwrap();
+
+    let text = std::fs::read_to_string(dir.join("aging_0.dat")).unwrap();
+    let rows: Vec<Vec<f64>> = text.lines()
+        .map(|line| line.split_whitespace().map(|x| x.parse().unwrap()).collect()).collect();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[1].len(), 7);
+    assert_eq!(rows[1][0], 0.);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
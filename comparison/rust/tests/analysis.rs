@@ -0,0 +1,378 @@
+use ising::{Configuration, Observables, Rng, evolve, fourier, fss, hamiltonian};
+use ising::analysis::{self, Autocorrelation, Summary, fits};
+use ising::reweighting::{self, MultiHistogram, Reweighting, Run};
+use ising::thermalisation::{self, Thermalisation};
+
+#[test]
+fn disorder_average_has_standard_error() {
+    let (mean, error) = analysis::disorder_average(&[1., 2., 3., 6.]);
+    assert!((mean - 3.).abs() < 1e-12);
+    // sample variance 14/3 over 4 realisations
+    assert!((error - (14./3. / 4f64).sqrt()).abs() < 1e-12);
+    assert_eq!(analysis::disorder_average(&[0.25]), (0.25, 0.));
+}
+
+#[test]
+fn reweighted_mean_handles_large_weights() {
+    assert!((analysis::reweighted_mean(&[1., 3.], &[0., 0.]) - 2.).abs() < 1e-12);
+    // weights e^1000 and e^1000 * 3
+    assert!((analysis::reweighted_mean(&[1., 5.], &[1000., 1000. + 3_f64.ln()]) - 4.).abs() < 1e-12);
+}
+
+#[test]
+fn variance_of_constant_is_zero() {
+    assert_eq!(analysis::variance(&[0.5; 16]), 0.);
+    assert_eq!(analysis::susceptibility(&[0.5; 16], 1., 16), 0.);
+    assert_eq!(analysis::specific_heat(&[-32.; 16], 1., 16), 0.);
+}
+
+#[test]
+fn susceptibility_of_two_state_series() {
+    // ⟨m²⟩ = 1, ⟨m⟩ = 0
+    let magnetisation = [1., -1., 1., -1.];
+    assert!((analysis::susceptibility(&magnetisation, 0.5, 16) - 8.).abs() < 1e-12);
+}
+
+#[test]
+fn specific_heat_of_two_state_series() {
+    // ⟨E²⟩ - ⟨E⟩² = 4
+    let energy = [-10., -6., -10., -6.];
+    assert!((analysis::specific_heat(&energy, 0.5, 4) - 0.25).abs() < 1e-12);
+}
+
+#[test]
+fn binder_cumulant_limits() {
+    // fully ordered
+    assert!((analysis::binder_cumulant(&[1., -1., 1., 1.]) - 2./3.).abs() < 1e-12);
+    // uniform on [-1, 1], ⟨m²⟩ = 1/3, ⟨m⁴⟩ = 1/5
+    let n = 100_000;
+    let uniform: Vec<f64> = (0..n).map(|i| 2.*(i as f64 + 0.5)/n as f64 - 1.).collect();
+    assert!((analysis::binder_cumulant(&uniform) - 0.4).abs() < 1e-6);
+}
+
+#[test]
+fn jackknife_of_mean_is_standard_error() {
+    let data = [1., 4., 2., 8., 5., 7.];
+    let (mean, error) = analysis::jackknife(&data, 1, analysis::mean);
+    assert_eq!(mean, 4.5);
+    let stderr = (analysis::variance(&data) / 5.).sqrt();
+    assert!((error - stderr).abs() < 1e-12);
+
+    // bins of 2 drop the trailing measurement
+    let (mean, _) = analysis::jackknife(&data[..5], 2, analysis::mean);
+    assert_eq!(mean, 3.75);
+}
+
+#[test]
+fn jackknife_of_series_keeps_correlations() {
+    let x = [1., 4., 2., 8., 5., 7.];
+    let y = [2., 8., 4., 16., 10., 14.];
+    // the ratio of means is exact in every jackknife sample
+    assert_eq!(analysis::jackknife_series(&[&x, &y], 2, |s| analysis::mean(&s[1]) / analysis::mean(&s[0])),
+               (2., 0.));
+    assert_eq!(analysis::jackknife_series(&[&x], 1, |s| analysis::mean(&s[0])),
+               analysis::jackknife(&x, 1, analysis::mean));
+}
+
+#[test]
+fn second_moment_length_from_structure_factors() {
+    // sqrt(5 - 1) / (2 sin(π/6)) = 2
+    assert!((analysis::second_moment_length(&[4., 6.], &[1., 1.], 6) - 2.).abs() < 1e-12);
+    assert_eq!(analysis::second_moment_length(&[1.], &[2.], 6), 0.);
+}
+
+#[test]
+fn smallest_structure_factor_of_simple_configurations() {
+    assert!(fourier::smallest_structure_factor(&Configuration::cold(8, 4, 1)).abs() < 1e-12);
+    // a stripe of down spins, its magnetisation profile has one wavelength along x
+    let mut cfg = Configuration::cold(8, 4, 1);
+    for y in 0..4 {
+        for x in 2..6 {
+            cfg[y*8 + x] = -1;
+        }
+    }
+    let expected = (fourier::fourier_mode(&cfg, (1, 0)).0.powi(2) + fourier::fourier_mode(&cfg, (1, 0)).1.powi(2))
+        / 32.;
+    assert!((fourier::smallest_structure_factor(&cfg) - expected).abs() < 1e-9);
+    assert!(expected > 1.);
+}
+
+#[test]
+fn bootstrap_of_mean_matches_standard_error() {
+    let mut rng = Rng::from_seed([20; 32]);
+    let data: Vec<f64> = (0..1000).map(|_| rng.gen_real()).collect();
+    let (mean, error) = analysis::bootstrap(&[&data], 1, 2000, &mut rng, |s| analysis::mean(&s[0]));
+    assert_eq!(mean, analysis::mean(&data));
+    let stderr = (analysis::variance(&data) / 1000.).sqrt();
+    assert!((error/stderr - 1.).abs() < 0.1, "{} vs {}", error, stderr);
+}
+
+#[test]
+fn bootstrap_resamples_series_jointly() {
+    // the ratio of a series with itself is always 1
+    let mut rng = Rng::from_seed([21; 32]);
+    let data: Vec<f64> = (0..100).map(|_| 1. + rng.gen_real()).collect();
+    let (ratio, error) = analysis::bootstrap(&[&data, &data], 5, 100, &mut rng,
+                                             |s| analysis::mean(&s[0]) / analysis::mean(&s[1]));
+    assert_eq!(ratio, 1.);
+    assert!(error < 1e-12);
+}
+
+#[test]
+fn binsize_from_tau() {
+    assert_eq!(analysis::binsize(0.5, 100), 3);
+    assert_eq!(analysis::binsize(0.5, 10000), 100);
+    assert_eq!(analysis::binsize(50., 10000), 250);
+    assert_eq!(analysis::binsize(1000., 10000), 1000);
+    assert_eq!(analysis::binsize(10., 5), 1);
+}
+
+#[test]
+fn summary_uses_absolute_magnetisation() {
+    let mut obs = Observables::new();
+    obs.energy = vec![-8., -4.];
+    obs.magnetisation = vec![0.5, -0.5];
+    obs.structure = vec![0.5, 0.5];
+    let summary = Summary::new(&obs, 1., 2, 4, 1, 100, &mut Rng::from_seed([19; 32]));
+    assert_eq!(summary.energy.0, -1.5);
+    assert_eq!(summary.abs_magnetisation, (0.5, 0.));
+    assert!((summary.susceptibility.0 - 1.).abs() < 1e-12);
+    assert!((summary.specific_heat.0 - 1.).abs() < 1e-12);
+    assert!((summary.binder.0 - 2./3.).abs() < 1e-12);
+    assert!(summary.binder.1 < 1e-12);
+    // S(0) = N m² = 1, twice S(k)
+    assert!((summary.correlation_length.0 - 1. / (2. * (std::f64::consts::PI / 2.).sin())).abs() < 1e-12);
+}
+
+#[test]
+fn autocorrelation_of_uncorrelated_data() {
+    let mut rng = Rng::from_seed([17; 32]);
+    let data: Vec<f64> = (0..10000).map(|_| rng.gen_real()).collect();
+    let autocorr = Autocorrelation::new(&data);
+    assert!((autocorr.tau - 0.5).abs() < 3.*autocorr.error.max(0.01));
+    assert!((autocorr.effective_samples() - 10000.).abs() < 1000.);
+
+    assert_eq!(Autocorrelation::new(&[1.; 100]).tau, 0.5);
+}
+
+#[test]
+fn autocorrelation_of_ar1_process() {
+    // x_{i+1} = a x_i + noise has τ_int = (1+a) / (2(1-a))
+    let a: f64 = 0.8;
+    let mut rng = Rng::from_seed([18; 32]);
+    let mut x = 0.;
+    let data: Vec<f64> = (0..100000).map(|_| {
+        x = a*x + rng.gen_real() - 0.5;
+        x
+    }).collect();
+    let autocorr = Autocorrelation::new(&data);
+    let exact = (1.+a) / (2.*(1.-a));
+    assert!((autocorr.tau - exact).abs() < 3.*autocorr.error, "{:?} vs {}", autocorr, exact);
+    assert!(autocorr.window > 1);
+}
+
+#[test]
+fn fft_matches_dft() {
+    let mut rng = Rng::from_seed([23; 32]);
+    for (nx, ny) in [(8, 4), (1, 16), (16, 1), (6, 4)] {
+        let data: Vec<fourier::Complex> = (0..nx*ny).map(|_| (rng.gen_real() - 0.5, rng.gen_real() - 0.5)).collect();
+        for inverse in [false, true] {
+            let mut fast = data.clone();
+            let mut slow = data.clone();
+            fourier::fft_2d(&mut fast, nx, ny, inverse);
+            fourier::dft_2d(&mut slow, nx, ny, inverse);
+            assert!(fast.iter().zip(slow.iter()).all(|(a, b)| (a.0-b.0).abs() < 1e-10 && (a.1-b.1).abs() < 1e-10),
+                    "FFT differs from DFT on {}x{}", nx, ny);
+        }
+    }
+}
+
+#[test]
+fn structure_factor_peaks_at_ordering_vector() {
+    let mut cold = fourier::StructureFactor::new(8, 4);
+    cold.measure(&Configuration::cold(8, 4, -1));
+    let sk = cold.average();
+    assert!((sk[0] - 32.).abs() < 1e-9);
+    assert!(sk[1..].iter().all(|s| s.abs() < 1e-9));
+
+    // Néel state orders at k = (π, π)
+    let mut neel = Configuration::cold(8, 4, 1);
+    for idx in 0..32 {
+        if (idx % 8 + idx / 8) % 2 == 1 {
+            neel[idx] = -1;
+        }
+    }
+    let mut antiferro = fourier::StructureFactor::new(8, 4);
+    antiferro.measure(&neel);
+    antiferro.measure(&neel);
+    let sk = antiferro.average();
+    assert!((sk[2*8 + 4] - 32.).abs() < 1e-9);
+    assert!((sk.iter().sum::<f64>() - 32.).abs() < 1e-9);
+}
+
+#[test]
+fn dynamic_structure_factor_of_frozen_and_flipping_configurations() {
+    let modes = fourier::axis_modes(8, 4);
+    assert_eq!(modes, vec![(0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (0, 1), (0, 2)]);
+
+    // a frozen stripe configuration correlates perfectly with itself at all times
+    let mut stripes = Configuration::cold(8, 4, 1);
+    for idx in (0..32).filter(|idx| idx % 8 >= 4) {
+        stripes[idx] = -1;
+    }
+    let frozen = vec![fourier::fourier_modes(&stripes, &modes); 5];
+    let skt = fourier::dynamic_structure_factor(&frozen, 3, 32);
+    assert_eq!(skt.len(), modes.len());
+    for (k, mode) in modes.iter().enumerate() {
+        let (re, im) = fourier::fourier_mode(&stripes, *mode);
+        assert_eq!(skt[k].len(), 3);
+        assert!(skt[k].iter().all(|s| (s - (re*re + im*im)/32.).abs() < 1e-9), "{:?}: {:?}", mode, skt[k]);
+    }
+    assert!((skt[0][0]).abs() < 1e-9 && skt[1][0] > 1.);
+
+    // the uniform mode of a cold configuration that flips every step alternates in sign
+    let up = fourier::fourier_modes(&Configuration::cold(8, 4, 1), &modes);
+    let down = fourier::fourier_modes(&Configuration::cold(8, 4, -1), &modes);
+    let flipping = vec![up.clone(), down.clone(), up, down];
+    let skt = fourier::dynamic_structure_factor(&flipping, 10, 32);
+    // ntau is limited by the length of the history
+    assert_eq!(skt[0].len(), 4);
+    for (tau, s) in skt[0].iter().enumerate() {
+        assert!((s - if tau % 2 == 0 { 32. } else { -32. }).abs() < 1e-9);
+    }
+    assert!(skt[1..].iter().flatten().all(|s| s.abs() < 1e-9));
+}
+
+#[test]
+fn energy_histogram_counts_distinct_energies() {
+    assert_eq!(reweighting::histogram(&[-4., 0., -8., -4., 0., -4.]), vec![(-8., 1), (-4., 3), (0., 2)]);
+}
+
+#[test]
+fn reweighting_matches_enumeration() {
+    let mut rng = Rng::from_seed([31; 32]);
+    let mut cfg = Configuration::random(3, 3, &mut rng);
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    let runs: Vec<Run> = [2., 3., 4.].iter().map(|&temp| {
+        let mut obs = Observables::new();
+        evolve(&mut cfg, &mut energy, 1./temp, 1., 0., &mut rng, 1000, None);
+        evolve(&mut cfg, &mut energy, 1./temp, 1., 0., &mut rng, 40000, Some(&mut obs));
+        Run{beta: 1./temp, energy: obs.energy, magnetisation: obs.magnetisation}
+    }).collect();
+
+    let mut exact = cfg.clone();
+    let energies: Vec<f64> = (0..1 << 9).map(|bits: usize| {
+        exact.cfg.iter_mut().enumerate().for_each(|(idx, s)| *s = if bits >> idx & 1 == 1 { 1 } else { -1 });
+        hamiltonian(&exact, 1., 0.)
+    }).collect();
+    let exact_ln_z = |temp: f64| energies.iter().map(|e| (-e/temp).exp()).sum::<f64>().ln();
+    let exact_energy = |temp: f64| energies.iter().map(|e| e*(-e/temp).exp()).sum::<f64>()
+        / exact_ln_z(temp).exp() / 9.;
+
+    let multi = MultiHistogram::new(&runs, 1e-10, 10000);
+    for (r, &temp) in [2., 3., 4.].iter().enumerate() {
+        assert!((multi.ln_z[r] - (exact_ln_z(temp) - exact_ln_z(2.))).abs() < 0.05);
+    }
+
+    for method in [Reweighting::Single, Reweighting::Multiple] {
+        let points = reweighting::reweight(&runs, method, 9, 5);
+        assert_eq!(points.iter().map(|p| p.temperature).collect::<Vec<f64>>(), vec![2., 2.5, 3., 3.5, 4.]);
+        for p in &points {
+            assert!((p.energy - exact_energy(p.temperature)).abs() < 0.03, "{:?} {:?}", method, p);
+        }
+    }
+    // single histogram reweighting to a simulated temperature does not change the sample means
+    let single = reweighting::reweight(&runs[..1], Reweighting::Single, 9, 1);
+    assert!((single[0].energy - analysis::mean(&runs[0].energy) / 9.).abs() < 1e-12);
+    assert!((single[0].specific_heat - analysis::specific_heat(&runs[0].energy, 0.5, 9)).abs() < 1e-9);
+}
+
+#[test]
+fn peak_interpolates_parabola() {
+    let temps = [2.5, 1.5, 2., 3.];
+    let values: Vec<f64> = temps.iter().map(|t: &f64| 4. - (t - 2.2).powi(2)).collect();
+    let (t, max) = fss::peak(&temps, &values);
+    assert!((t - 2.2).abs() < 1e-12 && (max - 4.).abs() < 1e-12);
+    // maximum at the edge of the range
+    assert_eq!(fss::peak(&[1., 2., 3.], &[3., 2., 1.]), (1., 3.));
+}
+
+#[test]
+fn crossing_interpolates_linearly() {
+    let temps = [3., 1., 2.];
+    assert_eq!(fss::crossing(&temps, &[0., 1., 0.5], &[1., 0., 0.5]), Some(2.));
+    assert_eq!(fss::crossing(&[1., 2.], &[0., 1.], &[0.5, 0.5]), Some(1.5));
+    assert_eq!(fss::crossing(&[1., 2.], &[1., 1.], &[0., 0.]), None);
+}
+
+#[test]
+fn fits_recover_exact_scaling_laws() {
+    let lengths = [8., 16., 32., 64.];
+    let sigma = [0.1, 0.2, 0.1, 0.3];
+    let chi: Vec<f64> = lengths.iter().map(|l: &f64| 0.5 * l.powf(1.75)).collect();
+    let power = fits::power_law(&lengths, &chi, &sigma);
+    assert!((power.exponent - 1.75).abs() < 1e-12 && (power.amplitude - 0.5).abs() < 1e-12);
+    assert!(power.chi2 < 1e-12);
+
+    let temps: Vec<f64> = lengths.iter().map(|l: &f64| 2.269 + 1.5 * l.powf(-1.)).collect();
+    let shift = fits::shift(&lengths, &temps, &sigma);
+    assert!((shift.limit - 2.269).abs() < 1e-6, "{:?}", shift);
+    assert!((shift.amplitude - 1.5).abs() < 1e-4 && (shift.exponent - 1.).abs() < 1e-5, "{:?}", shift);
+    assert!((shift.eval(128.) - temps[0] + 1.5 * (1./8. - 1./128.)).abs() < 1e-6);
+}
+
+#[test]
+fn fit_errors_propagate_from_jackknife_samples() {
+    let mut rng = Rng::from_seed([22; 32]);
+    let data: Vec<Vec<f64>> = (0..3).map(|i| (0..200).map(|_| i as f64 + rng.gen_real()).collect()).collect();
+    let (y, samples): (Vec<f64>, Vec<Vec<f64>>) = data.iter()
+        .map(|d| analysis::jackknife_samples(&[d], 10, |s| analysis::mean(&s[0])))
+        .unzip();
+    for (d, samples) in data.iter().zip(&samples) {
+        let (_, error) = analysis::jackknife(d, 10, analysis::mean);
+        assert!((analysis::jackknife_error(samples) - error).abs() < 1e-14);
+    }
+
+    // the error of a sum of independent points adds in quadrature
+    let sum = fits::jackknife(&y, &samples, |y| vec![y.iter().sum()]);
+    let expected = samples.iter().map(|s| analysis::jackknife_error(s).powi(2)).sum::<f64>().sqrt();
+    assert!((sum[0].0 - y.iter().sum::<f64>()).abs() < 1e-12);
+    assert!((sum[0].1 - expected).abs() < 1e-12);
+    let propagated = fits::propagate(&y, &[0.1, 0.2, 0.2], |y| vec![2.*y[0] - y[1]]);
+    assert!((propagated[0].1 - 0.2_f64.hypot(0.2)).abs() < 1e-12);
+}
+
+#[test]
+fn mser_truncates_transient() {
+    let mut rng = Rng::from_seed([5; 32]);
+    let series: Vec<f64> = (0..2000).map(|t| 10.*(-(t as f64)/50.).exp() + rng.gen_real() - 0.5).collect();
+    let ndiscard = thermalisation::mser(&series).unwrap();
+    assert!((100..400).contains(&ndiscard), "discarded {}", ndiscard);
+
+    let stationary: Vec<f64> = (0..2000).map(|_| rng.gen_real()).collect();
+    assert!(thermalisation::mser(&stationary).unwrap() < 1000);
+    assert_eq!(thermalisation::mser(&[1.; 100]), Some(0));
+    assert_eq!(thermalisation::mser(&[1.; 7]), None);
+    // still drifting
+    let drift: Vec<f64> = (0..1000).map(|t| -(t as f64)).collect();
+    assert_eq!(thermalisation::mser(&drift), None);
+}
+
+#[test]
+fn thermalise_stops_when_equilibrated() {
+    let run = |target: f64, nmax: usize| {
+        let mut t = 0;
+        thermalisation::thermalise(100, nmax, |nsweep, energies| {
+            for _ in 0..nsweep {
+                energies.push(target.min(t as f64));
+                t += 1;
+            }
+            nsweep
+        })
+    };
+    let therm = run(300., 100000);
+    assert!(therm.nsweep >= 600 && therm.nsweep <= 1000, "ran {} sweeps", therm.nsweep);
+    assert!(therm.nequilibrate.is_some_and(|n| (295..=305).contains(&n)));
+    assert_eq!(therm.naccept, therm.nsweep);
+    assert_eq!(run(f64::INFINITY, 250), Thermalisation{nsweep: 250, nequilibrate: None, naccept: 250});
+}
@@ -0,0 +1,41 @@
+use ising::{Configuration, Rng, hamiltonian, magnetisation};
+use ising::cftp;
+use ising::exact::Enumeration;
+
+/// Compute the mean of independent samples and its standard error.
+fn mean_and_error(samples: &[f64]) -> (f64, f64) {
+    let n = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / n;
+    let var = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.);
+    (mean, (var / n).sqrt())
+}
+
+#[test]
+fn samples_match_enumeration() {
+    let (nx, ny, j, h) = (3, 3, 1., 0.1);
+    let mut rng = Rng::from_seed([11; 32]);
+    for temp in [2., 4.] {
+        let mut energy = Vec::new();
+        let mut abs_mag = Vec::new();
+        for _ in 0..1000 {
+            let sample = cftp::sample(nx, ny, 1./temp, j, h, &mut rng, 1 << 16).unwrap();
+            energy.push(hamiltonian(&sample.cfg, j, h) / (nx*ny) as f64);
+            abs_mag.push(magnetisation(&sample.cfg).abs());
+        }
+
+        let exact = Enumeration::new(&Configuration::cold(nx, ny, 1), j, h).averages(&[temp])[0];
+        let (e, e_err) = mean_and_error(&energy);
+        let (m, m_err) = mean_and_error(&abs_mag);
+        assert!((e - exact.energy).abs() < 4.*e_err, "T = {}: E = {} +- {}, exact {}", temp, e, e_err, exact.energy);
+        assert!((m - exact.abs_magnetisation).abs() < 4.*m_err,
+                "T = {}: |m| = {} +- {}, exact {}", temp, m, m_err, exact.abs_magnetisation);
+    }
+}
+
+#[test]
+fn sample_gives_up_after_maxsweep() {
+    let mut rng = Rng::from_seed([12; 32]);
+    // a single sweep cannot bring all up and all down together deep in the ordered phase
+    assert!(cftp::sample(8, 8, 1./0.5, 1., 0., &mut rng, 1).is_none());
+    assert!(cftp::sample(8, 8, 1./0.5, 1., 0., &mut rng, 4).is_none());
+}
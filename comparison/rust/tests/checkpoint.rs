@@ -0,0 +1,37 @@
+use ising::{Configuration, Observables, Rng, RngKind};
+use ising::checkpoint::Checkpoint;
+
+fn make_checkpoint(local_field: Option<Vec<f64>>) -> Checkpoint {
+    let mut rng = Rng::new(RngKind::ChaCha, [21; 32]);
+    let cfg = Configuration::random(5, 4, &mut rng);
+    let mut obs = Observables::new();
+    obs.measure(&cfg, -3.5);
+    obs.measure(&cfg, 0.25);
+    Checkpoint{params: "nx = 5\nny = 4\n".to_string(), temperature: 3, sweep: 17, spins: cfg.cfg.clone(),
+               local_field, energy: -1.75, rng: rng.state(), analysis_rng: Rng::from_seed([4; 32]).state(), obs}
+}
+
+#[test]
+fn checkpoint_roundtrip() {
+    let dir = std::env::temp_dir().join(format!("ising-checkpoint-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let fname = dir.join("checkpoint.bin");
+    for local_field in [None, Some(vec![0.5, -1.0, 2.0])] {
+        let checkpoint = make_checkpoint(local_field);
+        checkpoint.write(&fname).unwrap();
+        assert_eq!(Checkpoint::read(&fname).unwrap(), checkpoint);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn truncated_checkpoint_is_rejected() {
+    let dir = std::env::temp_dir().join(format!("ising-truncated-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let fname = dir.join("checkpoint.bin");
+    make_checkpoint(None).write(&fname).unwrap();
+    let bytes = std::fs::read(&fname).unwrap();
+    std::fs::write(&fname, &bytes[..bytes.len()-1]).unwrap();
+    assert!(Checkpoint::read(&fname).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,28 @@
+use ising::Configuration;
+use ising::classifier::Classifier;
+
+/// Shell command that answers with the total spin and the lattice shape of each configuration.
+const TOTAL_SPIN: &str = "while read -r line; do s=0; for x in $line; do s=$((s + x)); done; \
+                          echo $s $ISING_NX $ISING_NY; done";
+
+#[test]
+fn classifier_answers_every_configuration() {
+    let mut classifier = Classifier::spawn(TOTAL_SPIN, 4, 2).unwrap();
+    assert_eq!(classifier.classify(&Configuration::cold(4, 2, 1)).unwrap(), vec![8., 4., 2.]);
+    let mut cfg = Configuration::cold(4, 2, -1);
+    cfg[0] = 1;
+    cfg[5] = 1;
+    assert_eq!(classifier.classify(&cfg).unwrap(), vec![-4., 4., 2.]);
+}
+
+#[test]
+fn invalid_classifier_output_is_an_error() {
+    let mut classifier = Classifier::spawn("while read line; do echo 0.5 oops; done", 2, 2).unwrap();
+    let err = classifier.classify(&Configuration::cold(2, 2, 1)).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("oops"));
+
+    // a classifier that exits without answering
+    let mut classifier = Classifier::spawn("exit 0", 2, 2).unwrap();
+    assert!(classifier.classify(&Configuration::cold(2, 2, 1)).is_err());
+}
@@ -0,0 +1,221 @@
+use std::path::PathBuf;
+
+use ising::config::{Constants, RunParams, derive_seed, expand_seed, manifest, parse_temperatures, random_seed, read_temperatures, refine};
+use ising::RngKind;
+use ising::logging::{self, Level, LogFormat};
+
+fn constants() -> Constants {
+    Constants{ntau_skt: 100, t_quench: 1.5, nsweep_aging: 10000, nrepeat_aging: 20, h_response: 0.05,
+              waiting_times: vec![10, 100, 1000], nbin_histogram: 50, nreweight: 200, t_latent: 2.3,
+              latent_lattices: vec![(4, 4), (6, 6), (8, 8)], npilot_thin: 1000, energy_tolerance: 1e-9,
+              nblock_auto_therm: 100, nmax_auto_therm: 100000, t_benchmark: 2.3, nsweep_benchmark: 10000,
+              nskip_classifier: 10, frame_delay_movie: 4, max_angle_heisenberg: 1., acceptance_heisenberg: 0.5,
+              nsample_export: 1000, nskip_export: 10, npilot_export: 1000, shard_size_export: 10000,
+              nrepeat_nucleation: 100, nmax_nucleation: 100000, nucleation_temperatures: vec![1., 1.5], nucleation_fields: vec![0.1, 0.2, 0.4],
+              nsample_cftp: 1000, nmax_cftp: 1 << 16, t_multicanonical: 2., niter_multicanonical: 100,
+              nsweep_multicanonical: 1000, ln_f_wang_landau: 1e-8, flatness_wang_landau: 0.8, ncheck_wang_landau: 1000,
+              nbin_validate: 20, max_deviation: 4., width_refine: 0.1}
+}
+
+fn defaults() -> RunParams {
+    RunParams{nx: 4, ny: 3, ntherm_init: 10, ntherm: 10, nprod: 100,
+              temperatures: vec![1., 2.], seed: [138; 32], rng: RngKind::Xoshiro, updates: None,
+              observables: Vec::new(), datadir: PathBuf::from("./data"), constants: constants()}
+}
+
+#[test]
+fn config_overwrites_given_parameters() {
+    let mut params = defaults();
+    params.apply_toml("
+        # comment
+        nx = 16  # trailing comment
+        nprod = 1_000
+        temperatures = [
+            0.5, 2,
+            3.5e0,
+        ]
+        datadir = \"out # not a comment\"
+    ").unwrap();
+
+    assert_eq!(params.nx, 16);
+    assert_eq!(params.ny, 3);
+    assert_eq!(params.nprod, 1000);
+    assert_eq!(params.temperatures, vec![0.5, 2., 3.5]);
+    assert_eq!(params.seed, [138; 32]);
+    assert_eq!(params.datadir, PathBuf::from("out # not a comment"));
+}
+
+#[test]
+fn config_seed() {
+    let mut params = defaults();
+    params.apply_toml("seed = 7").unwrap();
+    assert_eq!(params.seed, expand_seed(7));
+    assert_ne!(expand_seed(7), expand_seed(8));
+
+    let bytes: Vec<String> = (0..32).map(|b| b.to_string()).collect();
+    params.apply_toml(&format!("seed = [{}]", bytes.join(","))).unwrap();
+    assert_eq!(params.seed[31], 31);
+
+    params.apply_toml("rng = \"chacha\"").unwrap();
+    assert_eq!(params.rng, RngKind::ChaCha);
+}
+
+#[test]
+fn derived_seeds_differ() {
+    let seed = expand_seed(7);
+    assert_ne!(derive_seed(seed, 1), seed);
+    assert_ne!(derive_seed(seed, 1), derive_seed(seed, 2));
+    assert_eq!(derive_seed(seed, 1), derive_seed(seed, 1));
+}
+
+#[test]
+fn derived_seeds_of_related_seeds_are_unrelated() {
+    let xor = |a: [u8; 32], b: [u8; 32]| -> [u8; 32] { std::array::from_fn(|i| a[i] ^ b[i]) };
+    let seed = expand_seed(7);
+    // seeds that differ by the difference of the keys of two streams
+    let related = xor(seed, xor(expand_seed(1), expand_seed(2)));
+    assert_ne!(derive_seed(seed, 1), derive_seed(related, 2));
+    assert_ne!(xor(derive_seed(seed, 1), derive_seed(seed, 2)), xor(derive_seed(related, 1), derive_seed(related, 2)));
+
+    // flipping a single bit of the seed or stream changes about half the bits of the derived seed
+    let count_changed = |a: [u8; 32], b: [u8; 32]| xor(a, b).iter().map(|byte| byte.count_ones()).sum::<u32>();
+    for byte in [0, 13, 31] {
+        let mut flipped = seed;
+        flipped[byte] ^= 1;
+        let changed = count_changed(derive_seed(seed, 1), derive_seed(flipped, 1));
+        assert!((64..192).contains(&changed), "{} {}", byte, changed);
+    }
+    let changed = count_changed(derive_seed(seed, 1 << 32), derive_seed(seed, (1 << 32) + 1));
+    assert!((64..192).contains(&changed), "{}", changed);
+}
+
+#[test]
+fn config_round_trip() {
+    let mut params = defaults();
+    params.temperatures = vec![0.1, 2.269, 3.];
+    params.seed = expand_seed(1234);
+    params.rng = RngKind::ChaCha;
+    params.updates = Some("1 heat-bath + 5 overrelaxation".to_string());
+    params.observables = vec!["abs_magnetisation".to_string(), "row_magnetisation".to_string()];
+    params.constants.t_quench = 0.75;
+    params.constants.latent_lattices = vec![(2, 3)];
+    params.constants.nucleation_fields = vec![-0.1];
+    let mut read = RunParams{nx: 1, ny: 1, ntherm_init: 0, ntherm: 0, nprod: 1,
+                             temperatures: vec![1.], seed: [0; 32], rng: RngKind::Xoshiro, updates: None,
+                             observables: Vec::new(), datadir: PathBuf::new(), constants: constants()};
+    read.apply_toml(&params.to_toml()).unwrap();
+    assert_eq!(read, params);
+}
+
+#[test]
+fn config_rejects_invalid_input() {
+    for text in ["nx = 0", "nx = -3", "nx = 1.5", "ny = \"3\"", "nprod = 0", "temperatures = []",
+                 "temperatures = [1, -1]", "temperatures = 2", "seed = [1, 2]", "seed = -1",
+                 "unknown = 1", "nx = 3\nnx = 4", "[table]", "temperatures = [1, 2",
+                 "datadir = \"open", "nx 3", "nx = 3 4", "nx = 1\nny = 1", "rng = \"pcg\"", "rng = 1", "updates = 5",
+                 "observables = \"abs_magnetisation\"", "observables = [1]", "nbin_histogram = 0",
+                 "t_quench = -1", "waiting_times = []", "latent_lattices = [4, 4]", "frame_delay_movie = 70000",
+                 "flatness_wang_landau = 1", "nbin_validate = 1", "max_angle_heisenberg = 4",
+                 "waiting_times = [20000]"] {
+        assert!(defaults().apply_toml(text).is_err(), "accepted '{}'", text);
+    }
+}
+
+#[test]
+fn constants_from_config_and_arguments() {
+    let mut params = defaults();
+    params.apply_toml("nbin_histogram = 30\nwaiting_times = [5, 50]\nlatent_lattices = [[4, 4], [8, 6]]\nt_quench = 1")
+        .unwrap();
+    assert_eq!(params.constants.nbin_histogram, 30);
+    assert_eq!(params.constants.waiting_times, vec![5, 50]);
+    assert_eq!(params.constants.latent_lattices, vec![(4, 4), (8, 6)]);
+    assert_eq!(params.constants.t_quench, 1.);
+
+    assert_eq!(Constants::key("--nbin-histogram").as_deref(), Some("nbin_histogram"));
+    for arg in ["--nbin_histogram", "--nx", "nbin-histogram", "--unknown"] {
+        assert_eq!(Constants::key(arg), None, "{}", arg);
+    }
+    params.constants.parse_arg("latent_lattices", "4x4, 16x8").unwrap();
+    params.constants.parse_arg("nucleation_fields", "0.1,0.3").unwrap();
+    params.constants.parse_arg("ln_f_wang_landau", "1e-6").unwrap();
+    assert_eq!(params.constants.latent_lattices, vec![(4, 4), (16, 8)]);
+    assert_eq!(params.constants.nucleation_fields, vec![0.1, 0.3]);
+    assert_eq!(params.constants.ln_f_wang_landau, 1e-6);
+    for (key, value) in [("latent_lattices", "4"), ("nbin_histogram", "-1"), ("waiting_times", "1,x"),
+                         ("t_quench", "nan"), ("nx", "4")] {
+        assert!(params.constants.parse_arg(key, value).is_err(), "{} {}", key, value);
+    }
+    params.constants.parse_arg("acceptance_heisenberg", "1.5").unwrap();
+    assert!(params.constants.check().is_err());
+
+    assert!(Constants::NAMES.iter().all(|name| Constants::describe(name).is_some()));
+    assert_eq!(constants().values().len(), Constants::NAMES.len());
+}
+
+#[test]
+fn temperature_lists() {
+    assert_eq!(parse_temperatures("2.269").unwrap(), vec![2.269]);
+    assert_eq!(parse_temperatures("1, 2,3.5").unwrap(), vec![1., 2., 3.5]);
+    assert_eq!(parse_temperatures("1:2:5").unwrap(), vec![1., 1.25, 1.5, 1.75, 2.]);
+    assert_eq!(parse_temperatures("3:1:3").unwrap(), vec![3., 2., 1.]);
+    for spec in ["", "1,", "0", "-1", "nan", "1,x", "1:2:1", "0:2:3", "1:2", "1:2:x", "1:2:3:4"] {
+        assert!(parse_temperatures(spec).is_err(), "accepted '{}'", spec);
+    }
+}
+
+#[test]
+fn temperature_files() {
+    let fname = std::env::temp_dir().join(format!("ising_temperatures_{}.txt", std::process::id()));
+    std::fs::write(&fname, "# temperatures\n1.5 2.0\n2.5, 3  # high\n\n").unwrap();
+    assert_eq!(read_temperatures(&fname).unwrap(), vec![1.5, 2., 2.5, 3.]);
+    std::fs::write(&fname, "1.5 -2\n").unwrap();
+    assert!(read_temperatures(&fname).is_err());
+    std::fs::write(&fname, "# none\n").unwrap();
+    assert!(read_temperatures(&fname).is_err());
+    std::fs::remove_file(&fname).unwrap();
+}
+
+#[test]
+fn refined_temperatures_keep_direction() {
+    assert_eq!(refine(&[1., 2., 3.], 2., 0.5, 3), vec![1., 1.5, 2., 2.5, 3.]);
+    assert_eq!(refine(&[3., 1.], 2., 0.5, 2), vec![3., 2.5, 1.5, 1.]);
+    assert_eq!(refine(&[1.], 0.2, 0.5, 3), vec![0.2, 0.7, 1.]);
+    assert_eq!(refine(&[1.], 2., 0.5, 1), vec![1., 2.]);
+}
+
+#[test]
+fn manifest_records_seed_and_parameters() {
+    let mut params = defaults();
+    params.seed = expand_seed(42);
+    params.datadir = PathBuf::from("out \"quoted\"");
+    let text = manifest(&params, Some(42), &["ising".to_string(), "--seed".to_string(), "42".to_string()]);
+    assert!(text.starts_with('{') && text.trim_end().ends_with('}'));
+    assert!(text.contains("\"command\": [\"ising\", \"--seed\", \"42\"]"));
+    assert!(text.contains("\"seed\": 42,"));
+    let bytes: Vec<String> = expand_seed(42).iter().map(|b| b.to_string()).collect();
+    assert!(text.contains(&format!("\"seed_bytes\": [{}]", bytes.join(", "))));
+    assert!(text.contains("\"rng\": \"xoshiro\","));
+    assert!(text.contains("\"nprod\": 100,"));
+    assert!(text.contains("\"temperatures\": [1.0, 2.0]"));
+    assert!(text.contains("\"latent_lattices\": [[4, 4], [6, 6], [8, 8]],"));
+    assert!(text.contains("\"datadir\": \"out \\\"quoted\\\"\""));
+
+    assert!(manifest(&defaults(), None, &[]).contains("\"seed\": null,"));
+}
+
+#[test]
+fn random_seeds_differ() {
+    assert_ne!(random_seed(), random_seed());
+}
+
+#[test]
+fn log_levels_are_ordered_by_verbosity() {
+    let levels = ["error", "warn", "info", "debug"].map(|name| Level::parse(name).unwrap());
+    assert!(levels.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(levels.map(|level| level.name()), ["error", "warn", "info", "debug"]);
+    assert!(Level::parse("trace").is_err());
+    assert_eq!(LogFormat::parse("json"), Ok(LogFormat::Json));
+    assert!(LogFormat::parse("xml").is_err());
+    // info is the default level
+    assert!(logging::enabled(Level::Info) && !logging::enabled(Level::Debug));
+}
@@ -0,0 +1,54 @@
+use ising::{Configuration, Rng, evolve, hamiltonian};
+use ising::covariance::{self, Covariance, MAX_ENTRIES};
+use ising::export::ExportParams;
+
+#[test]
+fn covariance_of_two_snapshots() {
+    let mut covariance = Covariance::new(2);
+    let mut cfg = Configuration::cold(2, 1, 1);
+    covariance.push(&cfg);
+    cfg[1] = -1;
+    covariance.push(&cfg);
+    // s_0 is always 1, s_1 averages to 0 with ⟨s_1²⟩ = 1
+    assert_eq!(covariance.mean(), vec![1., 0.]);
+    assert_eq!(covariance.matrix(), vec![0., 0., 0., 1.]);
+}
+
+#[test]
+fn sites_decouple_at_infinite_temperature() {
+    let (nx, ny, nsample) = (3, 3, 4000);
+    let mut rng = Rng::from_seed([7; 32]);
+    let mut cfg = Configuration::random(nx, ny, &mut rng);
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    let mut covariance = Covariance::new(nx*ny);
+    for _ in 0..nsample {
+        evolve(&mut cfg, &mut energy, 0., 1., 0., &mut rng, 2, None);
+        covariance.push(&cfg);
+    }
+    // independent spins have the unit matrix as covariance
+    let tolerance = 5. / (nsample as f64).sqrt();
+    for (k, c) in covariance.matrix().iter().enumerate() {
+        let expected = if k / (nx*ny) == k % (nx*ny) { 1. } else { 0. };
+        assert!((c - expected).abs() < tolerance, "{} {}", k, c);
+    }
+    assert!(covariance.mean().iter().all(|m| m.abs() < tolerance));
+}
+
+#[test]
+fn large_covariance_matrices_are_rejected() {
+    assert!(covariance::check_size(64*64, 8).is_ok());
+    assert!(covariance::check_size(MAX_ENTRIES, 1).is_err());
+    assert!(covariance::check_size(usize::MAX, 1).is_err());
+    assert!(covariance::check_size(128*128, 12).is_err());
+
+    let dir = std::env::temp_dir().join(format!("ising-covariance-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let params = ExportParams{nsample: 1, nskip: 1, npilot: 10, shard_size: 1, ntherm: 1};
+    let mut rng = Rng::from_seed([9; 32]);
+    assert!(covariance::run(&dir, 128, 128, &[1.; 12], &params, &mut rng).is_err());
+    covariance::run(&dir, 4, 4, &[1., 2.], &params, &mut rng).unwrap();
+    // three arrays, the covariance alone takes 2*16*16 float64
+    assert!(std::fs::metadata(dir.join("covariance.npz")).unwrap().len() > 2*16*16*8);
+    assert_eq!(std::fs::read_to_string(dir.join("skip.dat")).unwrap().lines().count(), 2);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
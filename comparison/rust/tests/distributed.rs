@@ -0,0 +1,70 @@
+use std::net::TcpListener;
+use std::thread;
+
+use ising::checkerboard::evolve_checkerboard;
+use ising::distributed::{Ring, Strip};
+use ising::{Configuration, Rng, hamiltonian};
+
+/// Return nranks addresses on localhost with ports that were free a moment ago.
+fn local_addresses(nranks: usize) -> Vec<String> {
+    let listeners: Vec<TcpListener> = (0..nranks).map(|_| TcpListener::bind("127.0.0.1:0").unwrap()).collect();
+    listeners.iter().map(|listener| listener.local_addr().unwrap().to_string()).collect()
+}
+
+/// Run f on every rank of a ring of nranks threads and return the results in the order of the ranks.
+fn on_ranks<T: Send>(nranks: usize, f: impl Fn(&mut Ring) -> T + Sync) -> Vec<T> {
+    let addresses = local_addresses(nranks);
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..nranks).map(|rank| {
+            let (addresses, f) = (&addresses, &f);
+            scope.spawn(move || f(&mut Ring::connect(rank, addresses).unwrap()))
+        }).collect();
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    })
+}
+
+#[test]
+fn strips_reproduce_checkerboard_sweeps() {
+    let mut rng = Rng::from_seed([41; 32]);
+    let start = Configuration::random(12, 64, &mut rng);
+    let mut cfg = start.clone();
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    evolve_checkerboard(&mut cfg, &mut energy, 0.4, 1., 0., &mut Rng::from_seed([42; 32]), 5, None);
+
+    // 64 rows are 4 blocks, so 3 ranks get different numbers of rows
+    for nranks in [1, 2, 3] {
+        let results = on_ranks(nranks, |ring| {
+            let mut strip = Strip::from_configuration(&start, ring.rank(), nranks).unwrap();
+            let mut rng = Rng::from_seed([42; 32]);
+            for _sweep in 0..5 {
+                strip.sweep(ring, 0.4, 1., &mut rng).unwrap();
+            }
+            (strip.spins().to_vec(), strip.measure(ring, 1.).unwrap())
+        });
+        let spins: Vec<i32> = results.iter().flat_map(|(spins, _)| spins.clone()).collect();
+        assert_eq!(spins, cfg.cfg);
+        let magnetisation = cfg.cfg.iter().sum::<i32>() as f64 / cfg.size() as f64;
+        assert!(results.iter().all(|(_, measured)| *measured == (hamiltonian(&cfg, 1., 0.), magnetisation)));
+    }
+}
+
+#[test]
+fn random_strips_are_parts_of_one_configuration() {
+    let whole = Strip::random(8, 48, 0, 1, &mut Rng::from_seed([43; 32])).unwrap();
+    let spins: Vec<i32> = (0..2)
+        .flat_map(|rank| Strip::random(8, 48, rank, 2, &mut Rng::from_seed([43; 32])).unwrap().spins().to_vec())
+        .collect();
+    assert_eq!(spins, whole.spins());
+    assert!(spins.contains(&-1));
+}
+
+#[test]
+fn strips_need_enough_rows_and_a_supported_lattice() {
+    let mut rng = Rng::from_seed([44; 32]);
+    assert!(Strip::random(8, 32, 0, 3, &mut rng).is_err());
+    assert!(Strip::random(8, 15, 0, 1, &mut rng).is_err());
+    let mut cfg = Configuration::random(8, 32, &mut rng);
+    cfg.add_local_field(2, 0.1);
+    assert!(Strip::from_configuration(&cfg, 0, 1).is_err());
+    assert!(Ring::connect(2, &["127.0.0.1:0".to_string()]).is_err());
+}
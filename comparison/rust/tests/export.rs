@@ -0,0 +1,110 @@
+use std::path::Path;
+
+use ising::{Configuration, Rng, hamiltonian};
+use ising::exact::critical_temperature;
+use ising::export::{self, ExportParams, decorrelation_skip};
+use ising::npy::crc32;
+
+/// Read the names and contents of all entries of an uncompressed npz archive.
+fn read_npz(fname: &Path) -> Vec<(String, Vec<u8>)> {
+    let bytes = std::fs::read(fname).unwrap();
+    let u16_at = |pos: usize| u16::from_le_bytes([bytes[pos], bytes[pos+1]]) as usize;
+    let u32_at = |pos: usize| u32::from_le_bytes(bytes[pos..pos+4].try_into().unwrap());
+
+    let mut entries = Vec::new();
+    let mut pos = 0;
+    while u32_at(pos) == 0x04034b50 {
+        let (crc, size) = (u32_at(pos+14), u32_at(pos+18) as usize);
+        let name_start = pos + 30;
+        let data_start = name_start + u16_at(pos+26) + u16_at(pos+28);
+        let name = std::str::from_utf8(&bytes[name_start..name_start + u16_at(pos+26)]).unwrap().to_string();
+        let data = bytes[data_start..data_start+size].to_vec();
+        assert_eq!(crc, crc32(&data), "{}", name);
+        entries.push((name, data));
+        pos = data_start + size;
+    }
+    // the central directory follows the last entry
+    assert_eq!(u32_at(pos), 0x02014b50);
+    entries
+}
+
+/// Split the contents of an npy file into its header and data.
+fn split_npy(data: &[u8]) -> (&str, &[u8]) {
+    let header_len = u16::from_le_bytes([data[8], data[9]]) as usize;
+    (std::str::from_utf8(&data[10..10+header_len]).unwrap(), &data[10+header_len..])
+}
+
+#[test]
+fn shards_contain_labelled_configurations() {
+    let dir = std::env::temp_dir().join(format!("ising-export-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let params = ExportParams{nsample: 3, nskip: 2, npilot: 50, shard_size: 4, ntherm: 10};
+    let mut rng = Rng::from_seed([3; 32]);
+    export::run(&dir, 5, 4, &[1., 4.], &params, &mut rng).unwrap();
+
+    assert_eq!(std::fs::read_to_string(dir.join("export.dat")).unwrap(),
+               format!("nx: 5\nny: 4\ntc: {}\n", critical_temperature(1., 1.)));
+    assert_eq!(std::fs::read_to_string(dir.join("index.dat")).unwrap(),
+               "0: shard_0.npz 4\n1: shard_1.npz 2\n");
+
+    let mut temperatures = Vec::new();
+    let mut labels = Vec::new();
+    for (shard, n) in [(0, 4), (1, 2)] {
+        let entries = read_npz(&dir.join(format!("shard_{}.npz", shard)));
+        let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["configs.npy", "temperature.npy", "label.npy"]);
+
+        let (header, configs) = split_npy(&entries[0].1);
+        assert!(header.contains("'descr': '|i1'") && header.contains(&format!("'shape': ({}, 4, 5)", n)), "{}", header);
+        assert!(configs.iter().all(|&s| s as i8 == 1 || s as i8 == -1));
+
+        let (header, data) = split_npy(&entries[1].1);
+        assert!(header.contains("'descr': '<f8'") && header.contains(&format!("'shape': ({},)", n)), "{}", header);
+        temperatures.extend(data.chunks_exact(8).map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap())));
+
+        let (header, data) = split_npy(&entries[2].1);
+        assert!(header.contains("'descr': '|i1'") && header.contains(&format!("'shape': ({},)", n)), "{}", header);
+        labels.extend(data.iter().map(|&label| label as i8));
+    }
+    assert_eq!(temperatures, vec![1., 1., 1., 4., 4., 4.]);
+    let skips = std::fs::read_to_string(dir.join("skip.dat")).unwrap();
+    let temps: Vec<&str> = skips.lines().map(|line| line.split_once(':').unwrap().0).collect();
+    assert_eq!(temps, ["1", "4"]);
+    // ordered below Tc
+    assert_eq!(labels, vec![1, 1, 1, 0, 0, 0]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn chains_are_never_labelled_ordered() {
+    let dir = std::env::temp_dir().join(format!("ising-export-chain-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let params = ExportParams{nsample: 2, nskip: 1, npilot: 20, shard_size: 10, ntherm: 5};
+    let mut rng = Rng::from_seed([5; 32]);
+    export::run(&dir, 8, 1, &[0.5], &params, &mut rng).unwrap();
+
+    let entries = read_npz(&dir.join("shard_0.npz"));
+    let (header, labels) = split_npy(&entries[2].1);
+    assert!(header.contains("'shape': (2,)"));
+    assert_eq!(labels, [0, 0]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn skip_covers_autocorrelation_time() {
+    let params = ExportParams{nsample: 1, nskip: 2, npilot: 2000, shard_size: 1, ntherm: 500};
+    let mut rng = Rng::from_seed([6; 32]);
+    let mut skips = Vec::new();
+    for temp in [10., 2.3] {
+        let mut cfg = Configuration::random(12, 12, &mut rng);
+        let mut energy = hamiltonian(&cfg, 1., 0.);
+        ising::evolve(&mut cfg, &mut energy, 1./temp, 1., 0., &mut rng, params.ntherm, None);
+        let (nskip, tau) = decorrelation_skip(&mut cfg, &mut energy, 1./temp, &params, &mut rng);
+        assert_eq!(nskip, params.nskip.max((2.*tau).ceil() as usize));
+        assert!((energy - hamiltonian(&cfg, 1., 0.)).abs() < 1e-9);
+        skips.push(nskip);
+    }
+    // almost uncorrelated at high temperature, critical slowing down close to Tc
+    assert_eq!(skips[0], params.nskip);
+    assert!(skips[1] > 5*params.nskip, "{:?}", skips);
+}
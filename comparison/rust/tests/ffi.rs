@@ -0,0 +1,53 @@
+use std::ffi::CString;
+use std::ptr;
+
+use ising::ffi::{ising_clear_measurements, ising_create, ising_destroy, ising_energy, ising_nmeasurement,
+                 ising_read_energy, ising_read_magnetisation, ising_read_spins, ising_set_algorithm,
+                 ising_set_temperature, ising_sweep};
+
+#[test]
+fn simulation_round_trip_through_c_interface() {
+    assert!(ising_create(0, 4, 2., 1., 0., 1).is_null());
+    assert!(ising_create(4, 4, 0., 1., 0., 1).is_null());
+    assert!(ising_create(4, 4, f64::NAN, 1., 0., 1).is_null());
+
+    let sim = ising_create(6, 4, 2., 1., 0., 7);
+    assert!(!sim.is_null());
+    unsafe {
+        let bogus = CString::new("bogus").unwrap();
+        let wolff = CString::new("wolff").unwrap();
+        assert_eq!(ising_set_algorithm(sim, bogus.as_ptr()), -1);
+        assert_eq!(ising_set_algorithm(sim, wolff.as_ptr()), 0);
+        assert_eq!(ising_set_temperature(sim, -1.), -1);
+        assert_eq!(ising_set_temperature(sim, 1.5), 0);
+
+        ising_sweep(sim, 10, 0);
+        assert_eq!(ising_nmeasurement(sim), 0);
+        ising_sweep(sim, 20, 1);
+        assert_eq!(ising_nmeasurement(sim), 20);
+
+        let mut energy = [0.; 30];
+        assert_eq!(ising_read_energy(sim, energy.as_mut_ptr(), energy.len()), 20);
+        assert_eq!(energy[19], ising_energy(sim) / 24.);
+        let mut magnetisation = [0.; 5];
+        assert_eq!(ising_read_magnetisation(sim, magnetisation.as_mut_ptr(), magnetisation.len()), 5);
+        assert!(magnetisation.iter().all(|m| m.abs() <= 1.));
+
+        let mut spins = [0; 24];
+        assert_eq!(ising_read_spins(sim, spins.as_mut_ptr(), spins.len()), 24);
+        assert!(spins.iter().all(|&s| s == 1 || s == -1));
+        let mut bonds = 0;
+        for y in 0..4 {
+            for x in 0..6 {
+                bonds += spins[y*6 + x] * (spins[y*6 + (x+1) % 6] + spins[((y+1) % 4)*6 + x]);
+            }
+        }
+        assert_eq!(ising_energy(sim), -bonds as f64);
+
+        ising_clear_measurements(sim);
+        assert_eq!(ising_nmeasurement(sim), 0);
+        assert_eq!(ising_read_energy(sim, ptr::null_mut(), 0), 0);
+        ising_destroy(sim);
+        ising_destroy(ptr::null_mut());
+    }
+}
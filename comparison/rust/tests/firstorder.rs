@@ -0,0 +1,91 @@
+use ising::{Configuration, Observables, Rng, evolve, hamiltonian};
+use ising::firstorder::{self, energy_cumulant, extrapolate, histogram, is_double_peaked};
+
+/// Return energies spread symmetrically around the given peak positions, count times as many in the ordered peak.
+fn two_peaks(ordered: f64, disordered: f64, count: usize) -> Vec<f64> {
+    peaks_of_size(ordered, disordered, count, 10)
+}
+
+/// Like two_peaks but with `copies` times the 10 values in the disordered peak.
+fn peaks_of_size(ordered: f64, disordered: f64, count: usize, copies: usize) -> Vec<f64> {
+    let offsets = [-2., -1., -1., 0., 0., 0., 0., 1., 1., 2.];
+    let mut energy = Vec::new();
+    for _ in 0..count*copies {
+        energy.extend(offsets.iter().map(|o| ordered + o));
+    }
+    for _ in 0..copies {
+        energy.extend(offsets.iter().map(|o| disordered + o));
+    }
+    energy
+}
+
+#[test]
+fn discrete_values_get_a_bin_each() {
+    let hist = histogram(&[-4., 0., -8., -4., 0., -4.], 10);
+    assert_eq!(hist.centres, vec![-8., -4., 0.]);
+    assert_eq!(hist.counts, vec![1., 3., 2.]);
+
+    let hist = histogram(&(0..100).map(|i| i as f64).collect::<Vec<_>>(), 10);
+    assert_eq!(hist.counts, vec![10.; 10]);
+}
+
+#[test]
+fn double_peaks_are_detected() {
+    assert!(is_double_peaked(&histogram(&two_peaks(-100., -60., 1), 50)));
+    // 10 values per peak do not make the dip significant
+    assert!(!is_double_peaked(&histogram(&peaks_of_size(-100., -60., 1, 1), 50)));
+    // a flat distribution without a dip
+    let flat: Vec<f64> = (0..50).map(|i| -80. + ((i*7) % 11) as f64).collect();
+    assert!(!is_double_peaked(&histogram(&flat, 50)));
+    assert!(firstorder::transition(&flat, 0.5, 50, 50).is_none());
+}
+
+#[test]
+fn single_peaks_are_not_double_peaked() {
+    for seed in 0..10 {
+        let mut rng = Rng::from_seed([seed; 32]);
+        // Box-Muller
+        let gaussian: Vec<f64> = (0..300).map(|_| {
+            let (u, v) = (rng.gen_real(), rng.gen_real());
+            (-2.*(1.-u).ln()).sqrt() * (2.*std::f64::consts::PI*v).cos()
+        }).collect();
+        assert!(!is_double_peaked(&histogram(&gaussian, 50)), "{}", seed);
+
+        // a short scan of a small lattice close to Tc has a broad but single peaked energy histogram
+        let mut cfg = Configuration::random(8, 8, &mut rng);
+        let mut energy = hamiltonian(&cfg, 1., 0.);
+        evolve(&mut cfg, &mut energy, 1./2.3, 1., 0., &mut rng, 200, None);
+        let mut obs = Observables::new();
+        evolve(&mut cfg, &mut energy, 1./2.3, 1., 0., &mut rng, 300, Some(&mut obs));
+        assert!(!is_double_peaked(&histogram(&obs.energy, 50)), "{}", seed);
+    }
+}
+
+#[test]
+fn energy_cumulant_of_a_single_energy_is_two_thirds() {
+    assert!((energy_cumulant(&[-3.; 10]) - 2./3.).abs() < 1e-12);
+    // two phases lower the cumulant
+    assert!(energy_cumulant(&two_peaks(-100., -60., 1)) < 2./3. - 1e-3);
+}
+
+#[test]
+fn equal_phases_are_at_the_transition() {
+    let (beta0, size) = (0.5, 50);
+    let transition = firstorder::transition(&two_peaks(-100., -60., 1), beta0, size, 50).unwrap();
+    assert!((transition.beta_weight - beta0).abs() < 1e-9);
+    assert!((transition.beta_height - beta0).abs() < 1e-9);
+    assert!((transition.latent_heat - 40. / size as f64).abs() < 1e-9);
+
+    // the ordered phase dominates at beta0, so the transition is at a higher temperature
+    let transition = firstorder::transition(&two_peaks(-100., -60., 2), beta0, size, 50).unwrap();
+    assert!(transition.beta_weight < beta0 && transition.beta_height < beta0);
+    assert!(transition.latent_heat > 0.);
+}
+
+#[test]
+fn extrapolation_recovers_finite_size_law() {
+    let sizes = [16, 64, 256, 1024];
+    let values: Vec<f64> = sizes.iter().map(|&n| 0.5 + 3. / n as f64).collect();
+    let (a, b) = extrapolate(&sizes, &values);
+    assert!((a - 0.5).abs() < 1e-12 && (b - 3.).abs() < 1e-9);
+}
@@ -0,0 +1,164 @@
+use ising::{Configuration, Observables, Rng};
+use ising::heisenberg::{self, Heisenberg, Update, UpdateSchedule, dot, random_unit, rotate};
+use ising::model::{self, Model};
+
+/// Compute the energy per site of the infinite Heisenberg chain, -J L(βJ) with the Langevin function L.
+fn chain_energy(j: f64, temp: f64) -> f64 {
+    let x = j/temp;
+    -j*(1./x.tanh() - 1./x)
+}
+
+#[test]
+fn rotations_keep_spins_normalised() {
+    let mut rng = Rng::from_seed([2; 32]);
+    let model = Heisenberg{j: 1., h: 0., max_angle: 1.};
+    let mut spin = random_unit(&mut rng);
+    for _ in 0..1000 {
+        spin = model.propose(&spin, &mut rng);
+    }
+    assert!((dot(&spin, &spin) - 1.).abs() < 1e-12);
+
+    // a quarter turn around z maps x to y
+    let rotated = rotate(&[1., 0., 0.], &[0., 0., 1.], std::f64::consts::FRAC_PI_2);
+    assert!((rotated[0]).abs() < 1e-12 && (rotated[1] - 1.).abs() < 1e-12 && rotated[2].abs() < 1e-12);
+}
+
+#[test]
+fn heisenberg_delta_e_matches_hamiltonian() {
+    let mut rng = Rng::from_seed([4; 32]);
+    let model = Heisenberg{j: -0.7, h: 0.4, max_angle: 2.};
+    let mut cfg = model.random(5, 4, &mut rng);
+    for idx in 0..cfg.size() {
+        let before = model.hamiltonian(&cfg);
+        let spin = model.propose(&cfg[idx], &mut rng);
+        let delta = model.delta_e(&cfg, idx, &spin);
+        cfg[idx] = spin;
+        assert!((model.hamiltonian(&cfg) - before - delta).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn magnetisation_of_ordered_configurations() {
+    let cfg = Configuration::cold(4, 4, [0., 0.6, 0.8]);
+    let model = Heisenberg{j: 1., h: 0., max_angle: 1.};
+    assert!((heisenberg::magnetisation(&cfg) - 1.).abs() < 1e-12);
+    assert!(heisenberg::staggered_magnetisation(&cfg).abs() < 1e-12);
+    assert!(heisenberg::smallest_structure_factor(&cfg).abs() < 1e-12);
+    // 32 aligned bonds
+    assert!((model.hamiltonian(&cfg) + 32.).abs() < 1e-12);
+}
+
+#[test]
+fn updates_match_exact_chain() {
+    let (nx, j, temp) = (16, 1., 1.);
+    let model = Heisenberg{j, h: 0., max_angle: 1.5};
+    for (update, seed) in [(Update::Metropolis, 6), (Update::HeatBath, 8)] {
+        let mut rng = Rng::from_seed([seed; 32]);
+        let mut cfg = model.random(nx, 1, &mut rng);
+        let mut energy = model.hamiltonian(&cfg);
+        update.evolve(&model, &mut cfg, &mut energy, 1./temp, &mut rng, 1000, None);
+
+        let mut obs = Observables::new();
+        update.evolve(&model, &mut cfg, &mut energy, 1./temp, &mut rng, 20000, Some(&mut obs));
+        assert!((energy - model.hamiltonian(&cfg)).abs() < 1e-9);
+        let mean = obs.energy.iter().sum::<f64>() / (obs.energy.len()*nx) as f64;
+        assert!((mean - chain_energy(j, temp)).abs() < 0.02, "{:?}: {}", update, mean);
+    }
+}
+
+#[test]
+fn model_metropolis_rejects_only_uphill() {
+    let mut rng = Rng::from_seed([10; 32]);
+    let model = Heisenberg{j: 1., h: 0., max_angle: 0.5};
+    let mut cfg = Configuration::cold(4, 4, [0., 0., 1.]);
+    let mut energy = model.hamiltonian(&cfg);
+    // at zero temperature, no rotation away from the ground state is accepted
+    assert_eq!(model::evolve(&model, &mut cfg, &mut energy, f64::INFINITY, &mut rng, 10, None), 0);
+}
+
+#[test]
+fn overrelaxation_keeps_energy_and_moves_spins() {
+    let mut rng = Rng::from_seed([12; 32]);
+    let model = Heisenberg{j: -0.8, h: 0.3, max_angle: 1.};
+    let mut cfg = model.random(6, 5, &mut rng);
+    let start = cfg.clone();
+    let mut energy = model.hamiltonian(&cfg);
+    let initial = energy;
+    let naccept = Update::Overrelaxation.evolve(&model, &mut cfg, &mut energy, 1., &mut rng, 10, None);
+    assert_eq!(naccept, 10*cfg.size());
+    assert!((energy - initial).abs() < 1e-9);
+    assert!((model.hamiltonian(&cfg) - initial).abs() < 1e-9);
+    assert!(cfg.cfg.iter().all(|spin| (dot(spin, spin) - 1.).abs() < 1e-12));
+    assert!(cfg.cfg.iter().zip(&start.cfg).all(|(a, b)| dot(a, b) < 1. - 1e-6));
+}
+
+#[test]
+fn update_schedules_are_parsed() {
+    assert_eq!(UpdateSchedule::parse("1 heat-bath + 5 overrelaxation").unwrap().steps,
+               vec![(1, Update::HeatBath), (5, Update::Overrelaxation)]);
+    let schedule = UpdateSchedule::parse("metropolis+2 overrelaxation").unwrap();
+    assert_eq!(schedule.steps, vec![(1, Update::Metropolis), (2, Update::Overrelaxation)]);
+    assert_eq!(schedule.nsweep(), 3);
+    assert_eq!(UpdateSchedule::parse("heat-bath").unwrap(), UpdateSchedule::single(Update::HeatBath));
+    for spec in ["", "5 overrelaxation", "0 heat-bath", "1 heat-bath +", "x metropolis", "1 2 metropolis",
+                 "1 wolff"] {
+        assert!(UpdateSchedule::parse(spec).is_err(), "{}", spec);
+    }
+}
+
+#[test]
+fn mixed_updates_match_exact_chain() {
+    let (nx, j, temp) = (16, 1., 1.);
+    let model = Heisenberg{j, h: 0., max_angle: 1.5};
+    let updates = UpdateSchedule::parse("1 heat-bath + 3 overrelaxation").unwrap();
+    let mut rng = Rng::from_seed([14; 32]);
+    let mut cfg = model.random(nx, 1, &mut rng);
+    let mut energy = model.hamiltonian(&cfg);
+    updates.evolve(&model, &mut cfg, &mut energy, 1./temp, &mut rng, 500, None);
+
+    let mut obs = Observables::new();
+    let naccept = updates.evolve(&model, &mut cfg, &mut energy, 1./temp, &mut rng, 10000, Some(&mut obs));
+    assert_eq!(naccept, 10000*4*nx);
+    assert_eq!(obs.energy.len(), 10000);
+    assert!((energy - model.hamiltonian(&cfg)).abs() < 1e-9);
+    let mean = obs.energy.iter().sum::<f64>() / (obs.energy.len()*nx) as f64;
+    assert!((mean - chain_energy(j, temp)).abs() < 0.02, "{}", mean);
+}
+
+#[test]
+fn thermalisation_tunes_proposal_width() {
+    let updates = UpdateSchedule::single(Update::Metropolis);
+    let mut rng = Rng::from_seed([16; 32]);
+    for (temp, max_angle) in [(0.2, 3.), (0.5, 0.05)] {
+        let mut model = Heisenberg{j: 1., h: 0., max_angle};
+        let mut cfg = model.random(8, 8, &mut rng);
+        let mut energy = model.hamiltonian(&cfg);
+        let acceptance = heisenberg::thermalise(&mut model, &updates, &mut cfg, &mut energy, 1./temp, 0.5,
+                                                &mut rng, 500).unwrap();
+        assert!((acceptance - 0.5).abs() < 0.1, "{} {}", temp, acceptance);
+        assert!((energy - model.hamiltonian(&cfg)).abs() < 1e-9);
+        // the frozen width keeps the acceptance rate in production
+        let naccept = updates.evolve(&model, &mut cfg, &mut energy, 1./temp, &mut rng, 200, None);
+        assert!((naccept as f64 / (200*cfg.size()) as f64 - 0.5).abs() < 0.1);
+    }
+
+    // narrower steps at low temperature, bounded by π at high temperature
+    let mut widths = Vec::new();
+    for temp in [0.2, 1000.] {
+        let mut model = Heisenberg{j: 1., h: 0., max_angle: 1.};
+        let mut cfg = model.random(8, 8, &mut rng);
+        let mut energy = model.hamiltonian(&cfg);
+        heisenberg::thermalise(&mut model, &updates, &mut cfg, &mut energy, 1./temp, 0.5, &mut rng, 300);
+        widths.push(model.max_angle);
+    }
+    assert!(widths[0] < 1.);
+    assert_eq!(widths[1], std::f64::consts::PI);
+
+    // heat bath alone has no width to tune
+    let mut model = Heisenberg{j: 1., h: 0., max_angle: 1.};
+    let mut cfg = model.random(4, 4, &mut rng);
+    let mut energy = model.hamiltonian(&cfg);
+    assert!(heisenberg::thermalise(&mut model, &UpdateSchedule::single(Update::HeatBath), &mut cfg, &mut energy,
+                                   1., 0.5, &mut rng, 30).is_none());
+    assert_eq!(model.max_angle, 1.);
+}
@@ -0,0 +1,192 @@
+use std::path::Path;
+
+use ising::{Configuration, Rng};
+use ising::image::{self, Image, Movie, MovieFormat, adler32, lzw};
+use ising::npy::crc32;
+
+/// Split a PNG file into its chunks, checking the signature and checksums.
+fn split_chunks(png: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    let mut chunks = Vec::new();
+    let mut pos = 8;
+    while pos < png.len() {
+        let len = u32::from_be_bytes(png[pos..pos+4].try_into().unwrap()) as usize;
+        let body = &png[pos+4..pos+8+len];
+        let crc = u32::from_be_bytes(png[pos+8+len..pos+12+len].try_into().unwrap());
+        assert_eq!(crc, crc32(body));
+        chunks.push((body[..4].try_into().unwrap(), body[4..].to_vec()));
+        pos += 12 + len;
+    }
+    chunks
+}
+
+/// Decode a zlib stream of stored deflate blocks.
+fn inflate_stored(stream: &[u8]) -> Vec<u8> {
+    assert_eq!(((stream[0] as u16) << 8 | stream[1] as u16) % 31, 0);
+    let mut data = Vec::new();
+    let mut pos = 2;
+    loop {
+        let last = stream[pos] & 1 == 1;
+        let len = u16::from_le_bytes([stream[pos+1], stream[pos+2]]);
+        assert_eq!(!len, u16::from_le_bytes([stream[pos+3], stream[pos+4]]));
+        data.extend_from_slice(&stream[pos+5..pos+5+len as usize]);
+        pos += 5 + len as usize;
+        if last {
+            break;
+        }
+    }
+    assert_eq!(&stream[pos..], &adler32(&data).to_be_bytes());
+    data
+}
+
+/// Decode GIF LZW codes following the specification.
+fn lzw_decode(data: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1usize << min_code_size;
+    let (mut pos, mut buffer, mut nbits) = (0, 0u32, 0);
+    let mut width = min_code_size as u32 + 1;
+    let mut table: Vec<Vec<u8>> = Vec::new();
+    let mut prev: Option<Vec<u8>> = None;
+    let mut out = Vec::new();
+    loop {
+        while nbits < width {
+            buffer |= (data[pos] as u32) << nbits;
+            pos += 1;
+            nbits += 8;
+        }
+        let code = (buffer & ((1 << width) - 1)) as usize;
+        buffer >>= width;
+        nbits -= width;
+
+        if code == clear {
+            table = (0..clear+2).map(|i| vec![i as u8]).collect();
+            width = min_code_size as u32 + 1;
+            prev = None;
+            continue;
+        }
+        if code == clear + 1 {
+            assert_eq!(pos, data.len());
+            return out;
+        }
+        let entry = match &prev {
+            None => table[code].clone(),
+            Some(prev) if code == table.len() => [prev.as_slice(), &prev[..1]].concat(),
+            Some(_) => table[code].clone(),
+        };
+        out.extend_from_slice(&entry);
+        if let Some(prev) = prev {
+            if table.len() < 4096 {
+                table.push([prev.as_slice(), &entry[..1]].concat());
+                if table.len() == 1 << width && width < 12 {
+                    width += 1;
+                }
+            }
+        }
+        prev = Some(entry);
+    }
+}
+
+#[test]
+fn adler32_of_known_data() {
+    assert_eq!(adler32(b""), 1);
+    assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+}
+
+#[test]
+fn configuration_images_decode_to_spins() {
+    let mut cfg = Configuration::cold(3, 2, 1);
+    cfg[1] = -1;
+    cfg.remove_sites(&[5]);
+    let img = Image::of_configuration(&cfg, 2);
+    assert_eq!((img.width, img.height), (6, 4));
+    assert_eq!(&img.pixels[..6], &[255, 255, 0, 0, 255, 255]);
+    assert_eq!(&img.pixels[18..], &[255, 255, 255, 255, 128, 128]);
+
+    let chunks = split_chunks(&img.png_bytes());
+    let kinds: Vec<&[u8; 4]> = chunks.iter().map(|(kind, _)| kind).collect();
+    assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+    assert_eq!(chunks[0].1, [0, 0, 0, 6, 0, 0, 0, 4, 8, 0, 0, 0, 0]);
+    let raw = inflate_stored(&chunks[1].1);
+    for (row, line) in raw.chunks(7).enumerate() {
+        assert_eq!(line[0], 0);
+        assert_eq!(&line[1..], &img.pixels[row*6..(row+1)*6]);
+    }
+
+    // needs several stored blocks
+    let large = Image::of_configuration(&Configuration::cold(300, 300, -1), 1);
+    let raw = inflate_stored(&split_chunks(&large.png_bytes())[1].1);
+    assert_eq!(raw.len(), 301*300);
+    assert!(raw.iter().all(|&byte| byte == 0));
+}
+
+#[test]
+fn snapshot_names_sort_by_sweep() {
+    let dir = Path::new("snapshots");
+    assert_eq!(image::snapshot_name(dir, 2.5, 7, 1000), dir.join("T2.5_sweep0007.png"));
+    assert_eq!(image::snapshot_name(dir, 1., 1000, 1000), dir.join("T1_sweep1000.png"));
+    assert_eq!(image::snapshot_scale(8, 16), 32);
+    assert_eq!(image::snapshot_scale(512, 1024), 1);
+}
+
+#[test]
+fn lzw_codes_decode_to_indices() {
+    let mut rng = Rng::from_seed([3; 32]);
+    // random data fills the table and resets it, uniform data grows long strings
+    for len in [1, 2, 3, 10, 4000, 20000] {
+        let random: Vec<u8> = (0..len).map(|_| rng.gen_index(3) as u8).collect();
+        assert_eq!(lzw_decode(&lzw(&random, 2), 2), random);
+        let uniform = vec![1; len];
+        assert_eq!(lzw_decode(&lzw(&uniform, 2), 2), uniform);
+    }
+    // lengths around the growth of the code width
+    for len in 1..200 {
+        let data: Vec<u8> = (0..len).map(|i| (i % 3 + i / 7 % 2) as u8).collect();
+        assert_eq!(lzw_decode(&lzw(&data, 2), 2), data);
+    }
+}
+
+#[test]
+fn movies_contain_all_frames() {
+    assert_eq!(MovieFormat::from_path(Path::new("quench.gif")), Ok(MovieFormat::Gif));
+    assert_eq!(MovieFormat::from_path(Path::new("quench.ppm")), Ok(MovieFormat::Ppm));
+    assert!(MovieFormat::from_path(Path::new("quench.mp4")).is_err());
+
+    let mut rng = Rng::from_seed([4; 32]);
+    let frames: Vec<Image> = (0..3).map(|_| Image::of_configuration(&Configuration::random(5, 4, &mut rng), 1)).collect();
+    let dir = std::env::temp_dir();
+    let gif = dir.join(format!("ising_movie_{}.gif", std::process::id()));
+    let ppm = dir.join(format!("ising_movie_{}.ppm", std::process::id()));
+    for fname in [&gif, &ppm] {
+        let mut movie = Movie::create(fname, 5, 4, 4).unwrap();
+        frames.iter().for_each(|frame| movie.add_frame(frame).unwrap());
+        movie.finish().unwrap();
+    }
+
+    let bytes = std::fs::read(&ppm).unwrap();
+    let frame_len = b"P6\n5 4\n255\n".len() + 3*20;
+    assert_eq!(bytes.len(), 3*frame_len);
+    assert_eq!(&bytes[frame_len..frame_len+11], b"P6\n5 4\n255\n");
+    assert_eq!(&bytes[frame_len+11..frame_len+14], &[frames[1].pixels[0]; 3]);
+
+    let bytes = std::fs::read(&gif).unwrap();
+    assert_eq!(&bytes[..10], b"GIF89a\x05\x00\x04\x00");
+    assert_eq!(bytes.last(), Some(&0x3b));
+    // the frames start after the header of 13 bytes, the palette of 12, and the loop extension of 19
+    let mut pos = 13 + 12 + 19;
+    for frame in &frames {
+        assert_eq!(&bytes[pos..pos+8], &[0x21, 0xf9, 4, 0, 4, 0, 0, 0]);
+        pos += 18;
+        assert_eq!(bytes[pos], 2);
+        let mut codes = Vec::new();
+        pos += 1;
+        while bytes[pos] != 0 {
+            codes.extend_from_slice(&bytes[pos+1..pos+1+bytes[pos] as usize]);
+            pos += 1 + bytes[pos] as usize;
+        }
+        pos += 1;
+        let grey: Vec<u8> = lzw_decode(&codes, 2).iter().map(|&index| [0, 255, 128][index as usize]).collect();
+        assert_eq!(grey, frame.pixels);
+    }
+    assert_eq!(pos + 1, bytes.len());
+    std::fs::remove_file(&gif).unwrap();
+    std::fs::remove_file(&ppm).unwrap();
+}
@@ -0,0 +1,65 @@
+use ising::{Configuration, Format, Rng, evolve, hamiltonian};
+use ising::interface::{self, column_heights, width_squared};
+
+/// Return a pinned nx*ny configuration with up spins in the given number of rows above the bottom row.
+fn flat_interface(nx: usize, ny: usize, height: usize) -> Configuration {
+    let mut cfg = Configuration::cold(nx, ny, -1);
+    cfg.cfg[..(height+1)*nx].iter_mut().for_each(|s| *s = 1);
+    cfg.pin_boundary_rows();
+    cfg
+}
+
+#[test]
+fn flat_interface_has_zero_width() {
+    let cfg = flat_interface(6, 8, 3);
+    assert_eq!(column_heights(&cfg), vec![3.; 6]);
+    assert_eq!(width_squared(&column_heights(&cfg)), 0.);
+}
+
+#[test]
+fn step_interface_has_half_step_width() {
+    let mut cfg = flat_interface(4, 8, 2);
+    // raise the interface by 4 rows in the left half
+    for y in 3..7 {
+        cfg[y*4] = 1;
+        cfg[y*4 + 1] = 1;
+    }
+    assert_eq!(column_heights(&cfg), vec![6., 6., 2., 2.]);
+    assert_eq!(width_squared(&column_heights(&cfg)), 4.);
+}
+
+#[test]
+fn flat_interface_is_stable_at_zero_temperature() {
+    let mut rng = Rng::from_seed([4; 32]);
+    let mut cfg = flat_interface(6, 8, 3);
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    evolve(&mut cfg, &mut energy, f64::INFINITY, 1., 0., &mut rng, 10, None);
+    assert_eq!(width_squared(&column_heights(&cfg)), 0.);
+    assert_eq!(cfg.cfg, flat_interface(6, 8, 3).cfg);
+}
+
+#[test]
+fn run_writes_width_for_all_temperatures() {
+    let dir = std::env::temp_dir().join(format!("ising-interface-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut rng = Rng::from_seed([6; 32]);
+    interface::run(&dir, 6, 8, &[1., 3.], 1., 100, 10, 20, Format::Dat, &mut rng).unwrap();
+
+    let summary = std::fs::read_to_string(dir.join("interface.dat")).unwrap();
+    let widths: Vec<f64> = summary.lines().enumerate().map(|(i, line)| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        assert_eq!(fields[0], format!("{}:", i));
+        assert_eq!(fields[2], "6");
+        fields[3].parse().unwrap()
+    }).collect();
+    assert_eq!(widths.len(), 2);
+    assert!(widths.iter().all(|&w| w >= 0.));
+
+    for i in 0..2 {
+        assert!(dir.join(format!("{}.dat", i)).exists());
+        let text = std::fs::read_to_string(dir.join(format!("{}_interface.dat", i))).unwrap();
+        let rows: Vec<usize> = text.lines().map(|line| line.split_whitespace().count()).collect();
+        assert_eq!(rows, vec![20, 20]);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,1240 @@
+use std::ops::ControlFlow;
+
+use ising::{BondEnergy, Configuration, FlipClock, Format, Ising, Observables, Rng, binned_mean, binned_specific_heat,
+            bond_sum, check_energy, create_datadir, delta_e, evolve, evolve_field, evolve_field_flips, evolve_glauber,
+            evolve_observed, evolve_ramp, hamiltonian, has_bond_energy, magnetisation, neighbour_sum,
+            staggered_magnetisation, write_observables};
+use ising::{anneal, chains, exact, multicanonical, progress, schedule};
+use ising::schedule::Schedule;
+use ising::benchmark::{Algorithm, UpdateSchedule};
+use ising::checkerboard::{evolve_checkerboard, is_supported, neighbour_products, set_max_threads};
+use ising::chains::Chain;
+use ising::clusters::ClusterDistribution;
+use ising::model::{self, Acceptance, Model, SweepOrder};
+use ising::padded::Padded;
+use ising::correlation::Correlation;
+use ising::disorder::RandomBonds;
+use ising::error::Error;
+use ising::kawasaki::evolve_kawasaki;
+use ising::lattice::{Anisotropic, Boundaries, Square, Triangular};
+use ising::stats::{self, TemperatureStats};
+use ising::stream::{Sink, StreamWriter};
+use ising::swendsen_wang::evolve_swendsen_wang;
+use ising::wang_landau::{self, WangLandauParams};
+use ising::wolff::{evolve_wolff, wolff_update};
+
+const NSAMPLES: usize = 10;
+
+/// Return a checkerboard configuration on an nx*ny lattice with spin -1 at the origin.
+fn checkerboard(nx: usize, ny: usize) -> Configuration {
+    let mut cfg = Configuration::cold(nx, ny, 1);
+    for y in 0..ny {
+        for x in 0..nx {
+            if (x+y) % 2 == 0 {
+                cfg[y*nx + x] = -1;
+            }
+        }
+    }
+    cfg
+}
+
+#[test]
+fn hamiltonian_checkerboard() {
+    // - + - +
+    // + - + -
+    // - + - +
+    // + - + -
+    let cfg = checkerboard(4, 4);
+    // all 32 links are anti-aligned, the field cancels
+    for h in [0., 0.3, -1.2] {
+        assert_eq!(hamiltonian(&cfg, 1., h), 32.);
+        assert_eq!(hamiltonian(&cfg, -1., h), -32.);
+        assert_eq!(hamiltonian(&cfg, 0.5, h), 16.);
+    }
+    assert_eq!(magnetisation(&cfg), 0.);
+    assert_eq!(staggered_magnetisation(&cfg), -1.);
+}
+
+#[test]
+fn hamiltonian_cluster() {
+    // + + - -
+    // + + - -
+    // - - - -
+    // - - - -
+    let mut cfg = Configuration::cold(4, 4, -1);
+    for idx in [0, 1, 4, 5] {
+        cfg[idx] = 1;
+    }
+    // 8 broken links out of 32
+    assert_eq!(hamiltonian(&cfg, 1., 0.), -16.);
+    assert!((hamiltonian(&cfg, 1., 0.5) - (-16. + 0.5*8.)).abs() < 1e-12);
+    assert_eq!(hamiltonian(&cfg, -2., 0.), 32.);
+    assert_eq!(staggered_magnetisation(&cfg), 0.);
+}
+
+#[test]
+fn hamiltonian_aligned() {
+    for (nx, ny) in [(5, 1), (3, 3), (8, 4)] {
+        let cfg = Configuration::cold(nx, ny, 1);
+        let nlinks = if ny == 1 { nx } else { 2*nx*ny };
+        assert_eq!(hamiltonian(&cfg, 1., 0.7), -(nlinks as f64) - 0.7*(nx*ny) as f64);
+    }
+}
+
+#[test]
+fn delta_e_is_difference_of_hamiltonian() {
+    let mut rng = Rng::from_seed([62; 32]);
+    for (nx, ny) in [(3, 3), (32, 16), (7, 1)] {
+        for _ in 0..NSAMPLES {
+            let j = rng.gen_real()*4. - 2.;
+            let h = rng.gen_real()*3.2 - 1.6;
+            let mut cfg = Configuration::random(nx, ny, &mut rng);
+            cfg.add_local_field(rng.gen_index(nx*ny), rng.gen_real() - 0.5);
+            let energy = hamiltonian(&cfg, j, h);
+
+            for idx in 0..cfg.size() {
+                cfg[idx] *= -1;
+                let flipped = hamiltonian(&cfg, j, h);
+                cfg[idx] *= -1;
+                assert!((delta_e(&cfg, idx, j, h) - (flipped-energy)).abs() < 1e-9);
+            }
+        }
+    }
+}
+
+#[test]
+fn exact_anisotropic_critical_temperature() {
+    let onsager = 2. / (1. + 2f64.sqrt()).ln();
+    assert!((exact::critical_temperature(1., 1.) - onsager).abs() < 1e-12);
+    assert!((exact::critical_temperature(-0.5, -0.5) - onsager/2.).abs() < 1e-12);
+    assert_eq!(exact::critical_temperature(1., 0.), 0.);
+
+    let tc = exact::critical_temperature(1., 0.1);
+    assert!((exact::critical_temperature(0.1, 1.) - tc).abs() < 1e-12);
+    assert!(tc > 0. && tc < onsager);
+    assert!(((2./tc).sinh() * (0.2/tc).sinh() - 1.).abs() < 1e-12);
+}
+
+#[test]
+fn kaufman_partition_function_matches_enumeration() {
+    let mut rng = Rng::from_seed([23; 32]);
+    for (nx, ny, j) in [(4, 4, 1.), (3, 4, 1.), (5, 3, 0.7), (4, 2, 1.), (4, 4, -1.)] {
+        let cfg = Configuration::random(nx, ny, &mut rng);
+        let temperatures = [1., 2.269, 3.5];
+        let enumeration = exact::Enumeration::new(&cfg, j, 0.).averages(&temperatures);
+        for (temp, averages) in temperatures.into_iter().zip(enumeration) {
+            let ln_z = -averages.free_energy * (nx*ny) as f64 / temp;
+            assert!((exact::square_ln_partition_function(nx, ny, j, temp) - ln_z).abs() < 1e-10,
+                    "{}x{} J = {} T = {}", nx, ny, j, temp);
+            assert!((exact::square_energy(nx, ny, j, temp) - averages.energy).abs() < 1e-7);
+            assert!((exact::square_specific_heat(nx, ny, j, temp) - averages.specific_heat).abs() < 1e-5);
+        }
+    }
+    // Z does not depend on the orientation of the lattice
+    assert!((exact::square_ln_partition_function(6, 4, 1., 2.)
+             - exact::square_ln_partition_function(4, 6, 1., 2.)).abs() < 1e-10);
+}
+
+#[test]
+fn enumeration_averages() {
+    let mut rng = Rng::from_seed([24; 32]);
+    let cfg = Configuration::random(6, 1, &mut rng);
+    let enumeration = exact::Enumeration::new(&cfg, 1., 0.);
+    let mut nstate = 0;
+    enumeration.for_each(|_, _| nstate += 1);
+    assert_eq!((nstate, enumeration.nsite(), enumeration.ground_state_energy()), (64, 6, -6.));
+    let temperatures = [0.5, 2.];
+    for (temp, averages) in temperatures.iter().zip(enumeration.averages(&temperatures)) {
+        assert!((averages.energy - exact::chain_energy(6, 1., *temp)).abs() < 1e-12);
+        assert!((averages.specific_heat - exact::chain_specific_heat(6, 1., *temp)).abs() < 1e-12);
+    }
+    // ordered at low and disordered at high temperatures
+    let [cold, hot] = enumeration.averages(&[0.05, 1e6])[..] else { unreachable!() };
+    assert!((cold.abs_magnetisation - 1.).abs() < 1e-6 && (cold.binder - 2./3.).abs() < 1e-6);
+    assert!((hot.susceptibility * 1e6 - 1.).abs() < 1e-4);
+    assert!((hot.free_energy + 1e6 * 2_f64.ln()).abs() < 1e-3);
+
+    // a field polarises the spins and vacancies are not enumerated
+    let mut cfg = Configuration::random(3, 3, &mut rng);
+    cfg.dilute(1./9., &mut rng);
+    let enumeration = exact::Enumeration::new(&cfg, 0., 1.);
+    assert_eq!(enumeration.nsite(), 8);
+    assert!((enumeration.averages(&[1.])[0].energy + 1_f64.tanh()).abs() < 1e-12);
+}
+
+#[test]
+fn exact_antiferromagnetic_chain() {
+    // an even chain is bipartite, so J -> -J is a symmetry
+    for temp in [0.5, 1.3, 4.] {
+        assert!((exact::chain_energy(6, -1., temp) - exact::chain_energy(6, 1., temp)).abs() < 1e-12);
+        assert!((exact::chain_specific_heat(6, -1., temp)
+                 - exact::chain_specific_heat(6, 1., temp)).abs() < 1e-12);
+    }
+    // an odd chain is frustrated, at least one link is broken in the ground state
+    assert!((exact::chain_energy(5, -1., 0.2) - (-3./5.)).abs() < 1e-6);
+}
+
+#[test]
+fn ising_model_matches_generic_sums() {
+    let mut rng = Rng::from_seed([13; 32]);
+    let model = Ising{j: -0.6, h: 0.25};
+    let mut cfg = Configuration::random(5, 4, &mut rng);
+    cfg.add_local_field(3, 0.7);
+
+    let generic: f64 = (0..cfg.size()).map(|idx| {
+        cfg.neighbours_of(idx).iter().map(|&neigh| model.pair_energy(&cfg[idx], &cfg[neigh])).sum::<f64>() / 2.
+            + model.site_energy(&cfg, idx, &cfg[idx])
+    }).sum();
+    assert!((model.hamiltonian(&cfg) - generic).abs() < 1e-12);
+    for idx in 0..cfg.size() {
+        let flipped = model.propose(&cfg[idx], &mut rng);
+        assert_eq!(flipped, -cfg[idx]);
+        assert!((model.delta_e(&cfg, idx, &flipped) - delta_e(&cfg, idx, model.j, model.h)).abs() < 1e-12);
+    }
+
+    // in a field, evolve is the generic Metropolis loop
+    let mut other = cfg.clone();
+    let (mut e1, mut e2) = (model.hamiltonian(&cfg), model.hamiltonian(&cfg));
+    let (mut rng1, mut rng2) = (Rng::from_seed([14; 32]), Rng::from_seed([14; 32]));
+    evolve(&mut cfg, &mut e1, 0.5, model.j, model.h, &mut rng1, 10, None);
+    model::evolve(&model, &mut other, &mut e2, 0.5, &mut rng2, 10, None);
+    assert_eq!(cfg.cfg, other.cfg);
+    assert_eq!(e1, e2);
+}
+
+#[test]
+fn evolve_tracks_energy() {
+    let mut rng = Rng::from_seed([7; 32]);
+    for j in [1., -0.7] {
+        let mut cfg = Configuration::random(6, 5, &mut rng);
+        let h = 0.3;
+        let mut energy = hamiltonian(&cfg, j, h);
+        let mut obs = Observables::new();
+        evolve(&mut cfg, &mut energy, 1./2.5, j, h, &mut rng, 50, Some(&mut obs));
+
+        assert_eq!(obs.energy.len(), 50);
+        assert_eq!(obs.magnetisation.len(), 50);
+        assert_eq!(obs.staggered.len(), 50);
+        assert!((energy - hamiltonian(&cfg, j, h)).abs() < 1e-9);
+        assert_eq!(obs.magnetisation[49], magnetisation(&cfg));
+        assert_eq!(obs.staggered[49], staggered_magnetisation(&cfg));
+    }
+}
+
+#[test]
+fn evolve_ramp_matches_sweeps_of_evolve_field() {
+    // zero field in the first and last sweeps runs tabulated sweeps, padded only on the square lattice
+    let field = schedule::Piecewise(vec![(0, 0.), (5, 0.), (6, 0.4), (10, 0.4), (11, 0.)]);
+    let ramp = schedule::Linear{start: 3., stop: 1.5};
+    let nsweep = 16;
+    for start in [Configuration::random(6, 6, &mut Rng::from_seed([24; 32])),
+                  Configuration::random(6, 6, &mut Rng::from_seed([24; 32])).with_lattice(&Triangular{nx: 6, ny: 6})] {
+        let mut cfg = start.clone();
+        let mut energy = hamiltonian(&cfg, 1., 0.);
+        let mut obs = Observables::new();
+        let naccept = evolve_ramp(&mut cfg, &mut energy, 1., &ramp, &field, &mut Rng::from_seed([25; 32]), nsweep,
+                                  Some(&mut obs));
+
+        let mut swept = start.clone();
+        let mut swept_energy = hamiltonian(&swept, 1., 0.);
+        let mut swept_obs = Observables::new();
+        let mut rng = Rng::from_seed([25; 32]);
+        let mut swept_naccept = 0;
+        for sweep in 0..nsweep {
+            swept_naccept += evolve_field(&mut swept, &mut swept_energy, 1./ramp.value(sweep, nsweep), 1., &field,
+                                          sweep, nsweep, &mut rng, Some(&mut swept_obs));
+        }
+        assert_eq!((cfg.cfg, energy, naccept), (swept.cfg, swept_energy, swept_naccept));
+        assert_eq!(obs, swept_obs);
+    }
+}
+
+#[test]
+fn flip_clock_measures_equidistantly_across_sweeps() {
+    let mut rng = Rng::from_seed([9; 32]);
+    let mut cfg = Configuration::random(5, 3, &mut rng);
+    let field = schedule::Constant(0.2);
+    let mut energy = hamiltonian(&cfg, 1., 0.2);
+    let mut clock = FlipClock::new(4);
+    let mut obs = Observables::new();
+    for sweep in 0..3 {
+        evolve_field_flips(&mut cfg, &mut energy, 1./2., 1., &field, sweep, 3, &mut rng, &mut clock, &mut obs);
+    }
+
+    // 15 sites do not divide into measurements every 4 flips, the clock keeps running across sweeps
+    assert_eq!(clock.flips, 45);
+    assert_eq!(clock.times, (1..=11).map(|i| 4*i).collect::<Vec<_>>());
+    assert!(clock.times.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(obs.energy.len(), 11);
+    assert!((energy - hamiltonian(&cfg, 1., 0.2)).abs() < 1e-9);
+    assert_eq!(obs.magnetisation[10], magnetisation(&cfg));
+}
+
+#[test]
+fn evolve_observed_calls_back_after_every_sweep() {
+    for h in [0., 0.3] {
+        let start = Configuration::random(6, 6, &mut Rng::from_seed([19; 32]));
+        let mut cfg = start.clone();
+        let mut energy = hamiltonian(&cfg, 1., h);
+        let mut obs = Observables::new();
+        let naccept = evolve(&mut cfg, &mut energy, 0.4, 1., h, &mut Rng::from_seed([20; 32]), 30, Some(&mut obs));
+
+        let mut called = start.clone();
+        let mut called_energy = hamiltonian(&called, 1., h);
+        let mut sweeps = Vec::new();
+        let mut measured = Observables::new();
+        let called_naccept = evolve_observed(&mut called, &mut called_energy, 0.4, 1., h, &mut Rng::from_seed([20; 32]), 30,
+                                             |sweep, cfg, energy| {
+            sweeps.push(sweep);
+            measured.measure(cfg, energy);
+            ControlFlow::Continue(())
+        });
+        assert_eq!(sweeps, (0..30).collect::<Vec<usize>>());
+        assert_eq!(measured, obs);
+        assert_eq!((called.cfg, called_energy, called_naccept), (cfg.cfg, energy, naccept));
+    }
+
+    // stop once the lattice is magnetised
+    let mut cfg = Configuration::random(8, 8, &mut Rng::from_seed([21; 32]));
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    let mut nsweep = 0;
+    evolve_observed(&mut cfg, &mut energy, 1., 1., 0., &mut Rng::from_seed([22; 32]), 10000, |sweep, cfg, _| {
+        nsweep = sweep + 1;
+        if magnetisation(cfg).abs() > 0.9 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+    });
+    assert!(nsweep < 10000);
+    assert!(magnetisation(&cfg).abs() > 0.9);
+}
+
+#[test]
+fn cluster_distribution_counts_clusters_of_both_spins() {
+    let mut cfg = Configuration::random(4, 3, &mut Rng::from_seed([23; 32]));
+    let mut clusters = ClusterDistribution::new(cfg.nsite());
+    // an up row and a down cluster of the other two rows, then a single cluster
+    cfg.cfg = vec![1, 1, 1, 1, -1, -1, -1, -1, -1, -1, -1, -1];
+    clusters.measure(&cfg);
+    cfg.cfg = vec![1; 12];
+    clusters.measure(&cfg);
+
+    let mut expected = vec![0.; 12];
+    for size in [4, 8, 12] {
+        expected[size-1] = 1. / 24.;
+    }
+    assert_eq!(clusters.distribution(), expected);
+    assert_eq!(clusters.largest(), [8. / 12., 1.]);
+}
+
+#[test]
+fn acceptance_rules_satisfy_detailed_balance() {
+    for (beta, delta) in [(0.5, 2.), (1.3, -4.), (0.2, 0.)] {
+        for acceptance in [Acceptance::Metropolis, Acceptance::Glauber] {
+            let ratio = acceptance.probability(beta, delta).min(1.) / acceptance.probability(beta, -delta).min(1.);
+            assert!((ratio - (-beta*delta).exp()).abs() < 1e-12, "{:?}", acceptance);
+        }
+        let glauber = Acceptance::Glauber.probability(beta, delta) + Acceptance::Glauber.probability(beta, -delta);
+        assert!((glauber - 1.).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn glauber_tracks_energy_and_matches_exact_chain() {
+    let mut rng = Rng::from_seed([18; 32]);
+    for (j, h) in [(1., 0.), (-0.7, 0.3)] {
+        let mut cfg = Configuration::random(6, 5, &mut rng);
+        let mut energy = hamiltonian(&cfg, j, h);
+        let mut obs = Observables::new();
+        evolve_glauber(&mut cfg, &mut energy, 1./2.5, j, h, &mut rng, 50, Some(&mut obs));
+        assert_eq!(obs.energy.len(), 50);
+        assert!((energy - hamiltonian(&cfg, j, h)).abs() < 1e-9);
+    }
+
+    let (nx, temp) = (8, 1.5);
+    let mut cfg = Configuration::random(nx, 1, &mut rng);
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    evolve_glauber(&mut cfg, &mut energy, 1./temp, 1., 0., &mut rng, 1000, None);
+    let mut obs = Observables::new();
+    evolve_glauber(&mut cfg, &mut energy, 1./temp, 1., 0., &mut rng, 50000, Some(&mut obs));
+    let mean = obs.energy.iter().sum::<f64>() / (obs.energy.len()*nx) as f64;
+    assert!((mean - exact::chain_energy(nx, 1., temp)).abs() < 0.02);
+}
+
+#[test]
+fn evolve_orders_antiferromagnet() {
+    let mut rng = Rng::from_seed([17; 32]);
+    let mut cfg = Configuration::random(8, 8, &mut rng);
+    let mut energy = hamiltonian(&cfg, -1., 0.);
+    evolve(&mut cfg, &mut energy, 1./0.5, -1., 0., &mut rng, 2000, None);
+    assert!(staggered_magnetisation(&cfg).abs() > 0.9);
+}
+
+#[test]
+fn evolve_does_not_touch_pinned_rows() {
+    let mut rng = Rng::from_seed([8; 32]);
+    let mut cfg = Configuration::random(4, 5, &mut rng);
+    cfg.pin_boundary_rows();
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    evolve(&mut cfg, &mut energy, 0.1, 1., 0., &mut rng, 20, None);
+
+    assert!(cfg.cfg[..4].iter().all(|&s| s == 1));
+    assert!(cfg.cfg[16..].iter().all(|&s| s == -1));
+    assert_eq!(cfg.active_sites().len(), 12);
+}
+
+#[test]
+fn dilution_leaves_vacancies() {
+    let mut rng = Rng::from_seed([14; 32]);
+    let mut cfg = Configuration::cold(8, 8, 1);
+    cfg.dilute(0.25, &mut rng);
+    assert_eq!(cfg.vacancies().len(), 16);
+    assert_eq!((cfg.nsite(), cfg.active_sites().len()), (48, 48));
+    assert!(cfg.vacancies().iter().all(|&idx| cfg[idx] == 0 && !cfg.active_sites().contains(&idx)));
+    // per occupied site
+    assert_eq!(magnetisation(&cfg), 1.);
+    // vacancies feel no field and break their bonds
+    assert_eq!(hamiltonian(&cfg, 0., 0.5), -0.5 * 48.);
+    let bonds: usize = (0..cfg.size()).map(|idx| cfg.neighbours_of(idx).iter()
+                                            .filter(|&&neigh| cfg[idx] != 0 && cfg[neigh] != 0).count()).sum();
+    assert_eq!(hamiltonian(&cfg, 1., 0.), -((bonds / 2) as f64));
+
+    // removing a vacancy again changes nothing
+    let vacancies = cfg.vacancies().to_vec();
+    cfg.remove_sites(&vacancies[..2]);
+    assert_eq!(cfg.vacancies(), vacancies.as_slice());
+
+    for algorithm in [Algorithm::Metropolis, Algorithm::Wolff, Algorithm::SwendsenWang, Algorithm::Checkerboard,
+                      Algorithm::Kawasaki] {
+        let mut diluted = cfg.clone();
+        let mut energy = hamiltonian(&diluted, 1., 0.);
+        algorithm.evolve(&mut diluted, &mut energy, 0.3, 1., 0., &mut rng, 20, None);
+        assert!(vacancies.iter().all(|&idx| diluted[idx] == 0), "{:?}", algorithm);
+        assert!((energy - hamiltonian(&diluted, 1., 0.)).abs() < 1e-9, "{:?}", algorithm);
+    }
+}
+
+#[test]
+fn wolff_tracks_energy() {
+    let mut rng = Rng::from_seed([11; 32]);
+    for (nx, ny, j, h) in [(6, 5, 1., 0.), (6, 5, 1., 0.4), (9, 1, 1., -0.2), (6, 6, -1.3, 0.2)] {
+        let mut cfg = Configuration::random(nx, ny, &mut rng);
+        cfg.add_local_field(3, 0.25);
+        let mut energy = hamiltonian(&cfg, j, h);
+        let mut obs = Observables::new();
+        evolve_wolff(&mut cfg, &mut energy, 1./2.2, j, h, &mut rng, 200, Some(&mut obs));
+
+        assert_eq!(obs.energy.len(), 200);
+        assert!((energy - hamiltonian(&cfg, j, h)).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn wolff_flips_whole_lattice_at_zero_temperature() {
+    let mut rng = Rng::from_seed([12; 32]);
+    let mut cfg = Configuration::cold(5, 4, 1);
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    // all bonds are activated with probability 1 - exp(-2 beta) = 1
+    assert_eq!(wolff_update(&mut cfg, &mut energy, f64::INFINITY, 1., 0., &mut rng), (20, true));
+    assert!(cfg.cfg.iter().all(|&s| s == -1));
+    assert_eq!(energy, hamiltonian(&cfg, 1., 0.));
+
+    // same for the antiferromagnetic ground state
+    let mut cfg = checkerboard(4, 4);
+    let mut energy = hamiltonian(&cfg, -1., 0.);
+    assert_eq!(wolff_update(&mut cfg, &mut energy, f64::INFINITY, -1., 0., &mut rng), (16, true));
+    assert_eq!(staggered_magnetisation(&cfg), 1.);
+}
+
+#[test]
+fn wolff_uses_coupling_factors() {
+    let mut rng = Rng::from_seed([26; 32]);
+    // without couplings between rows, a cluster at zero temperature is a single row
+    let decoupled = Anisotropic{lattice: Box::new(Square{nx: 5, ny: 4}), x: 1., y: 0.};
+    let mut cfg = Configuration::cold(5, 4, 1).with_lattice(&decoupled);
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    assert_eq!(wolff_update(&mut cfg, &mut energy, f64::INFINITY, 1., 0., &mut rng), (5, true));
+    assert_eq!(cfg.cfg.iter().filter(|&&s| s == -1).count(), 5);
+    assert_eq!(energy, hamiltonian(&cfg, 1., 0.));
+
+    // antiferromagnetic couplings between rows join the rows of the striped ground state
+    let striped = Anisotropic{lattice: Box::new(Square{nx: 5, ny: 4}), x: 1., y: -1.};
+    let mut cfg = Configuration::cold(5, 4, 1).with_lattice(&striped);
+    (0..20).filter(|idx| (idx / 5) % 2 == 1).for_each(|idx| cfg[idx] = -1);
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    assert_eq!(wolff_update(&mut cfg, &mut energy, f64::INFINITY, 1., 0., &mut rng), (20, true));
+    assert_eq!(energy, hamiltonian(&cfg, 1., 0.));
+
+    for boundaries in ["open", "antiperiodic:periodic"] {
+        let mut cfg = Configuration::random(6, 5, &mut rng)
+            .with_boundaries(&Square{nx: 6, ny: 5}, Boundaries::parse(boundaries).unwrap());
+        let mut energy = hamiltonian(&cfg, 1., 0.2);
+        evolve_wolff(&mut cfg, &mut energy, 1./2.2, 1., 0.2, &mut rng, 100, None);
+        assert!((energy - hamiltonian(&cfg, 1., 0.2)).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn wolff_does_not_touch_pinned_rows() {
+    let mut rng = Rng::from_seed([13; 32]);
+    let mut cfg = Configuration::random(4, 5, &mut rng);
+    cfg.pin_boundary_rows();
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    evolve_wolff(&mut cfg, &mut energy, 0.3, 1., 0., &mut rng, 100, None);
+
+    assert!(cfg.cfg[..4].iter().all(|&s| s == 1));
+    assert!(cfg.cfg[16..].iter().all(|&s| s == -1));
+    assert!((energy - hamiltonian(&cfg, 1., 0.)).abs() < 1e-9);
+}
+
+#[test]
+fn swendsen_wang_tracks_energy() {
+    let mut rng = Rng::from_seed([14; 32]);
+    for (nx, ny, j, h) in [(6, 5, 1., 0.), (6, 5, 1., 0.4), (9, 1, 1., -0.2), (2, 2, 1., 0.1),
+                           (6, 6, -0.8, 0.3)] {
+        let mut cfg = Configuration::random(nx, ny, &mut rng);
+        cfg.add_local_field(1, -0.5);
+        let mut energy = hamiltonian(&cfg, j, h);
+        let mut obs = Observables::new();
+        evolve_swendsen_wang(&mut cfg, &mut energy, 1./2.2, j, h, &mut rng, 50, Some(&mut obs));
+
+        assert_eq!(obs.energy.len(), 50);
+        assert!((energy - hamiltonian(&cfg, j, h)).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn swendsen_wang_keeps_ground_state_at_zero_temperature() {
+    let mut rng = Rng::from_seed([15; 32]);
+    let mut cfg = Configuration::cold(5, 4, 1);
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    // the whole lattice is a single cluster
+    evolve_swendsen_wang(&mut cfg, &mut energy, f64::INFINITY, 1., 0., &mut rng, 10, None);
+    assert!(cfg.cfg.iter().all(|&s| s == cfg[0]));
+    assert_eq!(energy, -40.);
+
+    let mut cfg = checkerboard(4, 4);
+    let mut energy = hamiltonian(&cfg, -1., 0.);
+    evolve_swendsen_wang(&mut cfg, &mut energy, f64::INFINITY, -1., 0., &mut rng, 10, None);
+    assert_eq!(staggered_magnetisation(&cfg).abs(), 1.);
+    assert_eq!(energy, -32.);
+}
+
+#[test]
+fn swendsen_wang_uses_coupling_factors() {
+    let mut rng = Rng::from_seed([27; 32]);
+    // without couplings between rows, every row is a cluster of its own at low temperature
+    let decoupled = Anisotropic{lattice: Box::new(Square{nx: 5, ny: 4}), x: 1., y: 0.};
+    let mut cfg = Configuration::cold(5, 4, 1).with_lattice(&decoupled);
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    let mut mixed = false;
+    for _update in 0..10 {
+        evolve_swendsen_wang(&mut cfg, &mut energy, 10., 1., 0., &mut rng, 1, None);
+        assert!(cfg.cfg.chunks(5).all(|row| row.iter().all(|&s| s == row[0])));
+        mixed |= cfg.cfg.iter().any(|&s| s != cfg[0]);
+        assert_eq!(energy, -20.);
+    }
+    assert!(mixed);
+
+    // open boundaries cut the links across the edges, the lattice is still a single cluster
+    let mut cfg = Configuration::cold(5, 4, 1).with_boundaries(&Square{nx: 5, ny: 4}, Boundaries::parse("open").unwrap());
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    evolve_swendsen_wang(&mut cfg, &mut energy, 10., 1., 0., &mut rng, 10, None);
+    assert!(cfg.cfg.iter().all(|&s| s == cfg[0]));
+    assert_eq!(energy, -31.);
+}
+
+#[test]
+fn swendsen_wang_does_not_touch_pinned_rows() {
+    let mut rng = Rng::from_seed([16; 32]);
+    let mut cfg = Configuration::random(4, 5, &mut rng);
+    cfg.pin_boundary_rows();
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    evolve_swendsen_wang(&mut cfg, &mut energy, 0.3, 1., 0., &mut rng, 100, None);
+
+    assert!(cfg.cfg[..4].iter().all(|&s| s == 1));
+    assert!(cfg.cfg[16..].iter().all(|&s| s == -1));
+}
+
+#[test]
+fn chains_are_independent_and_merged_in_order() {
+    let mut rng = Rng::from_seed([22; 32]);
+    let mut cfg = Configuration::random(4, 5, &mut rng);
+    cfg.pin_boundary_rows();
+    let mut chains: Vec<Chain> = (0..3).map(|c| Chain::new(&cfg, Rng::from_seed([c; 32]))).collect();
+    let results = chains::run_parallel(&mut chains, |chain| {
+        chain.run(&UpdateSchedule::single(Algorithm::Metropolis), SweepOrder::Random, 0.4, 1., &schedule::Constant(0.), 10, 20)
+    });
+    for (chain, (obs, _)) in chains.iter().zip(results.iter()) {
+        assert!(chain.cfg.cfg[..4].iter().all(|&s| s == 1));
+        assert_eq!(chain.energy, hamiltonian(&chain.cfg, 1., 0.));
+        assert_eq!(obs.energy.len(), 20);
+    }
+    assert_ne!(results[0].0.energy, results[1].0.energy);
+
+    let (merged, ids) = chains::merge(results.into_iter().map(|(obs, _)| obs).collect());
+    assert_eq!(merged.energy.len(), 60);
+    assert_eq!(ids[..21], [vec![0; 20], vec![1]].concat());
+    assert_eq!(ids[59], 2);
+}
+
+#[test]
+fn checkerboard_tracks_energy() {
+    let mut rng = Rng::from_seed([23; 32]);
+    for j in [1., -0.7] {
+        let mut cfg = Configuration::random(6, 4, &mut rng);
+        cfg.add_local_field(5, 0.3);
+        let mut energy = hamiltonian(&cfg, j, 0.2);
+        evolve_checkerboard(&mut cfg, &mut energy, 0.5, j, 0.2, &mut rng, 50, None);
+        assert!((energy - hamiltonian(&cfg, j, 0.2)).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn checkerboard_does_not_touch_pinned_rows() {
+    let mut rng = Rng::from_seed([24; 32]);
+    let mut cfg = Configuration::random(4, 6, &mut rng);
+    cfg.pin_boundary_rows();
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    evolve_checkerboard(&mut cfg, &mut energy, 0.3, 1., 0., &mut rng, 100, None);
+
+    assert!(cfg.cfg[..4].iter().all(|&s| s == 1));
+    assert!(cfg.cfg[20..].iter().all(|&s| s == -1));
+}
+
+#[test]
+fn checkerboard_needs_even_2d_lattice() {
+    assert!(is_supported(4, 6));
+    assert!(!is_supported(4, 3));
+    assert!(!is_supported(5, 4));
+    assert!(!is_supported(8, 1));
+}
+
+#[test]
+fn row_neighbour_sums_match_neighbour_lists() {
+    let mut rng = Rng::from_seed([26; 32]);
+    // widths around the AVX2 register size of 8 sites
+    for (nx, ny) in [(2, 2), (3, 4), (8, 2), (10, 3), (17, 4), (64, 6)] {
+        let cfg = Configuration::random(nx, ny, &mut rng);
+        assert!(cfg.is_periodic_square());
+        let mut products = vec![0; nx];
+        for y in 0..ny {
+            neighbour_products(&cfg, y, &mut products);
+            let expected: Vec<i32> = (y*nx..(y+1)*nx).map(|idx| cfg[idx] * neighbour_sum(&cfg, idx)).collect();
+            assert_eq!(products, expected);
+        }
+    }
+
+    assert!(!Configuration::cold(6, 1, 1).is_periodic_square());
+    assert!(!Configuration::on_lattice(&Triangular{nx: 6, ny: 6}, 1).is_periodic_square());
+    let square = Square{nx: 6, ny: 4};
+    assert!(!Configuration::cold(6, 4, 1).with_boundaries(&square, Boundaries::parse("open").unwrap())
+            .is_periodic_square());
+    let mut cfg = Configuration::cold(6, 4, 1);
+    cfg.pin_boundary_rows();
+    assert!(!cfg.is_periodic_square());
+}
+
+#[test]
+fn checkerboard_flips_all_spins_at_infinite_temperature() {
+    let mut rng = Rng::from_seed([27; 32]);
+    for h in [0., 0.4] {
+        let start = Configuration::random(8, 6, &mut rng);
+        let mut cfg = start.clone();
+        let mut energy = hamiltonian(&cfg, 1., h);
+        assert_eq!(evolve_checkerboard(&mut cfg, &mut energy, 0., 1., h, &mut rng, 1, None), 48);
+        assert!(cfg.cfg.iter().zip(start.cfg.iter()).all(|(&s, &t)| s == -t));
+        assert!((energy - hamiltonian(&cfg, 1., h)).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn checkerboard_does_not_depend_on_the_number_of_threads() {
+    // large enough for 8 threads, the local field takes the general path
+    for h in [0., 0.3] {
+        let results: Vec<(Vec<i32>, f64)> = [1, 3, 8].into_iter().map(|nthreads| {
+            set_max_threads(nthreads);
+            let mut rng = Rng::from_seed([28; 32]);
+            let mut cfg = Configuration::random(256, 256, &mut rng);
+            let mut energy = hamiltonian(&cfg, 1., h);
+            evolve_checkerboard(&mut cfg, &mut energy, 0.4, 1., h, &mut rng, 3, None);
+            (cfg.cfg, energy)
+        }).collect();
+        set_max_threads(0);
+        assert!(results.iter().all(|result| *result == results[0]));
+    }
+}
+
+#[test]
+fn kawasaki_conserves_magnetisation_and_tracks_energy() {
+    let mut rng = Rng::from_seed([25; 32]);
+    // nx = 2 links neighbours in x twice
+    for (nx, ny, j, h) in [(6, 5, 1., 0.), (6, 5, -0.8, 0.3), (2, 5, 1., 0.), (9, 1, 1., -0.2)] {
+        let mut cfg = Configuration::random(nx, ny, &mut rng);
+        cfg.add_local_field(3, 0.25);
+        let mut factor = 0.;
+        cfg.scale_bonds(|| { factor += 0.1; factor });
+        let magnetisation = cfg.cfg.iter().sum::<i32>();
+        let mut energy = hamiltonian(&cfg, j, h);
+        let mut obs = Observables::new();
+        let nexchange = evolve_kawasaki(&mut cfg, &mut energy, 1./1.5, j, h, &mut rng, 100, Some(&mut obs));
+
+        assert!(nexchange > 0);
+        assert_eq!(obs.energy.len(), 100);
+        assert_eq!(cfg.cfg.iter().sum::<i32>(), magnetisation);
+        assert!((energy - hamiltonian(&cfg, j, h)).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn kawasaki_separates_phases_at_low_temperature() {
+    let mut rng = Rng::from_seed([26; 32]);
+    let mut cfg = Configuration::random(16, 16, &mut rng);
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    let initial = energy;
+    evolve_kawasaki(&mut cfg, &mut energy, 1./1.0, 1., 0., &mut rng, 200, None);
+    // domains coarsen, random spins have E ~ 0 and two stripes with one phase each E = -2 N + 4 nx
+    assert!(energy < initial - 200.);
+}
+
+#[test]
+fn kawasaki_does_not_touch_pinned_rows() {
+    let mut rng = Rng::from_seed([27; 32]);
+    let mut cfg = Configuration::random(4, 5, &mut rng);
+    cfg.pin_boundary_rows();
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    evolve_kawasaki(&mut cfg, &mut energy, 0.3, 1., 0., &mut rng, 100, None);
+
+    assert!(cfg.cfg[..4].iter().all(|&s| s == 1));
+    assert!(cfg.cfg[16..].iter().all(|&s| s == -1));
+    assert!((energy - hamiltonian(&cfg, 1., 0.)).abs() < 1e-9);
+}
+
+#[test]
+fn evolve_in_zero_field_matches_metropolis_steps() {
+    // evolve uses tabulated acceptance probabilities and draws the random numbers of each sweep up front
+    for (nx, ny, j) in [(5, 4, 1.), (6, 1, -0.7), (3, 3, 1.), (2, 2, 0.5)] {
+        let mut rng = Rng::from_seed([25; 32]);
+        let mut cfg = Configuration::random(nx, ny, &mut rng);
+        let mut energy = hamiltonian(&cfg, j, 0.);
+        let mut reference = cfg.clone();
+        let mut reference_energy = energy;
+        let mut reference_rng = Rng::from_seed([26; 32]);
+        let mut rng = Rng::from_seed([26; 32]);
+
+        let naccept = evolve(&mut cfg, &mut energy, 0.6, j, 0., &mut rng, 20, None);
+        let mut reference_naccept = 0;
+        for _sweep in 0..20 {
+            let sites: Vec<usize> = (0..nx*ny).map(|_| reference_rng.gen_index(nx*ny)).collect();
+            let uniforms: Vec<f64> = (0..nx*ny).map(|_| reference_rng.gen_real()).collect();
+            for (idx, uniform) in sites.into_iter().zip(uniforms) {
+                let delta = delta_e(&reference, idx, j, 0.);
+                if delta <= 0. || (-0.6*delta).exp() > uniform {
+                    reference[idx] *= -1;
+                    reference_energy += delta;
+                    reference_naccept += 1;
+                }
+            }
+        }
+        assert_eq!(naccept, reference_naccept);
+        assert_eq!(cfg.cfg, reference.cfg);
+        // evolve counts the bond sum, the reference adds up ΔE
+        assert_eq!(energy, hamiltonian(&cfg, j, 0.));
+        assert!((energy - reference_energy).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn sweep_orders_visit_every_site_once() {
+    for order in [SweepOrder::Random, SweepOrder::Sequential, SweepOrder::Shuffled] {
+        assert_eq!(SweepOrder::parse(order.name()).unwrap(), order);
+    }
+    assert!(SweepOrder::parse("typewriter").is_err());
+
+    let mut rng = Rng::from_seed([28; 32]);
+    let mut positions = vec![0; 50];
+    SweepOrder::Sequential.fill(&mut positions, &mut rng);
+    assert_eq!(positions, (0..50).collect::<Vec<_>>());
+    SweepOrder::Shuffled.fill(&mut positions, &mut rng);
+    let first = positions.clone();
+    positions.sort();
+    assert_eq!(positions, (0..50).collect::<Vec<_>>());
+    SweepOrder::Shuffled.fill(&mut positions, &mut rng);
+    assert_ne!(positions, first);
+}
+
+#[test]
+fn sequential_sweeps_match_metropolis_steps() {
+    for (nx, ny, order) in [(5, 4, SweepOrder::Sequential), (6, 1, SweepOrder::Sequential),
+                            (5, 4, SweepOrder::Shuffled)] {
+        let mut rng = Rng::from_seed([29; 32]);
+        let mut cfg = Configuration::random(nx, ny, &mut rng);
+        let mut energy = hamiltonian(&cfg, 1., 0.);
+        let mut reference = cfg.clone();
+        let mut reference_rng = Rng::from_seed([30; 32]);
+        let mut rng = Rng::from_seed([30; 32]);
+
+        let naccept = ising::evolve_in_order(&mut cfg, &mut energy, 0.5, 1., 0., Acceptance::Metropolis, order,
+                                             &mut rng, 10, None);
+        let mut reference_naccept = 0;
+        let mut sites = vec![0; nx*ny];
+        for _sweep in 0..10 {
+            order.fill(&mut sites, &mut reference_rng);
+            let uniforms: Vec<f64> = (0..nx*ny).map(|_| reference_rng.gen_real()).collect();
+            for (&idx, uniform) in sites.iter().zip(uniforms) {
+                let delta = delta_e(&reference, idx, 1., 0.);
+                if delta <= 0. || (-0.5*delta).exp() > uniform {
+                    reference[idx] *= -1;
+                    reference_naccept += 1;
+                }
+            }
+        }
+        assert_eq!(naccept, reference_naccept);
+        assert_eq!(cfg.cfg, reference.cfg);
+        assert_eq!(energy, hamiltonian(&cfg, 1., 0.));
+    }
+
+    // in a field, the generic loop of model visits the sites
+    let mut rng = Rng::from_seed([31; 32]);
+    let mut cfg = Configuration::random(6, 6, &mut rng);
+    let mut energy = hamiltonian(&cfg, 1., 0.2);
+    let naccept = ising::evolve_in_order(&mut cfg, &mut energy, 0.5, 1., 0.2, Acceptance::Glauber,
+                                         SweepOrder::Sequential, &mut rng, 10, None);
+    assert!(naccept > 0);
+    assert!((energy - hamiltonian(&cfg, 1., 0.2)).abs() < 1e-9);
+}
+
+#[test]
+fn update_schedules_run_their_steps_in_order() {
+    let schedule = UpdateSchedule::parse("metropolis: 1, wolff: 2").unwrap();
+    assert_eq!(schedule.steps, vec![(1, Algorithm::Metropolis), (2, Algorithm::Wolff)]);
+    assert_eq!(UpdateSchedule::parse("1 metropolis + 2 wolff").unwrap(), schedule);
+    assert_eq!(UpdateSchedule::parse("heatbath").unwrap(), UpdateSchedule::single(Algorithm::HeatBath));
+    for spec in ["", "metropolis: 0", "metropolis: x", "wolff +", "2 3 wolff", "metropolis, kawasaki", "glauber"] {
+        assert!(UpdateSchedule::parse(spec).is_err(), "{}", spec);
+    }
+
+    let mut rng = Rng::from_seed([32; 32]);
+    let mut cfg = Configuration::random(8, 8, &mut rng);
+    let mut energy = hamiltonian(&cfg, 1., 0.1);
+    let mut reference = cfg.clone();
+    let mut reference_energy = energy;
+    let mut reference_rng = Rng::from_seed([33; 32]);
+    let mut rng = Rng::from_seed([33; 32]);
+
+    let mut obs = Observables::new();
+    let naccept = schedule.evolve_in_order(SweepOrder::Random, &mut cfg, &mut energy, 0.4, 1., 0.1, &mut rng, 5,
+                                           Some(&mut obs));
+    let mut reference_naccept = 0;
+    for _sweep in 0..5 {
+        reference_naccept += evolve(&mut reference, &mut reference_energy, 0.4, 1., 0.1, &mut reference_rng, 1, None);
+        reference_naccept += evolve_wolff(&mut reference, &mut reference_energy, 0.4, 1., 0.1, &mut reference_rng, 2,
+                                          None);
+    }
+    assert_eq!(naccept, reference_naccept);
+    assert_eq!(cfg.cfg, reference.cfg);
+    assert_eq!(obs.energy.len(), 5);
+    assert_eq!(*obs.energy.last().unwrap(), energy);
+    assert!((energy - hamiltonian(&cfg, 1., 0.1)).abs() < 1e-9);
+}
+
+#[test]
+fn padded_layout_matches_neighbour_lists() {
+    for (nx, ny) in [(5, 4), (1, 3), (2, 2), (3, 2)] {
+        let mut rng = Rng::from_seed([27; 32]);
+        let mut cfg = Configuration::random(nx, ny, &mut rng);
+        let mut padded = Padded::new(&cfg);
+        for _step in 0..50 {
+            let idx = rng.gen_index(nx*ny);
+            padded.flip(padded.index(idx));
+            cfg[idx] *= -1;
+            for idx in 0..nx*ny {
+                let p = padded.index(idx);
+                assert_eq!((padded.spin(p), padded.neighbour_sum(p)), (cfg[idx], neighbour_sum(&cfg, idx)));
+            }
+        }
+        let mut written = Configuration::random(nx, ny, &mut rng);
+        padded.write(&mut written);
+        assert_eq!(written.cfg, cfg.cfg);
+    }
+}
+
+#[test]
+fn annealing_schedules_are_parsed() {
+    assert_eq!(schedule::parse_annealing("linear:0.5:2:4").unwrap(), vec![0.5, 1., 1.5, 2.]);
+    let geometric = schedule::parse_annealing("geometric:0.1:10:3").unwrap();
+    assert!(geometric.iter().zip([0.1, 1., 10.]).all(|(a, b)| (a-b).abs() < 1e-12));
+    assert_eq!(schedule::parse_annealing("list:0.3,1,0.7").unwrap(), vec![0.3, 1., 0.7]);
+    for spec in ["linear:0.5:2", "linear:0:2:4", "geometric:1:2:0", "list:1,inf", "exp:1:2:3"] {
+        assert!(schedule::parse_annealing(spec).is_err(), "{}", spec);
+    }
+}
+
+#[test]
+fn temperature_schedules_reach_their_endpoints() {
+    let close = |a: f64, b: f64| (a-b).abs() < 1e-12;
+    for spec in ["linear:3:1", "exp:3:1", "power:3:1:2", "piecewise:0=3,50=2,99=1"] {
+        let ramp = schedule::parse_temperature(spec).unwrap();
+        let values = ramp.values(100);
+        assert!(close(ramp.start(), 3.) && close(values[0], 3.) && close(values[99], 1.), "{}: {:?}", spec, values);
+        assert!(values.windows(2).all(|pair| pair[1] <= pair[0] + 1e-12), "{} is not monotonic", spec);
+        assert!(!ramp.is_constant());
+    }
+    // midpoints of the interpolations
+    assert!(close(schedule::parse_temperature("linear:3:1").unwrap().value(2, 5), 2.));
+    assert!(close(schedule::parse_temperature("exp:4:1").unwrap().value(2, 5), 2.));
+    assert!(close(schedule::parse_temperature("power:3:1:2").unwrap().value(2, 5), 2.5));
+    // piecewise schedules hold their values outside the knots
+    let piecewise = schedule::parse_temperature("piecewise:10=2,20=1").unwrap();
+    assert_eq!((piecewise.value(0, 30), piecewise.value(15, 30), piecewise.value(29, 30)), (2., 1.5, 1.));
+
+    for spec in ["linear:3", "linear:3:-1", "exp:0:1", "power:3:1:0", "piecewise:5=1,2=2", "adaptive:3:1:2",
+                 "cubic:3:1", "3"] {
+        assert!(schedule::parse_temperature(spec).is_err(), "{}", spec);
+    }
+}
+
+#[test]
+fn adaptive_schedule_follows_acceptance() {
+    // at the target acceptance rate, the schedule is exponential
+    let adaptive = schedule::parse_temperature("adaptive:4:1:0.5").unwrap();
+    let exponential = schedule::parse_temperature("exp:4:1").unwrap();
+    for sweep in 0..5 {
+        assert!((adaptive.value(sweep, 5) - exponential.value(sweep, 5)).abs() < 1e-12);
+        adaptive.record_acceptance(sweep, 5, 0.5);
+    }
+
+    // without accepted moves, the temperature stays, with many it moves on but not past stop
+    let adaptive = schedule::parse_temperature("adaptive:4:1:0.5").unwrap();
+    adaptive.record_acceptance(0, 5, 0.);
+    assert_eq!(adaptive.value(1, 5), 4.);
+    for sweep in 1..4 {
+        adaptive.record_acceptance(sweep, 5, 1.);
+    }
+    assert_eq!(adaptive.value(4, 5), 1.);
+}
+
+#[test]
+fn field_schedules_are_parsed() {
+    for spec in ["0.5", "const:0.5"] {
+        let field = schedule::parse_field(spec).unwrap();
+        assert!(field.is_constant());
+        assert_eq!((field.start(), field.value(7, 10), field.phase(7, 10)), (0.5, 0.5, 0.));
+    }
+    let linear = schedule::parse_field("linear:-1:1").unwrap();
+    assert_eq!((linear.start(), linear.value(4, 5), linear.phase(2, 5)), (-1., 1., 0.5));
+
+    // periodic with its own phase
+    let sin = schedule::parse_field("sin:2:8:0.5").unwrap();
+    assert!(!sin.is_constant());
+    assert_eq!(sin.start(), 0.5);
+    assert!((sin.value(2, 100) - 2.5).abs() < 1e-12 && (sin.value(10, 100) - 2.5).abs() < 1e-12);
+    assert_eq!(sin.phase(10, 100), 0.25);
+    assert_eq!(schedule::parse_field("sin:1:4").unwrap().start(), 0.);
+
+    for spec in ["", "x", "linear:1", "sin:1:0", "sin:1:4:0:0", "power:0:1:-1", "piecewise:1"] {
+        assert!(schedule::parse_field(spec).is_err(), "{}", spec);
+    }
+}
+
+#[test]
+fn annealing_finds_ground_state_of_random_bonds() {
+    let mut rng = Rng::from_seed([28; 32]);
+    let mut cfg = Configuration::random(4, 3, &mut rng);
+    RandomBonds::Gaussian.apply(&mut cfg, &mut rng);
+    // enumerate all configurations
+    let mut exact = cfg.clone();
+    let ground = (0..1 << exact.size()).map(|bits: usize| {
+        exact.cfg.iter_mut().enumerate().for_each(|(idx, s)| *s = if bits >> idx & 1 == 1 { 1 } else { -1 });
+        hamiltonian(&exact, 1., 0.)
+    }).fold(f64::INFINITY, f64::min);
+
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    let betas = schedule::parse_annealing("geometric:0.1:5:20").unwrap();
+    let (steps, best) = anneal::anneal(&mut cfg, &mut energy, 1., 0., Algorithm::Metropolis, &betas, 50, &mut rng);
+    assert_eq!(steps.len(), 20);
+    assert!(steps.iter().zip(&betas).all(|(step, &beta)| step.beta == beta && step.min_energy <= step.mean_energy));
+    assert!((energy - hamiltonian(&cfg, 1., 0.)).abs() < 1e-9);
+    assert!((hamiltonian(&best, 1., 0.) - ground).abs() < 1e-9);
+    assert!((steps.iter().map(|step| step.min_energy).fold(f64::INFINITY, f64::min) - ground).abs() < 1e-9);
+}
+
+#[test]
+fn wang_landau_matches_enumeration() {
+    let mut rng = Rng::from_seed([29; 32]);
+    let params = WangLandauParams{ln_f_final: 1e-7, flatness: 0.9, ncheck: 100};
+    for j in [1., -0.5] {
+        let mut cfg = Configuration::random(4, 4, &mut rng);
+        let dos = wang_landau::sample(&mut cfg, j, &params, &mut rng);
+        // the periodic 4x4 lattice has no levels with an odd number of antiparallel bonds or with 2 or 30
+        assert_eq!(dos.energies.len(), 15);
+        assert!(dos.energies.windows(2).all(|e| e[0] < e[1]));
+        assert!((dos.ln_g[0] - 2_f64.ln()).abs() < 0.1);
+
+        let mut exact = cfg.clone();
+        let energies: Vec<f64> = (0..1 << 16).map(|bits: usize| {
+            exact.cfg.iter_mut().enumerate().for_each(|(idx, s)| *s = if bits >> idx & 1 == 1 { 1 } else { -1 });
+            hamiltonian(&exact, j, 0.)
+        }).collect();
+        let ground = energies.iter().cloned().fold(f64::INFINITY, f64::min);
+        for temp in [1., 2.5] {
+            let weights: Vec<f64> = energies.iter().map(|e| (-(e - ground) / temp).exp()).collect();
+            let z: f64 = weights.iter().sum();
+            let mean = energies.iter().zip(&weights).map(|(e, w)| e*w).sum::<f64>() / z;
+            let thermo = dos.thermodynamics(temp);
+            assert!((thermo.energy - mean/16.).abs() < 0.02, "{} {} {}", j, thermo.energy, mean/16.);
+        }
+    }
+}
+
+#[test]
+fn multicanonical_distribution_matches_enumeration() {
+    let mut rng = Rng::from_seed([30; 32]);
+    let (temp, j) = (1.8, 1.);
+    let mut cfg = Configuration::random(4, 4, &mut rng);
+    let mut energy = hamiltonian(&cfg, j, 0.);
+    let weights = multicanonical::iterate_weights(&mut cfg, &mut energy, 1./temp, j, 0., &mut rng, 20, 500);
+    let samples = multicanonical::evolve(&mut cfg, &mut energy, 1./temp, j, 0., &weights, &mut rng, 20000);
+    assert!((energy - hamiltonian(&cfg, j, 0.)).abs() < 1e-9);
+    // all levels are visited, even though m = 0 is suppressed by more than e^-6
+    assert!(samples.histogram.iter().all(|&count| count > 0));
+
+    let mut exact = cfg.clone();
+    let mut p = [0.; 17];
+    for bits in 0..1_usize << 16 {
+        exact.cfg.iter_mut().enumerate().for_each(|(idx, s)| *s = if bits >> idx & 1 == 1 { 1 } else { -1 });
+        p[bits.count_ones() as usize] += (-(hamiltonian(&exact, j, 0.) + 32.) / temp).exp();
+    }
+    let ln_p = multicanonical::canonical_distribution(&weights, &samples.histogram);
+    let exact_barrier = multicanonical::barrier(&p.map(f64::ln));
+    assert!(exact_barrier > 6.);
+    assert!((multicanonical::barrier(&ln_p) - exact_barrier).abs() < 0.3);
+    // relative to the peak at m = -1
+    for (level, x) in ln_p.iter().enumerate() {
+        assert!(((x - ln_p[0]) - (p[level] / p[0]).ln()).abs() < 0.3, "{}", level);
+    }
+}
+
+#[test]
+fn observables_are_written_in_all_formats() {
+    let obs = Observables{energy: vec![-2., 0.5], magnetisation: vec![1., -0.25], staggered: vec![0., 0.],
+                          structure: vec![0., 0.], custom: Vec::new()};
+    let dir = std::env::temp_dir().join(format!("ising-formats-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let expected = [(Format::Dat, "-2 0.5 \n1 -0.25 \n"),
+                    (Format::Csv, "energy,magnetisation\n-2,1\n0.5,-0.25\n"),
+                    (Format::Json, "{\"energy\": [-2, 0.5], \"magnetisation\": [1, -0.25]}\n")];
+    for (format, text) in expected {
+        assert_eq!(Format::parse(format.extension()), Ok(format));
+        let fname = dir.join(format!("0.{}", format.extension()));
+        write_observables(&fname, &obs, format).unwrap();
+        assert_eq!(std::fs::read_to_string(&fname).unwrap(), text);
+    }
+    assert!(Format::parse("hdf5").is_err());
+
+    write_observables(&dir.join("0.npy"), &obs, Format::Npy).unwrap();
+    let bytes = std::fs::read(dir.join("0.npy")).unwrap();
+    let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+    let header = std::str::from_utf8(&bytes[10..10+header_len]).unwrap();
+    assert!(header.contains("'descr': '<f8'") && header.contains("'shape': (2, 2)"));
+    let values: Vec<f64> = bytes[10+header_len..].chunks_exact(8)
+        .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap())).collect();
+    assert_eq!(values, vec![-2., 0.5, 1., -0.25]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn non_empty_datadir_is_only_deleted_with_force() {
+    let dir = std::env::temp_dir().join(format!("ising-datadir-{}", std::process::id()));
+    create_datadir(&dir, false).unwrap();
+    // an empty directory is reused
+    create_datadir(&dir, false).unwrap();
+    std::fs::write(dir.join("0.dat"), "old").unwrap();
+    match create_datadir(&dir, false) {
+        Err(Error::NonEmptyDirectory(path)) => assert_eq!(path, dir),
+        other => panic!("expected NonEmptyDirectory, got {:?}", other),
+    }
+    assert!(dir.join("0.dat").exists());
+    create_datadir(&dir, true).unwrap();
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+    let obs = Observables::new();
+    let missing = dir.join("missing").join("0.dat");
+    let err = write_observables(&missing, &obs, Format::Dat).unwrap_err();
+    assert!(matches!(&err, Error::Io{path, ..} if *path == missing));
+    assert!(err.to_string().contains(&missing.display().to_string()));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn running_energy_matches_hamiltonian() {
+    let mut rng = Rng::from_seed([7; 32]);
+    let mut cfg = Configuration::random(6, 5, &mut rng);
+    cfg.local_field = Some((0..cfg.size()).map(|idx| 0.1 * (idx % 7) as f64).collect());
+    let (j, h) = (0.7, 0.3);
+    let mut energy = hamiltonian(&cfg, j, h);
+    evolve(&mut cfg, &mut energy, 1./2.5, j, h, &mut rng, 1000, None);
+    let exact = hamiltonian(&cfg, j, h);
+    let drift = check_energy(&mut energy, exact, cfg.nsite(), 1e-9).unwrap();
+    assert!(drift.abs() < 1e-9, "drift {}", drift);
+    assert_eq!(energy, exact);
+
+    energy += 1.;
+    assert!(check_energy(&mut energy, exact, cfg.nsite(), 1e-9).is_err());
+    assert_eq!(energy, exact + 1.);
+    let mut nan = f64::NAN;
+    assert!(check_energy(&mut nan, exact, cfg.nsite(), 1e-9).is_err());
+}
+
+#[test]
+fn bond_energy_stays_exact_in_zero_field() {
+    let mut rng = Rng::from_seed([8; 32]);
+    let j = 0.3;
+    type Evolver = fn(&mut Configuration, &mut f64, f64, f64, f64, &mut Rng, usize, Option<&mut Observables>) -> usize;
+    let evolvers: [Evolver; 5] = [evolve, evolve_glauber, evolve_wolff, evolve_checkerboard, evolve_kawasaki];
+    for evolver in evolvers {
+        let mut cfg = Configuration::random(8, 6, &mut rng);
+        assert!(has_bond_energy(&cfg, 0.));
+        let mut energy = hamiltonian(&cfg, j, 0.);
+        evolver(&mut cfg, &mut energy, 1./2.3, j, 0., &mut rng, 2000, None);
+        assert_eq!(energy, hamiltonian(&cfg, j, 0.));
+    }
+
+    let mut cfg = Configuration::random(5, 4, &mut rng);
+    let mut bond_energy = BondEnergy::new(&cfg, j);
+    assert_eq!(bond_energy, BondEnergy::from_energy(hamiltonian(&cfg, j, 0.), j));
+    let k = cfg[7] * neighbour_sum(&cfg, 7);
+    cfg[7] *= -1;
+    bond_energy.flip(k);
+    assert_eq!(bond_energy.bonds(), bond_sum(&cfg));
+    assert_eq!(bond_energy.energy(), hamiltonian(&cfg, j, 0.));
+
+    let mut energy = bond_energy.energy() + delta_e(&cfg, 3, j, 0.) + 1e-12;
+    let before = bond_energy.energy();
+    cfg[3] *= -1;
+    bond_energy.track(before, &mut energy);
+    assert_eq!(energy, hamiltonian(&cfg, j, 0.));
+    assert!(!has_bond_energy(&cfg, 0.5));
+}
+
+#[test]
+fn stats_file_keeps_completed_temperatures() {
+    let fname = std::env::temp_dir().join(format!("ising-stats-{}.csv", std::process::id()));
+    let mut file = stats::open(&fname, 0).unwrap();
+    for index in 0..3 {
+        let stats = TemperatureStats{index, temperature: 1. + index as f64, ntherm: 100, therm_acceptance: 0.5,
+                                     nprod: 1000, acceptance: 0.25, flips_per_sweep: 4., wall_time: 3.,
+                                     production_time: 2.};
+        assert_eq!(stats.sweeps_per_second(), 500.);
+        stats.write(&mut file).unwrap();
+    }
+    drop(file);
+    let text = std::fs::read_to_string(&fname).unwrap();
+    assert_eq!(text.lines().count(), 4);
+    assert_eq!(text.lines().next().unwrap(), stats::HEADER);
+    assert_eq!(text.lines().nth(2).unwrap(), "1,2,100,0.5,1000,0.25,4,3,2,500");
+    assert!(text.lines().all(|line| line.split(',').count() == stats::HEADER.split(',').count()));
+
+    // resuming at temperature 2 drops the line of temperature 2
+    drop(stats::open(&fname, 2).unwrap());
+    let resumed = std::fs::read_to_string(&fname).unwrap();
+    assert_eq!(resumed.lines().collect::<Vec<_>>(), text.lines().take(3).collect::<Vec<_>>());
+    std::fs::remove_file(&fname).unwrap();
+}
+
+#[test]
+fn streamed_observables_match_written_ones() {
+    let mut rng = Rng::from_seed([6; 32]);
+    let mut cfg = Configuration::random(4, 4, &mut rng);
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    let mut obs = Observables::new();
+    evolve(&mut cfg, &mut energy, 0.4, 1., 0., &mut rng, 25, Some(&mut obs));
+
+    let dir = std::env::temp_dir().join(format!("ising-stream-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for format in [Format::Csv, Format::Npy] {
+        let written = dir.join(format!("written.{}", format.extension()));
+        let streamed = dir.join(format!("streamed.{}", format.extension()));
+        write_observables(&written, &obs, format).unwrap();
+        let mut writer = StreamWriter::create(&streamed, format).unwrap();
+        let mut collected = Observables::new();
+        for chunk in (0..25).collect::<Vec<usize>>().chunks(7) {
+            let mut part = Observables{energy: chunk.iter().map(|&i| obs.energy[i]).collect(),
+                                       magnetisation: chunk.iter().map(|&i| obs.magnetisation[i]).collect(),
+                                       staggered: chunk.iter().map(|&i| obs.staggered[i]).collect(),
+                                       structure: chunk.iter().map(|&i| obs.structure[i]).collect(),
+                                       custom: Vec::new()};
+            collected.append(&part).unwrap();
+            writer.take(&mut part).unwrap();
+            assert!(part.energy.is_empty());
+        }
+        writer.finish().unwrap();
+        assert_eq!(collected, obs);
+
+        let (written, streamed) = (std::fs::read(&written).unwrap(), std::fs::read(&streamed).unwrap());
+        if format == Format::Csv {
+            assert_eq!(streamed, written);
+        } else {
+            // same header length and shape, but the streamed array is stored in Fortran order
+            let header = |bytes: &[u8]| std::str::from_utf8(&bytes[10..10+bytes[8] as usize]).unwrap().to_string();
+            assert!(header(&streamed).contains("'fortran_order': True, 'shape': (2, 25)"));
+            assert_eq!(header(&written).replace("False", "True").trim_end(), header(&streamed).trim_end());
+            let values = |bytes: &[u8]| bytes[10+bytes[8] as usize..].chunks_exact(8)
+                .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap())).collect::<Vec<f64>>();
+            let interleaved: Vec<f64> = obs.energy.iter().zip(obs.magnetisation.iter())
+                .flat_map(|(&e, &m)| [e, m]).collect();
+            assert_eq!(values(&streamed), interleaved);
+            assert_eq!(values(&written), [obs.energy.clone(), obs.magnetisation.clone()].concat());
+        }
+    }
+    assert!(!StreamWriter::supports(Format::Dat) && !StreamWriter::supports(Format::Json));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn correlation_of_ordered_configurations() {
+    let mut correlation = Correlation::new(6, 4);
+    correlation.measure(&Configuration::cold(6, 4, -1));
+    assert_eq!(correlation.x(), vec![1.; 4]);
+    assert_eq!(correlation.y(), vec![1.; 3]);
+
+    // the average of a cold and a checkerboard configuration
+    correlation.measure(&checkerboard(6, 4));
+    assert_eq!(correlation.x(), vec![1., 0., 1., 0.]);
+    assert_eq!(correlation.y(), vec![1., 0., 1.]);
+
+    let mut chain = Correlation::new(5, 1);
+    let mut cfg = Configuration::cold(5, 1, 1);
+    cfg[0] = -1;
+    chain.measure(&cfg);
+    // one flipped spin breaks 2 of the 5 pairs at every separation
+    assert_eq!(chain.x(), vec![1., 0.2, 0.2]);
+    assert!(chain.y().is_empty());
+}
+
+#[test]
+fn durations_are_formatted_as_hours_minutes_seconds() {
+    assert_eq!(progress::format_duration(0.4), "0:00:00");
+    assert_eq!(progress::format_duration(59.6), "0:01:00");
+    assert_eq!(progress::format_duration(3.*3600. + 25.*60. + 7.), "3:25:07");
+    assert_eq!(progress::format_duration(f64::INFINITY), "?");
+}
+
+#[test]
+fn binned_estimates_of_short_series() {
+    // bin means 1, 2, 3, 4
+    let (mean, err) = binned_mean(&[0., 2., 1., 3., 2., 4., 3., 5., 100.], 4);
+    assert!((mean - 2.5).abs() < 1e-12);
+    assert!((err - (5./12_f64).sqrt()).abs() < 1e-12, "{}", err);
+    let (c, c_err) = binned_specific_heat(&[-4.; 6], 0.5, 4, 3);
+    assert!(c.abs() < 1e-12 && c_err.abs() < 1e-12);
+}
+
+#[test]
+#[should_panic(expected = "Binning needs")]
+fn binning_needs_a_value_per_bin() {
+    binned_mean(&[1., 2., 3.], 4);
+}
+
+#[test]
+#[should_panic(expected = "Binning needs")]
+fn binning_needs_two_bins() {
+    binned_specific_heat(&[1., 2., 3.], 1., 1, 1);
+}
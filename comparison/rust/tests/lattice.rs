@@ -0,0 +1,489 @@
+use std::path::PathBuf;
+
+use ising::{Configuration, Observables, Rng, delta_e, evolve, exact, hamiltonian, make_neighbour_list,
+            stripe_magnetisation};
+use ising::lattice::{Anisotropic, Boundaries, Boundary, Geometry, Honeycomb, Lattice, NextNearest, Square,
+                     Triangular};
+use ising::disorder::{CorrelatedDisorder, RandomBonds};
+use ising::profile::{self, Axis, Profile};
+use ising::surface::{self, Edge, SurfaceField};
+use ising::start::{Start, read_configuration, write_configuration};
+
+#[test]
+fn neighbours_chain() {
+    let neighbours = make_neighbour_list(4, 1);
+    // x+1, x-1 for each site
+    assert_eq!(neighbours, vec![1, 3, 2, 0, 3, 1, 0, 2]);
+}
+
+#[test]
+fn neighbours_2d() {
+    // 0 1 2
+    // 3 4 5
+    let neighbours = make_neighbour_list(3, 2);
+    // x+1, x-1, y+1, y-1 for each site
+    assert_eq!(&neighbours[0..4], &[1, 2, 3, 3]);
+    assert_eq!(&neighbours[16..20], &[5, 3, 1, 1]);
+    assert_eq!(&neighbours[20..24], &[3, 4, 2, 2]);
+}
+
+#[test]
+fn neighbours_are_symmetric() {
+    for (nx, ny) in [(5, 1), (4, 4), (7, 3)] {
+        let cfg = Configuration::cold(nx, ny, 1);
+        assert_eq!(cfg.coordination(), if ny == 1 { 2 } else { 4 });
+        for idx in 0..cfg.size() {
+            for &neigh in cfg.neighbours_of(idx) {
+                assert!(neigh < cfg.size());
+                assert!(cfg.neighbours_of(neigh).contains(&idx));
+            }
+        }
+    }
+}
+
+#[test]
+fn lattices_have_symmetric_neighbours() {
+    let lattices: Vec<Box<dyn Lattice>> = vec![Box::new(Square{nx: 5, ny: 3}), Box::new(Square{nx: 6, ny: 1}),
+                                               Box::new(Honeycomb{nx: 4, ny: 6}), Box::new(Triangular{nx: 3, ny: 4}),
+                                               Box::new(Triangular{nx: 6, ny: 6})];
+    for lattice in lattices {
+        let cfg = Configuration::on_lattice(lattice.as_ref(), 1);
+        assert_eq!((cfg.nx, cfg.ny), lattice.extent());
+        assert_eq!(cfg.coordination(), lattice.coordination());
+        for idx in 0..cfg.size() {
+            assert_eq!(cfg.neighbours_of(idx), lattice.neighbours(idx).as_slice());
+            for &neigh in cfg.neighbours_of(idx) {
+                assert_ne!(neigh, idx);
+                assert_eq!(cfg.neighbours_of(neigh).iter().filter(|&&n| n == idx).count(),
+                           cfg.neighbours_of(idx).iter().filter(|&&n| n == neigh).count());
+            }
+        }
+    }
+    assert_eq!(Square{nx: 7, ny: 3}.neighbour_list(), make_neighbour_list(7, 3));
+}
+
+#[test]
+fn honeycomb_ground_state() {
+    // 0 1 2 3  row 0 links 0-4 and 2-6 upwards
+    // 4 5 6 7  row 1 links 5-1 and 7-3 downwards
+    let lattice = Honeycomb{nx: 4, ny: 2};
+    assert_eq!(lattice.neighbours(0), vec![1, 3, 4]);
+    assert_eq!(lattice.neighbours(5), vec![6, 4, 1]);
+
+    let cfg = Configuration::on_lattice(&Honeycomb{nx: 6, ny: 4}, 1);
+    // 3 bonds per pair of sites
+    assert_eq!(hamiltonian(&cfg, 1., 0.), -36.);
+}
+
+#[test]
+fn triangular_neighbours() {
+    // 0 1 2 3
+    // 4 5 6 7
+    // 8 9 10 11
+    let lattice = Triangular{nx: 4, ny: 3};
+    assert_eq!(lattice.neighbours(5), vec![6, 4, 9, 1, 10, 0]);
+    assert_eq!(lattice.neighbours(0), vec![1, 3, 4, 8, 5, 11]);
+    assert_eq!(Configuration::on_lattice(&lattice, 1).coordination(), 6);
+}
+
+#[test]
+fn triangular_antiferromagnet_is_frustrated() {
+    let lattice = Triangular{nx: 6, ny: 6};
+    let ferro = Configuration::on_lattice(&lattice, 1);
+    // 3 bonds per site
+    assert_eq!(hamiltonian(&ferro, 1., 0.), -108.);
+
+    // sublattices (x+y) mod 3 = 0, 1, 2 take +1, -1, +1, every triangle has one broken bond
+    let mut cfg = Configuration::on_lattice(&lattice, 1);
+    for idx in 0..cfg.size() {
+        if (idx % 6 + idx / 6) % 3 == 1 {
+            cfg[idx] = -1;
+        }
+    }
+    assert_eq!(hamiltonian(&cfg, -1., 0.), -36.);
+    // the best the antiferromagnet can do: E/N = -|J|
+    assert!(hamiltonian(&ferro, -1., 0.) > hamiltonian(&cfg, -1., 0.));
+}
+
+#[test]
+fn geometry_is_parsed_and_checked() {
+    assert_eq!(Geometry::parse("square"), Ok(Geometry::Square));
+    assert_eq!(Geometry::parse("triangular"), Ok(Geometry::Triangular));
+    assert_eq!(Geometry::parse("honeycomb"), Ok(Geometry::Honeycomb));
+    assert!(Geometry::parse("cubic").is_err());
+
+    assert_eq!(Geometry::Triangular.create(4, 3).unwrap().coordination(), 6);
+    assert!(Geometry::Triangular.create(4, 1).is_err());
+    assert!(Geometry::Honeycomb.create(4, 3).is_err());
+    assert_eq!(Geometry::Square.create(5, 1).unwrap().coordination(), 2);
+
+    let cfg = Configuration::cold(4, 3, 1).with_lattice(&Triangular{nx: 4, ny: 3});
+    assert_eq!(cfg.neighbours_of(5), Triangular{nx: 4, ny: 3}.neighbours(5).as_slice());
+}
+
+#[test]
+fn boundaries_are_parsed() {
+    assert_eq!(Boundaries::parse("periodic"), Ok(Boundaries::PERIODIC));
+    assert_eq!(Boundaries::parse("open"), Ok(Boundaries{x: Boundary::Open, y: Boundary::Open}));
+    assert_eq!(Boundaries::parse("periodic:open"), Ok(Boundaries{x: Boundary::Periodic, y: Boundary::Open}));
+    assert_eq!(Boundaries::parse("antiperiodic:periodic"),
+               Ok(Boundaries{x: Boundary::Antiperiodic, y: Boundary::Periodic}));
+    for spec in ["", "closed", "open:", "open:open:open"] {
+        assert!(Boundaries::parse(spec).is_err(), "accepted '{}'", spec);
+    }
+}
+
+#[test]
+fn open_boundaries_drop_links() {
+    let open = Boundaries{x: Boundary::Open, y: Boundary::Open};
+    let chain = Configuration::cold(5, 1, 1).with_boundaries(&Square{nx: 5, ny: 1}, open);
+    assert_eq!(chain.neighbours_of(0), &[1]);
+    assert_eq!(chain.neighbours_of(2), &[3, 1]);
+    assert_eq!(chain.neighbours_of(4), &[3]);
+    assert_eq!(chain.coordination(), 2);
+    assert!(!chain.has_couplings());
+    assert_eq!(hamiltonian(&chain, 1., 0.), -4.);
+
+    // periodic in x, open in y: 4*3 horizontal and 4*2 vertical bonds
+    let mixed = Boundaries{x: Boundary::Periodic, y: Boundary::Open};
+    let cfg = Configuration::cold(4, 3, 1).with_boundaries(&Square{nx: 4, ny: 3}, mixed);
+    assert_eq!(hamiltonian(&cfg, 1., 0.), -20.);
+    assert_eq!(cfg.neighbours_of(1), &[2, 0, 5]);
+    assert_eq!(cfg.neighbours_of(9), &[10, 8, 5]);
+}
+
+#[test]
+fn antiperiodic_boundaries_flip_couplings() {
+    let anti = Boundaries{x: Boundary::Antiperiodic, y: Boundary::Periodic};
+    let cfg = Configuration::cold(5, 1, 1).with_boundaries(&Square{nx: 5, ny: 1}, anti);
+    assert_eq!(cfg.couplings_of(0), Some(&[1., -1.][..]));
+    assert_eq!(cfg.couplings_of(2), Some(&[1., 1.][..]));
+    assert_eq!(cfg.couplings_of(4), Some(&[-1., 1.][..]));
+    // the bond across the boundary is broken in the ferromagnetic state
+    assert_eq!(hamiltonian(&cfg, 1., 0.), -3.);
+
+    // a single domain wall is the ground state
+    let mut wall = cfg.clone();
+    wall[0] = -1;
+    wall[1] = -1;
+    assert_eq!(hamiltonian(&wall, 1., 0.), -3.);
+    for idx in 0..cfg.size() {
+        let mut flipped = wall.clone();
+        flipped[idx] *= -1;
+        assert!((hamiltonian(&flipped, 1., 0.) - hamiltonian(&wall, 1., 0.)
+                 - delta_e(&wall, idx, 1., 0.)).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn open_chain_matches_exact_energy() {
+    // Z = 2 (2 cosh βJ)^(N-1), so E = -(N-1) J tanh(βJ)
+    let (nx, temp) = (6, 1.5);
+    let open = Boundaries{x: Boundary::Open, y: Boundary::Open};
+    let mut rng = Rng::from_seed([21; 32]);
+    let mut cfg = Configuration::random(nx, 1, &mut rng).with_boundaries(&Square{nx, ny: 1}, open);
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    evolve(&mut cfg, &mut energy, 1./temp, 1., 0., &mut rng, 1000, None);
+    let mut obs = Observables::new();
+    evolve(&mut cfg, &mut energy, 1./temp, 1., 0., &mut rng, 50000, Some(&mut obs));
+    assert_eq!(energy, hamiltonian(&cfg, 1., 0.));
+
+    let mean = obs.energy.iter().sum::<f64>() / obs.energy.len() as f64;
+    let exact = -((nx-1) as f64) * (1./temp).tanh();
+    assert!((mean - exact).abs() < 0.05, "{} vs {}", mean, exact);
+}
+
+#[test]
+fn anisotropic_couplings_scale_links() {
+    let (nx, ny) = (5, 4);
+    let lattice = Anisotropic{lattice: Box::new(Square{nx, ny}), x: 1., y: 0.25};
+    let cold = Configuration::on_lattice(&lattice, 1);
+    assert!((hamiltonian(&cold, 2., 0.) - -2.*(1. + 0.25)*(nx*ny) as f64).abs() < 1e-12);
+    assert_eq!(cold.couplings_of(0), Some(&[1., 1., 0.25, 0.25][..]));
+
+    let mut rng = Rng::from_seed([23; 32]);
+    let mut cfg = Configuration::random(nx, ny, &mut rng).with_lattice(&lattice);
+    for idx in 0..cfg.size() {
+        let before = hamiltonian(&cfg, 1., 0.3);
+        let delta = delta_e(&cfg, idx, 1., 0.3);
+        cfg[idx] *= -1;
+        assert!((hamiltonian(&cfg, 1., 0.3) - before - delta).abs() < 1e-12);
+    }
+
+    // anisotropy and boundary factors multiply
+    let boundaries = Boundaries{x: Boundary::Periodic, y: Boundary::Antiperiodic};
+    let cfg = Configuration::cold(nx, ny, 1).with_boundaries(&lattice, boundaries);
+    assert_eq!(cfg.couplings_of((ny-1)*nx), Some(&[1., 1., -0.25, 0.25][..]));
+}
+
+#[test]
+fn decoupled_rows_match_exact_chain() {
+    let (nx, ny, temp) = (6, 3, 1.5);
+    let lattice = Anisotropic{lattice: Box::new(Square{nx, ny}), x: 1., y: 0.};
+    let mut rng = Rng::from_seed([25; 32]);
+    let mut cfg = Configuration::random(nx, ny, &mut rng).with_lattice(&lattice);
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    evolve(&mut cfg, &mut energy, 1./temp, 1., 0., &mut rng, 1000, None);
+    let mut obs = Observables::new();
+    evolve(&mut cfg, &mut energy, 1./temp, 1., 0., &mut rng, 20000, Some(&mut obs));
+    assert!((energy - hamiltonian(&cfg, 1., 0.)).abs() < 1e-9);
+
+    let mean = obs.energy.iter().sum::<f64>() / (obs.energy.len()*nx*ny) as f64;
+    let exact = exact::chain_energy(nx, 1., temp);
+    assert!((mean - exact).abs() < 0.02, "{} vs {}", mean, exact);
+}
+
+#[test]
+fn metropolis_matches_enumeration_with_anisotropy_field_and_dilution() {
+    let (nx, ny, temp) = (4, 4, 2.);
+    let lattice = Anisotropic{lattice: Box::new(Square{nx, ny}), x: 1., y: 0.5};
+    let mut rng = Rng::from_seed([26; 32]);
+    let mut cfg = Configuration::random(nx, ny, &mut rng).with_lattice(&lattice);
+    cfg.add_local_field(5, -0.8);
+    cfg.dilute(0.125, &mut rng);
+    let averages = exact::Enumeration::new(&cfg, 1., 0.3).averages(&[temp])[0];
+
+    let mut energy = hamiltonian(&cfg, 1., 0.3);
+    evolve(&mut cfg, &mut energy, 1./temp, 1., 0.3, &mut rng, 1000, None);
+    let mut obs = Observables::new();
+    evolve(&mut cfg, &mut energy, 1./temp, 1., 0.3, &mut rng, 40000, Some(&mut obs));
+    let mean = obs.energy.iter().sum::<f64>() / (obs.energy.len()*cfg.nsite()) as f64;
+    let abs = obs.magnetisation.iter().map(|m| m.abs()).sum::<f64>() / obs.magnetisation.len() as f64;
+    assert!((mean - averages.energy).abs() < 0.01, "{} vs {}", mean, averages.energy);
+    assert!((abs - averages.abs_magnetisation).abs() < 0.01, "{} vs {}", abs, averages.abs_magnetisation);
+}
+
+#[test]
+fn next_nearest_neighbours_include_diagonals() {
+    let lattice = NextNearest{lattice: Box::new(Square{nx: 4, ny: 3}), j2: -0.5};
+    assert_eq!(lattice.coordination(), 8);
+    // site (1, 1): x+1, x-1, y+1, y-1, then the diagonals
+    assert_eq!(lattice.neighbours(5), vec![6, 4, 9, 1, 10, 0, 2, 8]);
+
+    let cfg = Configuration::on_lattice(&lattice, 1);
+    assert_eq!(cfg.couplings_of(5), Some(&[1., 1., 1., 1., -0.5, -0.5, -0.5, -0.5][..]));
+    for idx in 0..cfg.size() {
+        for &neigh in cfg.neighbours_of(idx) {
+            assert!(cfg.neighbours_of(neigh).contains(&idx));
+        }
+    }
+}
+
+#[test]
+fn j1_j2_ground_states() {
+    let (nx, ny) = (6, 4);
+    let n = (nx*ny) as f64;
+    let lattice = NextNearest{lattice: Box::new(Square{nx, ny}), j2: -1.};
+    let ferro = Configuration::on_lattice(&lattice, 1);
+    let mut stripes = ferro.clone();
+    for idx in 0..stripes.size() {
+        stripes[idx] = if (idx % nx).is_multiple_of(2) { 1 } else { -1 };
+    }
+
+    // 2N nearest and 2N diagonal bonds, the stripes satisfy all diagonal bonds and half of the others
+    assert!(hamiltonian(&ferro, 1., 0.).abs() < 1e-12);
+    assert!((hamiltonian(&stripes, 1., 0.) - -2.*n).abs() < 1e-12);
+    assert!((stripe_magnetisation(&stripes) - 1.).abs() < 1e-12);
+    assert!(stripe_magnetisation(&ferro).abs() < 1e-12);
+
+    let mut rng = Rng::from_seed([27; 32]);
+    let mut cfg = Configuration::random(nx, ny, &mut rng).with_lattice(&lattice);
+    for idx in 0..cfg.size() {
+        let before = hamiltonian(&cfg, 0.7, 0.2);
+        let delta = delta_e(&cfg, idx, 0.7, 0.2);
+        cfg[idx] *= -1;
+        assert!((hamiltonian(&cfg, 0.7, 0.2) - before - delta).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn field_profiles_are_parsed() {
+    assert_eq!(Profile::parse("gradient:x:-1:1"), Ok(Profile::Gradient{axis: Axis::X, start: -1., stop: 1.}));
+    assert_eq!(Profile::parse("step:y:2:0.5:-0.5"),
+               Ok(Profile::Step{axis: Axis::Y, position: 2., below: 0.5, above: -0.5}));
+    assert_eq!(Profile::parse("gauss:1:2:3:1.5"), Ok(Profile::Gaussian{h: 1., x0: 2., y0: 3., width: 1.5}));
+    for spec in ["gradient:z:0:1", "gradient:x:0", "step:x:1:a:2", "gauss:1:2:3:0", "ramp:x:0:1", ""] {
+        assert!(Profile::parse(spec).is_err(), "accepted '{}'", spec);
+    }
+}
+
+#[test]
+fn field_profiles_take_expected_values() {
+    let gradient = Profile::parse("gradient:x:-1:1").unwrap();
+    let values: Vec<f64> = (0..5).map(|x| gradient.value(x, 0, 5, 3)).collect();
+    assert_eq!(values, vec![-1., -0.5, 0., 0.5, 1.]);
+
+    let step = Profile::parse("step:y:1.5:0.5:-0.5").unwrap();
+    let values: Vec<f64> = (0..4).map(|y| step.value(3, y, 5, 4)).collect();
+    assert_eq!(values, vec![0.5, 0.5, -0.5, -0.5]);
+
+    // distances to the centre wrap around the periodic boundaries
+    let spot = Profile::parse("gauss:2:0:0:1").unwrap();
+    assert_eq!(spot.value(0, 0, 6, 6), 2.);
+    assert!((spot.value(5, 0, 6, 6) - spot.value(1, 0, 6, 6)).abs() < 1e-12);
+    assert!((spot.value(1, 1, 6, 6) - 2.*(-1_f64).exp()).abs() < 1e-12);
+
+    // profiles add up in the local field
+    let mut cfg = Configuration::cold(5, 1, 1);
+    profile::apply(&mut cfg, &[gradient, Profile::parse("step:x:3:1:0").unwrap()]);
+    assert_eq!(cfg.local_field.unwrap(), vec![0., 0.5, 1., 0.5, 1.]);
+}
+
+#[test]
+fn correlated_disorder_is_parsed() {
+    assert_eq!(CorrelatedDisorder::parse("0.5:1.2"), Ok(CorrelatedDisorder{strength: 0.5, exponent: 1.2}));
+    for spec in ["0.5", "0.5:x", "1:2:3", ""] {
+        assert!(CorrelatedDisorder::parse(spec).is_err(), "accepted '{}'", spec);
+    }
+}
+
+#[test]
+fn correlated_disorder_has_given_strength_and_correlations() {
+    let (nx, ny) = (16, 16);
+    // mean product of horizontal neighbours in units of the variance
+    let neighbour_correlation = |field: &[f64], strength: f64| {
+        (0..nx*ny).map(|idx| field[idx] * field[idx/nx*nx + (idx+1) % nx]).sum::<f64>()
+            / (nx*ny) as f64 / strength.powi(2)
+    };
+
+    let mut rng = Rng::from_seed([31; 32]);
+    let (mut correlated, mut white) = (0., 0.);
+    for _ in 0..10 {
+        for (exponent, sum) in [(0.5, &mut correlated), (2., &mut white)] {
+            let field = CorrelatedDisorder{strength: 0.7, exponent}.generate(nx, ny, &mut rng);
+            let mean = field.iter().sum::<f64>() / (nx*ny) as f64;
+            let var = field.iter().map(|f| f*f).sum::<f64>() / (nx*ny) as f64;
+            assert!(mean.abs() < 1e-12);
+            assert!((var - 0.49).abs() < 1e-12);
+            *sum += neighbour_correlation(&field, 0.7) / 10.;
+        }
+    }
+    assert!(correlated > 0.3 && white.abs() < 0.1, "correlated {}, white {}", correlated, white);
+
+    // the same seed gives the same field
+    let disorder = CorrelatedDisorder{strength: 1., exponent: 1.};
+    assert_eq!(disorder.generate(8, 1, &mut Rng::from_seed([3; 32])),
+               disorder.generate(8, 1, &mut Rng::from_seed([3; 32])));
+}
+
+#[test]
+fn random_bonds_are_symmetric() {
+    assert_eq!(RandomBonds::parse("binary"), Ok(RandomBonds::Binary));
+    assert_eq!(RandomBonds::parse("gaussian"), Ok(RandomBonds::Gaussian));
+    assert!(RandomBonds::parse("uniform").is_err());
+
+    let mut rng = Rng::from_seed([29; 32]);
+    for (bonds, (nx, ny)) in [(RandomBonds::Binary, (6, 5)), (RandomBonds::Gaussian, (32, 32)),
+                              (RandomBonds::Binary, (2, 3))] {
+        let mut cfg = Configuration::random(nx, ny, &mut rng);
+        bonds.apply(&mut cfg, &mut rng);
+        let mut factors = Vec::new();
+        for idx in 0..cfg.size() {
+            for (&neigh, &factor) in cfg.neighbours_of(idx).iter().zip(cfg.couplings_of(idx).unwrap()) {
+                // some link back from neigh has the same factor
+                assert!(cfg.neighbours_of(neigh).iter().zip(cfg.couplings_of(neigh).unwrap())
+                        .any(|(&back, &f)| back == idx && f == factor));
+                factors.push(factor);
+            }
+            let before = hamiltonian(&cfg, 1., 0.);
+            let delta = delta_e(&cfg, idx, 1., 0.);
+            cfg[idx] *= -1;
+            assert!((hamiltonian(&cfg, 1., 0.) - before - delta).abs() < 1e-9);
+        }
+        match bonds {
+            RandomBonds::Binary => {
+                assert!(factors.iter().all(|&f| f == 1. || f == -1.));
+                assert!(factors.contains(&1.) && factors.contains(&-1.));
+            }
+            RandomBonds::Gaussian => {
+                let n = factors.len() as f64;
+                let mean = factors.iter().sum::<f64>() / n;
+                let var = factors.iter().map(|f| f*f).sum::<f64>() / n - mean*mean;
+                assert!(mean.abs() < 0.1 && (var - 1.).abs() < 0.1, "{} {}", mean, var);
+            }
+        }
+    }
+
+    // the random factors multiply those of the boundaries
+    let open = Boundaries{x: Boundary::Open, y: Boundary::Antiperiodic};
+    let mut cfg = Configuration::cold(4, 3, 1).with_boundaries(&Square{nx: 4, ny: 3}, open);
+    RandomBonds::Binary.apply(&mut cfg, &mut rng);
+    assert_eq!(cfg.neighbours_of(0), &[1, 4, 8]);
+    assert!(cfg.couplings_of(0).unwrap().iter().all(|f| f.abs() == 1.));
+}
+
+#[test]
+fn local_field_is_created_on_demand() {
+    let mut cfg = Configuration::cold(3, 3, -1);
+    assert!(cfg.local_field.is_none());
+    cfg.add_local_field(4, 0.5);
+    cfg.add_local_field(4, 0.25);
+    let field = cfg.local_field.as_ref().unwrap();
+    assert_eq!(field.len(), 9);
+    assert_eq!(field[4], 0.75);
+    assert_eq!(field.iter().sum::<f64>(), 0.75);
+}
+
+#[test]
+fn surface_fields_are_parsed() {
+    assert_eq!(SurfaceField::parse_list("bottom=0.5, top = -0.5").unwrap(),
+               vec![SurfaceField{edge: Edge::Bottom, h: 0.5}, SurfaceField{edge: Edge::Top, h: -0.5}]);
+    assert_eq!(SurfaceField::parse_list("left=1,right=2").unwrap().iter().map(|f| f.edge).collect::<Vec<_>>(),
+               vec![Edge::Left, Edge::Right]);
+    for spec in ["bottom", "middle=1", "top=x", ""] {
+        assert!(SurfaceField::parse_list(spec).is_err(), "{}", spec);
+    }
+}
+
+#[test]
+fn surface_fields_act_on_edges() {
+    // 8  9 10 11
+    // 4  5  6  7
+    // 0  1  2  3
+    let mut cfg = Configuration::cold(4, 3, 1);
+    let energy = hamiltonian(&cfg, 1., 0.);
+    let fields = SurfaceField::parse_list("bottom=0.5,left=-0.25,top=1").unwrap();
+    assert_eq!(fields[0].sites(&cfg), vec![0, 1, 2, 3]);
+    assert_eq!(fields[1].sites(&cfg), vec![0, 4, 8]);
+    assert_eq!(fields[2].sites(&cfg), vec![8, 9, 10, 11]);
+    assert_eq!(SurfaceField{edge: Edge::Right, h: 1.}.sites(&cfg), vec![3, 7, 11]);
+
+    surface::apply(&mut cfg, &fields);
+    let field = cfg.local_field.as_ref().unwrap();
+    // fields add up in the corners
+    assert_eq!(field, &vec![0.25, 0.5, 0.5, 0.5, -0.25, 0., 0., 0., 0.75, 1., 1., 1.]);
+    // aligned spins gain -h per site
+    assert!((hamiltonian(&cfg, 1., 0.) - (energy - 0.5*4. + 0.25*3. - 1.*4.)).abs() < 1e-12);
+}
+
+#[test]
+fn start_is_parsed() {
+    assert_eq!(Start::parse("hot"), Ok(Start::Hot));
+    assert_eq!(Start::parse("cold-up"), Ok(Start::Cold(1)));
+    assert_eq!(Start::parse("cold-down"), Ok(Start::Cold(-1)));
+    assert_eq!(Start::parse("file:data/final.dat"), Ok(Start::File(PathBuf::from("data/final.dat"))));
+    for spec in ["", "cold", "file:", "warm"] {
+        assert!(Start::parse(spec).is_err(), "accepted '{}'", spec);
+    }
+
+    let mut rng = Rng::from_seed([3; 32]);
+    assert!(Start::Cold(-1).create(5, 2, &mut rng).unwrap().cfg.iter().all(|&s| s == -1));
+}
+
+#[test]
+fn configuration_file_round_trip() {
+    let dir = std::env::temp_dir().join(format!("ising-start-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let fname = dir.join("final.dat");
+    let mut rng = Rng::from_seed([12; 32]);
+    let cfg = Configuration::random(5, 3, &mut rng);
+    write_configuration(&fname, &cfg).unwrap();
+    assert_eq!(read_configuration(&fname, 5, 3).unwrap().cfg, cfg.cfg);
+    assert_eq!(Start::File(fname.clone()).create(5, 3, &mut rng).unwrap().cfg, cfg.cfg);
+    assert!(read_configuration(&fname, 3, 5).is_err());
+    assert!(read_configuration(&fname, 5, 2).is_err());
+
+    std::fs::write(&fname, "1 -1\n0 1\n").unwrap();
+    assert!(read_configuration(&fname, 2, 2).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
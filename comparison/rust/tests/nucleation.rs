@@ -0,0 +1,49 @@
+use ising::Rng;
+use ising::nucleation;
+
+#[test]
+fn strong_field_reverses_magnetisation() {
+    let mut rng = Rng::from_seed([3; 32]);
+    for h in [3., -3.] {
+        let decay = nucleation::decay(8, 8, 1./2., 1., h, &mut rng, 100).unwrap();
+        assert!(decay.lifetime >= 1 && decay.lifetime < 100, "h = {}: {}", h, decay.lifetime);
+        assert!(decay.droplet < 64);
+    }
+}
+
+#[test]
+fn weak_field_keeps_metastable_state() {
+    let mut rng = Rng::from_seed([5; 32]);
+    // flipping a single spin costs 2*(4J - h), which is never accepted at this temperature
+    assert!(nucleation::decay(8, 8, 1./0.2, 1., 0.1, &mut rng, 50).is_none());
+}
+
+#[test]
+fn lifetime_decreases_with_field() {
+    let mean_lifetime = |h: f64, rng: &mut Rng| {
+        (0..20).map(|_| nucleation::decay(8, 8, 1./1.5, 1., h, rng, 10000).unwrap().lifetime as f64)
+            .sum::<f64>() / 20.
+    };
+    let mut rng = Rng::from_seed([7; 32]);
+    let weak = mean_lifetime(0.8, &mut rng);
+    let strong = mean_lifetime(2., &mut rng);
+    assert!(strong < weak, "lifetime {} at strong field, {} at weak field", strong, weak);
+}
+
+#[test]
+fn run_writes_lifetimes_for_all_temperatures_and_fields() {
+    let dir = std::env::temp_dir().join(format!("ising-nucleation-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut rng = Rng::from_seed([9; 32]);
+    nucleation::run(&dir, 6, 6, &[0.2, 2.], 1., &[0.1, 3.], 3, 20, &mut rng).unwrap();
+
+    assert_eq!(std::fs::read_to_string(dir.join("nucleation.dat")).unwrap(),
+               "0_0: 0.2 0.1\n0_1: 0.2 3\n1_0: 2 0.1\n1_1: 2 3\n");
+    // no decay at low temperature in a weak field
+    assert_eq!(std::fs::read_to_string(dir.join("0_0.dat")).unwrap(), "-1 -1 -1 \n-1 -1 -1 \n");
+    let text = std::fs::read_to_string(dir.join("1_1.dat")).unwrap();
+    let lifetimes: Vec<i64> = text.lines().next().unwrap().split_whitespace().map(|x| x.parse().unwrap()).collect();
+    assert_eq!(lifetimes.len(), 3);
+    assert!(lifetimes.iter().all(|&t| t > 0));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use ising::chains;
+use ising::lattice::{Boundaries, Square};
+use ising::observable::{Observable, Registry};
+use ising::{Configuration, Observables, Rng, evolve, hamiltonian, magnetisation, unsatisfied_bonds};
+
+/// Number of up spins, a custom observable to register through the library.
+struct UpSpins;
+
+impl Observable for UpSpins {
+    fn name(&self) -> &str {
+        "up_spins"
+    }
+
+    fn measure(&self, cfg: &Configuration) -> f64 {
+        cfg.cfg.iter().filter(|&&s| s == 1).count() as f64
+    }
+}
+
+#[test]
+fn custom_observables_are_measured_with_the_others() {
+    let mut registry = Registry::new();
+    registry.register(Arc::new(UpSpins)).unwrap();
+    assert!(registry.register(Arc::new(UpSpins)).is_err());
+    let selected = registry.select(&["up_spins".to_string(), "abs_magnetisation".to_string()]).unwrap();
+
+    let mut rng = Rng::from_seed([51; 32]);
+    let mut cfg = Configuration::random(6, 4, &mut rng);
+    let mut energy = hamiltonian(&cfg, 1., 0.);
+    let mut obs = Observables::with_observables(&selected);
+    let mut plain = Observables::new();
+    let mut plain_cfg = cfg.clone();
+    let mut plain_energy = energy;
+    evolve(&mut cfg, &mut energy, 0.4, 1., 0., &mut Rng::from_seed([52; 32]), 20, Some(&mut obs));
+    evolve(&mut plain_cfg, &mut plain_energy, 0.4, 1., 0., &mut Rng::from_seed([52; 32]), 20, Some(&mut plain));
+
+    assert_eq!(obs.energy, plain.energy);
+    assert_eq!(obs.magnetisation, plain.magnetisation);
+    let (up, abs) = (obs.series("up_spins").unwrap(), obs.series("abs_magnetisation").unwrap());
+    assert_eq!(up.values.len(), 20);
+    for ((up, abs), m) in up.values.iter().zip(&abs.values).zip(&obs.magnetisation) {
+        // 2 n_up - N = N m
+        assert_eq!(up, &vec![(24. + 24.*m) / 2.]);
+        assert_eq!(abs, &vec![m.abs()]);
+    }
+
+    let (merged, _) = chains::merge(vec![obs.clone(), obs.clone()]);
+    assert_eq!(merged.series("up_spins").unwrap().values.len(), 40);
+    obs.clear();
+    assert!(obs.custom.iter().all(|series| series.values.is_empty()));
+}
+
+#[test]
+fn builtin_observables() {
+    let registry = Registry::new();
+    assert!(registry.select(&["unknown".to_string()]).is_err());
+    assert!(registry.select(&["abs_magnetisation".to_string(), "abs_magnetisation".to_string()]).is_err());
+
+    // rows of all up and all down spins
+    let mut cfg = Configuration::random(4, 2, &mut Rng::from_seed([53; 32]));
+    cfg.cfg = vec![1, 1, 1, 1, -1, -1, -1, -1];
+    let measure = |name: &str| registry.get(name).unwrap().measure_vector(&cfg);
+    assert_eq!(magnetisation(&cfg), 0.);
+    assert_eq!(measure("abs_magnetisation"), vec![0.]);
+    assert_eq!(measure("row_magnetisation"), vec![1., -1.]);
+    assert_eq!(registry.get("row_magnetisation").unwrap().measure(&cfg), 1.);
+    // all 8 links along x are satisfied, both links between the rows of each column are not
+    assert_eq!(measure("bond_correlation"), vec![0.]);
+    assert_eq!(measure("stripe_magnetisation"), vec![1.]);
+    assert_eq!(measure("largest_cluster"), vec![0.5]);
+}
+
+#[test]
+fn domain_walls_are_unsatisfied_links() {
+    let mut cfg = Configuration::cold(4, 3, 1);
+    assert_eq!(unsatisfied_bonds(&cfg), 0);
+    // a down column has a wall on either side in every row
+    for y in 0..3 {
+        cfg.cfg[4*y + 1] = -1;
+    }
+    assert_eq!(unsatisfied_bonds(&cfg), 6);
+
+    // anti-periodic boundaries along x put a wall between aligned spins in every row
+    let boundaries = Boundaries::parse("antiperiodic:periodic").unwrap();
+    let cfg = Configuration::cold(4, 3, 1).with_boundaries(&Square{nx: 4, ny: 3}, boundaries);
+    assert_eq!(Registry::new().get("domain_wall_length").unwrap().measure(&cfg), 3.);
+}
@@ -0,0 +1,35 @@
+use ising::plot::{self, Plot, Style};
+
+#[test]
+fn ticks_are_round_numbers_inside_the_range() {
+    let ticks = plot::ticks(-0.12, 1.07);
+    let labels: Vec<&str> = ticks.iter().map(|(_, label)| label.as_str()).collect();
+    assert_eq!(labels, ["0.0", "0.2", "0.4", "0.6", "0.8", "1.0"]);
+    assert!(ticks.iter().all(|&(tick, _)| (-0.12..=1.07).contains(&tick)));
+
+    let labels: Vec<String> = plot::ticks(-330., 2100.).into_iter().map(|(_, label)| label).collect();
+    assert_eq!(labels, ["0", "500", "1000", "1500", "2000"]);
+    // no negative zero
+    let labels: Vec<String> = plot::ticks(-0.3, 0.3).into_iter().map(|(_, label)| label).collect();
+    assert!(labels.contains(&"0.0".to_string()) && !labels.contains(&"-0.0".to_string()));
+}
+
+#[test]
+fn plots_draw_all_finite_points() {
+    let x = [1., 2., 3., 4.];
+    let plot = Plot::new("a < b & c", "T", "χ")
+        .points(&x, &[1., f64::NAN, 3., 2.], &[0.1, 0.1, 0., 0.2])
+        .line(&x, &[0., 1., 0., 1.])
+        .bars(&x, &[5., 0., 2., 1.]);
+    assert_eq!(plot.series.iter().map(|series| series.style).collect::<Vec<_>>(),
+               [Style::Points, Style::Line, Style::Bars]);
+
+    let svg = plot.svg();
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+    assert!(svg.ends_with("</svg>\n"));
+    assert!(svg.contains(">a &lt; b &amp; c</text>"));
+    // the NaN point is skipped, the point without error has no error bar
+    assert_eq!(svg.matches("<circle").count(), 3);
+    assert_eq!(svg.matches("<polyline").count(), 1);
+    assert_eq!(svg.matches("<rect").count(), 2 + 4);
+}
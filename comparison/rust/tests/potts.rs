@@ -0,0 +1,100 @@
+use ising::{Configuration, Observables, Rng, hamiltonian, magnetisation};
+use ising::fourier::smallest_structure_factor;
+use ising::model::{self, Model};
+use ising::potts::{self, Potts};
+
+/// Compute the exact energy per site of a periodic Potts chain with nx sites from the transfer matrix.
+fn potts_chain_energy(nx: usize, q: usize, j: f64, temp: f64) -> f64 {
+    let boltzmann = (j/temp).exp();
+    let (l1, l2) = (boltzmann + (q - 1) as f64, boltzmann - 1.);
+    let n = nx as i32;
+    let z = l1.powi(n) + (q - 1) as f64 * l2.powi(n);
+    let dz = n as f64 * j * boltzmann * (l1.powi(n - 1) + (q - 1) as f64 * l2.powi(n - 1));
+    -dz / z / nx as f64
+}
+
+/// Evaluate the Hamiltonian as a sum of pair energies over all bonds.
+fn generic_hamiltonian(model: &Potts, cfg: &Configuration) -> f64 {
+    (0..cfg.size()).map(|idx| cfg.neighbours_of(idx).iter()
+                        .map(|&neigh| model.pair_energy(&cfg[idx], &cfg[neigh])).sum::<f64>())
+        .sum::<f64>() / 2.
+}
+
+#[test]
+fn random_potts_states_are_in_range() {
+    let mut rng = Rng::from_seed([3; 32]);
+    let cfg = Potts{q: 4, j: 1.}.random(7, 5, &mut rng);
+    assert!(cfg.cfg.iter().all(|&s| (0..4).contains(&s)));
+    for s in 0..4 {
+        assert!(cfg.cfg.contains(&s));
+    }
+}
+
+#[test]
+fn two_state_potts_is_ising() {
+    let mut rng = Rng::from_seed([5; 32]);
+    let j = 1.3;
+    let model = Potts{q: 2, j};
+    for (nx, ny) in [(6, 5), (7, 1)] {
+        let cfg = model.random(nx, ny, &mut rng);
+        let mut ising = cfg.clone();
+        ising.cfg.iter_mut().for_each(|s| *s = 2 * *s - 1);
+
+        let nbond = (cfg.size() * cfg.coordination() / 2) as f64;
+        assert!((model.hamiltonian(&cfg) - (hamiltonian(&ising, j/2., 0.) - j/2. * nbond)).abs() < 1e-12);
+        // exp(iπs) = -(2s-1) for s = 0, 1
+        assert!((potts::order_parameter(&cfg, 2) - magnetisation(&ising).abs()).abs() < 1e-12);
+        assert!((potts::smallest_structure_factor(&cfg, 2) - smallest_structure_factor(&ising)).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn potts_delta_e_matches_hamiltonian() {
+    let mut rng = Rng::from_seed([9; 32]);
+    let model = Potts{q: 3, j: 0.8};
+    let mut cfg = model.random(5, 4, &mut rng);
+    for idx in 0..cfg.size() {
+        for state in 0..3 {
+            let before = model.hamiltonian(&cfg);
+            let delta = model.delta_e(&cfg, idx, &state);
+            let old = cfg[idx];
+            cfg[idx] = state;
+            assert!((model.hamiltonian(&cfg) - before - delta).abs() < 1e-12);
+            // the integer counting agrees with the generic sum over bonds
+            assert!((model.hamiltonian(&cfg) - generic_hamiltonian(&model, &cfg)).abs() < 1e-12);
+            cfg[idx] = old;
+        }
+    }
+}
+
+#[test]
+fn order_parameter_of_ordered_and_mixed_states() {
+    assert!((potts::order_parameter(&Configuration::cold(4, 3, 2), 3) - 1.).abs() < 1e-12);
+    assert!(potts::staggered_order_parameter(&Configuration::cold(4, 3, 2), 3).abs() < 1e-12);
+
+    // all three states equally often cancel out
+    let mut cfg = Configuration::cold(6, 1, 0);
+    for (idx, s) in cfg.cfg.iter_mut().enumerate() {
+        *s = (idx % 3) as i32;
+    }
+    assert!(potts::order_parameter(&cfg, 3) < 1e-12);
+}
+
+#[test]
+fn evolve_potts_matches_exact_chain() {
+    let mut rng = Rng::from_seed([11; 32]);
+    let (nx, q, j, temp) = (8, 3, 1., 1.);
+    let model = Potts{q, j};
+    let mut cfg = model.random(nx, 1, &mut rng);
+    let mut energy = model.hamiltonian(&cfg);
+    model::evolve(&model, &mut cfg, &mut energy, 1./temp, &mut rng, 1000, None);
+
+    let mut obs = Observables::new();
+    model::evolve(&model, &mut cfg, &mut energy, 1./temp, &mut rng, 50000, Some(&mut obs));
+    assert!((energy - model.hamiltonian(&cfg)).abs() < 1e-9);
+    assert_eq!(obs.energy.len(), 50000);
+    assert!(obs.magnetisation.iter().all(|&m| (0.0..=1.).contains(&m)));
+
+    let mean = obs.energy.iter().sum::<f64>() / (obs.energy.len()*nx) as f64;
+    assert!((mean - potts_chain_energy(nx, q, j, temp)).abs() < 0.02);
+}
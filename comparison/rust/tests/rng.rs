@@ -0,0 +1,149 @@
+use rand::prelude::*;
+// the traits of rand, Rng is the generator of ising
+use rand::Rng as _;
+
+use ising::{Rng, RngKind, RngState};
+use ising::xoshiro::Xoshiro256PlusPlus;
+
+const NCHECK: usize = 500;
+
+#[test]
+fn rng_produces_numbers_in_the_correct_range() {
+    let mut rng = Rng::from_seed([53; 32]);
+    let latsize = 143;
+    assert!((0..NCHECK).all(|_| rng.gen_index(latsize) < latsize));
+    assert!((0..NCHECK).all(|_| (0.0..1.0).contains(&rng.gen_real())));
+    assert!((0..NCHECK).all(|_| matches!(rng.gen_spin(), -1 | 1)));
+}
+
+#[test]
+fn rng_is_reproducible() {
+    let mut a = Rng::from_seed([9; 32]);
+    let mut b = Rng::from_seed([9; 32]);
+    assert!((0..NCHECK).all(|_| a.gen_real() == b.gen_real()));
+}
+
+#[test]
+fn rng_continues_from_state() {
+    for kind in [RngKind::Xoshiro, RngKind::ChaCha] {
+        let mut rng = Rng::new(kind, [10; 32]);
+        assert_eq!(rng.kind(), kind);
+        continues_from_state(&mut rng);
+    }
+}
+
+/// Check that Rng::from_state continues the stream of rng at several positions.
+fn continues_from_state(rng: &mut Rng) {
+    // mix draws of different sizes to end up at arbitrary positions in the stream
+    for n in [0, 1, 7, 15, 16, 33, 100] {
+        for i in 0..n {
+            match i % 3 {
+                0 => { rng.gen_real(); }
+                1 => { rng.gen_index(143); }
+                _ => { rng.gen_spin(); }
+            }
+        }
+        let mut restored = Rng::from_state(rng.state());
+        assert_eq!(restored.state(), rng.state());
+        assert!((0..NCHECK).all(|i| match i % 3 {
+            0 => rng.gen_real() == restored.gen_real(),
+            1 => rng.gen_index(143) == restored.gen_index(143),
+            _ => rng.gen_spin() == restored.gen_spin(),
+        }));
+    }
+}
+
+#[test]
+fn rng_fills_buffers_like_single_draws() {
+    for kind in [RngKind::Xoshiro, RngKind::ChaCha] {
+        let mut rng = Rng::new(kind, [11; 32]);
+        let mut reference = Rng::new(kind, [11; 32]);
+        let (mut indices, mut reals) = ([0; 37], [0.; 37]);
+        rng.fill_indices(143, &mut indices);
+        rng.fill_reals(&mut reals);
+        assert!(indices.iter().all(|&idx| idx == reference.gen_index(143)));
+        assert!(reals.iter().all(|&real| real == reference.gen_real()));
+        assert_eq!(rng.state(), reference.state());
+    }
+}
+
+#[test]
+fn xoshiro_matches_rand() {
+    for seed in [[0; 32], [7; 32], std::array::from_fn(|i| i as u8)] {
+        let mut ours = Xoshiro256PlusPlus::from_seed(seed);
+        let mut reference = rand::rngs::Xoshiro256PlusPlus::from_seed(seed);
+        assert!((0..NCHECK).all(|_| ours.next_u64() == reference.next_u64()));
+        assert!((0..NCHECK).all(|_| ours.next_u32() == reference.next_u32()));
+        let (mut a, mut b) = ([0; 13], [0; 13]);
+        ours.fill_bytes(&mut a);
+        reference.fill_bytes(&mut b);
+        assert_eq!(a, b);
+        assert_eq!(Xoshiro256PlusPlus::from_state(ours.state()), ours);
+    }
+
+    let mut rng = Rng::new(RngKind::Xoshiro, [12; 32]);
+    let mut reference = rand::rngs::Xoshiro256PlusPlus::from_seed([12; 32]);
+    assert!((0..NCHECK).all(|_| rng.gen_real() == reference.random::<f64>()));
+    assert!(matches!(rng.state(), RngState::Xoshiro(_)));
+}
+
+#[test]
+fn xoshiro_jumps_match_powers_of_the_transition_matrix() {
+    // states computed by squaring the 256x256 transition matrix over GF(2) 128 and 192 times
+    let mut rng = Xoshiro256PlusPlus::from_seed([1; 32]);
+    rng.jump();
+    assert_eq!(rng.state(), [0xfcace8d93ccbe7df, 0x88b2f69ff926b3f1, 0xc6cc51f45539242b, 0xd789f3a62735e7fe]);
+    let mut rng = Xoshiro256PlusPlus::from_seed([1; 32]);
+    rng.long_jump();
+    assert_eq!(rng.state(), [0x181ffae233a27524, 0x048f2807c84c72bd, 0x1ed10359b9761213, 0x8bf6dd179f368a32]);
+}
+
+#[test]
+fn rng_streams_are_reproducible_and_distinct() {
+    for kind in [RngKind::Xoshiro, RngKind::ChaCha] {
+        assert_eq!(Rng::stream(kind, [13; 32], 0).state(), Rng::new(kind, [13; 32]).state());
+        let mut streams: Vec<Rng> = (0..4).map(|i| Rng::stream(kind, [13; 32], i)).collect();
+        let mut again: Vec<Rng> = (0..4).map(|i| Rng::stream(kind, [13; 32], i)).collect();
+        let draws: Vec<Vec<f64>> = streams.iter_mut().map(|rng| (0..NCHECK).map(|_| rng.gen_real()).collect())
+            .collect();
+        assert!(again.iter_mut().zip(&draws).all(|(rng, draws)| draws.iter().all(|&x| x == rng.gen_real())));
+        assert!(draws.iter().enumerate().all(|(i, a)| draws[i+1..].iter().all(|b| a != b)));
+        // the stream is part of the state
+        continues_from_state(&mut streams[3]);
+    }
+}
+
+#[test]
+fn rng_splits_are_reproducible_and_distinct() {
+    for kind in [RngKind::Xoshiro, RngKind::ChaCha] {
+        let (mut rng, mut again) = (Rng::new(kind, [14; 32]), Rng::new(kind, [14; 32]));
+        let mut parts: Vec<Rng> = (0..3).map(|_| rng.split()).collect();
+        let mut parts_again: Vec<Rng> = (0..3).map(|_| again.split()).collect();
+        assert_eq!(rng.state(), again.state());
+        assert!(parts.iter().zip(&parts_again).all(|(a, b)| a.state() == b.state()));
+        parts.push(rng);
+        let draws: Vec<Vec<f64>> = parts.iter_mut().map(|rng| (0..NCHECK).map(|_| rng.gen_real()).collect())
+            .collect();
+        assert!(draws.iter().enumerate().all(|(i, a)| draws[i+1..].iter().all(|b| a != b)));
+        assert!(parts_again.iter_mut().zip(&draws).all(|(rng, draws)| draws.iter().all(|&x| x == rng.gen_real())));
+    }
+
+    // a xoshiro256++ part continues where the generator was, which jumped ahead
+    let mut rng = Xoshiro256PlusPlus::from_seed([14; 32]);
+    let mut split = Rng::new(RngKind::Xoshiro, [14; 32]);
+    assert_eq!(split.split().state(), RngState::Xoshiro(rng.state()));
+    rng.jump();
+    assert_eq!(split.state(), RngState::Xoshiro(rng.state()));
+}
+
+#[test]
+fn rng_kinds_parse() {
+    for kind in [RngKind::Xoshiro, RngKind::ChaCha] {
+        assert_eq!(RngKind::parse(kind.name()), Ok(kind));
+    }
+    assert_eq!(RngKind::default(), RngKind::Xoshiro);
+    assert!(RngKind::parse("pcg").is_err());
+    // different algorithms give different streams from the same seed
+    let (mut a, mut b) = (Rng::new(RngKind::Xoshiro, [5; 32]), Rng::new(RngKind::ChaCha, [5; 32]));
+    assert!((0..NCHECK).any(|_| a.gen_real() != b.gen_real()));
+}
@@ -0,0 +1,40 @@
+use ising::{Configuration, Observables};
+use ising::watch::{self, sparkline};
+
+#[test]
+fn large_lattices_are_averaged_in_blocks() {
+    let mut cfg = Configuration::cold(6, 4, 1);
+    cfg[0] = -1;
+    cfg[7] = -1;
+    let (ncol, nrow, means) = watch::downsample(&cfg, 3, 3);
+    assert_eq!((ncol, nrow), (3, 2));
+    assert_eq!(means, [0., 1., 1., 1., 1., 1.]);
+    // smaller than the view
+    assert_eq!(watch::downsample(&cfg, 10, 10).2.len(), 24);
+    // blocks at the edge may be smaller
+    let (ncol, nrow, means) = watch::downsample(&Configuration::cold(5, 1, -1), 2, 2);
+    assert_eq!((ncol, nrow, means), (2, 1, vec![-1., -1.]));
+}
+
+#[test]
+fn sparklines_span_the_range_of_the_last_values() {
+    assert_eq!(sparkline(&[5., 0., 1., 2.], 3), "▁▅█");
+    assert_eq!(sparkline(&[3., 3.], 10), "▁▁");
+    assert_eq!(sparkline(&[], 10), "");
+}
+
+#[test]
+fn view_shows_lattice_and_observables() {
+    let cfg = Configuration::cold(4, 3, -1);
+    let mut obs = Observables::new();
+    let view = watch::render(&cfg, 2.5, 7, 10, &obs);
+    assert!(view.starts_with("\x1b[H\x1b[J T = 2.5, sweep 7/10\n"));
+    // two lines of half blocks for three rows, the last without background
+    assert_eq!(view.matches('▀').count(), 8);
+    assert_eq!(view.matches("\x1b[48;5;232m").count(), 1);
+    assert!(view.ends_with(" E/N\n m  \n"));
+
+    obs.measure(&cfg, -24.);
+    let view = watch::render(&cfg, 2.5, 8, 10, &obs);
+    assert!(view.ends_with(" E/N ▁ -2.0000\n m   ▁ -1.0000\n"));
+}